@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use winnow::{error::InputError, prelude::*};
+use winnow_json::{json, JsonValue};
+
+fn main() {
+	let input = r#"
+  {
+    "null" : null,
+    "true"  :true ,
+    "false":  false  ,
+    "number" : 123e4 ,
+    "string" : " abc 123 " ,
+    "array" : [ false , 1 , "two" ] ,
+    "object" : { "a" : 1.0 , "b" : "c" } ,
+    "empty_array" : [  ] ,
+    "empty_object" : {   }
+  }
+  "#;
+
+	assert_eq!(
+		json::<_, InputError<&'_ str>>.parse_peek(input),
+		Ok((
+			"",
+			JsonValue::Object(
+				vec![
+					("null".to_string(), JsonValue::Null),
+					(
+						"true".to_string(),
+						JsonValue::Boolean(true)
+					),
+					(
+						"false".to_string(),
+						JsonValue::Boolean(false)
+					),
+					(
+						"number".to_string(),
+						JsonValue::Number(123e4)
+					),
+					(
+						"string".to_string(),
+						JsonValue::String(" abc 123 ".to_string())
+					),
+					(
+						"array".to_string(),
+						JsonValue::Array(vec![
+							JsonValue::Boolean(false),
+							JsonValue::Number(1.0),
+							JsonValue::String("two".to_string())
+						])
+					),
+					(
+						"object".to_string(),
+						JsonValue::Object(
+							vec![
+								("a".to_string(), JsonValue::Number(1.0)),
+								(
+									"b".to_string(),
+									JsonValue::String("c".to_string())
+								),
+							]
+							.into_iter()
+							.collect::<HashMap<_, _>>()
+							.into()
+						)
+					),
+					(
+						"empty_array".to_string(),
+						JsonValue::Array(vec![]),
+					),
+					(
+						"empty_object".to_string(),
+						JsonValue::Object(Box::default()),
+					),
+				]
+				.into_iter()
+				.collect::<HashMap<_, _>>()
+				.into()
+			)
+		))
+	);
+	let value: JsonValue = input.parse().unwrap();
+	assert_eq!(
+		value.to_string_stable(),
+		r#"{
+  "array": [
+    false,
+    1,
+    "two"
+  ],
+  "empty_array": [],
+  "empty_object": {},
+  "false": false,
+  "null": null,
+  "number": 1230000,
+  "object": {
+    "a": 1,
+    "b": "c"
+  },
+  "string": " abc 123 ",
+  "true": true
+}
+"#
+	);
+	for number in ["1", "-0", "123e4", "0.1", "1.5e-10", "9007199254740992"] {
+		let value: JsonValue = number.parse().unwrap();
+		assert_eq!(
+			value.to_string().parse::<JsonValue>(),
+			Ok(value)
+		);
+	}
+	assert_eq!(JsonValue::Number(1.0).to_string(), "1");
+	assert_eq!(
+		JsonValue::Number(123e4).to_string(),
+		"1230000"
+	);
+	let mut parser = winnow_json::Parser::new();
+	for _ in 0..2 {
+		assert_eq!(
+			parser.parse(input),
+			winnow_json::parse(input)
+		);
+	}
+}
@@ -0,0 +1,124 @@
+//! Measures what a parsed tree costs in memory with `JsonValue`'s layout
+//! against the one it had before its object variant was boxed, by counting
+//! the bytes allocated while building the same tree in each.
+//!
+//! ```text
+//! cargo run --release --example memory [records]
+//! ```
+
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	collections::HashMap,
+	mem::size_of,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+use winnow_json::{parse, JsonValue};
+
+/// The system allocator, keeping count of the bytes it has handed out.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// `JsonValue` as it was laid out before, with the map held inline.
+#[allow(dead_code)]
+enum Unboxed {
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Unboxed>),
+	Object(HashMap<String, Unboxed>),
+}
+
+fn unboxed(value: &JsonValue) -> Unboxed {
+	match value {
+		JsonValue::Null => Unboxed::Null,
+		JsonValue::Boolean(b) => Unboxed::Boolean(*b),
+		JsonValue::Number(n) => Unboxed::Number(*n),
+		JsonValue::String(s) => Unboxed::String(s.clone()),
+		JsonValue::Array(items) => {
+			Unboxed::Array(items.iter().map(unboxed).collect())
+		}
+		JsonValue::Object(map) => Unboxed::Object(
+			map.iter()
+				.map(|(key, value)| (key.clone(), unboxed(value)))
+				.collect(),
+		),
+	}
+}
+
+/// The bytes allocated while `build` runs and still held by what it
+/// returns, which is dropped afterwards.
+fn heap<T>(build: impl FnOnce() -> T) -> usize {
+	let before = ALLOCATED.load(Ordering::Relaxed);
+	let built = build();
+	let after = ALLOCATED.load(Ordering::Relaxed);
+	drop(built);
+	after - before
+}
+
+fn main() {
+	let records: usize = std::env::args().nth(1).map_or(100_000, |n| {
+		n.parse().expect("records must be a number")
+	});
+	let record = r#"{"id": 1, "name": "name", "tags": ["a", "b", 3], "geo": {"lat": 1.5, "lon": -2.5}, "active": true, "parent": null}"#;
+	let input = format!("[{}]", vec![record; records].join(","));
+	let value = parse(&input).expect("the input is valid JSON");
+	let nodes = count(&value);
+
+	// Both trees are built by the same kind of copy, so their vectors and
+	// maps are sized alike.
+	let boxed = heap(|| value.clone());
+	let inline = heap(|| unboxed(&value));
+
+	println!("{records} records, {nodes} values");
+	println!(
+		"{:<24}{:>10}{:>16}{:>16}",
+		"layout", "size_of", "heap bytes", "bytes per value"
+	);
+	for (name, size, heap) in [
+		(
+			"before (inline map)",
+			size_of::<Unboxed>(),
+			inline,
+		),
+		(
+			"after (boxed map)",
+			size_of::<JsonValue>(),
+			boxed,
+		),
+	] {
+		println!(
+			"{name:<24}{size:>10}{heap:>16}{:>16.1}",
+			(heap + size) as f64 / nodes as f64
+		);
+	}
+	println!(
+		"saved {:.1}%",
+		100.0 * (1.0 - boxed as f64 / inline as f64)
+	);
+}
+
+fn count(value: &JsonValue) -> usize {
+	1 + match value {
+		JsonValue::Array(items) => items.iter().map(count).sum(),
+		JsonValue::Object(map) => map.values().map(count).sum(),
+		_ => 0,
+	}
+}
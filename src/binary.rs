@@ -0,0 +1,55 @@
+//! Pieces shared by the binary formats.
+
+use std::{error, fmt};
+
+/// An error from decoding a binary format: `JsonValue::from_cbor` with the
+/// `cbor` feature or `JsonValue::from_msgpack` with the `msgpack` one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+	offset: usize,
+	message: &'static str,
+}
+
+impl DecodeError {
+	pub(crate) fn new(offset: usize, message: &'static str) -> Self {
+		Self { offset, message }
+	}
+
+	/// The byte offset of the item that could not be decoded.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	pub fn message(&self) -> &str {
+		self.message
+	}
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} at byte {}",
+			self.message, self.offset
+		)
+	}
+}
+
+impl error::Error for DecodeError {}
+
+/// Encodes `bytes` as base64url without padding, the form RFC 8949
+/// suggests for binary data converted to JSON.
+pub(crate) fn base64url(bytes: &[u8]) -> String {
+	const ALPHABET: &[u8; 64] =
+		b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+	let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+	for chunk in bytes.chunks(3) {
+		let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| {
+			n | u32::from(b) << (16 - 8 * i)
+		});
+		for i in 0..=chunk.len() {
+			out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize].into());
+		}
+	}
+	out
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use crate::JsonValue;
+
+/// A fluent builder for [`JsonValue::Object`].
+#[derive(Debug, Clone, Default)]
+pub struct ObjectBuilder {
+	map: HashMap<String, JsonValue>,
+}
+
+impl ObjectBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets `key` to `value`, replacing any previous value.
+	pub fn field(
+		mut self,
+		key: impl Into<String>,
+		value: impl Into<JsonValue>,
+	) -> Self {
+		self.map.insert(key.into(), value.into());
+		self
+	}
+
+	/// Sets `key` to the object built by `f`.
+	pub fn object(
+		self,
+		key: impl Into<String>,
+		f: impl FnOnce(ObjectBuilder) -> ObjectBuilder,
+	) -> Self {
+		self.field(key, f(ObjectBuilder::new()))
+	}
+
+	/// Sets `key` to the array built by `f`.
+	pub fn array(
+		self,
+		key: impl Into<String>,
+		f: impl FnOnce(ArrayBuilder) -> ArrayBuilder,
+	) -> Self {
+		self.field(key, f(ArrayBuilder::new()))
+	}
+
+	pub fn build(self) -> JsonValue {
+		JsonValue::Object(Box::new(self.map))
+	}
+}
+
+impl From<ObjectBuilder> for JsonValue {
+	fn from(builder: ObjectBuilder) -> Self {
+		builder.build()
+	}
+}
+
+/// A fluent builder for [`JsonValue::Array`].
+#[derive(Debug, Clone, Default)]
+pub struct ArrayBuilder {
+	items: Vec<JsonValue>,
+}
+
+impl ArrayBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(mut self, value: impl Into<JsonValue>) -> Self {
+		self.items.push(value.into());
+		self
+	}
+
+	/// Appends the object built by `f`.
+	pub fn object(
+		self,
+		f: impl FnOnce(ObjectBuilder) -> ObjectBuilder,
+	) -> Self {
+		self.push(f(ObjectBuilder::new()))
+	}
+
+	/// Appends the array built by `f`.
+	pub fn array(self, f: impl FnOnce(ArrayBuilder) -> ArrayBuilder) -> Self {
+		self.push(f(ArrayBuilder::new()))
+	}
+
+	pub fn build(self) -> JsonValue {
+		JsonValue::Array(self.items)
+	}
+}
+
+impl From<ArrayBuilder> for JsonValue {
+	fn from(builder: ArrayBuilder) -> Self {
+		builder.build()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn builds_nested_values() {
+		let value = ObjectBuilder::new()
+			.field("name", "box")
+			.field("size", 3)
+			.field("size", 4)
+			.field("lid", None::<bool>)
+			.object("owner", |owner| {
+				owner.field("admin", true)
+			})
+			.array("tags", |tags| {
+				tags.push("a")
+					.object(|tag| tag.field("id", 1.5))
+					.array(|nested| nested)
+			})
+			.build();
+		assert_eq!(
+			value,
+			parse(
+				r#"{"name": "box", "size": 4, "lid": null,
+				"owner": {"admin": true}, "tags": ["a", {"id": 1.5}, []]}"#
+			)
+			.unwrap()
+		);
+		assert_eq!(
+			JsonValue::from(ObjectBuilder::new()),
+			parse("{}").unwrap()
+		);
+		assert_eq!(
+			JsonValue::from(ArrayBuilder::new()),
+			parse("[]").unwrap()
+		);
+	}
+}
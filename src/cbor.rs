@@ -0,0 +1,448 @@
+//! Conversion to and from [CBOR](https://www.rfc-editor.org/rfc/rfc8949),
+//! behind the `cbor` feature.
+
+use std::collections::HashMap;
+
+use crate::{
+	binary::{base64url, DecodeError},
+	JsonValue,
+};
+
+impl JsonValue {
+	/// Encodes the value as CBOR.
+	///
+	/// Integral numbers become CBOR integers, and other numbers single
+	/// precision floats when that loses nothing, double precision otherwise.
+	/// Object members are written ordered by key, so equal values always
+	/// encode to the same bytes.
+	pub fn to_cbor(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode(self, &mut out);
+		out
+	}
+
+	/// Decodes one CBOR data item, which must span all of `bytes`.
+	///
+	/// CBOR is mapped onto JSON following RFC 8949 section 6.1: tags are
+	/// dropped in favour of their content, `undefined` becomes `null`, and
+	/// byte strings become base64url strings without padding. Map keys that
+	/// are numbers are converted to strings; other non-string keys are
+	/// rejected.
+	pub fn from_cbor(bytes: &[u8]) -> Result<JsonValue, DecodeError> {
+		let mut decoder = Decoder {
+			bytes,
+			offset: 0,
+			depth: 0,
+		};
+		let value = decoder.item()?;
+		if decoder.offset < bytes.len() {
+			return Err(decoder.error("trailing bytes after the data item"));
+		}
+		Ok(value)
+	}
+}
+
+fn encode(value: &JsonValue, out: &mut Vec<u8>) {
+	match value {
+		JsonValue::Null => out.push(0xF6),
+		JsonValue::Boolean(false) => out.push(0xF4),
+		JsonValue::Boolean(true) => out.push(0xF5),
+		JsonValue::Number(n) => encode_number(*n, out),
+		JsonValue::String(s) => {
+			head(3, s.len() as u64, out);
+			out.extend_from_slice(s.as_bytes());
+		}
+		JsonValue::Array(items) => {
+			head(4, items.len() as u64, out);
+			for item in items {
+				encode(item, out);
+			}
+		}
+		JsonValue::Object(map) => {
+			let mut members: Vec<_> = map.iter().collect();
+			members.sort_unstable_by_key(|&(key, _)| key);
+			head(5, members.len() as u64, out);
+			for (key, value) in members {
+				head(3, key.len() as u64, out);
+				out.extend_from_slice(key.as_bytes());
+				encode(value, out);
+			}
+		}
+	}
+}
+
+fn encode_number(n: f64, out: &mut Vec<u8>) {
+	// Integers of up to 64 bits, except -0, which needs a float.
+	if n.fract() == 0.0
+		&& n.abs() < 2f64.powi(64)
+		&& !(n == 0.0 && n.is_sign_negative())
+	{
+		if n < 0.0 {
+			head(1, (-1.0 - n) as u64, out);
+		} else {
+			head(0, n as u64, out);
+		}
+	} else if (n as f32) as f64 == n || n.is_nan() {
+		out.push(0xFA);
+		out.extend_from_slice(&(n as f32).to_be_bytes());
+	} else {
+		out.push(0xFB);
+		out.extend_from_slice(&n.to_be_bytes());
+	}
+}
+
+/// Writes the initial bytes of an item: the major type and its argument.
+fn head(major: u8, argument: u64, out: &mut Vec<u8>) {
+	let major = major << 5;
+	match argument {
+		0..=23 => out.push(major | argument as u8),
+		24..=0xFF => out.extend_from_slice(&[major | 24, argument as u8]),
+		0x100..=0xFFFF => {
+			out.push(major | 25);
+			out.extend_from_slice(&(argument as u16).to_be_bytes());
+		}
+		0x1_0000..=0xFFFF_FFFF => {
+			out.push(major | 26);
+			out.extend_from_slice(&(argument as u32).to_be_bytes());
+		}
+		_ => {
+			out.push(major | 27);
+			out.extend_from_slice(&argument.to_be_bytes());
+		}
+	}
+}
+
+/// The argument of an item of indefinite length.
+const INDEFINITE: u8 = 31;
+const BREAK: u8 = 0xFF;
+
+/// How deeply arrays, maps and tags may nest, so hostile input cannot
+/// exhaust the stack.
+const MAX_DEPTH: usize = 512;
+
+struct Decoder<'b> {
+	bytes: &'b [u8],
+	offset: usize,
+	/// The number of arrays, maps and tags being decoded.
+	depth: usize,
+}
+
+impl<'b> Decoder<'b> {
+	fn error(&self, message: &'static str) -> DecodeError {
+		DecodeError::new(self.offset, message)
+	}
+
+	fn take(&mut self, len: usize) -> Result<&'b [u8], DecodeError> {
+		let end = self
+			.offset
+			.checked_add(len)
+			.filter(|&end| end <= self.bytes.len())
+			.ok_or_else(|| self.error("unexpected end of input"))?;
+		let taken = &self.bytes[self.offset..end];
+		self.offset = end;
+		Ok(taken)
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.bytes.get(self.offset).copied()
+	}
+
+	/// Reads the argument following an initial byte with the given
+	/// additional information.
+	fn argument(&mut self, info: u8) -> Result<u64, DecodeError> {
+		Ok(match info {
+			0..=23 => info.into(),
+			24 => self.take(1)?[0].into(),
+			25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()).into(),
+			26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()).into(),
+			27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+			_ => return Err(self.error("invalid additional information")),
+		})
+	}
+
+	/// Reads a length, which must leave at least one byte per element.
+	fn len(&mut self, info: u8) -> Result<usize, DecodeError> {
+		let start = self.offset;
+		let len = self.argument(info)?;
+		usize::try_from(len)
+			.ok()
+			.filter(|&len| len <= self.bytes.len() - self.offset)
+			.ok_or(DecodeError::new(
+				start,
+				"length exceeds the input",
+			))
+	}
+
+	/// Consumes a break code, if one comes next.
+	fn eat_break(&mut self) -> bool {
+		let found = self.peek() == Some(BREAK);
+		if found {
+			self.offset += 1;
+		}
+		found
+	}
+
+	fn item(&mut self) -> Result<JsonValue, DecodeError> {
+		if self.depth == MAX_DEPTH {
+			return Err(self.error("nesting too deep"));
+		}
+		self.depth += 1;
+		let item = self.nested();
+		self.depth -= 1;
+		item
+	}
+
+	fn nested(&mut self) -> Result<JsonValue, DecodeError> {
+		let start = self.offset;
+		let initial = self.take(1)?[0];
+		let (major, info) = (initial >> 5, initial & 0x1F);
+		Ok(match major {
+			0 => JsonValue::Number(self.argument(info)? as f64),
+			1 => JsonValue::Number(-1.0 - self.argument(info)? as f64),
+			2 => JsonValue::String(base64url(&self.chunks(2, info)?)),
+			3 => {
+				let bytes = self.chunks(3, info)?;
+				let string = String::from_utf8(bytes).map_err(|_| {
+					DecodeError::new(start, "text string is not UTF-8")
+				})?;
+				JsonValue::String(string)
+			}
+			4 => {
+				let mut items = Vec::new();
+				if info == INDEFINITE {
+					while !self.eat_break() {
+						items.push(self.item()?);
+					}
+				} else {
+					for _ in 0..self.len(info)? {
+						items.push(self.item()?);
+					}
+				}
+				JsonValue::Array(items)
+			}
+			5 => {
+				let mut map = HashMap::new();
+				if info == INDEFINITE {
+					while !self.eat_break() {
+						let key = self.key()?;
+						map.insert(key, self.item()?);
+					}
+				} else {
+					for _ in 0..self.len(info)? {
+						let key = self.key()?;
+						map.insert(key, self.item()?);
+					}
+				}
+				JsonValue::Object(Box::new(map))
+			}
+			6 => {
+				self.argument(info)?;
+				self.item()?
+			}
+			_ => match info {
+				20 => JsonValue::Boolean(false),
+				21 => JsonValue::Boolean(true),
+				22 | 23 => JsonValue::Null,
+				25 => {
+					let bits = self.take(2)?.try_into().unwrap();
+					JsonValue::Number(half(u16::from_be_bytes(bits)))
+				}
+				26 => {
+					let bits = self.take(4)?.try_into().unwrap();
+					JsonValue::Number(f32::from_be_bytes(bits).into())
+				}
+				27 => {
+					let bits = self.take(8)?.try_into().unwrap();
+					JsonValue::Number(f64::from_be_bytes(bits))
+				}
+				_ => {
+					self.offset = start;
+					return Err(self.error("unsupported simple value"));
+				}
+			},
+		})
+	}
+
+	/// Reads the contents of a byte or text string of the given major type,
+	/// joining the chunks of one of indefinite length.
+	fn chunks(&mut self, major: u8, info: u8) -> Result<Vec<u8>, DecodeError> {
+		if info != INDEFINITE {
+			let len = self.len(info)?;
+			return Ok(self.take(len)?.to_vec());
+		}
+		let mut bytes = Vec::new();
+		while !self.eat_break() {
+			let initial = self.take(1)?[0];
+			if initial >> 5 != major || initial & 0x1F == INDEFINITE {
+				self.offset -= 1;
+				return Err(self.error("invalid chunk in indefinite string"));
+			}
+			let len = self.len(initial & 0x1F)?;
+			bytes.extend_from_slice(self.take(len)?);
+		}
+		Ok(bytes)
+	}
+
+	fn key(&mut self) -> Result<String, DecodeError> {
+		let start = self.offset;
+		match self.item()? {
+			JsonValue::String(key) => Ok(key),
+			JsonValue::Number(n) => Ok(JsonValue::Number(n).to_string()),
+			_ => Err(DecodeError::new(
+				start,
+				"map key is not a string or number",
+			)),
+		}
+	}
+}
+
+/// Converts an IEEE 754 half-precision float.
+fn half(bits: u16) -> f64 {
+	let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+	let exponent = i32::from(bits >> 10 & 0x1F);
+	let mantissa = f64::from(bits & 0x3FF);
+	sign * match exponent {
+		0 => mantissa * 2f64.powi(-24),
+		31 if mantissa == 0.0 => f64::INFINITY,
+		31 => f64::NAN,
+		_ => (1024.0 + mantissa) * 2f64.powi(exponent - 25),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn decode(bytes: &[u8]) -> Result<JsonValue, DecodeError> {
+		JsonValue::from_cbor(bytes)
+	}
+
+	#[test]
+	fn round_trips() {
+		let value = parse(
+			r#"{"null": null, "bools": [true, false], "ints": [0, 23, 24,
+			255, 256, 65536, -1, -25, 18446744073709549568],
+			"floats": [1.5, -0.0, 0.1, 1e300, -3.4028234663852886e38],
+			"text": ["", "a", "ü€😀"], "nested": {"a": [[], {}]}}"#,
+		)
+		.unwrap();
+		let bytes = value.to_cbor();
+		assert_eq!(decode(&bytes), Ok(value.clone()));
+		assert_eq!(bytes, value.clone().to_cbor());
+		let negative_zero = decode(&JsonValue::from(-0.0).to_cbor()).unwrap();
+		assert!(negative_zero.as_f64().unwrap().is_sign_negative());
+	}
+
+	#[test]
+	fn decodes_rfc_examples() {
+		let cases: &[(&[u8], &str)] = &[
+			(
+				&[0x82, 0x01, 0x82, 0x02, 0x03],
+				"[1, [2, 3]]",
+			),
+			(
+				&[0x9F, 0x01, 0x9F, 0xFF, 0xFF],
+				"[1, []]",
+			),
+			(
+				&[0xBF, 0x61, 0x61, 0x01, 0xFF],
+				r#"{"a": 1}"#,
+			),
+			(&[0xA1, 0x01, 0x02], r#"{"1": 2}"#),
+			(&[0xF9, 0x3E, 0x00], "1.5"),
+			(&[0xF9, 0x7C, 0x00], "1e400"),
+			(
+				&[0xFA, 0x47, 0xC3, 0x50, 0x00],
+				"100000",
+			),
+			(&[0x3B, 0, 0, 0, 0, 0, 0, 0, 0], "-1"),
+			(
+				&[0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0],
+				"1363896240",
+			),
+			(
+				&[0x44, 0x01, 0x02, 0x03, 0x04],
+				r#""AQIDBA""#,
+			),
+			(
+				&[0x7F, 0x61, 0x61, 0x62, 0x62, 0x63, 0xFF],
+				r#""abc""#,
+			),
+			(&[0xF7], "null"),
+		];
+		for (bytes, expected) in cases {
+			let expected = match *expected {
+				"1e400" => JsonValue::Number(f64::INFINITY),
+				expected => parse(expected).unwrap(),
+			};
+			assert_eq!(
+				decode(bytes),
+				Ok(expected),
+				"{bytes:02X?}"
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_malformed_input() {
+		let error = |bytes: &[u8]| decode(bytes).unwrap_err();
+		let huge = [0x5B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+		assert_eq!(
+			error(&huge).message(),
+			"length exceeds the input"
+		);
+		let array = [0x9A, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+		assert_eq!(
+			error(&array).message(),
+			"length exceeds the input"
+		);
+		assert_eq!(
+			error(&[0x19, 0x01]).message(),
+			"unexpected end of input"
+		);
+		assert_eq!(
+			error(&[0x01, 0x02]).message(),
+			"trailing bytes after the data item"
+		);
+		assert_eq!(
+			error(&[0x62, 0xFF, 0xFE]).message(),
+			"text string is not UTF-8"
+		);
+		assert_eq!(
+			error(&[0x7F, 0x41, 0x00, 0xFF]).message(),
+			"invalid chunk in indefinite string"
+		);
+		assert_eq!(
+			error(&[0xA1, 0x80, 0x01]).message(),
+			"map key is not a string or number"
+		);
+		assert_eq!(
+			error(&[0x9F, 0x01]).message(),
+			"unexpected end of input"
+		);
+		assert_eq!(
+			error(&[0x1C]).message(),
+			"invalid additional information"
+		);
+		assert_eq!(
+			error(&[0xF0]).message(),
+			"unsupported simple value"
+		);
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		for open in [&[0x81][..], &[0x9F], &[0xC1], &[0xA1, 0x61, b'k']] {
+			let mut bytes = open.repeat(100_000);
+			bytes.push(0x01);
+			assert_eq!(
+				decode(&bytes).unwrap_err().message(),
+				"nesting too deep"
+			);
+		}
+		let mut bytes = vec![0x81; MAX_DEPTH - 1];
+		bytes.push(0x01);
+		assert!(decode(&bytes).is_ok());
+	}
+}
@@ -0,0 +1,155 @@
+//! Turning string-encoded scalars back into the types they stand for, for
+//! data from systems that stringify everything.
+
+use crate::{lexer::is_number, JsonValue};
+
+/// Which strings [`JsonValue::coerce`] converts. Each kind is converted by
+/// default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coercion {
+	/// Strings that are JSON number literals, such as `"42"` or `"-1.5e3"`,
+	/// become numbers. Others, such as `" 42"` or `"0x2A"`, are left alone.
+	pub numbers: bool,
+	/// `"true"` and `"false"` become booleans.
+	pub booleans: bool,
+	/// `"null"` becomes `null`.
+	pub null: bool,
+	/// `""` becomes `null`.
+	pub empty_as_null: bool,
+}
+
+impl Default for Coercion {
+	fn default() -> Self {
+		Self {
+			numbers: true,
+			booleans: true,
+			null: true,
+			empty_as_null: true,
+		}
+	}
+}
+
+impl JsonValue {
+	/// Converts every string in the tree that `rules` allow to the scalar
+	/// it spells, returning how many were converted. Object keys stay
+	/// strings.
+	///
+	/// To convert only where a schema asks for something other than a
+	/// string, use [`Schema::coerce`](crate::schema::Schema::coerce).
+	pub fn coerce(&mut self, rules: &Coercion) -> usize {
+		match self {
+			JsonValue::String(s) => match coerced(s, rules) {
+				Some(value) => {
+					*self = value;
+					1
+				}
+				None => 0,
+			},
+			JsonValue::Array(items) => {
+				items.iter_mut().map(|item| item.coerce(rules)).sum()
+			}
+			JsonValue::Object(map) => {
+				map.values_mut().map(|value| value.coerce(rules)).sum()
+			}
+			_ => 0,
+		}
+	}
+}
+
+/// The scalar `s` spells, if `rules` allow converting it.
+pub(crate) fn coerced(s: &str, rules: &Coercion) -> Option<JsonValue> {
+	match s {
+		"true" | "false" if rules.booleans => {
+			Some(JsonValue::Boolean(s == "true"))
+		}
+		"null" if rules.null => Some(JsonValue::Null),
+		"" if rules.empty_as_null => Some(JsonValue::Null),
+		_ if rules.numbers && is_number(s) => s
+			.parse()
+			.ok()
+			.filter(|n: &f64| n.is_finite())
+			.map(JsonValue::Number),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn coerce(input: &str, rules: &Coercion) -> (JsonValue, usize) {
+		let mut value = parse(input).unwrap();
+		let converted = value.coerce(rules);
+		(value, converted)
+	}
+
+	#[test]
+	fn converts_scalars() {
+		let (value, converted) = coerce(
+			r#"{"n": "42", "f": "-1.5e3", "t": "true", "u": "false",
+				"z": "null", "e": "", "a": ["7", {"x": "0"}]}"#,
+			&Coercion::default(),
+		);
+		assert_eq!(converted, 8);
+		assert_eq!(
+			value,
+			parse(
+				r#"{"n": 42, "f": -1500, "t": true, "u": false, "z": null,
+					"e": null, "a": [7, {"x": 0}]}"#
+			)
+			.unwrap()
+		);
+	}
+
+	#[test]
+	fn leaves_other_strings_alone() {
+		for input in [
+			r#"" 42""#,
+			r#""0x2A""#,
+			r#""+1""#,
+			r#""01""#,
+			r#""1e400""#,
+			r#""True""#,
+			r#""nan""#,
+		] {
+			let (value, converted) = coerce(input, &Coercion::default());
+			assert_eq!(converted, 0, "{input}");
+			assert_eq!(value, parse(input).unwrap());
+		}
+		let (value, _) = coerce(r#"{"42": "x"}"#, &Coercion::default());
+		assert_eq!(value, parse(r#"{"42": "x"}"#).unwrap());
+	}
+
+	#[test]
+	fn rules_may_be_disabled() {
+		let input = r#"["1", "true", "null", ""]"#;
+		let off = |rules: Coercion| coerce(input, &rules).0;
+		let all = Coercion::default();
+		assert_eq!(
+			off(Coercion {
+				numbers: false,
+				..all
+			}),
+			parse(r#"["1", true, null, null]"#).unwrap()
+		);
+		assert_eq!(
+			off(Coercion {
+				booleans: false,
+				..all
+			}),
+			parse(r#"[1, "true", null, null]"#).unwrap()
+		);
+		assert_eq!(
+			off(Coercion { null: false, ..all }),
+			parse(r#"[1, true, "null", null]"#).unwrap()
+		);
+		assert_eq!(
+			off(Coercion {
+				empty_as_null: false,
+				..all
+			}),
+			parse(r#"[1, true, null, ""]"#).unwrap()
+		);
+	}
+}
@@ -0,0 +1,199 @@
+//! Syntax highlighting of JSON text, with ANSI escape codes for terminals
+//! or as classified spans for other frontends to style.
+
+use std::{fmt::Write, ops::Range};
+
+use crate::{
+	lexer::{tokenize, TokenKind},
+	JsonValue,
+};
+
+/// The colors given to each kind of token, as the parameters of an ANSI
+/// SGR escape sequence such as `"1;34"` for bold blue. An empty string
+/// leaves that kind uncolored.
+///
+/// The default matches jq's colors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Palette {
+	pub null: String,
+	pub boolean: String,
+	pub number: String,
+	pub string: String,
+	/// Object keys, which are otherwise strings.
+	pub key: String,
+	/// Brackets, colons and commas.
+	pub punctuation: String,
+}
+
+impl Default for Palette {
+	fn default() -> Self {
+		Self {
+			null: "1;30".to_owned(),
+			boolean: "0;39".to_owned(),
+			number: "0;39".to_owned(),
+			string: "0;32".to_owned(),
+			key: "34;1".to_owned(),
+			punctuation: "1;39".to_owned(),
+		}
+	}
+}
+
+impl Palette {
+	/// Colors the tokens of `json`, leaving whitespace and anything that
+	/// is not JSON as it is.
+	pub fn paint(&self, json: &str) -> String {
+		let mut out = String::with_capacity(json.len() * 2);
+		let mut pos = 0;
+		for (span, class) in highlight(json) {
+			let color = match class {
+				TokenClass::Null => &self.null,
+				TokenClass::Boolean => &self.boolean,
+				TokenClass::Number => &self.number,
+				TokenClass::String => &self.string,
+				TokenClass::Key => &self.key,
+				TokenClass::Punctuation => &self.punctuation,
+				TokenClass::Comment | TokenClass::Error => "",
+			};
+			if color.is_empty() {
+				continue;
+			}
+			out.push_str(&json[pos..span.start]);
+			let _ = write!(
+				out,
+				"\x1b[{color}m{}\x1b[0m",
+				&json[span.clone()]
+			);
+			pos = span.end;
+		}
+		out.push_str(&json[pos..]);
+		out
+	}
+}
+
+/// The kind of styling a span of JSON text gets, from [`highlight`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenClass {
+	Null,
+	Boolean,
+	Number,
+	String,
+	/// A string that is an object key.
+	Key,
+	/// Brackets, colons and commas.
+	Punctuation,
+	Comment,
+	/// Text that is not JSON, such as a stray character or an unterminated
+	/// string.
+	Error,
+}
+
+/// Classifies the tokens of `json` for a frontend to style with a theme of
+/// its own, giving the byte span of each token in order. Whitespace is left
+/// out, and nothing else is.
+///
+/// A string is a key when the next token other than whitespace or a comment
+/// is a colon, so the text need not be well-formed.
+pub fn highlight(json: &str) -> Vec<(Range<usize>, TokenClass)> {
+	let tokens: Vec<_> = tokenize(json).collect();
+	tokens
+		.iter()
+		.enumerate()
+		.filter_map(|(i, token)| {
+			let class = match token.kind {
+				TokenKind::Whitespace => return None,
+				TokenKind::String => {
+					let next = tokens[i + 1..]
+						.iter()
+						.find(|token| !token.kind.is_trivia());
+					match next {
+						Some(next) if next.kind == TokenKind::Colon => {
+							TokenClass::Key
+						}
+						_ => TokenClass::String,
+					}
+				}
+				TokenKind::Number => TokenClass::Number,
+				TokenKind::True | TokenKind::False => TokenClass::Boolean,
+				TokenKind::Null => TokenClass::Null,
+				TokenKind::StartObject
+				| TokenKind::EndObject
+				| TokenKind::StartArray
+				| TokenKind::EndArray
+				| TokenKind::Colon
+				| TokenKind::Comma => TokenClass::Punctuation,
+				TokenKind::LineComment | TokenKind::BlockComment => {
+					TokenClass::Comment
+				}
+				TokenKind::Error => TokenClass::Error,
+			};
+			Some((token.span.clone(), class))
+		})
+		.collect()
+}
+
+impl JsonValue {
+	/// Formats the value as [`JsonValue::to_string_pretty`] does, colored
+	/// with the default [`Palette`] for display in a terminal.
+	pub fn to_string_colored(&self) -> String {
+		Palette::default().paint(&self.to_string_pretty())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn paint_colors_each_token() {
+		let palette = Palette {
+			null: "n".into(),
+			boolean: "b".into(),
+			number: "#".into(),
+			string: "s".into(),
+			key: "k".into(),
+			punctuation: String::new(),
+		};
+		assert_eq!(
+			palette.paint(r#"{"a": [1, true, null, "x"]} @"#),
+			"{\x1b[km\"a\"\x1b[0m: [\x1b[#m1\x1b[0m, \x1b[bmtrue\x1b[0m, \
+			 \x1b[nmnull\x1b[0m, \x1b[sm\"x\"\x1b[0m]} @"
+		);
+		let value = crate::parse(r#"{"k": "v"}"#).unwrap();
+		assert_eq!(
+			value.to_string_colored(),
+			"\x1b[1;39m{\x1b[0m\n  \x1b[34;1m\"k\"\x1b[0m\x1b[1;39m:\
+			 \x1b[0m \x1b[0;32m\"v\"\x1b[0m\n\x1b[1;39m}\x1b[0m"
+		);
+	}
+
+	#[test]
+	fn highlight_classifies_every_token_but_whitespace() {
+		use TokenClass::*;
+		let json = "{\"k\" /* c */ : [\"v\", 1e2, false], \"open\n}";
+		let classes: Vec<_> = highlight(json)
+			.into_iter()
+			.map(|(span, class)| (&json[span], class))
+			.collect();
+		assert_eq!(
+			classes,
+			[
+				("{", Punctuation),
+				("\"k\"", Key),
+				("/* c */", Comment),
+				(":", Punctuation),
+				("[", Punctuation),
+				("\"v\"", String),
+				(",", Punctuation),
+				("1e2", Number),
+				(",", Punctuation),
+				("false", Boolean),
+				("]", Punctuation),
+				(",", Punctuation),
+				("\"open", Error),
+				("}", Punctuation),
+			]
+		);
+		assert_eq!(highlight(r#"["k": 1]"#)[1].1, Key);
+		assert!(highlight(" \n\t").is_empty());
+	}
+}
@@ -0,0 +1,308 @@
+use std::{collections::HashMap, convert::Infallible, error, fmt};
+
+use crate::{JsonPointer, JsonType, JsonValue};
+
+macro_rules! from_number {
+	($($ty:ty),*) => {
+		$(
+			impl From<$ty> for JsonValue {
+				fn from(n: $ty) -> Self { JsonValue::Number(n as f64) }
+			}
+		)*
+	};
+}
+
+from_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+impl From<bool> for JsonValue {
+	fn from(b: bool) -> Self {
+		JsonValue::Boolean(b)
+	}
+}
+
+impl From<&str> for JsonValue {
+	fn from(s: &str) -> Self {
+		JsonValue::String(s.to_owned())
+	}
+}
+
+impl From<String> for JsonValue {
+	fn from(s: String) -> Self {
+		JsonValue::String(s)
+	}
+}
+
+impl<T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+	fn from(items: Vec<T>) -> Self {
+		JsonValue::Array(items.into_iter().map(Into::into).collect())
+	}
+}
+
+impl From<HashMap<String, JsonValue>> for JsonValue {
+	fn from(map: HashMap<String, JsonValue>) -> Self {
+		JsonValue::Object(Box::new(map))
+	}
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+	fn from(option: Option<T>) -> Self {
+		option.map_or(JsonValue::Null, Into::into)
+	}
+}
+
+/// The error returned when a [`JsonValue`] has the wrong shape for the
+/// requested Rust type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+	pub expected: &'static str,
+	pub found: JsonType,
+}
+
+impl fmt::Display for ConversionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"expected {}, found {}",
+			self.expected, self.found
+		)
+	}
+}
+
+impl error::Error for ConversionError {}
+
+/// For extracting a [`JsonValue`] itself, which takes any shape.
+impl From<Infallible> for ConversionError {
+	fn from(never: Infallible) -> Self {
+		match never {}
+	}
+}
+
+fn mismatch(expected: &'static str, value: &JsonValue) -> ConversionError {
+	ConversionError {
+		expected,
+		found: value.type_of(),
+	}
+}
+
+macro_rules! try_from_integer {
+	($($ty:ty),*) => {
+		$(
+			impl TryFrom<JsonValue> for $ty {
+				type Error = ConversionError;
+
+				fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+					// One past the largest value is a power of two, so it
+					// converts exactly, where `MAX as f64` itself rounds up
+					// to that for the 64-bit types.
+					match value {
+						JsonValue::Number(n)
+							if n.fract() == 0.0
+								&& n >= <$ty>::MIN as f64
+								&& n < <$ty>::MAX as f64 + 1.0 =>
+						{
+							Ok(n as $ty)
+						},
+						_ => Err(mismatch(stringify!($ty), &value)),
+					}
+				}
+			}
+		)*
+	};
+}
+
+try_from_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl TryFrom<JsonValue> for f64 {
+	type Error = ConversionError;
+
+	fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+		match value {
+			JsonValue::Number(n) => Ok(n),
+			_ => Err(mismatch("number", &value)),
+		}
+	}
+}
+
+impl TryFrom<JsonValue> for bool {
+	type Error = ConversionError;
+
+	fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+		match value {
+			JsonValue::Boolean(b) => Ok(b),
+			_ => Err(mismatch("boolean", &value)),
+		}
+	}
+}
+
+impl TryFrom<JsonValue> for String {
+	type Error = ConversionError;
+
+	fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+		match value {
+			JsonValue::String(s) => Ok(s),
+			_ => Err(mismatch("string", &value)),
+		}
+	}
+}
+
+impl TryFrom<JsonValue> for Vec<JsonValue> {
+	type Error = ConversionError;
+
+	fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+		match value {
+			JsonValue::Array(items) => Ok(items),
+			_ => Err(mismatch("array", &value)),
+		}
+	}
+}
+
+impl TryFrom<JsonValue> for HashMap<String, JsonValue> {
+	type Error = ConversionError;
+
+	fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+		match value {
+			JsonValue::Object(map) => Ok(*map),
+			_ => Err(mismatch("object", &value)),
+		}
+	}
+}
+
+/// The error returned by [`JsonValue::extract`], carrying the pointer that
+/// failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractError {
+	pub pointer: String,
+	pub kind: ExtractErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractErrorKind {
+	InvalidPointer,
+	Missing,
+	/// The value was found but has the wrong shape for the requested type.
+	Conversion(ConversionError),
+}
+
+impl fmt::Display for ExtractError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.kind {
+			ExtractErrorKind::InvalidPointer => {
+				write!(
+					f,
+					"invalid JSON pointer {:?}",
+					self.pointer
+				)
+			}
+			ExtractErrorKind::Missing => {
+				write!(
+					f,
+					"missing value at \"{}\"",
+					self.pointer
+				)
+			}
+			ExtractErrorKind::Conversion(e) => {
+				write!(f, "{e} at \"{}\"", self.pointer)
+			}
+		}
+	}
+}
+
+impl error::Error for ExtractError {}
+
+impl JsonValue {
+	/// Looks up `pointer` and converts the value found there into `T`.
+	///
+	/// `T` is any type with a `TryFrom<JsonValue>` conversion whose error
+	/// converts into a [`ConversionError`], as those of this crate are.
+	pub fn extract<T>(&self, pointer: &str) -> Result<T, ExtractError>
+	where
+		T: TryFrom<JsonValue>,
+		T::Error: Into<ConversionError>,
+	{
+		let parsed = JsonPointer::parse(pointer).map_err(|_| ExtractError {
+			pointer: pointer.to_owned(),
+			kind: ExtractErrorKind::InvalidPointer,
+		})?;
+		self.extract_at(&parsed)
+	}
+
+	pub fn extract_at<T>(
+		&self,
+		pointer: &JsonPointer,
+	) -> Result<T, ExtractError>
+	where
+		T: TryFrom<JsonValue>,
+		T::Error: Into<ConversionError>,
+	{
+		let error = |kind| ExtractError {
+			pointer: pointer.to_string(),
+			kind,
+		};
+		let value = self
+			.resolve(pointer)
+			.ok_or_else(|| error(ExtractErrorKind::Missing))?;
+		T::try_from(value.clone())
+			.map_err(|e| error(ExtractErrorKind::Conversion(e.into())))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn integer_bounds() {
+		let number = JsonValue::Number;
+		assert_eq!(u8::try_from(number(255.0)), Ok(255));
+		assert!(u8::try_from(number(256.0)).is_err());
+		assert_eq!(i8::try_from(number(-128.0)), Ok(-128));
+		assert!(i8::try_from(number(-129.0)).is_err());
+		assert_eq!(
+			u32::try_from(number(4_294_967_295.0)),
+			Ok(u32::MAX)
+		);
+		assert!(i64::try_from(number(9_223_372_036_854_775_808.0)).is_err());
+		assert!(isize::try_from(number(9_223_372_036_854_775_808.0)).is_err());
+		assert_eq!(
+			i64::try_from(number(-9_223_372_036_854_775_808.0)),
+			Ok(i64::MIN)
+		);
+		assert!(u64::try_from(number(18_446_744_073_709_551_616.0)).is_err());
+		assert!(usize::try_from(number(18_446_744_073_709_551_616.0)).is_err());
+		assert!(u64::try_from(number(0.5)).is_err());
+	}
+
+	#[test]
+	fn extract_reports_where_and_why() {
+		let value = crate::parse(r#"{"a": [1, "two"], "b": null}"#).unwrap();
+		assert_eq!(value.extract::<u8>("/a/0"), Ok(1));
+		assert_eq!(
+			value.extract::<JsonValue>("/b"),
+			Ok(JsonValue::Null)
+		);
+
+		let error = value.extract::<u8>("/a/1").unwrap_err();
+		assert_eq!(
+			error,
+			ExtractError {
+				pointer: "/a/1".to_owned(),
+				kind: ExtractErrorKind::Conversion(ConversionError {
+					expected: "u8",
+					found: JsonType::String,
+				}),
+			}
+		);
+		assert_eq!(
+			error.to_string(),
+			r#"expected u8, found string at "/a/1""#
+		);
+		assert_eq!(
+			value.extract::<u8>("/a/2").unwrap_err().kind,
+			ExtractErrorKind::Missing
+		);
+		assert_eq!(
+			value.extract::<u8>("a").unwrap_err().kind,
+			ExtractErrorKind::InvalidPointer
+		);
+	}
+}
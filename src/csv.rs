@@ -0,0 +1,345 @@
+//! Conversion between [CSV](https://www.rfc-editor.org/rfc/rfc4180) tables
+//! and arrays of flat objects.
+
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::Write,
+	io::Read,
+	str,
+};
+
+use crate::{ConversionError, Error, JsonValue, ReadError};
+
+/// Reads a CSV table whose first row names its columns, giving an array
+/// with an object per further row that maps each column name to the row's
+/// field in it.
+///
+/// Fields are always strings; nothing is guessed about what they hold.
+/// Quoted fields may contain commas, doubled quotes and line breaks, rows
+/// may end in `\n` or `\r\n`, and empty lines are skipped. Every row must
+/// have as many fields as the header, which may not name a column twice.
+pub fn from_csv(mut reader: impl Read) -> Result<JsonValue, ReadError> {
+	let mut bytes = Vec::new();
+	reader.read_to_end(&mut bytes)?;
+	let text = str::from_utf8(&bytes).map_err(ReadError::Utf8)?;
+	parse(text).map_err(ReadError::Parse)
+}
+
+fn parse(input: &str) -> Result<JsonValue, Error> {
+	let mut rows = Rows { input, pos: 0 };
+	let Some((_, header)) = rows.next().transpose()? else {
+		return Ok(JsonValue::Array(Vec::new()));
+	};
+	let mut names = HashSet::new();
+	for name in &header {
+		if !names.insert(name) {
+			return Err(Error::new(
+				input,
+				0,
+				format!("column {name:?} is named twice"),
+			));
+		}
+	}
+	let mut items = Vec::new();
+	for row in rows {
+		let (start, fields) = row?;
+		if fields.len() != header.len() {
+			return Err(Error::new(
+				input,
+				start,
+				format!(
+					"expected {} fields as in the header, found {}",
+					header.len(),
+					fields.len()
+				),
+			));
+		}
+		let object: HashMap<_, _> = header
+			.iter()
+			.cloned()
+			.zip(fields.into_iter().map(JsonValue::String))
+			.collect();
+		items.push(object.into());
+	}
+	Ok(JsonValue::Array(items))
+}
+
+/// The non-empty rows of a table, each with its offset.
+struct Rows<'i> {
+	input: &'i str,
+	pos: usize,
+}
+
+impl Iterator for Rows<'_> {
+	type Item = Result<(usize, Vec<String>), Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let rest = &self.input[self.pos..];
+			let blank = if rest.starts_with("\r\n") {
+				2
+			} else {
+				usize::from(rest.starts_with('\n'))
+			};
+			if blank == 0 {
+				break;
+			}
+			self.pos += blank;
+		}
+		if self.pos == self.input.len() {
+			return None;
+		}
+		let start = self.pos;
+		Some(self.row().map(|fields| (start, fields)))
+	}
+}
+
+impl Rows<'_> {
+	/// Reads a row and the line break ending it.
+	fn row(&mut self) -> Result<Vec<String>, Error> {
+		let mut fields = Vec::new();
+		loop {
+			fields.push(self.field()?);
+			let rest = &self.input[self.pos..];
+			if let Some(after) = rest.strip_prefix(',') {
+				self.pos = self.input.len() - after.len();
+				continue;
+			}
+			if rest.starts_with("\r\n") {
+				self.pos += 2;
+			} else if rest.starts_with('\n') {
+				self.pos += 1;
+			}
+			return Ok(fields);
+		}
+	}
+
+	fn field(&mut self) -> Result<String, Error> {
+		let rest = &self.input[self.pos..];
+		let Some(quoted) = rest.strip_prefix('"') else {
+			let len = rest.find([',', '\n']).unwrap_or(rest.len());
+			self.pos += len;
+			let field = &rest[..len];
+			let field = if len == rest.len() || rest[len..].starts_with('\n') {
+				field.strip_suffix('\r').unwrap_or(field)
+			} else {
+				field
+			};
+			return Ok(field.to_owned());
+		};
+		let start = self.pos;
+		let mut field = String::new();
+		let mut rest = quoted;
+		loop {
+			let Some(quote) = rest.find('"') else {
+				return Err(Error::new(
+					self.input,
+					start,
+					"unterminated quoted field".to_owned(),
+				));
+			};
+			field.push_str(&rest[..quote]);
+			rest = &rest[quote + 1..];
+			match rest.strip_prefix('"') {
+				Some(after) => {
+					field.push('"');
+					rest = after;
+				}
+				None => break,
+			}
+		}
+		self.pos = self.input.len() - rest.len();
+		if !(rest.is_empty()
+			|| rest.starts_with([',', '\n'])
+			|| rest.starts_with("\r\n"))
+		{
+			return Err(Error::new(
+				self.input,
+				self.pos,
+				"expected a comma or line break after a quoted field"
+					.to_owned(),
+			));
+		}
+		Ok(field)
+	}
+}
+
+impl JsonValue {
+	/// Writes an array of flat objects as a CSV table, with a column for
+	/// every key that appears in any of them, ordered by name.
+	///
+	/// Strings are written as they are and other scalars as JSON, except
+	/// that `null` and missing members leave their field empty. Fields are
+	/// quoted only when they hold a comma, quote or line break, and rows end
+	/// in `\n`.
+	///
+	/// Fails if the value is not an array of objects, or a member holds an
+	/// array or object.
+	pub fn to_csv(&self) -> Result<String, ConversionError> {
+		let mismatch = |expected, value: &JsonValue| ConversionError {
+			expected,
+			found: value.type_of(),
+		};
+		let JsonValue::Array(items) = self else {
+			return Err(mismatch("array", self));
+		};
+		let mut rows = Vec::with_capacity(items.len());
+		for item in items {
+			let JsonValue::Object(map) = item else {
+				return Err(mismatch("object", item));
+			};
+			if let Some(value) = map.values().find(|value| {
+				matches!(
+					value,
+					JsonValue::Array(_) | JsonValue::Object(_)
+				)
+			}) {
+				return Err(mismatch("scalar", value));
+			}
+			rows.push(map);
+		}
+		let mut columns: Vec<_> =
+			rows.iter().flat_map(|map| map.keys()).collect();
+		columns.sort_unstable();
+		columns.dedup();
+
+		let mut out = String::new();
+		write_row(
+			&mut out,
+			columns.iter().map(|column| column.as_str()),
+		);
+		for map in rows {
+			let fields: Vec<_> = columns
+				.iter()
+				.map(|&column| match map.get(column) {
+					None | Some(JsonValue::Null) => String::new(),
+					Some(JsonValue::String(s)) => s.clone(),
+					Some(value) => value.to_string(),
+				})
+				.collect();
+			write_row(
+				&mut out,
+				fields.iter().map(String::as_str),
+			);
+		}
+		Ok(out)
+	}
+}
+
+fn write_row<'a>(out: &mut String, fields: impl Iterator<Item = &'a str>) {
+	for (i, field) in fields.enumerate() {
+		if i > 0 {
+			out.push(',');
+		}
+		if field.contains([',', '"', '\r', '\n']) {
+			let _ = write!(
+				out,
+				"\"{}\"",
+				field.replace('"', "\"\"")
+			);
+		} else {
+			out.push_str(field);
+		}
+	}
+	out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse as json;
+
+	fn table(csv: &str) -> Result<JsonValue, ReadError> {
+		from_csv(csv.as_bytes())
+	}
+
+	#[track_caller]
+	fn error(csv: &str) -> Error {
+		match table(csv) {
+			Err(ReadError::Parse(e)) => e,
+			other => panic!("{csv:?}: {other:?}"),
+		}
+	}
+
+	#[test]
+	fn reads_tables() {
+		assert_eq!(
+			table("name,note\r\nann,\"a, \"\"b\"\"\nc\"\n\nbob,\r\n").unwrap(),
+			json(
+				r#"[{"name": "ann", "note": "a, \"b\"\nc"},
+				{"name": "bob", "note": ""}]"#
+			)
+			.unwrap()
+		);
+		assert_eq!(table("").unwrap(), json("[]").unwrap());
+		assert_eq!(
+			table("a,b\n").unwrap(),
+			json("[]").unwrap()
+		);
+		assert_eq!(
+			table("a\n1\r").unwrap(),
+			json(r#"[{"a": "1"}]"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_tables() {
+		let e = error("a,b\n1,2\n3\n");
+		assert_eq!(
+			e.message(),
+			"expected 2 fields as in the header, found 1"
+		);
+		assert_eq!(e.offset(), 8);
+		assert_eq!(
+			error("a,a\n").message(),
+			r#"column "a" is named twice"#
+		);
+		assert_eq!(
+			error("a\n\"open\n").message(),
+			"unterminated quoted field"
+		);
+		assert_eq!(
+			error("a\n\"x\"y\n").message(),
+			"expected a comma or line break after a quoted field"
+		);
+		assert!(matches!(
+			from_csv(&b"a\n\xFF"[..]),
+			Err(ReadError::Utf8(_))
+		));
+	}
+
+	#[test]
+	fn wide_headers_are_checked_in_linear_time() {
+		let header: Vec<String> = (0..100_000).map(|i| i.to_string()).collect();
+		let csv = header.join(",") + "\n";
+		assert_eq!(
+			table(&csv).unwrap(),
+			json("[]").unwrap()
+		);
+		let e = error(&(csv.trim_end().to_owned() + ",99999\n"));
+		assert_eq!(
+			e.message(),
+			r#"column "99999" is named twice"#
+		);
+	}
+
+	#[test]
+	fn round_trips() {
+		let value = json(
+			r#"[{"a": "1", "b": "x,y"}, {"a": "say \"hi\"", "b": "two\nlines"},
+			{"a": "", "b": "\r"}]"#,
+		)
+		.unwrap();
+		let csv = value.to_csv().unwrap();
+		assert_eq!(table(&csv).unwrap(), value, "{csv}");
+
+		let value = json(r#"[{"b": 1.5, "a": null}, {"c": true}]"#).unwrap();
+		assert_eq!(
+			value.to_csv().unwrap(),
+			"a,b,c\n,1.5,\n,,true\n"
+		);
+		assert!(json("{}").unwrap().to_csv().is_err());
+		assert!(json("[1]").unwrap().to_csv().is_err());
+		assert!(json(r#"[{"a": [1]}]"#).unwrap().to_csv().is_err());
+	}
+}
@@ -0,0 +1,172 @@
+//! Step-by-step navigation of a tree, remembering the way back up.
+
+use crate::{JsonPointer, JsonValue};
+
+/// A position in a tree that can move into children and back out, so
+/// navigation reads as a sequence of steps instead of nested `match`es.
+///
+/// Moves return the cursor to chain further moves with `?`. A move that
+/// fails leaves the cursor where it is.
+///
+/// ```text
+/// let mut cursor = value.cursor();
+/// cursor.enter_key("users")?.enter_index(0)?.enter_key("name")?;
+/// assert_eq!(cursor.path().to_string(), "/users/0/name");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+	/// The values from the root down to the current one.
+	stack: Vec<&'a JsonValue>,
+	path: JsonPointer,
+}
+
+impl<'a> Cursor<'a> {
+	/// A cursor at `root`.
+	pub fn new(root: &'a JsonValue) -> Self {
+		Self {
+			stack: vec![root],
+			path: JsonPointer::root(),
+		}
+	}
+
+	/// The value the cursor is at.
+	pub fn value(&self) -> &'a JsonValue {
+		self.stack[self.stack.len() - 1]
+	}
+
+	/// The location of the current value from the root.
+	pub fn path(&self) -> &JsonPointer {
+		&self.path
+	}
+
+	pub fn is_root(&self) -> bool {
+		self.stack.len() == 1
+	}
+
+	/// Moves to the value of the current object's member `key`.
+	pub fn enter_key(&mut self, key: &str) -> Option<&mut Self> {
+		let child = self.value().as_object()?.get(key)?;
+		self.enter(key.to_owned(), child);
+		Some(self)
+	}
+
+	/// Moves to item `index` of the current array.
+	pub fn enter_index(&mut self, index: usize) -> Option<&mut Self> {
+		let child = self.value().as_array()?.get(index)?;
+		self.enter(index.to_string(), child);
+		Some(self)
+	}
+
+	/// Moves to the array or object holding the current value, unless the
+	/// cursor is at the root.
+	pub fn parent(&mut self) -> Option<&mut Self> {
+		if self.is_root() {
+			return None;
+		}
+		self.stack.pop();
+		self.path.pop();
+		Some(self)
+	}
+
+	/// Cursors at the other items or member values of the container
+	/// holding the current value: in order for an array, in no particular
+	/// order for an object. There are none at the root.
+	pub fn siblings(&self) -> Vec<Cursor<'a>> {
+		let mut parent = self.clone();
+		if parent.parent().is_none() {
+			return Vec::new();
+		}
+		let own = self.path.tokens().last();
+		let children: Vec<_> = match parent.value() {
+			JsonValue::Array(items) => items
+				.iter()
+				.enumerate()
+				.map(|(i, item)| (i.to_string(), item))
+				.collect(),
+			JsonValue::Object(map) => map
+				.iter()
+				.map(|(key, value)| (key.clone(), value))
+				.collect(),
+			_ => Vec::new(),
+		};
+		children
+			.into_iter()
+			.filter(|(token, _)| Some(token) != own)
+			.map(|(token, child)| {
+				let mut sibling = parent.clone();
+				sibling.enter(token, child);
+				sibling
+			})
+			.collect()
+	}
+
+	fn enter(&mut self, token: String, child: &'a JsonValue) {
+		self.stack.push(child);
+		self.path.push(token);
+	}
+}
+
+impl JsonValue {
+	/// A [`Cursor`] at this value.
+	pub fn cursor(&self) -> Cursor<'_> {
+		Cursor::new(self)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::parse;
+
+	#[test]
+	fn cursors_move_down_and_back_up() {
+		let value =
+			parse(r#"{"users": [{"name": "a"}, {"name": "b"}]}"#).unwrap();
+		let mut cursor = value.cursor();
+		assert!(cursor.is_root());
+		cursor
+			.enter_key("users")
+			.and_then(|c| c.enter_index(1))
+			.and_then(|c| c.enter_key("name"))
+			.unwrap();
+		assert_eq!(
+			cursor.path().to_string(),
+			"/users/1/name"
+		);
+		assert_eq!(cursor.value(), &"b".into());
+
+		assert!(cursor.enter_key("x").is_none());
+		assert!(cursor.parent().unwrap().enter_index(0).is_none());
+		assert!(cursor.enter_key("missing").is_none());
+		assert_eq!(cursor.path().to_string(), "/users/1");
+
+		cursor.parent().unwrap().parent().unwrap();
+		assert!(cursor.is_root());
+		assert!(cursor.parent().is_none());
+		assert_eq!(cursor.value(), &value);
+	}
+
+	#[test]
+	fn siblings_are_the_other_children() {
+		let value = parse(r#"{"list": [1, 2, 3], "other": null}"#).unwrap();
+		let mut cursor = value.cursor();
+		cursor.enter_key("list").unwrap().enter_index(1).unwrap();
+		let siblings: Vec<_> = cursor
+			.siblings()
+			.iter()
+			.map(|s| (s.path().to_string(), s.value().clone()))
+			.collect();
+		assert_eq!(
+			siblings,
+			[
+				("/list/0".to_owned(), 1.into()),
+				("/list/2".to_owned(), 3.into())
+			]
+		);
+
+		cursor.parent().unwrap();
+		let siblings = cursor.siblings();
+		assert_eq!(siblings.len(), 1);
+		assert_eq!(siblings[0].path().to_string(), "/other");
+		assert!(value.cursor().siblings().is_empty());
+	}
+}
@@ -0,0 +1,633 @@
+//! A document model for language servers, which keeps a syntax tree of
+//! whatever text it is given, however malformed, along with diagnostics
+//! saying what is wrong with it.
+
+use std::{iter::Peekable, mem, ops::Range};
+
+use crate::{
+	lexer::{number_error, tokenize, Token, TokenKind, Tokens},
+	unescape_json_string, Comments, JsonPointer, LineIndex, Member, Node,
+	NodeKind, Position, TextEdit,
+};
+
+/// How deeply arrays and objects may nest, so hostile input cannot exhaust
+/// the stack.
+const MAX_DEPTH: usize = 512;
+
+/// A problem found in a document, and the bytes of it concerned.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+	pub span: Range<usize>,
+	pub message: String,
+}
+
+/// The text of a document being edited, always with a tree of it.
+///
+/// The tree gives the structure that could be made out and the
+/// diagnostics say where the text departs from JSON: values that are
+/// missing, such as after a colon, are `null` with an empty span, ones
+/// that cannot be read are `null` spanning their text, strings that do
+/// not end run to the end of their line, and unclosed arrays and objects
+/// end at a closing bracket of the other kind or the end of the text. A
+/// document
+/// without diagnostics is valid JSON, and its tree is what
+/// [`parse_spanned`](crate::parse_spanned) gives.
+#[derive(Debug, Clone)]
+pub struct Document {
+	text: String,
+	lines: LineIndex,
+	root: Node,
+	diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+	pub fn new(text: impl Into<String>) -> Self {
+		let text = text.into();
+		let (root, diagnostics) = recover(&text);
+		Self {
+			lines: LineIndex::new(&text),
+			text,
+			root,
+			diagnostics,
+		}
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	pub fn root(&self) -> &Node {
+		&self.root
+	}
+
+	/// The problems with the text, ordered by position.
+	pub fn diagnostics(&self) -> &[Diagnostic] {
+		&self.diagnostics
+	}
+
+	pub fn lines(&self) -> &LineIndex {
+		&self.lines
+	}
+
+	/// The position of a byte offset into the text.
+	pub fn position(&self, offset: usize) -> Position {
+		self.lines.position(&self.text, offset)
+	}
+
+	/// The byte offset of a position in the text.
+	pub fn offset(&self, position: Position) -> usize {
+		self.lines.offset(&self.text, position)
+	}
+
+	/// Changes the text, bringing the tree up to date with
+	/// [`Node::reparse`] while the text is valid and parsing it all again
+	/// otherwise.
+	pub fn edit(&mut self, edit: &TextEdit) {
+		let root = mem::replace(
+			&mut self.root,
+			Node {
+				span: 0..0,
+				kind: NodeKind::Null,
+				comments: Comments::default(),
+			},
+		);
+		let reparsed = if self.diagnostics.is_empty() {
+			root.reparse(&mut self.text, edit).ok()
+		} else {
+			self.text
+				.replace_range(edit.range.clone(), &edit.replacement);
+			None
+		};
+		(self.root, self.diagnostics) = match reparsed {
+			Some(root) => (root, Vec::new()),
+			None => recover(&self.text),
+		};
+		self.lines = LineIndex::new(&self.text);
+	}
+
+	/// The innermost node whose span holds the byte at `offset`, where the
+	/// key of a member counts as part of its value.
+	pub fn node_at_offset(&self, offset: usize) -> Option<&Node> {
+		self.locate(offset).map(|(_, node)| node)
+	}
+
+	/// The pointer to the node that [`Document::node_at_offset`] finds.
+	pub fn path_at_offset(&self, offset: usize) -> Option<JsonPointer> {
+		self.locate(offset).map(|(path, _)| path)
+	}
+
+	fn locate(&self, offset: usize) -> Option<(JsonPointer, &Node)> {
+		if !self.root.span.contains(&offset) {
+			return None;
+		}
+		let mut path = JsonPointer::root();
+		let mut node = &self.root;
+		loop {
+			let child = match &node.kind {
+				NodeKind::Array(items) => items
+					.iter()
+					.position(|item| item.span.contains(&offset))
+					.map(|i| (i.to_string(), &items[i])),
+				NodeKind::Object(members) => members
+					.iter()
+					.find(|member| {
+						member.key_span.contains(&offset)
+							|| member.value.span.contains(&offset)
+					})
+					.map(|member| (member.key.clone(), &member.value)),
+				_ => None,
+			};
+			let Some((token, child)) = child else {
+				return Some((path, node));
+			};
+			path.push(token);
+			node = child;
+		}
+	}
+}
+
+/// Parses as much of `input` as can be made out.
+fn recover(input: &str) -> (Node, Vec<Diagnostic>) {
+	let mut recovery = Recovery {
+		input,
+		tokens: tokenize(input).peekable(),
+		depth: 0,
+		diagnostics: Vec::new(),
+	};
+	let root = recovery.value();
+	if let Some(token) = recovery.peek() {
+		let span = token.span.start..input.len();
+		recovery.diagnose(span, "expected the end of the document");
+	}
+	let mut diagnostics = recovery.diagnostics;
+	diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+	(root, diagnostics)
+}
+
+struct Recovery<'i> {
+	input: &'i str,
+	tokens: Peekable<Tokens<'i>>,
+	/// The number of arrays and objects being parsed.
+	depth: usize,
+	diagnostics: Vec<Diagnostic>,
+}
+
+impl Recovery<'_> {
+	fn diagnose(&mut self, span: Range<usize>, message: impl Into<String>) {
+		self.diagnostics.push(Diagnostic {
+			span,
+			message: message.into(),
+		});
+	}
+
+	/// The next token that is not trivia, reporting any comments before it.
+	fn peek(&mut self) -> Option<Token> {
+		while let Some(token) =
+			self.tokens.next_if(|token| token.kind.is_trivia())
+		{
+			if token.kind != TokenKind::Whitespace {
+				self.diagnose(
+					token.span,
+					"comments are not allowed in JSON",
+				);
+			}
+		}
+		self.tokens.peek().cloned()
+	}
+
+	fn next(&mut self) -> Option<Token> {
+		self.peek()?;
+		self.tokens.next()
+	}
+
+	/// Where the next token starts.
+	fn here(&mut self) -> usize {
+		self.peek().map_or(self.input.len(), |token| {
+			token.span.start
+		})
+	}
+
+	/// Reads a value, which is `null` if there is none.
+	fn value(&mut self) -> Node {
+		let Some(token) = self.peek() else {
+			let end = self.input.len();
+			self.diagnose(end..end, "expected a value");
+			return null(end..end);
+		};
+		let text = &self.input[token.span.clone()];
+		let kind = match token.kind {
+			TokenKind::Comma | TokenKind::EndArray | TokenKind::EndObject => {
+				let start = token.span.start;
+				self.diagnose(start..start, "expected a value");
+				return null(start..start);
+			}
+			TokenKind::StartArray | TokenKind::StartObject
+				if self.depth == MAX_DEPTH =>
+			{
+				self.diagnose(token.span.clone(), "nesting too deep");
+				return self.skip_nested();
+			}
+			TokenKind::StartArray => {
+				self.depth += 1;
+				let array = self.array();
+				self.depth -= 1;
+				return array;
+			}
+			TokenKind::StartObject => {
+				self.depth += 1;
+				let object = self.object();
+				self.depth -= 1;
+				return object;
+			}
+			TokenKind::Null => NodeKind::Null,
+			TokenKind::True => NodeKind::Boolean(true),
+			TokenKind::False => NodeKind::Boolean(false),
+			TokenKind::Number => NodeKind::Number(text.parse().unwrap()),
+			TokenKind::String => NodeKind::String(self.string(&token)),
+			TokenKind::Error if text.starts_with('"') => {
+				NodeKind::String(self.string(&token))
+			}
+			TokenKind::Error if number_error(text).is_some() => {
+				let reason = number_error(text).unwrap_or_default();
+				self.diagnose(token.span.clone(), reason);
+				NodeKind::Null
+			}
+			_ => {
+				self.diagnose(
+					token.span.clone(),
+					format!("expected a value, found {text:?}"),
+				);
+				NodeKind::Null
+			}
+		};
+		self.tokens.next();
+		Node {
+			span: token.span,
+			kind,
+			comments: Comments::default(),
+		}
+	}
+
+	/// Decodes a string token, which may be unterminated.
+	fn string(&mut self, token: &Token) -> String {
+		let text = &self.input[token.span.clone()];
+		let contents = match text[1..].strip_suffix('"') {
+			Some(contents) if token.kind == TokenKind::String => contents,
+			_ => {
+				self.diagnose(
+					token.span.clone(),
+					"unterminated string",
+				);
+				&text[1..]
+			}
+		};
+		match unescape_json_string(contents) {
+			Ok(s) => s.into_owned(),
+			Err(e) => {
+				let start = token.span.start + 1 + e.offset();
+				self.diagnose(start..start + 1, e.message());
+				contents.to_owned()
+			}
+		}
+	}
+
+	/// Skips an array or object that is too deep to read, bracket by
+	/// bracket.
+	fn skip_nested(&mut self) -> Node {
+		let start = self.here();
+		let mut open = 0usize;
+		let mut end = start;
+		while let Some(token) = self.next() {
+			end = token.span.end;
+			match token.kind {
+				TokenKind::StartArray | TokenKind::StartObject => open += 1,
+				TokenKind::EndArray | TokenKind::EndObject => open -= 1,
+				_ => {}
+			}
+			if open == 0 {
+				break;
+			}
+		}
+		null(start..end)
+	}
+
+	fn array(&mut self) -> Node {
+		let start = self.here();
+		self.tokens.next();
+		let mut items = Vec::new();
+		// The comma since the last item, if one came.
+		let mut comma = None;
+		let end = loop {
+			let Some(token) = self.peek() else {
+				let end = self.input.len();
+				self.diagnose(start..start + 1, "unclosed array");
+				break end;
+			};
+			match token.kind {
+				TokenKind::EndArray => {
+					if let Some(comma) = comma {
+						self.diagnose(comma, "trailing comma");
+					}
+					self.tokens.next();
+					break token.span.end;
+				}
+				TokenKind::EndObject => {
+					self.diagnose(token.span.clone(), "expected ']'");
+					break token.span.start;
+				}
+				TokenKind::Comma => {
+					if items.is_empty() || comma.is_some() {
+						let at = token.span.start;
+						self.diagnose(at..at, "expected a value");
+					}
+					self.tokens.next();
+					comma = Some(token.span);
+				}
+				_ => {
+					if !items.is_empty() && comma.is_none() {
+						let at = token.span.start;
+						self.diagnose(at..at, "expected ','");
+					}
+					items.push(self.value());
+					comma = None;
+				}
+			}
+		};
+		Node {
+			span: start..end,
+			kind: NodeKind::Array(items),
+			comments: Comments::default(),
+		}
+	}
+
+	fn object(&mut self) -> Node {
+		let start = self.here();
+		self.tokens.next();
+		let mut members = Vec::new();
+		// Whether a member was read, even one left out.
+		let mut read = false;
+		let mut comma = None;
+		let end = loop {
+			let Some(token) = self.peek() else {
+				let end = self.input.len();
+				self.diagnose(start..start + 1, "unclosed object");
+				break end;
+			};
+			match token.kind {
+				TokenKind::EndObject => {
+					if let Some(comma) = comma {
+						self.diagnose(comma, "trailing comma");
+					}
+					self.tokens.next();
+					break token.span.end;
+				}
+				TokenKind::EndArray => {
+					self.diagnose(token.span.clone(), "expected '}'");
+					break token.span.start;
+				}
+				TokenKind::Comma => {
+					if !read || comma.is_some() {
+						let at = token.span.start;
+						self.diagnose(at..at, "expected a member");
+					}
+					self.tokens.next();
+					comma = Some(token.span);
+				}
+				_ => {
+					if read && comma.is_none() {
+						let at = token.span.start;
+						self.diagnose(at..at, "expected ','");
+					}
+					members.extend(self.member(token));
+					read = true;
+					comma = None;
+				}
+			}
+		};
+		Node {
+			span: start..end,
+			kind: NodeKind::Object(members),
+			comments: Comments::default(),
+		}
+	}
+
+	/// Reads a member starting with `token`. Without quotes, a key is taken
+	/// as written; a member whose key is missing or is an array or object
+	/// is read but left out.
+	fn member(&mut self, token: Token) -> Option<Member> {
+		let text = &self.input[token.span.clone()];
+		let key = match token.kind {
+			TokenKind::String => Some(self.string(&token)),
+			TokenKind::Error if text.starts_with('"') => {
+				Some(self.string(&token))
+			}
+			TokenKind::Colon => {
+				let at = token.span.start;
+				self.diagnose(at..at, "expected a key");
+				None
+			}
+			TokenKind::StartArray | TokenKind::StartObject => {
+				self.diagnose(token.span.clone(), "expected a key");
+				self.value();
+				None
+			}
+			_ => {
+				self.diagnose(
+					token.span.clone(),
+					"keys must be strings in double quotes",
+				);
+				Some(text.to_owned())
+			}
+		};
+		let after_key = match token.kind {
+			TokenKind::Colon => token.span.start,
+			TokenKind::StartArray | TokenKind::StartObject => self.here(),
+			_ => {
+				self.tokens.next();
+				token.span.end
+			}
+		};
+		let value = match self.peek() {
+			Some(colon) if colon.kind == TokenKind::Colon => {
+				self.tokens.next();
+				self.value()
+			}
+			next => {
+				self.diagnose(after_key..after_key, "expected ':'");
+				match next.map(|next| next.kind) {
+					None
+					| Some(
+						TokenKind::Comma
+						| TokenKind::EndArray
+						| TokenKind::EndObject,
+					) => null(after_key..after_key),
+					Some(_) => self.value(),
+				}
+			}
+		};
+		Some(Member {
+			key: key?,
+			key_span: token.span,
+			value,
+		})
+	}
+}
+
+fn null(span: Range<usize>) -> Node {
+	Node {
+		span,
+		kind: NodeKind::Null,
+		comments: Comments::default(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn number_errors() {
+		for (input, message) in [
+			(
+				"[01, 2]",
+				"leading zeros are not allowed",
+			),
+			(
+				"[+1, 2]",
+				"numbers cannot start with '+'",
+			),
+			(
+				"[.5, 2]",
+				"numbers need a digit before the decimal point",
+			),
+		] {
+			let document = Document::new(input);
+			assert_eq!(
+				document.diagnostics(),
+				[Diagnostic {
+					span: 1..3,
+					message: message.to_owned(),
+				}],
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn edits_match_a_fresh_document() {
+		let edits = [
+			(6..7, "[1, 2]"),
+			(11..11, ", 3"),
+			// Breaks the text, so the next edits parse it all again.
+			(1..2, ""),
+			(1..1, "\""),
+			(6..6, "."),
+			(6..7, ""),
+			(0..0, " "),
+		];
+		let mut document = Document::new(r#"{"a": 1, "b": true}"#);
+		for (range, replacement) in edits {
+			let edit = TextEdit {
+				range,
+				replacement: replacement.to_owned(),
+			};
+			document.edit(&edit);
+			let fresh = Document::new(document.text());
+			assert_eq!(
+				document.root(),
+				fresh.root(),
+				"{}",
+				document.text()
+			);
+			assert_eq!(
+				document.diagnostics(),
+				fresh.diagnostics()
+			);
+		}
+		assert_eq!(
+			document.text(),
+			r#" {"a": [1, 2, 3], "b": true}"#
+		);
+		assert!(document.diagnostics().is_empty());
+	}
+
+	#[test]
+	fn nodes_at_offsets() {
+		let document = Document::new(r#"{"a": [1, {"b": null}]}"#);
+		let path =
+			|offset| document.path_at_offset(offset).map(|p| p.to_string());
+		assert_eq!(path(0).as_deref(), Some(""));
+		assert_eq!(path(2).as_deref(), Some("/a"));
+		assert_eq!(path(7).as_deref(), Some("/a/0"));
+		assert_eq!(path(16).as_deref(), Some("/a/1/b"));
+		assert_eq!(path(100), None);
+	}
+
+	fn messages(text: &str) -> Vec<String> {
+		Document::new(text)
+			.diagnostics()
+			.iter()
+			.map(|d| format!("{:?} {}", d.span, d.message))
+			.collect()
+	}
+
+	#[test]
+	fn malformed_documents_are_diagnosed() {
+		for (text, expected) in [
+			(
+				r#"{"a": , "b": 1,}"#,
+				&["6..6 expected a value", "14..15 trailing comma"][..],
+			),
+			(
+				"[1 2",
+				&["0..1 unclosed array", "3..3 expected ','"],
+			),
+			(
+				"{\"a\": \"open\n}",
+				&["6..11 unterminated string"],
+			),
+			(
+				"{a: 1} x",
+				&[
+					"1..2 keys must be strings in double quotes",
+					"7..8 expected the end of the document",
+				],
+			),
+			(
+				"[1] // note",
+				&["4..11 comments are not allowed in JSON"],
+			),
+		] {
+			assert_eq!(messages(text), expected, "{text}");
+		}
+	}
+
+	#[test]
+	fn valid_documents_have_the_parsed_tree() {
+		let text = r#"{"a": [1, "x", {"b": null}], "c": true}"#;
+		let document = Document::new(text);
+		assert!(document.diagnostics().is_empty());
+		assert_eq!(
+			document.root(),
+			&crate::parse_spanned(text).unwrap()
+		);
+		assert_eq!(
+			document.position(2),
+			Position {
+				line: 0,
+				character: 2
+			}
+		);
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let text = "[".repeat(100_000);
+		let document = Document::new(&*text);
+		assert!(document
+			.diagnostics()
+			.iter()
+			.any(|d| d.message == "nesting too deep"
+				&& d.span.start == MAX_DEPTH));
+	}
+}
@@ -0,0 +1,184 @@
+use std::{error, fmt};
+
+use winnow::error::{
+	AddContext, ErrorKind as WinnowErrorKind, ParseError, ParserError,
+};
+
+use crate::lexer::{check_number, number_len};
+
+/// An error produced while parsing a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+	offset: usize,
+	line: usize,
+	column: usize,
+	message: String,
+	kind: ErrorKind,
+}
+
+/// What made a parse fail, for callers that treat running into a limit
+/// differently from malformed input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+	/// The input is not valid JSON, or not what was expected of it.
+	Syntax,
+	/// Arrays and objects are nested deeper than the limit allows.
+	TooDeep,
+	/// The input needs more values or string bytes than the
+	/// [`Budget`](crate::Budget) allows.
+	BudgetExceeded,
+	/// An object has a key twice under
+	/// [`DuplicateKeys::Error`](crate::DuplicateKeys::Error).
+	DuplicateKey,
+	/// The parse was cancelled through its cancellation token.
+	Cancelled,
+}
+
+impl Error {
+	pub(crate) fn new(input: &str, offset: usize, message: String) -> Self {
+		let offset = offset.min(input.len());
+		let before = &input[..offset];
+		let line = before.matches('\n').count() + 1;
+		let column = before
+			.rfind('\n')
+			.map_or(before, |newline| &before[newline + 1..])
+			.chars()
+			.count() + 1;
+		Self {
+			offset,
+			line,
+			column,
+			message,
+			kind: ErrorKind::Syntax,
+		}
+	}
+
+	pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+		self.kind = kind;
+		self
+	}
+
+	pub(crate) fn from_parse(
+		input: &str,
+		error: ParseError<&str, ContextTrail>,
+	) -> Self {
+		Self::from_trail(input, error.offset(), error.inner())
+	}
+
+	/// Describes a failure at `offset` from the contexts it happened in.
+	pub(crate) fn from_trail(
+		input: &str,
+		offset: usize,
+		trail: &ContextTrail,
+	) -> Self {
+		let message = match (&trail.message, trail.contexts.first()) {
+			(Some(message), _) => message.clone(),
+			(None, Some(&"number")) => {
+				let rest = &input[offset..];
+				let literal = &rest[..number_len(rest)];
+				check_number(literal)
+					.err()
+					.unwrap_or("invalid number")
+					.to_owned()
+			}
+			(None, Some(context)) => format!("invalid {context}"),
+			(None, None) => match input[offset..].chars().next() {
+				Some(c) => format!("unexpected character {c:?}"),
+				None => "unexpected end of input".to_owned(),
+			},
+		};
+		Self::new(input, offset, message).with_kind(trail.kind)
+	}
+
+	/// The byte offset into the input where the error was detected.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/// The 1-based line of [`Error::offset`].
+	pub fn line(&self) -> usize {
+		self.line
+	}
+
+	/// The 1-based column, in characters, of [`Error::offset`].
+	pub fn column(&self) -> usize {
+		self.column
+	}
+
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+
+	/// What made the parse fail.
+	pub fn kind(&self) -> ErrorKind {
+		self.kind
+	}
+
+	/// Whether the parse stopped at a limit of its
+	/// [`Budget`](crate::Budget) rather than at a fault in the input.
+	pub fn is_budget_exceeded(&self) -> bool {
+		self.kind == ErrorKind::BudgetExceeded
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} at line {} column {}",
+			self.message, self.line, self.column
+		)
+	}
+}
+
+impl error::Error for Error {}
+
+/// The winnow error type used by the crate's entry points, remembering the
+/// contexts (innermost first) the failure happened in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ContextTrail {
+	pub(crate) contexts: Vec<&'static str>,
+	/// Describes the failure in place of the contexts.
+	pub(crate) message: Option<String>,
+	pub(crate) kind: ErrorKind,
+}
+
+impl Default for ContextTrail {
+	fn default() -> Self {
+		Self {
+			contexts: Vec::new(),
+			message: None,
+			kind: ErrorKind::Syntax,
+		}
+	}
+}
+
+impl ContextTrail {
+	/// A failure that is not just malformed syntax, such as a limit being
+	/// exceeded.
+	pub(crate) fn custom(kind: ErrorKind, message: impl Into<String>) -> Self {
+		Self {
+			contexts: Vec::new(),
+			message: Some(message.into()),
+			kind,
+		}
+	}
+}
+
+impl<I> ParserError<I> for ContextTrail {
+	fn from_error_kind(_input: &I, _kind: WinnowErrorKind) -> Self {
+		Self::default()
+	}
+
+	fn append(self, _input: &I, _kind: WinnowErrorKind) -> Self {
+		self
+	}
+}
+
+impl<I> AddContext<I, &'static str> for ContextTrail {
+	fn add_context(mut self, _input: &I, context: &'static str) -> Self {
+		self.contexts.push(context);
+		self
+	}
+}
@@ -0,0 +1,104 @@
+//! Escaping for the contents of string literals, for code that assembles
+//! JSON text from fragments rather than whole documents.
+
+use std::borrow::Cow;
+
+use winnow::{
+	combinator::{alt, cut_err, preceded, repeat},
+	prelude::*,
+	token::take_till,
+};
+
+use crate::{
+	parser::{escape, Fragment},
+	ser::write_escaped,
+	Error,
+};
+
+/// Escapes `s` for use between the quotes of a string literal.
+///
+/// Borrows `s` when nothing in it needs escaping.
+pub fn escape_json_string(s: &str) -> Cow<'_, str> {
+	if !s.contains(|c: char| c < ' ' || c == '"' || c == '\\') {
+		return Cow::Borrowed(s);
+	}
+	let mut escaped = String::with_capacity(s.len() + 8);
+	let _ = write_escaped(&mut escaped, s);
+	Cow::Owned(escaped)
+}
+
+/// Decodes the escapes in the contents of a string literal, given without
+/// its quotes.
+///
+/// Borrows `s` when it has no escapes. Fails on a malformed escape or an
+/// unescaped `"`, with the error's position counted within `s`.
+pub fn unescape_json_string(s: &str) -> Result<Cow<'_, str>, Error> {
+	if !s.contains(['"', '\\']) {
+		return Ok(Cow::Borrowed(s));
+	}
+	let fragment = alt((
+		take_till(1.., ['"', '\\']).map(Fragment::Run),
+		preceded('\\', cut_err(escape).context("escape"))
+			.map(Fragment::Escaped),
+	));
+	repeat(0.., fragment)
+		.fold(
+			String::new,
+			|mut string, fragment: Fragment<&str>| {
+				match fragment {
+					Fragment::Run(run) => string.push_str(run),
+					Fragment::Escaped(c) => string.push(c),
+				}
+				string
+			},
+		)
+		.map(Cow::Owned)
+		.parse(s)
+		.map_err(|e| Error::from_parse(s, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn escaping_borrows_when_it_can() {
+		assert!(matches!(
+			escape_json_string("plain é"),
+			Cow::Borrowed(_)
+		));
+		assert!(matches!(
+			unescape_json_string("plain é"),
+			Ok(Cow::Borrowed(_))
+		));
+		let s = "q\" b\\ \n\t\u{1} 😀";
+		let escaped = escape_json_string(s);
+		assert_eq!(escaped, r#"q\" b\\ \n\t\u0001 😀"#);
+		assert_eq!(
+			unescape_json_string(&escaped).unwrap(),
+			s
+		);
+		assert_eq!(
+			crate::parse(&format!("\"{escaped}\"")).unwrap(),
+			crate::JsonValue::from(s)
+		);
+	}
+
+	#[test]
+	fn unescaping_decodes_every_escape() {
+		assert_eq!(
+			unescape_json_string(r#"\"\\\/\b\f\n\r\té😀"#).unwrap(),
+			"\"\\/\u{8}\u{c}\n\r\té😀"
+		);
+		for (input, offset) in [
+			(r#"ab\x"#, 3),
+			(r#"\u12"#, 2),
+			(r#"\ud800"#, 6),
+			(r#"a"b"#, 1),
+			(r#"end\"#, 4),
+		] {
+			let error = unescape_json_string(input).unwrap_err();
+			assert_eq!(error.offset(), offset, "{input}");
+		}
+	}
+}
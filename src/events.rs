@@ -0,0 +1,404 @@
+//! A pull parser that reports a document as a sequence of [`Event`]s
+//! instead of building a [`JsonValue`], so arbitrarily large inputs can be
+//! processed in memory proportional to their nesting depth.
+
+use std::collections::hash_map;
+
+use winnow::{error::ErrMode, Parser};
+
+use crate::{
+	error::ContextTrail,
+	parser::{scalar, string, ws},
+	Error, JsonValue,
+};
+
+/// One step through a document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+	StartObject,
+	EndObject,
+	StartArray,
+	EndArray,
+	/// The key of the next member of the innermost object.
+	Key(String),
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(String),
+}
+
+impl Event {
+	/// The value of a scalar event.
+	pub(crate) fn scalar(&self) -> Option<JsonValue> {
+		Some(match self {
+			Event::Null => JsonValue::Null,
+			Event::Boolean(b) => JsonValue::Boolean(*b),
+			Event::Number(n) => JsonValue::Number(*n),
+			Event::String(s) => JsonValue::String(s.clone()),
+			_ => return None,
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+	Array,
+	Object,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+	/// A value is required.
+	Value,
+	/// Just after `[`: a value or the end of the array.
+	FirstItem,
+	/// Just after `{`: a key or the end of the object.
+	FirstKey,
+	/// After a `,` in an object.
+	Key,
+	/// After a complete value: a separator, the end of the container or,
+	/// at the top level, the end of input.
+	AfterValue,
+	Done,
+}
+
+/// Reads [`Event`]s from a complete JSON document.
+///
+/// The reader stops after the first error.
+#[derive(Debug, Clone)]
+pub struct EventReader<'i> {
+	input: &'i str,
+	rest: &'i str,
+	stack: Vec<Container>,
+	state: State,
+}
+
+impl<'i> EventReader<'i> {
+	pub fn new(input: &'i str) -> Self {
+		Self {
+			input,
+			rest: input,
+			stack: Vec::new(),
+			state: State::Value,
+		}
+	}
+
+	/// The byte offset of the next unread character.
+	pub fn offset(&self) -> usize {
+		self.input.len() - self.rest.len()
+	}
+
+	/// The number of containers currently open.
+	pub fn depth(&self) -> usize {
+		self.stack.len()
+	}
+
+	fn eat(&mut self, c: char) -> bool {
+		match self.rest.strip_prefix(c) {
+			Some(rest) => {
+				self.rest = rest;
+				true
+			}
+			None => false,
+		}
+	}
+
+	fn fail(&mut self, trail: &ContextTrail) -> Error {
+		self.state = State::Done;
+		Error::from_trail(self.input, self.offset(), trail)
+	}
+
+	fn value(&mut self) -> Result<Event, Error> {
+		if self.eat('{') {
+			self.stack.push(Container::Object);
+			self.state = State::FirstKey;
+			return Ok(Event::StartObject);
+		}
+		if self.eat('[') {
+			self.stack.push(Container::Array);
+			self.state = State::FirstItem;
+			return Ok(Event::StartArray);
+		}
+		let value = match scalar::<_, ContextTrail>.parse_next(&mut self.rest) {
+			Ok(value) => value,
+			Err(ErrMode::Backtrack(e) | ErrMode::Cut(e)) => {
+				return Err(self.fail(&e))
+			}
+			Err(ErrMode::Incomplete(_)) => {
+				return Err(self.fail(&ContextTrail::default()))
+			}
+		};
+		self.state = State::AfterValue;
+		Ok(match value {
+			JsonValue::Boolean(b) => Event::Boolean(b),
+			JsonValue::Number(n) => Event::Number(n),
+			JsonValue::String(s) => Event::String(s),
+			_ => Event::Null,
+		})
+	}
+
+	fn key(&mut self) -> Result<Event, Error> {
+		if !self.rest.starts_with('"') {
+			return Err(self.fail(&ContextTrail::default()));
+		}
+		let key = match string::<_, ContextTrail>.parse_next(&mut self.rest) {
+			Ok(key) => key,
+			Err(ErrMode::Backtrack(e) | ErrMode::Cut(e)) => {
+				return Err(self.fail(&e))
+			}
+			Err(ErrMode::Incomplete(_)) => {
+				return Err(self.fail(&ContextTrail::default()))
+			}
+		};
+		let _ = ws::<_, ContextTrail>(&mut self.rest);
+		if !self.eat(':') {
+			return Err(self.fail(&ContextTrail::default()));
+		}
+		self.state = State::Value;
+		Ok(Event::Key(key))
+	}
+
+	/// Consumes the closing bracket of the innermost container.
+	fn close(&mut self, event: Event) -> Result<Event, Error> {
+		self.rest = &self.rest[1..];
+		self.stack.pop();
+		self.state = State::AfterValue;
+		Ok(event)
+	}
+}
+
+impl Iterator for EventReader<'_> {
+	type Item = Result<Event, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let _ = ws::<_, ContextTrail>(&mut self.rest);
+			let next = self.rest.chars().next();
+			let container = self.stack.last().copied();
+			return Some(match (self.state, container, next) {
+				(State::Done, ..) => return None,
+				(State::FirstItem, _, Some(']')) => self.close(Event::EndArray),
+				(State::Value | State::FirstItem, ..) => self.value(),
+				(State::FirstKey, _, Some('}')) => self.close(Event::EndObject),
+				(State::FirstKey | State::Key, ..) => self.key(),
+				(State::AfterValue, None, None) => {
+					self.state = State::Done;
+					return None;
+				}
+				(State::AfterValue, Some(Container::Array), Some(',')) => {
+					self.rest = &self.rest[1..];
+					self.state = State::Value;
+					continue;
+				}
+				(State::AfterValue, Some(Container::Object), Some(',')) => {
+					self.rest = &self.rest[1..];
+					self.state = State::Key;
+					continue;
+				}
+				(State::AfterValue, Some(Container::Array), Some(']')) => {
+					self.close(Event::EndArray)
+				}
+				(State::AfterValue, Some(Container::Object), Some('}')) => {
+					self.close(Event::EndObject)
+				}
+				(State::AfterValue, ..) => {
+					Err(self.fail(&ContextTrail::default()))
+				}
+			});
+		}
+	}
+}
+
+/// Assembles a [`JsonValue`] from a sequence of events.
+#[derive(Debug, Default)]
+pub(crate) struct ValueBuilder {
+	/// The open containers, each with the key it will be stored under.
+	stack: Vec<(JsonValue, Option<String>)>,
+	key: Option<String>,
+}
+
+impl ValueBuilder {
+	/// Feeds the next event, returning the value once it is complete.
+	pub(crate) fn push(&mut self, event: Event) -> Option<JsonValue> {
+		let value = match event {
+			Event::StartObject => {
+				let object = JsonValue::Object(Box::default());
+				self.stack.push((object, self.key.take()));
+				return None;
+			}
+			Event::StartArray => {
+				self.stack.push((
+					JsonValue::Array(Vec::new()),
+					self.key.take(),
+				));
+				return None;
+			}
+			Event::Key(key) => {
+				self.key = Some(key);
+				return None;
+			}
+			Event::EndObject | Event::EndArray => {
+				let (value, key) = self.stack.pop()?;
+				self.key = key;
+				value
+			}
+			Event::Null => JsonValue::Null,
+			Event::Boolean(b) => JsonValue::Boolean(b),
+			Event::Number(n) => JsonValue::Number(n),
+			Event::String(s) => JsonValue::String(s),
+		};
+		match self.stack.last_mut() {
+			None => Some(value),
+			Some((JsonValue::Array(items), _)) => {
+				items.push(value);
+				None
+			}
+			Some((JsonValue::Object(map), _)) => {
+				map.insert(
+					self.key.take().unwrap_or_default(),
+					value,
+				);
+				None
+			}
+			Some(_) => None,
+		}
+	}
+}
+
+/// Builds a value from a sequence of events, such as a filtered
+/// [`EventReader`].
+///
+/// Returns `None` if the events run out before a value is complete; any
+/// events after the first complete value are ignored.
+pub fn events_to_value(
+	events: impl IntoIterator<Item = Event>,
+) -> Option<JsonValue> {
+	let mut builder = ValueBuilder::default();
+	events.into_iter().find_map(|event| builder.push(event))
+}
+
+/// The events describing `value`, produced lazily in the order an
+/// [`EventReader`] would report them.
+///
+/// Object members come out in the map's iteration order.
+pub fn value_to_events(value: &JsonValue) -> impl Iterator<Item = Event> + '_ {
+	ValueEvents {
+		next: Some(value),
+		stack: Vec::new(),
+	}
+}
+
+struct ValueEvents<'a> {
+	/// A value whose key has just been reported.
+	next: Option<&'a JsonValue>,
+	stack: Vec<Members<'a>>,
+}
+
+enum Members<'a> {
+	Array(std::slice::Iter<'a, JsonValue>),
+	Object(hash_map::Iter<'a, String, JsonValue>),
+}
+
+impl<'a> ValueEvents<'a> {
+	fn enter(&mut self, value: &'a JsonValue) -> Event {
+		match value {
+			JsonValue::Null => Event::Null,
+			JsonValue::Boolean(b) => Event::Boolean(*b),
+			JsonValue::Number(n) => Event::Number(*n),
+			JsonValue::String(s) => Event::String(s.clone()),
+			JsonValue::Array(items) => {
+				self.stack.push(Members::Array(items.iter()));
+				Event::StartArray
+			}
+			JsonValue::Object(map) => {
+				self.stack.push(Members::Object(map.iter()));
+				Event::StartObject
+			}
+		}
+	}
+}
+
+impl Iterator for ValueEvents<'_> {
+	type Item = Event;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(value) = self.next.take() {
+			return Some(self.enter(value));
+		}
+		match self.stack.last_mut()? {
+			Members::Array(items) => match items.next() {
+				Some(item) => Some(self.enter(item)),
+				None => {
+					self.stack.pop();
+					Some(Event::EndArray)
+				}
+			},
+			Members::Object(members) => match members.next() {
+				Some((key, value)) => {
+					self.next = Some(value);
+					Some(Event::Key(key.clone()))
+				}
+				None => {
+					self.stack.pop();
+					Some(Event::EndObject)
+				}
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	const DOCUMENT: &str =
+		r#"{"a": [1, "two", null, {"b": false}], "c": {}, "d": []}"#;
+
+	#[test]
+	fn read_events_build_the_parsed_value() {
+		let events: Vec<Event> =
+			EventReader::new(DOCUMENT).map(Result::unwrap).collect();
+		assert_eq!(
+			events_to_value(events),
+			Some(parse(DOCUMENT).unwrap())
+		);
+	}
+
+	#[test]
+	fn values_round_trip_through_events() {
+		let value = parse(DOCUMENT).unwrap();
+		assert_eq!(
+			events_to_value(value_to_events(&value)),
+			Some(value)
+		);
+		assert_eq!(
+			value_to_events(&parse(r#"[1, ["x"]]"#).unwrap())
+				.collect::<Vec<_>>(),
+			[
+				Event::StartArray,
+				Event::Number(1.0),
+				Event::StartArray,
+				Event::String("x".to_owned()),
+				Event::EndArray,
+				Event::EndArray,
+			]
+		);
+	}
+
+	#[test]
+	fn values_need_complete_events() {
+		assert_eq!(
+			events_to_value([Event::StartArray, Event::Null]),
+			None
+		);
+		assert_eq!(
+			events_to_value([Event::StartObject, Event::Key("k".into())]),
+			None
+		);
+		assert_eq!(
+			events_to_value([Event::Boolean(true), Event::StartArray]),
+			Some(JsonValue::Boolean(true))
+		);
+	}
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, error, fmt};
+
+use winnow::{
+	ascii::digit1,
+	combinator::{alt, delimited, opt, preceded, repeat},
+	error::{AddContext, InputError, ParserError},
+	prelude::*,
+	token::take_while,
+	PResult,
+};
+
+use crate::{parser::string, JsonValue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+	Key(String),
+	Index(usize),
+}
+
+impl JsonValue {
+	/// Flattens the tree into a single-level object keyed by paths such as
+	/// `a.b[0].c`.
+	///
+	/// Keys that are empty or contain `.`, `[`, `]`, `"` or `\` are written
+	/// in bracket form, e.g. `a["x.y"]`. Empty arrays and objects are kept as
+	/// leaves, and a scalar root is stored under the empty key.
+	pub fn flatten(&self) -> JsonValue {
+		let mut flat = HashMap::new();
+		flatten(self, &mut String::new(), &mut flat);
+		JsonValue::Object(Box::new(flat))
+	}
+
+	/// Rebuilds a nested tree from the output of [`JsonValue::flatten`].
+	pub fn unflatten(&self) -> Result<JsonValue, UnflattenError> {
+		let JsonValue::Object(flat) = self else {
+			return Err(UnflattenError::NotObject);
+		};
+
+		let mut root = JsonValue::Null;
+		for (key, value) in flat.iter() {
+			let segments = path::<InputError<&str>>
+				.parse(key)
+				.map_err(|_| UnflattenError::InvalidKey(key.clone()))?;
+			insert(
+				&mut root,
+				key,
+				&segments,
+				value.clone(),
+				flat.len(),
+			)?;
+		}
+		Ok(root)
+	}
+}
+
+fn flatten(
+	node: &JsonValue,
+	prefix: &mut String,
+	flat: &mut HashMap<String, JsonValue>,
+) {
+	let len = prefix.len();
+	match node {
+		JsonValue::Array(items) if !items.is_empty() => {
+			for (index, item) in items.iter().enumerate() {
+				prefix.push_str(&format!("[{index}]"));
+				flatten(item, prefix, flat);
+				prefix.truncate(len);
+			}
+		}
+		JsonValue::Object(map) if !map.is_empty() => {
+			for (key, value) in map.iter() {
+				push_key(prefix, key);
+				flatten(value, prefix, flat);
+				prefix.truncate(len);
+			}
+		}
+		_ => {
+			flat.insert(prefix.clone(), node.clone());
+		}
+	}
+}
+
+fn push_key(prefix: &mut String, key: &str) {
+	if key.is_empty() || key.contains(['.', '[', ']', '"', '\\']) {
+		prefix.push_str("[\"");
+		for c in key.chars() {
+			if c == '"' || c == '\\' {
+				prefix.push('\\');
+			}
+			prefix.push(c);
+		}
+		prefix.push_str("\"]");
+	} else {
+		if !prefix.is_empty() {
+			prefix.push('.');
+		}
+		prefix.push_str(key);
+	}
+}
+
+/// Sets the value at `segments`, parsed from `flat_key`, in `root`. An
+/// array of `n` items flattens to at least `n` keys, so indices are bounded
+/// by `keys`, the number of keys being unflattened, and the gaps that keys
+/// arriving out of order leave stay small.
+fn insert(
+	root: &mut JsonValue,
+	flat_key: &str,
+	segments: &[Segment],
+	value: JsonValue,
+	keys: usize,
+) -> Result<(), UnflattenError> {
+	let conflict = || UnflattenError::Conflict(flat_key.to_owned());
+	let mut node = root;
+	for segment in segments {
+		if node.is_null() {
+			*node = match segment {
+				Segment::Key(_) => JsonValue::Object(Box::default()),
+				Segment::Index(_) => JsonValue::Array(Vec::new()),
+			};
+		}
+		node = match (node, segment) {
+			(JsonValue::Object(map), Segment::Key(key)) => {
+				map.entry(key.clone()).or_insert(JsonValue::Null)
+			}
+			(JsonValue::Array(items), Segment::Index(index)) => {
+				let len = index
+					.checked_add(1)
+					.filter(|&len| len <= keys)
+					.ok_or_else(|| {
+						UnflattenError::IndexOutOfRange(flat_key.to_owned())
+					})?;
+				if len > items.len() {
+					items.resize(len, JsonValue::Null);
+				}
+				&mut items[*index]
+			}
+			_ => return Err(conflict()),
+		};
+	}
+
+	let compatible = match (&*node, &value) {
+		(JsonValue::Null, _) => true,
+		(JsonValue::Array(_), JsonValue::Array(empty)) => empty.is_empty(),
+		(JsonValue::Object(_), JsonValue::Object(empty)) => empty.is_empty(),
+		_ => false,
+	};
+	if !compatible {
+		return Err(conflict());
+	}
+	if node.is_null() {
+		*node = value;
+	}
+	Ok(())
+}
+
+fn path<'i, E>(input: &mut &'i str) -> PResult<Vec<Segment>, E>
+where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
+	let first = opt(simple_key.map(Segment::Key)).parse_next(input)?;
+	let rest: Vec<_> = repeat(
+		0..,
+		alt((
+			preceded('.', simple_key.map(Segment::Key)),
+			bracketed,
+		)),
+	)
+	.parse_next(input)?;
+	Ok(first.into_iter().chain(rest).collect())
+}
+
+fn bracketed<'i, E>(input: &mut &'i str) -> PResult<Segment, E>
+where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
+	delimited(
+		'[',
+		alt((
+			digit1
+				.verify_map(|s: &str| s.parse().ok())
+				.map(Segment::Index),
+			string.map(Segment::Key),
+		)),
+		']',
+	)
+	.parse_next(input)
+}
+
+fn simple_key<'i, E>(input: &mut &'i str) -> PResult<String, E>
+where E: ParserError<&'i str> {
+	take_while(1.., |c| {
+		!matches!(c, '.' | '[' | ']' | '"' | '\\')
+	})
+	.map(str::to_owned)
+	.parse_next(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnflattenError {
+	/// Only objects can be unflattened.
+	NotObject,
+	/// The key is not a valid flattened path.
+	InvalidKey(String),
+	/// The key's path collides with another key's value.
+	Conflict(String),
+	/// The key's path has an array index past any that flattening the
+	/// object's keys could have made, which would only pad the array.
+	IndexOutOfRange(String),
+}
+
+impl fmt::Display for UnflattenError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NotObject => write!(f, "only objects can be unflattened"),
+			Self::InvalidKey(key) => write!(f, "invalid path {key:?}"),
+			Self::Conflict(key) => write!(f, "conflicting value at {key:?}"),
+			Self::IndexOutOfRange(key) => {
+				write!(f, "array index out of range in {key:?}")
+			}
+		}
+	}
+}
+
+impl error::Error for UnflattenError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn round_trip() {
+		let value =
+			parse(r#"{"a": {"b": [1, {"c.d": null}, []]}, "": {}, "e": "f"}"#)
+				.unwrap();
+		assert_eq!(
+			value.flatten().unflatten().unwrap(),
+			value
+		);
+	}
+
+	#[test]
+	fn indices_are_bounded() {
+		let unflatten = |flat: &str| parse(flat).unwrap().unflatten();
+		assert_eq!(
+			unflatten(r#"{"a[1]": 2, "a[0]": 1}"#).unwrap(),
+			parse("{\"a\": [1, 2]}").unwrap()
+		);
+		assert_eq!(
+			unflatten(r#"{"a[2]": 1, "b": 2}"#),
+			Err(UnflattenError::IndexOutOfRange(
+				"a[2]".to_owned()
+			))
+		);
+		let huge = format!(r#"{{"a[{}]": 1}}"#, usize::MAX);
+		assert!(matches!(
+			unflatten(&huge),
+			Err(UnflattenError::IndexOutOfRange(_))
+		));
+		assert!(matches!(
+			unflatten(r#"{"a": 1, "a.b": 2}"#),
+			Err(UnflattenError::Conflict(_))
+		));
+	}
+}
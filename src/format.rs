@@ -0,0 +1,1107 @@
+//! The hooks that decide what output looks like: punctuation, whitespace
+//! and how each scalar is spelled.
+
+use std::{
+	fmt,
+	io::{self, Write},
+	mem,
+};
+
+use crate::{
+	ser::{write_number, write_string},
+	JsonValue, JsonWriter, Node, NodeKind, WriteError,
+};
+
+/// Controls the text a [`JsonWriter`] produces around and for each value.
+///
+/// Every method has a default producing compact JSON, so an implementation
+/// only overrides what it changes. The writer has already checked nesting by
+/// the time a hook runs; `first` says whether a value or key is the first in
+/// its container.
+pub trait Formatter {
+	fn write_null<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		out.write_all(b"null")
+	}
+
+	fn write_bool<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: bool,
+	) -> io::Result<()> {
+		out.write_all(if value { b"true" } else { b"false" })
+	}
+
+	/// Writes a number; by default NaN and infinities become `null`.
+	fn write_number<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: f64,
+	) -> io::Result<()> {
+		write_io(out, |out| write_number(out, value))
+	}
+
+	/// Writes a string value or object key, quoted and escaped.
+	fn write_string<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: &str,
+	) -> io::Result<()> {
+		write_io(out, |out| write_string(out, value))
+	}
+
+	fn begin_array<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		out.write_all(b"[")
+	}
+
+	fn end_array<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		out.write_all(b"]")
+	}
+
+	fn begin_array_value<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		first: bool,
+	) -> io::Result<()> {
+		if first {
+			Ok(())
+		} else {
+			out.write_all(b",")
+		}
+	}
+
+	fn end_array_value<W: ?Sized + Write>(
+		&mut self,
+		_out: &mut W,
+	) -> io::Result<()> {
+		Ok(())
+	}
+
+	fn begin_object<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		out.write_all(b"{")
+	}
+
+	fn end_object<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		out.write_all(b"}")
+	}
+
+	fn begin_object_key<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		first: bool,
+	) -> io::Result<()> {
+		if first {
+			Ok(())
+		} else {
+			out.write_all(b",")
+		}
+	}
+
+	fn end_object_key<W: ?Sized + Write>(
+		&mut self,
+		_out: &mut W,
+	) -> io::Result<()> {
+		Ok(())
+	}
+
+	/// Writes what separates a key from its value.
+	fn begin_object_value<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		out.write_all(b":")
+	}
+
+	fn end_object_value<W: ?Sized + Write>(
+		&mut self,
+		_out: &mut W,
+	) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// How the built-in formatters spell numbers.
+///
+/// NaN and infinities are always written as `null`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+	style: Style,
+	normalize_zero: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Style {
+	#[default]
+	Shortest,
+	Fixed(usize),
+	Scientific {
+		low: i32,
+		high: i32,
+	},
+}
+
+impl NumberFormat {
+	/// The shortest digits that parse back to the same value, written out
+	/// in full: `1230000`, `0.0000001`. This is the default.
+	pub fn shortest() -> Self {
+		Self::default()
+	}
+
+	/// Rounds to `digits` places after the decimal point, e.g. `0.10` for
+	/// `fixed(2)`. Integral values are still written as integers.
+	pub fn fixed(digits: usize) -> Self {
+		Self {
+			style: Style::Fixed(digits),
+			..Self::default()
+		}
+	}
+
+	/// The shortest digits, switching to exponent notation (`1.5e21`,
+	/// `1e-7`) when the decimal exponent is at most `low` or at least
+	/// `high`. `scientific(-7, 21)` matches JavaScript.
+	pub fn scientific(low: i32, high: i32) -> Self {
+		Self {
+			style: Style::Scientific { low, high },
+			..Self::default()
+		}
+	}
+
+	/// Writes negative zero as `0` instead of keeping it as `-0`.
+	pub fn normalize_zero(mut self, normalize: bool) -> Self {
+		self.normalize_zero = normalize;
+		self
+	}
+
+	/// Writes `value` in this format, for use from custom [`Formatter`]s.
+	pub fn write<W: ?Sized + Write>(
+		&self,
+		out: &mut W,
+		value: f64,
+	) -> io::Result<()> {
+		if value == 0.0 && self.normalize_zero {
+			return out.write_all(b"0");
+		}
+		if !value.is_finite() {
+			return out.write_all(b"null");
+		}
+		match self.style {
+			Style::Shortest => write_io(out, |out| write_number(out, value)),
+			Style::Fixed(_) if value.fract() == 0.0 => {
+				write_io(out, |out| write_number(out, value))
+			}
+			Style::Fixed(digits) => write!(out, "{value:.digits$}"),
+			Style::Scientific { low, high } => {
+				let exponential = format!("{value:e}");
+				let exponent = exponential
+					.rsplit_once('e')
+					.and_then(|(_, exponent)| exponent.parse::<i32>().ok())
+					.unwrap_or(0);
+				if exponent <= low || exponent >= high {
+					out.write_all(exponential.as_bytes())
+				} else {
+					write_io(out, |out| write_number(out, value))
+				}
+			}
+		}
+	}
+}
+
+/// Output without any whitespace, the same as `to_string()` unless given a
+/// [`NumberFormat`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactFormatter {
+	numbers: NumberFormat,
+}
+
+impl CompactFormatter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn numbers(mut self, numbers: NumberFormat) -> Self {
+		self.numbers = numbers;
+		self
+	}
+}
+
+impl Formatter for CompactFormatter {
+	fn write_number<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: f64,
+	) -> io::Result<()> {
+		self.numbers.write(out, value)
+	}
+}
+
+/// Output with one value or member per line, indented by nesting depth.
+///
+/// Empty arrays and objects stay on one line as `[]` and `{}`, or `[ ]` and
+/// `{ }` with [`PrettyFormatter::space_in_empty`]. With
+/// [`PrettyFormatter::max_width`], any array or object that fits is kept on
+/// one line.
+#[derive(Debug, Clone)]
+pub struct PrettyFormatter {
+	indent: String,
+	newline: String,
+	space_in_empty: bool,
+	max_width: Option<usize>,
+	numbers: NumberFormat,
+	depth: usize,
+	/// Whether the innermost container has anything in it yet.
+	has_value: bool,
+	/// The column the output has reached, tracked only with a `max_width`.
+	column: usize,
+	/// The outermost container that may still fit on one line, held back
+	/// until it closes or outgrows the line.
+	pending: Vec<Token>,
+	/// The one-line length of `pending`.
+	pending_len: usize,
+	/// How many containers in `pending` are open.
+	pending_open: usize,
+	/// The column `pending` starts at.
+	pending_column: usize,
+	/// Trailing comments held back until the comma after their value.
+	trailing: Vec<Vec<u8>>,
+}
+
+/// A [`Formatter`] hook call, as held back by [`PrettyFormatter`].
+#[derive(Debug, Clone)]
+enum Token {
+	Open(&'static [u8]),
+	Close(&'static [u8]),
+	Item { first: bool },
+	Colon,
+	EndItem,
+	Text(Vec<u8>),
+	Comment(Vec<u8>, Placement),
+}
+
+/// Where a comment goes relative to the node it belongs to, as in
+/// [`Comments`](crate::Comments).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placement {
+	Leading,
+	Trailing,
+	Dangling,
+}
+
+impl Default for PrettyFormatter {
+	fn default() -> Self {
+		Self {
+			indent: "  ".to_owned(),
+			newline: "\n".to_owned(),
+			space_in_empty: false,
+			max_width: None,
+			numbers: NumberFormat::default(),
+			depth: 0,
+			has_value: false,
+			column: 0,
+			pending: Vec::new(),
+			pending_len: 0,
+			pending_open: 0,
+			pending_column: 0,
+			trailing: Vec::new(),
+		}
+	}
+}
+
+impl PrettyFormatter {
+	/// Indents with two spaces per level and ends lines with `\n`.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the text written once per level of nesting.
+	pub fn indent(mut self, indent: impl Into<String>) -> Self {
+		self.indent = indent.into();
+		self
+	}
+
+	/// Indents with one tab per level.
+	pub fn tabs(self) -> Self {
+		self.indent("\t")
+	}
+
+	/// Sets the line ending, e.g. `"\r\n"`.
+	pub fn newline(mut self, newline: impl Into<String>) -> Self {
+		self.newline = newline.into();
+		self
+	}
+
+	/// Writes empty arrays and objects as `[ ]` and `{ }`.
+	pub fn space_in_empty(mut self, space: bool) -> Self {
+		self.space_in_empty = space;
+		self
+	}
+
+	/// Writes arrays and objects on one line, as `[1, 2]` or `{"a": 1}`,
+	/// when they end within `width` columns; the rest are broken one item
+	/// per line, and each item gets the same chance.
+	///
+	/// Columns are counted in characters, so a tab of indentation counts as
+	/// one.
+	pub fn max_width(mut self, width: usize) -> Self {
+		self.max_width = Some(width);
+		self
+	}
+
+	pub fn numbers(mut self, numbers: NumberFormat) -> Self {
+		self.numbers = numbers;
+		self
+	}
+
+	/// Writes a scalar's text as it is, for copying tokens from a source
+	/// document.
+	pub(crate) fn write_raw<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		text: &[u8],
+	) -> io::Result<()> {
+		self.step(out, Token::Text(text.to_vec()))
+	}
+
+	fn step<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		token: Token,
+	) -> io::Result<()> {
+		let Some(width) = self.max_width else {
+			return self.expanded(out, &token);
+		};
+		// A comment never fits on a line with other items, so it breaks
+		// every container around it.
+		if let Token::Comment(..) = token {
+			if self.pending.is_empty() {
+				return self.expanded(out, &token);
+			}
+			self.pending.push(token);
+			return self.break_pending(out);
+		}
+		if self.pending.is_empty() {
+			if !matches!(token, Token::Open(_)) {
+				return self.expanded(out, &token);
+			}
+			self.pending_column = self.column;
+			self.pending_len = 0;
+		}
+
+		match token {
+			Token::Open(_) => self.pending_open += 1,
+			Token::Close(_) => self.pending_open -= 1,
+			_ => {}
+		}
+		self.pending_len += columns(&self.flat(&token, self.pending.last()));
+		self.pending.push(token);
+
+		if self.pending_column + self.pending_len > width {
+			self.break_pending(out)
+		} else if self.pending_open == 0 {
+			let pending = mem::take(&mut self.pending);
+			for (i, token) in pending.iter().enumerate() {
+				let previous = i.checked_sub(1).map(|i| &pending[i]);
+				let text = self.flat(token, previous);
+				out.write_all(&text)?;
+			}
+			self.column += self.pending_len;
+			Ok(())
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Gives up on keeping the pending container on one line, writing its
+	/// opening bracket and reconsidering everything inside it.
+	fn break_pending<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		let mut tokens = mem::take(&mut self.pending).into_iter();
+		self.pending_open = 0;
+		if let Some(open) = tokens.next() {
+			self.expanded(out, &open)?;
+		}
+		tokens.try_for_each(|token| self.step(out, token))
+	}
+
+	/// The one-line text of a token.
+	fn flat(&self, token: &Token, previous: Option<&Token>) -> Vec<u8> {
+		match token {
+			Token::Open(bracket) => bracket.to_vec(),
+			Token::Close(bracket)
+				if self.space_in_empty
+					&& matches!(previous, Some(Token::Open(_))) =>
+			{
+				[b" ", *bracket].concat()
+			}
+			Token::Close(bracket) => bracket.to_vec(),
+			Token::Item { first: true } | Token::EndItem => Vec::new(),
+			Token::Item { first: false } => b", ".to_vec(),
+			Token::Colon => b": ".to_vec(),
+			Token::Text(text) | Token::Comment(text, _) => text.clone(),
+		}
+	}
+
+	/// Writes a token in the one-item-per-line layout.
+	fn expanded<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		token: &Token,
+	) -> io::Result<()> {
+		match token {
+			Token::Open(bracket) => {
+				self.depth += 1;
+				self.has_value = false;
+				self.emit(out, bracket)
+			}
+			Token::Close(bracket) => {
+				self.depth -= 1;
+				if self.has_value {
+					self.flush_trailing(out)?;
+					self.line(out)?;
+				} else if self.space_in_empty {
+					self.emit(out, b" ")?;
+				}
+				self.emit(out, bracket)
+			}
+			Token::Item { first } => {
+				if !first {
+					self.emit(out, b",")?;
+				}
+				self.flush_trailing(out)?;
+				self.line(out)
+			}
+			Token::Colon => self.emit(out, b": "),
+			Token::EndItem => {
+				self.has_value = true;
+				Ok(())
+			}
+			Token::Text(text) => self.emit(out, text),
+			Token::Comment(text, Placement::Leading) => {
+				self.flush_trailing(out)?;
+				self.emit(out, text)?;
+				self.line(out)
+			}
+			Token::Comment(text, Placement::Trailing) => {
+				self.trailing.push(text.clone());
+				Ok(())
+			}
+			Token::Comment(text, Placement::Dangling) => {
+				self.flush_trailing(out)?;
+				self.line(out)?;
+				self.has_value = true;
+				self.emit(out, text)
+			}
+		}
+	}
+
+	/// Writes the trailing comments held back, each after a space or,
+	/// after a line comment, on a line of its own.
+	fn flush_trailing<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		let mut line_comment = false;
+		for text in mem::take(&mut self.trailing) {
+			if line_comment {
+				self.line(out)?;
+			} else {
+				self.emit(out, b" ")?;
+			}
+			line_comment = text.starts_with(b"//");
+			self.emit(out, &text)?;
+		}
+		Ok(())
+	}
+
+	fn comment<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		text: &str,
+		placement: Placement,
+	) -> io::Result<()> {
+		self.step(
+			out,
+			Token::Comment(text.as_bytes().to_vec(), placement),
+		)
+	}
+
+	fn emit<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		text: &[u8],
+	) -> io::Result<()> {
+		self.column += columns(text);
+		out.write_all(text)
+	}
+
+	fn line<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		out.write_all(self.newline.as_bytes())?;
+		for _ in 0..self.depth {
+			out.write_all(self.indent.as_bytes())?;
+		}
+		self.column = self.depth * self.indent.chars().count();
+		Ok(())
+	}
+
+	/// Writes a scalar, holding it back with the rest of a pending
+	/// container if there is one.
+	fn scalar<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>,
+	) -> io::Result<()> {
+		let mut text = Vec::new();
+		write(&mut text)?;
+		self.step(out, Token::Text(text))
+	}
+}
+
+impl Formatter for PrettyFormatter {
+	fn write_null<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		self.scalar(out, |text| {
+			CompactFormatter::new().write_null(text)
+		})
+	}
+
+	fn write_bool<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: bool,
+	) -> io::Result<()> {
+		self.scalar(out, |text| {
+			CompactFormatter::new().write_bool(text, value)
+		})
+	}
+
+	fn write_number<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: f64,
+	) -> io::Result<()> {
+		let numbers = self.numbers;
+		self.scalar(out, |text| numbers.write(text, value))
+	}
+
+	fn write_string<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		value: &str,
+	) -> io::Result<()> {
+		self.scalar(out, |text| {
+			CompactFormatter::new().write_string(text, value)
+		})
+	}
+
+	fn begin_array<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		self.step(out, Token::Open(b"["))
+	}
+
+	fn end_array<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		self.step(out, Token::Close(b"]"))
+	}
+
+	fn begin_array_value<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		first: bool,
+	) -> io::Result<()> {
+		self.step(out, Token::Item { first })
+	}
+
+	fn end_array_value<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		self.step(out, Token::EndItem)
+	}
+
+	fn begin_object<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		self.step(out, Token::Open(b"{"))
+	}
+
+	fn end_object<W: ?Sized + Write>(&mut self, out: &mut W) -> io::Result<()> {
+		self.step(out, Token::Close(b"}"))
+	}
+
+	fn begin_object_key<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+		first: bool,
+	) -> io::Result<()> {
+		self.step(out, Token::Item { first })
+	}
+
+	fn begin_object_value<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		self.step(out, Token::Colon)
+	}
+
+	fn end_object_value<W: ?Sized + Write>(
+		&mut self,
+		out: &mut W,
+	) -> io::Result<()> {
+		self.step(out, Token::EndItem)
+	}
+}
+
+impl JsonValue {
+	/// Writes the value to `out`, shaped by `formatter`.
+	pub fn write_with<W: Write, F: Formatter>(
+		&self,
+		out: W,
+		formatter: F,
+	) -> io::Result<()> {
+		let mut writer = JsonWriter::with_formatter(out, formatter);
+		let written = writer.value(self).and_then(|()| writer.finish());
+		match written {
+			Ok(_) => Ok(()),
+			Err(WriteError::Io(e)) => Err(e),
+			Err(WriteError::Misplaced(message)) => {
+				unreachable!("a fresh writer takes any one value: {message}")
+			}
+		}
+	}
+
+	/// Writes the value to `out` as compact JSON.
+	pub fn to_writer<W: Write>(&self, out: W) -> io::Result<()> {
+		self.write_with(out, CompactFormatter::new())
+	}
+
+	/// Writes the value to `out` with [`PrettyFormatter`]'s defaults.
+	pub fn to_writer_pretty<W: Write>(&self, out: W) -> io::Result<()> {
+		self.write_with(out, PrettyFormatter::new())
+	}
+
+	/// The value as compact JSON bytes, as `to_string()` gives it as text.
+	pub fn to_vec(&self) -> Vec<u8> {
+		self.to_string().into_bytes()
+	}
+
+	/// Formats the value with [`PrettyFormatter`]'s defaults.
+	pub fn to_string_pretty(&self) -> String {
+		let mut out = Vec::new();
+		self.write_with(&mut out, PrettyFormatter::new())
+			.expect("writing to a Vec cannot fail");
+		String::from_utf8(out).expect("PrettyFormatter writes UTF-8")
+	}
+
+	/// Formats the value for machine-generated files that are committed and
+	/// diffed, where the same value must always produce the same bytes.
+	///
+	/// The output is [`PrettyFormatter`]'s defaults with object keys sorted
+	/// by their UTF-8 bytes and a final newline. Lines end in `\n` on every
+	/// platform, and numbers are written in the shortest form that parses
+	/// back to the same value, never in exponent notation and without a
+	/// fraction for integers, independent of locale. Changing any of this
+	/// is treated as a breaking change.
+	pub fn to_string_stable(&self) -> String {
+		let mut writer =
+			JsonWriter::with_formatter(Vec::new(), PrettyFormatter::new())
+				.sort_keys(true);
+		writer
+			.value(self)
+			.expect("a fresh writer takes any one value");
+		let mut out = writer.finish().expect("writing to a Vec cannot fail");
+		out.push(b'\n');
+		String::from_utf8(out).expect("PrettyFormatter writes UTF-8")
+	}
+}
+
+impl Node {
+	/// Writes the tree to `out` as [`PrettyFormatter`] lays it out, with
+	/// the comments of [`Node::comments`] each put back next to its node:
+	/// leading ones on lines of their own before it, trailing ones after it
+	/// and its comma, and dangling ones before the closing bracket.
+	///
+	/// Strings and numbers are written from their values, not their source
+	/// text.
+	pub fn write_pretty<W: Write>(
+		&self,
+		mut out: W,
+		mut formatter: PrettyFormatter,
+	) -> io::Result<()> {
+		write_node(self, &mut out, &mut formatter, true)?;
+		formatter.flush_trailing(&mut out)
+	}
+
+	/// Formats the tree with [`PrettyFormatter`]'s defaults, comments and
+	/// all; see [`Node::write_pretty`].
+	pub fn to_string_pretty(&self) -> String {
+		let mut out = Vec::new();
+		self.write_pretty(&mut out, PrettyFormatter::new())
+			.expect("writing to a Vec cannot fail");
+		String::from_utf8(out).expect("PrettyFormatter writes UTF-8")
+	}
+}
+
+/// Writes `node` and its comments, the leading ones only if `leading`, as
+/// a member's come before its key.
+fn write_node<W: Write>(
+	node: &Node,
+	out: &mut W,
+	formatter: &mut PrettyFormatter,
+	leading: bool,
+) -> io::Result<()> {
+	if leading {
+		for comment in &node.comments.leading {
+			formatter.comment(out, comment, Placement::Leading)?;
+		}
+	}
+	match &node.kind {
+		NodeKind::Null => formatter.write_null(out)?,
+		NodeKind::Boolean(b) => formatter.write_bool(out, *b)?,
+		NodeKind::Number(n) => formatter.write_number(out, *n)?,
+		NodeKind::String(s) => formatter.write_string(out, s)?,
+		NodeKind::Array(items) => {
+			formatter.begin_array(out)?;
+			for (i, item) in items.iter().enumerate() {
+				formatter.begin_array_value(out, i == 0)?;
+				write_node(item, out, formatter, true)?;
+				formatter.end_array_value(out)?;
+			}
+			write_dangling(node, out, formatter)?;
+			formatter.end_array(out)?;
+		}
+		NodeKind::Object(members) => {
+			formatter.begin_object(out)?;
+			for (i, member) in members.iter().enumerate() {
+				formatter.begin_object_key(out, i == 0)?;
+				for comment in &member.value.comments.leading {
+					formatter.comment(out, comment, Placement::Leading)?;
+				}
+				formatter.write_string(out, &member.key)?;
+				formatter.end_object_key(out)?;
+				formatter.begin_object_value(out)?;
+				write_node(&member.value, out, formatter, false)?;
+				formatter.end_object_value(out)?;
+			}
+			write_dangling(node, out, formatter)?;
+			formatter.end_object(out)?;
+		}
+	}
+	for comment in &node.comments.trailing {
+		formatter.comment(out, comment, Placement::Trailing)?;
+	}
+	Ok(())
+}
+
+fn write_dangling<W: Write>(
+	node: &Node,
+	out: &mut W,
+	formatter: &mut PrettyFormatter,
+) -> io::Result<()> {
+	for comment in &node.comments.dangling {
+		formatter.comment(out, comment, Placement::Dangling)?;
+	}
+	Ok(())
+}
+
+/// How many columns `text` takes up.
+fn columns(text: &[u8]) -> usize {
+	std::str::from_utf8(text).map_or(text.len(), |s| s.chars().count())
+}
+
+/// Runs a [`fmt::Write`]-based helper against an [`io::Write`] destination.
+pub(crate) fn write_io<W: ?Sized + Write>(
+	out: &mut W,
+	write: impl FnOnce(&mut dyn fmt::Write) -> fmt::Result,
+) -> io::Result<()> {
+	let mut adapter = Adapter { out, error: None };
+	match write(&mut adapter) {
+		Ok(()) => Ok(()),
+		Err(_) => Err(adapter
+			.error
+			.unwrap_or_else(|| io::Error::other("formatter error"))),
+	}
+}
+
+/// Bridges [`fmt::Write`] to [`io::Write`], keeping the underlying error.
+struct Adapter<'a, W: ?Sized> {
+	out: &'a mut W,
+	error: Option<io::Error>,
+}
+
+impl<W: ?Sized + Write> fmt::Write for Adapter<'_, W> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		self.out.write_all(s.as_bytes()).map_err(|e| {
+			self.error = Some(e);
+			fmt::Error
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::parse;
+
+	use super::*;
+
+	#[test]
+	fn stable_output() {
+		let value = parse(
+			r#"{"b": [1.0, 2.5, -0, 1e21], "a": {"z": null, "é": true, "Z": "x"}}"#,
+		)
+		.unwrap();
+		let expected = "{\n  \"a\": {\n    \"Z\": \"x\",\n    \"z\": null,\n    \"é\": true\n  },\n  \"b\": [\n    1,\n    2.5,\n    -0,\n    1000000000000000000000\n  ]\n}\n";
+		assert_eq!(value.to_string_stable(), expected);
+		// The same value built in another order gives the same bytes.
+		let mut reordered = value.clone();
+		let map = reordered.as_object_mut().unwrap();
+		let a = map.remove("a").unwrap();
+		map.insert("a".to_owned(), a);
+		assert_eq!(reordered.to_string_stable(), expected);
+		assert_eq!(parse(expected).unwrap(), value);
+		assert_eq!(
+			parse(expected).unwrap().to_string_stable(),
+			expected
+		);
+	}
+
+	#[test]
+	fn number_formats() {
+		let write = |format: NumberFormat, n: f64| {
+			let mut out = Vec::new();
+			format.write(&mut out, n).unwrap();
+			String::from_utf8(out).unwrap()
+		};
+		let scientific = NumberFormat::scientific(-7, 21);
+		assert_eq!(write(scientific, 1e21), "1e21");
+		assert_eq!(
+			write(scientific, 1e20),
+			"100000000000000000000"
+		);
+		assert_eq!(write(scientific, 1.5e-7), "1.5e-7");
+		assert_eq!(write(scientific, 0.000001), "0.000001");
+		assert_eq!(
+			write(NumberFormat::fixed(2), 0.1),
+			"0.10"
+		);
+		assert_eq!(write(NumberFormat::fixed(2), 3.0), "3");
+		let zero = NumberFormat::shortest().normalize_zero(true);
+		assert_eq!(write(zero, -0.0), "0");
+		assert_eq!(
+			write(NumberFormat::shortest(), f64::NAN),
+			"null"
+		);
+	}
+
+	#[test]
+	fn comments_round_trip() {
+		let input = "// Editor settings\n/* top */\n{\n  // The font.\n  \"font\": \"mono\", // trailing\n  \"size\": 12 /* px */, // more\n  \"list\": [\n    1, // one\n    2\n    // after two\n  ],\n  \"empty\": {\n    // nothing yet\n  },\n  \"last\": true\n  // end\n} // done\n// eof";
+		let expected = input.replace("12 /* px */,", "12, /* px */");
+		let node = crate::parse_spanned_with_comments(input).unwrap();
+		let written = node.to_string_pretty();
+		assert_eq!(written, expected);
+		let again = crate::parse_spanned_with_comments(&written).unwrap();
+		assert_eq!(again.to_string_pretty(), written);
+	}
+
+	#[test]
+	fn comments_break_lines() {
+		let input = r#"{"a": [1, 2], "b": [3, /* three */ 4], "c": 5 // five
+		}"#;
+		let node = crate::parse_spanned_with_comments(input).unwrap();
+		let mut out = Vec::new();
+		node.write_pretty(
+			&mut out,
+			PrettyFormatter::new().max_width(80),
+		)
+		.unwrap();
+		assert_eq!(
+			String::from_utf8(out).unwrap(),
+			"{\n  \"a\": [1, 2],\n  \"b\": [\n    3, /* three */\n    4\n  ],\n  \"c\": 5 // five\n}"
+		);
+	}
+
+	fn formatted(value: &str, formatter: impl Formatter) -> String {
+		let mut out = Vec::new();
+		parse(value)
+			.unwrap()
+			.write_with(&mut out, formatter)
+			.unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn formatters_shape_the_output() {
+		assert_eq!(
+			formatted(
+				r#"[1, {"a": [true, null]}, "s", [], {}]"#,
+				CompactFormatter::new()
+			),
+			r#"[1,{"a":[true,null]},"s",[],{}]"#
+		);
+		let value = r#"{"a": [1, {"b": null}]}"#;
+		assert_eq!(
+			formatted(value, PrettyFormatter::new()),
+			"{\n  \"a\": [\n    1,\n    {\n      \"b\": null\n    }\n  ]\n}"
+		);
+
+		/// Compact output with spaces after separators, changing only the
+		/// hooks for them.
+		struct Spaced;
+
+		impl Formatter for Spaced {
+			fn begin_array_value<W: ?Sized + Write>(
+				&mut self,
+				out: &mut W,
+				first: bool,
+			) -> io::Result<()> {
+				out.write_all(if first { b"" } else { b", " })
+			}
+
+			fn begin_object_value<W: ?Sized + Write>(
+				&mut self,
+				out: &mut W,
+			) -> io::Result<()> {
+				out.write_all(b": ")
+			}
+		}
+
+		assert_eq!(
+			formatted(value, Spaced),
+			r#"{"a": [1, {"b": null}]}"#
+		);
+	}
+
+	/// `value` written by `formatter`, with its keys in order.
+	fn sorted(value: &str, formatter: PrettyFormatter) -> String {
+		let mut writer =
+			JsonWriter::with_formatter(Vec::new(), formatter).sort_keys(true);
+		writer.value(&parse(value).unwrap()).unwrap();
+		String::from_utf8(writer.finish().unwrap()).unwrap()
+	}
+
+	#[test]
+	fn pretty_layout_options() {
+		let value = r#"{"a": [1, []], "b": {}}"#;
+		assert_eq!(
+			sorted(value, PrettyFormatter::new().tabs()),
+			"{\n\t\"a\": [\n\t\t1,\n\t\t[]\n\t],\n\t\"b\": {}\n}"
+		);
+		assert_eq!(
+			sorted(
+				value,
+				PrettyFormatter::new().indent(" ").newline("\r\n")
+			),
+			"{\r\n \"a\": [\r\n  1,\r\n  []\r\n ],\r\n \"b\": {}\r\n}"
+		);
+		assert_eq!(
+			sorted(
+				value,
+				PrettyFormatter::new().space_in_empty(true)
+			),
+			"{\n  \"a\": [\n    1,\n    [ ]\n  ],\n  \"b\": { }\n}"
+		);
+	}
+
+	#[test]
+	fn containers_that_fit_stay_on_one_line() {
+		let value = r#"{"short": [1, 2], "nested": {"x": [1, 2, 3]},
+			"long": ["aaaaaaaaaa", "bbbbbbbbbb", "cccccccccc"]}"#;
+		assert_eq!(
+			sorted(value, PrettyFormatter::new().max_width(30)),
+			"{\n  \"long\": [\n    \"aaaaaaaaaa\",\n    \"bbbbbbbbbb\",\n    \"cccccccccc\"\n  ],\n  \"nested\": {\"x\": [1, 2, 3]},\n  \"short\": [1, 2]\n}"
+		);
+		assert_eq!(
+			sorted(
+				r#"[[1, 2], [3]]"#,
+				PrettyFormatter::new().max_width(13)
+			),
+			"[[1, 2], [3]]"
+		);
+		assert_eq!(
+			sorted(
+				r#"[[1, 2], [3]]"#,
+				PrettyFormatter::new().max_width(12)
+			),
+			"[\n  [1, 2],\n  [3]\n]"
+		);
+		assert_eq!(
+			sorted(
+				r#"{"a": []}"#,
+				PrettyFormatter::new().max_width(0)
+			),
+			"{\n  \"a\": []\n}"
+		);
+	}
+
+	#[test]
+	fn formatters_use_their_number_format() {
+		let value = "[0.125, -0, 25, 1e-9, -1.5e300, 12345.678]";
+		assert_eq!(
+			formatted(
+				"[0.25, -0, 25, 1e-9, 12345.678]",
+				CompactFormatter::new().numbers(NumberFormat::fixed(1))
+			),
+			"[0.2,-0,25,0.0,12345.7]",
+		);
+		let scientific = NumberFormat::scientific(-7, 21).normalize_zero(true);
+		assert_eq!(
+			formatted(
+				value,
+				CompactFormatter::new().numbers(scientific)
+			),
+			"[0.125,0,25,1e-9,-1.5e300,12345.678]"
+		);
+		assert_eq!(
+			sorted(
+				"[2.5]",
+				PrettyFormatter::new().numbers(NumberFormat::fixed(3))
+			),
+			"[\n  2.500\n]"
+		);
+	}
+
+	#[test]
+	fn writer_and_byte_outputs() {
+		let value = parse(r#"{"a": [1, "é", null]}"#).unwrap();
+		assert_eq!(
+			value.to_vec(),
+			r#"{"a":[1,"é",null]}"#.as_bytes()
+		);
+
+		let mut out = Vec::new();
+		value.to_writer(&mut out).unwrap();
+		assert_eq!(out, value.to_vec());
+
+		let mut out = Vec::new();
+		value.to_writer_pretty(&mut out).unwrap();
+		assert_eq!(
+			String::from_utf8(out).unwrap(),
+			value.to_string_pretty()
+		);
+
+		struct Full;
+		impl Write for Full {
+			fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+				Err(io::ErrorKind::WriteZero.into())
+			}
+			fn flush(&mut self) -> io::Result<()> {
+				Ok(())
+			}
+		}
+		assert_eq!(
+			value.to_writer(Full).unwrap_err().kind(),
+			io::ErrorKind::WriteZero
+		);
+		assert_eq!(
+			value.to_writer_pretty(Full).unwrap_err().kind(),
+			io::ErrorKind::WriteZero
+		);
+	}
+}
@@ -0,0 +1,226 @@
+//! Building values from arbitrary bytes, for fuzzing code that consumes
+//! JSON.
+
+use std::collections::HashMap;
+
+use crate::JsonValue;
+
+/// Bounds on the values [`JsonValue::from_fuzz_input`] builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerateLimits {
+	/// How deeply arrays and objects may nest. At 0 only scalars are made.
+	pub max_depth: usize,
+	/// The most items in an array, members in an object, or characters in
+	/// a string or key.
+	pub max_len: usize,
+}
+
+impl Default for GenerateLimits {
+	fn default() -> Self {
+		Self {
+			max_depth: 8,
+			max_len: 16,
+		}
+	}
+}
+
+impl JsonValue {
+	/// Builds a value from `data`, such as the input a fuzzer such as
+	/// `cargo fuzz` provides, within `limits`.
+	///
+	/// The same bytes always build the same value, so a fuzzer can steer
+	/// towards the values that matter. Once the bytes run out, the rest of the
+	/// value is filled in with `null`s and empty containers. Strings range
+	/// over all of Unicode, and numbers over every finite `f64`, with
+	/// integers more likely.
+	///
+	/// ```text
+	/// fuzz_target!(|data: &[u8]| {
+	///     let value = JsonValue::from_fuzz_input(data, GenerateLimits::default());
+	///     my_crate::handle(&value);
+	/// });
+	/// ```
+	pub fn from_fuzz_input(data: &[u8], limits: GenerateLimits) -> JsonValue {
+		Generator { data, limits }.value(0)
+	}
+}
+
+struct Generator<'d> {
+	/// The bytes not used yet.
+	data: &'d [u8],
+	limits: GenerateLimits,
+}
+
+impl Generator<'_> {
+	/// The next byte, or 0 once there are none left.
+	fn byte(&mut self) -> u8 {
+		match self.data.split_first() {
+			Some((&b, rest)) => {
+				self.data = rest;
+				b
+			}
+			None => 0,
+		}
+	}
+
+	fn bytes<const N: usize>(&mut self) -> [u8; N] {
+		std::array::from_fn(|_| self.byte())
+	}
+
+	fn len(&mut self) -> usize {
+		usize::from(self.byte()) % (self.limits.max_len + 1)
+	}
+
+	fn value(&mut self, depth: usize) -> JsonValue {
+		let kinds = if depth < self.limits.max_depth { 6 } else { 4 };
+		match self.byte() % kinds {
+			0 => JsonValue::Null,
+			1 => JsonValue::Boolean(self.byte() & 1 == 1),
+			2 => JsonValue::Number(self.number()),
+			3 => JsonValue::String(self.string()),
+			4 => {
+				let len = self.len();
+				JsonValue::Array(
+					(0..len).map(|_| self.value(depth + 1)).collect(),
+				)
+			}
+			_ => {
+				let len = self.len();
+				let mut map = HashMap::with_capacity(len);
+				for _ in 0..len {
+					let key = self.string();
+					map.insert(key, self.value(depth + 1));
+				}
+				JsonValue::Object(Box::new(map))
+			}
+		}
+	}
+
+	fn number(&mut self) -> f64 {
+		if self.byte() < 192 {
+			return f64::from(i32::from_le_bytes(self.bytes()));
+		}
+		let n = f64::from_le_bytes(self.bytes());
+		if n.is_finite() {
+			n
+		} else {
+			0.0
+		}
+	}
+
+	fn string(&mut self) -> String {
+		let len = self.len();
+		(0..len)
+			.map(|_| {
+				let b = self.byte();
+				if b < 192 {
+					return char::from(b & 0x7f);
+				}
+				let [x, y, z] = self.bytes();
+				let code = u32::from_le_bytes([x, y, z, 0]) % 0x11_0000;
+				char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn depth(value: &JsonValue) -> usize {
+		match value {
+			JsonValue::Array(items) => {
+				1 + items.iter().map(depth).max().unwrap_or(0)
+			}
+			JsonValue::Object(map) => {
+				1 + map.values().map(depth).max().unwrap_or(0)
+			}
+			_ => 0,
+		}
+	}
+
+	fn len(value: &JsonValue) -> usize {
+		match value {
+			JsonValue::Array(items) => items.len(),
+			JsonValue::Object(map) => map.len(),
+			JsonValue::String(s) => s.chars().count(),
+			_ => 0,
+		}
+	}
+
+	#[test]
+	fn bytes_choose_the_value() {
+		let limits = GenerateLimits::default();
+		assert_eq!(
+			JsonValue::from_fuzz_input(&[], limits),
+			JsonValue::Null
+		);
+		assert_eq!(
+			JsonValue::from_fuzz_input(&[4, 2, 2, 0, 5, 0, 0, 0, 1, 1], limits),
+			parse("[5, true]").unwrap()
+		);
+		assert_eq!(
+			JsonValue::from_fuzz_input(
+				&[3, 2, b'h', 0xe8, 0x4e, 0x01, 0],
+				limits
+			),
+			JsonValue::String("hŎ".to_owned())
+		);
+		// The array's items run out of bytes and become nulls.
+		assert_eq!(
+			JsonValue::from_fuzz_input(&[4, 3], limits),
+			parse("[null, null, null]").unwrap()
+		);
+		let scalars = GenerateLimits {
+			max_depth: 0,
+			..limits
+		};
+		assert_eq!(
+			JsonValue::from_fuzz_input(&[4, 3], scalars),
+			JsonValue::Null
+		);
+	}
+
+	#[test]
+	fn values_stay_within_limits() {
+		let limits = GenerateLimits {
+			max_depth: 3,
+			max_len: 4,
+		};
+		let mut state = 0x2545_f491_4f6c_dd1d_u64;
+		for _ in 0..500 {
+			let data: Vec<u8> = (0..256)
+				.map(|_| {
+					state ^= state << 13;
+					state ^= state >> 7;
+					state ^= state << 17;
+					state as u8
+				})
+				.collect();
+			let value = JsonValue::from_fuzz_input(&data, limits);
+			assert_eq!(
+				value,
+				JsonValue::from_fuzz_input(&data, limits)
+			);
+			assert!(
+				depth(&value) <= limits.max_depth,
+				"{value}"
+			);
+			let mut stack = vec![&value];
+			while let Some(value) = stack.pop() {
+				assert!(len(value) <= limits.max_len, "{value}");
+				match value {
+					JsonValue::Array(items) => stack.extend(items),
+					JsonValue::Object(map) => stack.extend(map.values()),
+					_ => {}
+				}
+			}
+			assert_eq!(
+				parse(&value.to_string()).unwrap(),
+				value
+			);
+		}
+	}
+}
@@ -0,0 +1,59 @@
+use std::fmt::Write;
+
+use crate::{ser::write_string, JsonValue};
+
+impl JsonValue {
+	/// Renders the value as `gron`-style assignments, one per line, e.g.
+	/// `json.users[0].name = "alice";`.
+	///
+	/// Object keys are sorted so the output is stable and greppable.
+	pub fn to_gron(&self) -> String {
+		let mut out = String::new();
+		gron(self, &mut "json".to_owned(), &mut out);
+		out
+	}
+}
+
+fn gron(node: &JsonValue, path: &mut String, out: &mut String) {
+	let len = path.len();
+	match node {
+		JsonValue::Array(items) => {
+			out.push_str(path);
+			out.push_str(" = [];\n");
+			for (index, item) in items.iter().enumerate() {
+				let _ = write!(path, "[{index}]");
+				gron(item, path, out);
+				path.truncate(len);
+			}
+		}
+		JsonValue::Object(map) => {
+			out.push_str(path);
+			out.push_str(" = {};\n");
+			let mut entries: Vec<_> = map.iter().collect();
+			entries.sort_by_key(|(key, _)| *key);
+			for (key, value) in entries {
+				if is_identifier(key) {
+					path.push('.');
+					path.push_str(key);
+				} else {
+					path.push('[');
+					let _ = write_string(path, key);
+					path.push(']');
+				}
+				gron(value, path, out);
+				path.truncate(len);
+			}
+		}
+		_ => {
+			let _ = writeln!(out, "{path} = {node};");
+		}
+	}
+}
+
+fn is_identifier(key: &str) -> bool {
+	let mut chars = key.chars();
+	chars
+		.next()
+		.is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+		&& chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
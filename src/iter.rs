@@ -0,0 +1,186 @@
+use std::{collections::hash_map, vec};
+
+use crate::JsonValue;
+
+impl FromIterator<JsonValue> for JsonValue {
+	fn from_iter<T: IntoIterator<Item = JsonValue>>(iter: T) -> Self {
+		JsonValue::Array(iter.into_iter().collect())
+	}
+}
+
+impl<K: Into<String>> FromIterator<(K, JsonValue)> for JsonValue {
+	fn from_iter<T: IntoIterator<Item = (K, JsonValue)>>(iter: T) -> Self {
+		JsonValue::Object(Box::new(
+			iter.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+		))
+	}
+}
+
+/// Appends to an array. A `null` is turned into an empty array first, and
+/// any other value is left unchanged, with the elements dropped.
+impl Extend<JsonValue> for JsonValue {
+	fn extend<T: IntoIterator<Item = JsonValue>>(&mut self, iter: T) {
+		if self.is_null() {
+			*self = JsonValue::Array(Vec::new());
+		}
+		if let JsonValue::Array(items) = self {
+			items.extend(iter);
+		}
+	}
+}
+
+/// Inserts into an object. A `null` is turned into an empty object first,
+/// and any other value is left unchanged, with the entries dropped.
+impl<K: Into<String>> Extend<(K, JsonValue)> for JsonValue {
+	fn extend<T: IntoIterator<Item = (K, JsonValue)>>(&mut self, iter: T) {
+		if self.is_null() {
+			*self = JsonValue::Object(Default::default());
+		}
+		if let JsonValue::Object(map) = self {
+			map.extend(iter.into_iter().map(|(k, v)| (k.into(), v)));
+		}
+	}
+}
+
+/// An item of [`IntoIter`]: an array element or an object entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Child {
+	Element(JsonValue),
+	Entry(String, JsonValue),
+}
+
+impl Child {
+	/// The object key, if this is an entry.
+	pub fn key(&self) -> Option<&str> {
+		match self {
+			Child::Element(_) => None,
+			Child::Entry(key, _) => Some(key),
+		}
+	}
+
+	pub fn value(&self) -> &JsonValue {
+		match self {
+			Child::Element(value) | Child::Entry(_, value) => value,
+		}
+	}
+
+	pub fn into_value(self) -> JsonValue {
+		match self {
+			Child::Element(value) | Child::Entry(_, value) => value,
+		}
+	}
+}
+
+/// The owning iterator over a [`JsonValue`]'s children.
+///
+/// Scalars have no children and yield nothing.
+#[derive(Debug)]
+pub enum IntoIter {
+	Array(vec::IntoIter<JsonValue>),
+	Object(hash_map::IntoIter<String, JsonValue>),
+	Scalar,
+}
+
+impl Iterator for IntoIter {
+	type Item = Child;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self {
+			IntoIter::Array(items) => items.next().map(Child::Element),
+			IntoIter::Object(entries) => {
+				entries.next().map(|(k, v)| Child::Entry(k, v))
+			}
+			IntoIter::Scalar => None,
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		match self {
+			IntoIter::Array(items) => items.size_hint(),
+			IntoIter::Object(entries) => entries.size_hint(),
+			IntoIter::Scalar => (0, Some(0)),
+		}
+	}
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for JsonValue {
+	type IntoIter = IntoIter;
+	type Item = Child;
+
+	fn into_iter(self) -> Self::IntoIter {
+		match self {
+			JsonValue::Array(items) => IntoIter::Array(items.into_iter()),
+			JsonValue::Object(map) => IntoIter::Object(map.into_iter()),
+			_ => IntoIter::Scalar,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn collects_into_arrays_and_objects() {
+		let array: JsonValue = (1..=3).map(JsonValue::from).collect();
+		assert_eq!(array, parse("[1, 2, 3]").unwrap());
+		let object: JsonValue = [("a", JsonValue::from(1)), ("b", true.into())]
+			.into_iter()
+			.collect();
+		assert_eq!(
+			object,
+			parse(r#"{"a": 1, "b": true}"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn extends_containers_and_null() {
+		let mut value = parse("[1]").unwrap();
+		value.extend([JsonValue::from(2)]);
+		assert_eq!(value, parse("[1, 2]").unwrap());
+
+		let mut value = JsonValue::Null;
+		value.extend([JsonValue::from("a")]);
+		assert_eq!(value, parse(r#"["a"]"#).unwrap());
+
+		let mut value = JsonValue::Null;
+		value.extend([("k", JsonValue::Null)]);
+		assert_eq!(value, parse(r#"{"k": null}"#).unwrap());
+
+		for input in ["1", "\"s\"", "true", "{}"] {
+			let mut value = parse(input).unwrap();
+			value.extend([JsonValue::from(2)]);
+			assert_eq!(value, parse(input).unwrap());
+		}
+		for input in ["1", "[]"] {
+			let mut value = parse(input).unwrap();
+			value.extend([("k", JsonValue::Null)]);
+			assert_eq!(value, parse(input).unwrap());
+		}
+	}
+
+	#[test]
+	fn iterates_over_children() {
+		let children: Vec<Child> =
+			parse(r#"[1, "a"]"#).unwrap().into_iter().collect();
+		assert_eq!(
+			children,
+			[Child::Element(1.into()), Child::Element("a".into())]
+		);
+		assert_eq!(children[0].key(), None);
+
+		let mut children = parse(r#"{"k": null}"#).unwrap().into_iter();
+		assert_eq!(children.len(), 1);
+		let child = children.next().unwrap();
+		assert_eq!(child.key(), Some("k"));
+		assert_eq!(child.value(), &JsonValue::Null);
+		assert_eq!(child.into_value(), JsonValue::Null);
+
+		let scalar = parse("42").unwrap().into_iter();
+		assert_eq!(scalar.size_hint(), (0, Some(0)));
+		assert_eq!(scalar.count(), 0);
+	}
+}
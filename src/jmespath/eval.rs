@@ -0,0 +1,542 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use super::{Ast, Comparator, SearchError};
+use crate::{sort::numbers, JsonValue};
+
+type Outcome = Result<JsonValue, SearchError>;
+
+fn error<T>(message: impl Into<String>) -> Result<T, SearchError> {
+	Err(SearchError {
+		message: message.into(),
+	})
+}
+
+/// JMESPath's truthiness: `false`, `null` and empty strings, arrays and
+/// objects are false.
+fn truthy(value: &JsonValue) -> bool {
+	match value {
+		JsonValue::Null | JsonValue::Boolean(false) => false,
+		JsonValue::String(s) => !s.is_empty(),
+		JsonValue::Array(items) => !items.is_empty(),
+		JsonValue::Object(map) => !map.is_empty(),
+		_ => true,
+	}
+}
+
+pub(super) fn eval(ast: &Ast, data: &JsonValue) -> Outcome {
+	Ok(match ast {
+		Ast::Current => data.clone(),
+		Ast::Field(name) => data.get(name).cloned().unwrap_or(JsonValue::Null),
+		Ast::Literal(value) => value.clone(),
+		Ast::Subexpression(left, right) | Ast::Pipe(left, right) => {
+			eval(right, &eval(left, data)?)?
+		}
+		Ast::Index(i) => match data {
+			JsonValue::Array(items) => {
+				let i = match *i < 0 {
+					true => items.len() as i64 + i,
+					false => *i,
+				};
+				usize::try_from(i)
+					.ok()
+					.and_then(|i| items.get(i))
+					.cloned()
+					.unwrap_or(JsonValue::Null)
+			}
+			_ => JsonValue::Null,
+		},
+		Ast::Slice(start, stop, step) => match data {
+			JsonValue::Array(items) => {
+				JsonValue::Array(slice(items, *start, *stop, *step))
+			}
+			_ => JsonValue::Null,
+		},
+		Ast::Projection(left, right) => match eval(left, data)? {
+			JsonValue::Array(items) => project(&items, right)?,
+			_ => JsonValue::Null,
+		},
+		Ast::ValueProjection(left, right) => match eval(left, data)? {
+			JsonValue::Object(map) => project(
+				&map.into_values().collect::<Vec<_>>(),
+				right,
+			)?,
+			_ => JsonValue::Null,
+		},
+		Ast::FilterProjection {
+			left,
+			right,
+			condition,
+		} => match eval(left, data)? {
+			JsonValue::Array(items) => {
+				let mut kept = Vec::new();
+				for item in items {
+					if truthy(&eval(condition, &item)?) {
+						kept.push(item);
+					}
+				}
+				project(&kept, right)?
+			}
+			_ => JsonValue::Null,
+		},
+		Ast::Flatten(inner) => match eval(inner, data)? {
+			JsonValue::Array(items) => {
+				let mut flat = Vec::new();
+				for item in items {
+					match item {
+						JsonValue::Array(inner) => flat.extend(inner),
+						item => flat.push(item),
+					}
+				}
+				JsonValue::Array(flat)
+			}
+			_ => JsonValue::Null,
+		},
+		Ast::MultiSelectList(_) | Ast::MultiSelectHash(_) if data.is_null() => {
+			JsonValue::Null
+		}
+		Ast::MultiSelectList(items) => JsonValue::Array(
+			items
+				.iter()
+				.map(|item| eval(item, data))
+				.collect::<Result<_, _>>()?,
+		),
+		Ast::MultiSelectHash(entries) => entries
+			.iter()
+			.map(|(key, value)| Ok((key.clone(), eval(value, data)?)))
+			.collect::<Result<_, SearchError>>()?,
+		Ast::Or(left, right) => {
+			let left = eval(left, data)?;
+			match truthy(&left) {
+				true => left,
+				false => eval(right, data)?,
+			}
+		}
+		Ast::And(left, right) => {
+			let left = eval(left, data)?;
+			match truthy(&left) {
+				true => eval(right, data)?,
+				false => left,
+			}
+		}
+		Ast::Not(inner) => JsonValue::Boolean(!truthy(&eval(inner, data)?)),
+		Ast::Comparison(op, left, right) => {
+			let (left, right) = (eval(left, data)?, eval(right, data)?);
+			let order = match (&left, &right) {
+				(JsonValue::Number(a), JsonValue::Number(b)) => {
+					a.partial_cmp(b)
+				}
+				_ => None,
+			};
+			match (op, order) {
+				(Comparator::Eq, _) => JsonValue::Boolean(left == right),
+				(Comparator::Ne, _) => JsonValue::Boolean(left != right),
+				(_, None) => JsonValue::Null,
+				(Comparator::Lt, Some(order)) => order.is_lt().into(),
+				(Comparator::Le, Some(order)) => order.is_le().into(),
+				(Comparator::Gt, Some(order)) => order.is_gt().into(),
+				(Comparator::Ge, Some(order)) => order.is_ge().into(),
+			}
+		}
+		Ast::Function(name, args) => call(name, args, data)?,
+		Ast::Expref(_) => {
+			return error(
+				"expression references can only be passed to functions",
+			)
+		}
+	})
+}
+
+/// Applies `right` to each item, dropping `null` results.
+fn project(items: &[JsonValue], right: &Ast) -> Outcome {
+	let mut out = Vec::new();
+	for item in items {
+		let value = eval(right, item)?;
+		if !value.is_null() {
+			out.push(value);
+		}
+	}
+	Ok(JsonValue::Array(out))
+}
+
+/// Python-style slicing, with negative bounds counting from the end.
+fn slice(
+	items: &[JsonValue],
+	start: Option<i64>,
+	stop: Option<i64>,
+	step: Option<i64>,
+) -> Vec<JsonValue> {
+	let len = items.len() as i64;
+	let step = step.unwrap_or(1);
+	let clamp = |bound: Option<i64>, default: i64| match bound {
+		None => default,
+		Some(n) if n < 0 => match n + len {
+			n if n >= 0 => n,
+			_ if step < 0 => -1,
+			_ => 0,
+		},
+		Some(n) if n >= len && step < 0 => len - 1,
+		Some(n) => n.min(len),
+	};
+	let (start, stop) = match step < 0 {
+		true => (clamp(start, len - 1), clamp(stop, -1)),
+		false => (clamp(start, 0), clamp(stop, len)),
+	};
+	let mut out = Vec::new();
+	let mut i = start;
+	while (step > 0 && i < stop) || (step < 0 && i > stop) {
+		out.push(items[i as usize].clone());
+		i += step;
+	}
+	out
+}
+
+/// A function argument: a value, or an unevaluated `&expression`.
+enum Arg<'a> {
+	Value(JsonValue),
+	Expref(&'a Ast),
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+	value.type_of().as_str()
+}
+
+fn invalid_type<T>(
+	name: &str,
+	expected: &str,
+	found: &JsonValue,
+) -> Result<T, SearchError> {
+	error(format!(
+		"invalid-type: {name}() expects {expected}, found {}",
+		type_name(found)
+	))
+}
+
+fn number(name: &str, value: &JsonValue) -> Result<f64, SearchError> {
+	match value {
+		JsonValue::Number(n) => Ok(*n),
+		value => invalid_type(name, "a number", value),
+	}
+}
+
+fn string<'a>(
+	name: &str,
+	value: &'a JsonValue,
+) -> Result<&'a str, SearchError> {
+	match value {
+		JsonValue::String(s) => Ok(s),
+		value => invalid_type(name, "a string", value),
+	}
+}
+
+fn array<'a>(
+	name: &str,
+	value: &'a JsonValue,
+) -> Result<&'a [JsonValue], SearchError> {
+	match value {
+		JsonValue::Array(items) => Ok(items),
+		value => invalid_type(name, "an array", value),
+	}
+}
+
+fn object<'a>(
+	name: &str,
+	value: &'a JsonValue,
+) -> Result<&'a HashMap<String, JsonValue>, SearchError> {
+	match value {
+		JsonValue::Object(map) => Ok(map),
+		value => invalid_type(name, "an object", value),
+	}
+}
+
+/// Checks that sort keys are all numbers or all strings.
+fn compare_keys(name: &str, keys: &[JsonValue]) -> Result<(), SearchError> {
+	match keys.first() {
+		Some(JsonValue::Number(_)) | None => {
+			keys.iter().try_for_each(|key| number(name, key).map(drop))
+		}
+		Some(JsonValue::String(_)) => {
+			keys.iter().try_for_each(|key| string(name, key).map(drop))
+		}
+		Some(value) => invalid_type(name, "numbers or strings", value),
+	}
+}
+
+fn order(a: &JsonValue, b: &JsonValue) -> Ordering {
+	match (a, b) {
+		(JsonValue::Number(a), JsonValue::Number(b)) => numbers(*a, *b),
+		(JsonValue::String(a), JsonValue::String(b)) => a.cmp(b),
+		_ => Ordering::Equal,
+	}
+}
+
+/// Evaluates `expression` for every item, as `sort_by`, `min_by` and
+/// `max_by` do, checking the keys have a sortable type.
+fn keys_by(
+	name: &str,
+	items: &[JsonValue],
+	expression: &Ast,
+) -> Result<Vec<JsonValue>, SearchError> {
+	let keys = items
+		.iter()
+		.map(|item| eval(expression, item))
+		.collect::<Result<Vec<_>, _>>()?;
+	compare_keys(name, &keys)?;
+	Ok(keys)
+}
+
+/// The built-in functions, which a call must name; the parser checks.
+pub(super) const FUNCTIONS: &[&str] = &[
+	"abs",
+	"avg",
+	"ceil",
+	"contains",
+	"ends_with",
+	"floor",
+	"join",
+	"keys",
+	"length",
+	"map",
+	"max",
+	"max_by",
+	"merge",
+	"min",
+	"min_by",
+	"not_null",
+	"reverse",
+	"sort",
+	"sort_by",
+	"starts_with",
+	"sum",
+	"to_array",
+	"to_number",
+	"to_string",
+	"type",
+	"values",
+];
+
+fn call(name: &str, args: &[Ast], data: &JsonValue) -> Outcome {
+	let args = args
+		.iter()
+		.map(|arg| match arg {
+			Ast::Expref(expression) => Ok(Arg::Expref(expression)),
+			arg => eval(arg, data).map(Arg::Value),
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let arity = |expected: usize| match args.len() == expected {
+		true => Ok(()),
+		false => error(format!(
+			"invalid-arity: {name}() takes {expected} arguments, found {}",
+			args.len()
+		)),
+	};
+	let value = |i: usize| match &args[i] {
+		Arg::Value(value) => Ok(value),
+		Arg::Expref(_) => error(format!(
+			"invalid-type: {name}() does not take an expression reference"
+		)),
+	};
+	let expref = |i: usize| match &args[i] {
+		Arg::Expref(expression) => Ok(*expression),
+		Arg::Value(value) => {
+			invalid_type(name, "an expression reference", value)
+		}
+	};
+
+	Ok(match name {
+		"abs" | "ceil" | "floor" => {
+			arity(1)?;
+			let n = number(name, value(0)?)?;
+			JsonValue::Number(match name {
+				"abs" => n.abs(),
+				"ceil" => n.ceil(),
+				_ => n.floor(),
+			})
+		}
+		"avg" | "sum" => {
+			arity(1)?;
+			let items = array(name, value(0)?)?;
+			let mut sum = 0.0;
+			for item in items {
+				sum += number(name, item)?;
+			}
+			match (name, items.len()) {
+				("avg", 0) => JsonValue::Null,
+				("avg", len) => JsonValue::Number(sum / len as f64),
+				_ => JsonValue::Number(sum),
+			}
+		}
+		"contains" => {
+			arity(2)?;
+			let needle = value(1)?;
+			match value(0)? {
+				JsonValue::Array(items) => items.contains(needle),
+				JsonValue::String(s) => s.contains(string(name, needle)?),
+				subject => {
+					return invalid_type(name, "an array or string", subject)
+				}
+			}
+			.into()
+		}
+		"ends_with" | "starts_with" => {
+			arity(2)?;
+			let (subject, affix) = (
+				string(name, value(0)?)?,
+				string(name, value(1)?)?,
+			);
+			match name {
+				"ends_with" => subject.ends_with(affix),
+				_ => subject.starts_with(affix),
+			}
+			.into()
+		}
+		"join" => {
+			arity(2)?;
+			let glue = string(name, value(0)?)?;
+			let parts = array(name, value(1)?)?
+				.iter()
+				.map(|part| string(name, part))
+				.collect::<Result<Vec<_>, _>>()?;
+			parts.join(glue).into()
+		}
+		"keys" | "values" => {
+			arity(1)?;
+			let map = object(name, value(0)?)?;
+			match name {
+				"keys" => map
+					.keys()
+					.map(|key| JsonValue::from(key.as_str()))
+					.collect(),
+				_ => map.values().cloned().collect(),
+			}
+		}
+		"length" => {
+			arity(1)?;
+			match value(0)? {
+				JsonValue::String(s) => s.chars().count(),
+				JsonValue::Array(items) => items.len(),
+				JsonValue::Object(map) => map.len(),
+				subject => {
+					return invalid_type(
+						name,
+						"a string, array or object",
+						subject,
+					)
+				}
+			}
+			.into()
+		}
+		"map" => {
+			arity(2)?;
+			let expression = expref(0)?;
+			array(name, value(1)?)?
+				.iter()
+				.map(|item| eval(expression, item))
+				.collect::<Result<JsonValue, _>>()?
+		}
+		"max" | "min" => {
+			arity(1)?;
+			let items = array(name, value(0)?)?;
+			compare_keys(name, items)?;
+			let found = match name {
+				"max" => items.iter().max_by(|a, b| order(a, b)),
+				_ => items.iter().min_by(|a, b| order(a, b)),
+			};
+			found.cloned().unwrap_or(JsonValue::Null)
+		}
+		"max_by" | "min_by" => {
+			arity(2)?;
+			let items = array(name, value(0)?)?;
+			let keys = keys_by(name, items, expref(1)?)?;
+			let pairs = keys.iter().zip(items);
+			let found = match name {
+				"max_by" => pairs.max_by(|a, b| order(a.0, b.0)),
+				_ => pairs.min_by(|a, b| order(a.0, b.0)),
+			};
+			found.map_or(JsonValue::Null, |(_, item)| {
+				item.clone()
+			})
+		}
+		"merge" => {
+			let mut merged = HashMap::new();
+			for i in 0..args.len() {
+				merged.extend(object(name, value(i)?)?.clone());
+			}
+			JsonValue::Object(Box::new(merged))
+		}
+		"not_null" => {
+			if args.is_empty() {
+				return error(
+					"invalid-arity: not_null() takes at least 1 argument",
+				);
+			}
+			let mut found = JsonValue::Null;
+			for i in 0..args.len() {
+				if !value(i)?.is_null() {
+					found = value(i)?.clone();
+					break;
+				}
+			}
+			found
+		}
+		"reverse" => {
+			arity(1)?;
+			match value(0)? {
+				JsonValue::String(s) => {
+					s.chars().rev().collect::<String>().into()
+				}
+				JsonValue::Array(items) => {
+					items.iter().rev().cloned().collect()
+				}
+				subject => {
+					return invalid_type(name, "an array or string", subject)
+				}
+			}
+		}
+		"sort" => {
+			arity(1)?;
+			let mut items = array(name, value(0)?)?.to_vec();
+			compare_keys(name, &items)?;
+			items.sort_by(order);
+			JsonValue::Array(items)
+		}
+		"sort_by" => {
+			arity(2)?;
+			let items = array(name, value(0)?)?;
+			let keys = keys_by(name, items, expref(1)?)?;
+			let mut pairs: Vec<_> = keys.iter().zip(items).collect();
+			pairs.sort_by(|a, b| order(a.0, b.0));
+			pairs.into_iter().map(|(_, item)| item.clone()).collect()
+		}
+		"to_array" => {
+			arity(1)?;
+			match value(0)? {
+				JsonValue::Array(_) => value(0)?.clone(),
+				value => JsonValue::Array(vec![value.clone()]),
+			}
+		}
+		"to_string" => {
+			arity(1)?;
+			match value(0)? {
+				JsonValue::String(_) => value(0)?.clone(),
+				value => value.to_string().into(),
+			}
+		}
+		"to_number" => {
+			arity(1)?;
+			match value(0)? {
+				JsonValue::Number(_) => value(0)?.clone(),
+				JsonValue::String(s) => match s.parse::<f64>() {
+					Ok(n) if n.is_finite() => n.into(),
+					_ => JsonValue::Null,
+				},
+				_ => JsonValue::Null,
+			}
+		}
+		"type" => {
+			arity(1)?;
+			type_name(value(0)?).into()
+		}
+		_ => unreachable!("the parser only accepts known functions"),
+	})
+}
@@ -0,0 +1,263 @@
+//! [JMESPath](https://jmespath.org/specification.html) queries.
+//!
+//! The whole specification is supported: sub-expressions, index and slice
+//! expressions, list, object, flatten and filter projections, multi-select
+//! lists and hashes, pipes, `||`/`&&`/`!`, comparisons, literals, raw
+//! strings, expression references and the built-in functions.
+//!
+//! ```text
+//! people[?age > `20`].{name: name, city: address.city} | sort_by(@, &name)
+//! ```
+
+mod eval;
+mod parse;
+
+use std::{error, fmt, str::FromStr};
+
+use crate::{Error, JsonValue};
+
+/// A compiled JMESPath expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+	ast: Ast,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Ast {
+	/// `@`, and the implicit right-hand side of a projection.
+	Current,
+	Field(String),
+	Literal(JsonValue),
+	Subexpression(Box<Ast>, Box<Ast>),
+	Index(i64),
+	Slice(Option<i64>, Option<i64>, Option<i64>),
+	/// Applies the right side to every element of the array on the left.
+	Projection(Box<Ast>, Box<Ast>),
+	/// Applies the right side to every value of the object on the left.
+	ValueProjection(Box<Ast>, Box<Ast>),
+	FilterProjection {
+		left: Box<Ast>,
+		right: Box<Ast>,
+		condition: Box<Ast>,
+	},
+	Flatten(Box<Ast>),
+	MultiSelectList(Vec<Ast>),
+	MultiSelectHash(Vec<(String, Ast)>),
+	Pipe(Box<Ast>, Box<Ast>),
+	Or(Box<Ast>, Box<Ast>),
+	And(Box<Ast>, Box<Ast>),
+	Not(Box<Ast>),
+	Comparison(Comparator, Box<Ast>, Box<Ast>),
+	Function(String, Vec<Ast>),
+	/// `&expr`, passed unevaluated to functions like `sort_by`.
+	Expref(Box<Ast>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Comparator {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+/// An error raised while evaluating an expression, such as a function
+/// called with arguments of the wrong type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchError {
+	pub message: String,
+}
+
+impl fmt::Display for SearchError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.message)
+	}
+}
+
+impl error::Error for SearchError {}
+
+impl Expression {
+	pub fn parse(expression: &str) -> Result<Self, Error> {
+		parse::parse(expression).map(|ast| Self { ast })
+	}
+
+	/// Evaluates the expression against `data`.
+	pub fn search(&self, data: &JsonValue) -> Result<JsonValue, SearchError> {
+		eval::eval(&self.ast, data)
+	}
+}
+
+impl FromStr for Expression {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::parse;
+
+	use super::*;
+
+	fn search(expression: &str, data: &str) -> Result<JsonValue, SearchError> {
+		Expression::parse(expression)
+			.unwrap_or_else(|e| panic!("{expression}: {e}"))
+			.search(&parse(data).unwrap())
+	}
+
+	#[track_caller]
+	fn check(expression: &str, data: &str, expected: &str) {
+		assert_eq!(
+			search(expression, data),
+			Ok(parse(expected).unwrap()),
+			"{expression}"
+		);
+	}
+
+	#[test]
+	fn basic_expressions() {
+		let data = r#"{"a": {"b": {"c": [0, 1, 2, 3, 4]}}, "d d": 5}"#;
+		check("a.b.c", data, "[0, 1, 2, 3, 4]");
+		check("a.b.c[1]", data, "1");
+		check("a.b.c[-1]", data, "4");
+		check("a.b.c[1:3]", data, "[1, 2]");
+		check("a.b.c[::2]", data, "[0, 2, 4]");
+		check("a.b.c[::-1]", data, "[4, 3, 2, 1, 0]");
+		check(r#""d d""#, data, "5");
+		check("a.missing.c", data, "null");
+		check("@.a.b.c[0]", data, "0");
+	}
+
+	#[test]
+	fn projections() {
+		let data = r#"{"people": [
+			{"first": "James", "age": 30},
+			{"first": "Jacob", "age": 20},
+			{"missing": true}
+		], "ops": {"a": {"n": 1}, "b": {"n": 2}}, "nested": [[1, 2], [3], 4]}"#;
+		check(
+			"people[*].first",
+			data,
+			r#"["James", "Jacob"]"#,
+		);
+		check(
+			"people[?age > `20`].first",
+			data,
+			r#"["James"]"#,
+		);
+		check("ops.*.n | sort(@)", data, "[1, 2]");
+		check("nested[]", data, "[1, 2, 3, 4]");
+		check(
+			"people[*].first | [0]",
+			data,
+			r#""James""#,
+		);
+		check(
+			"people[0].[first, age]",
+			data,
+			r#"["James", 30]"#,
+		);
+		check(
+			"people[1].{name: first, years: age}",
+			data,
+			r#"{"name": "Jacob", "years": 20}"#,
+		);
+	}
+
+	#[test]
+	fn operators_and_literals() {
+		let data = r#"{"a": null, "b": false, "c": [], "d": "x"}"#;
+		check("a || d", data, r#""x""#);
+		check("c || d", data, r#""x""#);
+		check("d && b", data, "false");
+		check("!b", data, "true");
+		check("d == 'x'", data, "true");
+		check(
+			"`[1, {\"k\": 2}]`",
+			data,
+			r#"[1, {"k": 2}]"#,
+		);
+		check("`1` < `2`", data, "true");
+		check("'a' < 'b'", data, "null");
+	}
+
+	#[test]
+	fn functions() {
+		let data = r#"{"items": [
+			{"n": "b", "v": 2},
+			{"n": "a", "v": 3},
+			{"n": "c", "v": 1}
+		], "words": ["x", "y"]}"#;
+		check("length(items)", data, "3");
+		check(
+			"sort_by(items, &v)[*].n",
+			data,
+			r#"["c", "b", "a"]"#,
+		);
+		check("max_by(items, &v).n", data, r#""a""#);
+		check("join('-', words)", data, r#""x-y""#);
+		check("map(&v, items)", data, "[2, 3, 1]");
+		check("contains(words, 'y')", data, "true");
+		check("to_number('12')", data, "12");
+		check("type(words)", data, r#""array""#);
+		check(
+			"merge(items[0], `{\"x\": 1}`)",
+			data,
+			r#"{"n": "b", "v": 2, "x": 1}"#,
+		);
+		assert!(search("length(`1`)", data).is_err());
+		assert!(search("abs('a')", data).is_err());
+	}
+
+	#[test]
+	fn functions_are_resolved_when_parsing() {
+		let error = Expression::parse("items[?lenght(@) > `1`]").unwrap_err();
+		assert_eq!(
+			error.message(),
+			"unknown-function: lenght()"
+		);
+		assert_eq!(error.offset(), 7);
+		assert!(Expression::parse("a || nope(@)").is_err());
+		for name in eval::FUNCTIONS {
+			let expression = Expression::parse(&format!("{name}(@)")).unwrap();
+			// Every listed function is one the evaluator has.
+			let _ = expression.search(&JsonValue::Null);
+		}
+	}
+
+	#[test]
+	fn invalid_expressions() {
+		for expression in ["", "a.", "a[", "[?a", "a ||", "{a}", "foo(", "`[`"]
+		{
+			assert!(
+				Expression::parse(expression).is_err(),
+				"{expression}"
+			);
+		}
+	}
+
+	#[test]
+	fn deep_expressions_are_too_deep() {
+		let n = 100_000;
+		for expression in [
+			format!("{}a{}", "(".repeat(n), ")".repeat(n)),
+			format!("{}a", "!".repeat(n)),
+			format!("{}a", "& ".repeat(n)),
+			format!("{}a{}", "[".repeat(n), "]".repeat(n)),
+		] {
+			let e = Expression::parse(&expression).unwrap_err();
+			assert_eq!(e.kind(), crate::ErrorKind::TooDeep);
+		}
+		let depth = crate::ParseOptions::DEFAULT_MAX_DEPTH - 1;
+		let expression = format!(
+			"{}a{}",
+			"(".repeat(depth),
+			")".repeat(depth)
+		);
+		check(&expression, r#"{"a": 1}"#, "1");
+	}
+}
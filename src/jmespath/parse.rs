@@ -0,0 +1,542 @@
+//! The JMESPath grammar: a winnow lexer feeding a Pratt parser, with the
+//! binding powers from the reference implementation.
+
+use winnow::{
+	ascii::digit1,
+	combinator::{alt, cut_err, delimited, opt, repeat},
+	error::ErrMode,
+	prelude::*,
+	token::{none_of, one_of, take_while},
+	PResult,
+};
+
+use super::{eval::FUNCTIONS, Ast, Comparator};
+use crate::{
+	error::ContextTrail,
+	parser::{string, ws},
+	Error, ErrorKind, JsonValue, ParseOptions,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	Identifier(String),
+	QuotedIdentifier(String),
+	Literal(JsonValue),
+	Number(i64),
+	Dot,
+	Star,
+	Comma,
+	Colon,
+	LeftBrace,
+	RightBrace,
+	LeftBracket,
+	RightBracket,
+	/// `[]`
+	Flatten,
+	/// `[?`
+	Filter,
+	LeftParen,
+	RightParen,
+	Current,
+	Expref,
+	Pipe,
+	Or,
+	And,
+	Not,
+	Compare(Comparator),
+	End,
+}
+
+impl Token {
+	fn binding_power(&self) -> u8 {
+		match self {
+			Token::Pipe => 1,
+			Token::Or => 2,
+			Token::And => 3,
+			Token::Compare(_) => 5,
+			Token::Flatten => 9,
+			Token::Star => 20,
+			Token::Filter => 21,
+			Token::Dot => 40,
+			Token::Not => 45,
+			Token::LeftBrace => 50,
+			Token::LeftBracket => 55,
+			Token::LeftParen => 60,
+			_ => 0,
+		}
+	}
+}
+
+/// Projections stop at tokens binding looser than this.
+const PROJECTION_STOP: u8 = 10;
+
+fn identifier(input: &mut &str) -> PResult<String, ContextTrail> {
+	(
+		one_of(|c: char| c.is_ascii_alphabetic() || c == '_'),
+		take_while(0.., |c: char| {
+			c.is_ascii_alphanumeric() || c == '_'
+		}),
+	)
+		.recognize()
+		.map(str::to_owned)
+		.parse_next(input)
+}
+
+/// `'...'`, where only `\'` and `\\` are escapes.
+fn raw_string(input: &mut &str) -> PResult<String, ContextTrail> {
+	let character = alt((
+		"\\'".value('\''),
+		"\\\\".value('\\'),
+		none_of('\''),
+	));
+	delimited(
+		'\'',
+		cut_err(
+			repeat(0.., character).fold(String::new, |mut s, c| {
+				s.push(c);
+				s
+			}),
+		),
+		cut_err('\''),
+	)
+	.context("raw string")
+	.parse_next(input)
+}
+
+/// `` `json` ``, where `` \` `` stands for a backtick.
+fn literal(input: &mut &str) -> PResult<JsonValue, ContextTrail> {
+	let character = alt(("\\`".value('`'), none_of('`')));
+	delimited(
+		'`',
+		cut_err(
+			repeat(0.., character).fold(String::new, |mut s, c| {
+				s.push(c);
+				s
+			}),
+		)
+		.verify_map(|json: String| json.parse().ok()),
+		cut_err('`'),
+	)
+	.context("literal")
+	.parse_next(input)
+}
+
+fn number(input: &mut &str) -> PResult<i64, ContextTrail> {
+	(opt('-'), digit1)
+		.recognize()
+		.verify_map(|n: &str| n.parse().ok())
+		.parse_next(input)
+}
+
+fn token(input: &mut &str) -> PResult<Token, ContextTrail> {
+	alt((
+		alt((
+			identifier.map(Token::Identifier),
+			string.map(Token::QuotedIdentifier),
+			raw_string.map(|s| Token::Literal(s.into())),
+			literal.map(Token::Literal),
+			number.map(Token::Number),
+		)),
+		alt((
+			"[?".value(Token::Filter),
+			"[]".value(Token::Flatten),
+			"||".value(Token::Or),
+			"&&".value(Token::And),
+			"==".value(Token::Compare(Comparator::Eq)),
+			"!=".value(Token::Compare(Comparator::Ne)),
+			"<=".value(Token::Compare(Comparator::Le)),
+			">=".value(Token::Compare(Comparator::Ge)),
+			'<'.value(Token::Compare(Comparator::Lt)),
+			'>'.value(Token::Compare(Comparator::Gt)),
+		)),
+		alt((
+			'.'.value(Token::Dot),
+			'*'.value(Token::Star),
+			','.value(Token::Comma),
+			':'.value(Token::Colon),
+			'{'.value(Token::LeftBrace),
+			'}'.value(Token::RightBrace),
+			'['.value(Token::LeftBracket),
+			']'.value(Token::RightBracket),
+			'('.value(Token::LeftParen),
+			')'.value(Token::RightParen),
+			'@'.value(Token::Current),
+			'&'.value(Token::Expref),
+			'|'.value(Token::Pipe),
+			'!'.value(Token::Not),
+		)),
+	))
+	.parse_next(input)
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, Error> {
+	let mut rest = input;
+	let mut tokens = Vec::new();
+	loop {
+		let _ = ws::<_, ContextTrail>(&mut rest);
+		let offset = input.len() - rest.len();
+		if rest.is_empty() {
+			tokens.push((offset, Token::End));
+			return Ok(tokens);
+		}
+		match token(&mut rest) {
+			Ok(token) => tokens.push((offset, token)),
+			Err(ErrMode::Cut(e)) => {
+				return Err(Error::from_trail(
+					input,
+					input.len() - rest.len(),
+					&e,
+				))
+			}
+			Err(_) => {
+				return Err(Error::from_trail(
+					input,
+					offset,
+					&ContextTrail::default(),
+				))
+			}
+		}
+	}
+}
+
+pub(super) fn parse(input: &str) -> Result<Ast, Error> {
+	let mut parser = Pratt {
+		input,
+		tokens: tokenize(input)?,
+		pos: 0,
+		depth: 0,
+	};
+	let ast = parser.expression(0)?;
+	match parser.peek() {
+		Token::End => Ok(ast),
+		_ => Err(parser.unexpected()),
+	}
+}
+
+struct Pratt<'i> {
+	input: &'i str,
+	tokens: Vec<(usize, Token)>,
+	pos: usize,
+	/// How many expressions the one being parsed is nested in.
+	depth: usize,
+}
+
+impl Pratt<'_> {
+	fn peek(&self) -> &Token {
+		self.peek_at(0)
+	}
+
+	fn peek_at(&self, n: usize) -> &Token {
+		self.tokens
+			.get(self.pos + n)
+			.map_or(&Token::End, |(_, token)| token)
+	}
+
+	fn advance(&mut self) -> Token {
+		let token = self.peek().clone();
+		self.pos += 1;
+		token
+	}
+
+	fn error(&self, message: impl Into<String>) -> Error {
+		let offset = match self.tokens.get(self.pos) {
+			Some((offset, _)) => *offset,
+			None => self.input.len(),
+		};
+		Error::new(self.input, offset, message.into())
+	}
+
+	fn unexpected(&self) -> Error {
+		match self.peek() {
+			Token::End => self.error("unexpected end of expression"),
+			token => self.error(format!("unexpected token {token:?}")),
+		}
+	}
+
+	fn expect(&mut self, token: Token) -> Result<(), Error> {
+		if *self.peek() != token {
+			return Err(self.unexpected());
+		}
+		self.advance();
+		Ok(())
+	}
+
+	fn expression(&mut self, rbp: u8) -> Result<Ast, Error> {
+		let max = ParseOptions::DEFAULT_MAX_DEPTH;
+		if self.depth >= max {
+			return Err(self
+				.error(format!(
+					"nesting deeper than {max} levels"
+				))
+				.with_kind(ErrorKind::TooDeep));
+		}
+		self.depth += 1;
+		let parsed = self.operand(rbp);
+		self.depth -= 1;
+		parsed
+	}
+
+	fn operand(&mut self, rbp: u8) -> Result<Ast, Error> {
+		let token = self.advance();
+		let mut left = self.nud(token)?;
+		while rbp < self.peek().binding_power() {
+			let token = self.advance();
+			left = self.led(token, left)?;
+		}
+		Ok(left)
+	}
+
+	/// Parses a token at the start of an expression.
+	fn nud(&mut self, token: Token) -> Result<Ast, Error> {
+		Ok(match token {
+			Token::Literal(value) => Ast::Literal(value),
+			Token::Identifier(name) => Ast::Field(name),
+			Token::QuotedIdentifier(name) => {
+				if *self.peek() == Token::LeftParen {
+					return Err(self.error("function names cannot be quoted"));
+				}
+				Ast::Field(name)
+			}
+			Token::Star => {
+				let right = self.projection_rhs(Token::Star.binding_power())?;
+				Ast::ValueProjection(Box::new(Ast::Current), Box::new(right))
+			}
+			Token::Filter => self.filter(Ast::Current)?,
+			Token::LeftBrace => self.multi_select_hash()?,
+			Token::LeftParen => {
+				let expression = self.expression(0)?;
+				self.expect(Token::RightParen)?;
+				expression
+			}
+			Token::Flatten => {
+				let flatten = Ast::Flatten(Box::new(Ast::Current));
+				let right =
+					self.projection_rhs(Token::Flatten.binding_power())?;
+				Ast::Projection(Box::new(flatten), Box::new(right))
+			}
+			Token::Not => {
+				let expression = self.expression(Token::Not.binding_power())?;
+				Ast::Not(Box::new(expression))
+			}
+			Token::LeftBracket => match (self.peek(), self.peek_at(1)) {
+				(Token::Number(_) | Token::Colon, _) => {
+					let index = self.index()?;
+					self.project_if_slice(Ast::Current, index)?
+				}
+				(Token::Star, Token::RightBracket) => {
+					self.advance();
+					self.advance();
+					let right =
+						self.projection_rhs(Token::Star.binding_power())?;
+					Ast::Projection(Box::new(Ast::Current), Box::new(right))
+				}
+				_ => self.multi_select_list()?,
+			},
+			Token::Current => Ast::Current,
+			Token::Expref => Ast::Expref(Box::new(self.expression(0)?)),
+			_ => {
+				self.pos -= 1;
+				return Err(self.unexpected());
+			}
+		})
+	}
+
+	/// Parses a token that continues the expression `left`.
+	fn led(&mut self, token: Token, left: Ast) -> Result<Ast, Error> {
+		let left = Box::new(left);
+		Ok(match token {
+			Token::Dot if *self.peek() == Token::Star => {
+				self.advance();
+				let right = self.projection_rhs(Token::Dot.binding_power())?;
+				Ast::ValueProjection(left, Box::new(right))
+			}
+			Token::Dot => {
+				let right = self.dot_rhs(Token::Dot.binding_power())?;
+				Ast::Subexpression(left, Box::new(right))
+			}
+			Token::Pipe => {
+				let right = self.expression(Token::Pipe.binding_power())?;
+				Ast::Pipe(left, Box::new(right))
+			}
+			Token::Or => Ast::Or(left, Box::new(self.expression(2)?)),
+			Token::And => Ast::And(left, Box::new(self.expression(3)?)),
+			Token::Compare(op) => {
+				let right = self.expression(5)?;
+				Ast::Comparison(op, left, Box::new(right))
+			}
+			Token::LeftParen => {
+				let Ast::Field(name) = *left else {
+					return Err(self.error("only names can be called"));
+				};
+				if !FUNCTIONS.contains(&name.as_str()) {
+					self.pos -= 2;
+					return Err(
+						self.error(format!("unknown-function: {name}()"))
+					);
+				}
+				let mut args = Vec::new();
+				while *self.peek() != Token::RightParen {
+					args.push(self.expression(0)?);
+					if *self.peek() == Token::Comma {
+						self.advance();
+					} else if *self.peek() != Token::RightParen {
+						return Err(self.unexpected());
+					}
+				}
+				self.advance();
+				Ast::Function(name, args)
+			}
+			Token::Filter => self.filter(*left)?,
+			Token::Flatten => {
+				let right =
+					self.projection_rhs(Token::Flatten.binding_power())?;
+				Ast::Projection(
+					Box::new(Ast::Flatten(left)),
+					Box::new(right),
+				)
+			}
+			Token::LeftBracket => match self.peek() {
+				Token::Number(_) | Token::Colon => {
+					let index = self.index()?;
+					self.project_if_slice(*left, index)?
+				}
+				_ => {
+					self.expect(Token::Star)?;
+					self.expect(Token::RightBracket)?;
+					let right =
+						self.projection_rhs(Token::Star.binding_power())?;
+					Ast::Projection(left, Box::new(right))
+				}
+			},
+			_ => {
+				self.pos -= 1;
+				return Err(self.unexpected());
+			}
+		})
+	}
+
+	/// The inside of `[n]` or `[start:stop:step]`, after the `[`.
+	fn index(&mut self) -> Result<Ast, Error> {
+		let mut parts = [None, None, None];
+		let mut part = 0;
+		loop {
+			match self.advance() {
+				Token::Number(n) if parts[part].is_none() => {
+					parts[part] = Some(n)
+				}
+				Token::Colon if part < 2 => part += 1,
+				Token::RightBracket => break,
+				_ => {
+					self.pos -= 1;
+					return Err(self.unexpected());
+				}
+			}
+		}
+		match (part, parts) {
+			(0, [Some(n), ..]) => Ok(Ast::Index(n)),
+			(_, [_, _, Some(0)]) => Err(self.error("slice step cannot be 0")),
+			(0, _) => Err(self.error("missing index")),
+			(_, [start, stop, step]) => Ok(Ast::Slice(start, stop, step)),
+		}
+	}
+
+	fn project_if_slice(
+		&mut self,
+		left: Ast,
+		index: Ast,
+	) -> Result<Ast, Error> {
+		let is_slice = matches!(index, Ast::Slice(..));
+		let expression = Ast::Subexpression(Box::new(left), Box::new(index));
+		if !is_slice {
+			return Ok(expression);
+		}
+		let right = self.projection_rhs(Token::Star.binding_power())?;
+		Ok(Ast::Projection(
+			Box::new(expression),
+			Box::new(right),
+		))
+	}
+
+	/// `[? condition ]` applied to `left`, after the `[?`.
+	fn filter(&mut self, left: Ast) -> Result<Ast, Error> {
+		let condition = self.expression(0)?;
+		self.expect(Token::RightBracket)?;
+		let right = match self.peek() {
+			Token::Flatten => Ast::Current,
+			_ => self.projection_rhs(Token::Filter.binding_power())?,
+		};
+		Ok(Ast::FilterProjection {
+			left: Box::new(left),
+			right: Box::new(right),
+			condition: Box::new(condition),
+		})
+	}
+
+	/// What a projection applies to each element.
+	fn projection_rhs(&mut self, rbp: u8) -> Result<Ast, Error> {
+		match self.peek() {
+			token if token.binding_power() < PROJECTION_STOP => {
+				Ok(Ast::Current)
+			}
+			Token::LeftBracket | Token::Filter => self.expression(rbp),
+			Token::Dot => {
+				self.advance();
+				self.dot_rhs(rbp)
+			}
+			_ => Err(self.unexpected()),
+		}
+	}
+
+	/// What may follow a `.`.
+	fn dot_rhs(&mut self, rbp: u8) -> Result<Ast, Error> {
+		match self.peek() {
+			Token::Identifier(_) | Token::QuotedIdentifier(_) | Token::Star => {
+				self.expression(rbp)
+			}
+			Token::LeftBracket => {
+				self.advance();
+				self.multi_select_list()
+			}
+			Token::LeftBrace => {
+				self.advance();
+				self.multi_select_hash()
+			}
+			_ => Err(self.unexpected()),
+		}
+	}
+
+	/// `[a, b]`, after the `[`.
+	fn multi_select_list(&mut self) -> Result<Ast, Error> {
+		let mut items = vec![self.expression(0)?];
+		while *self.peek() == Token::Comma {
+			self.advance();
+			items.push(self.expression(0)?);
+		}
+		self.expect(Token::RightBracket)?;
+		Ok(Ast::MultiSelectList(items))
+	}
+
+	/// `{key: a, other: b}`, after the `{`.
+	fn multi_select_hash(&mut self) -> Result<Ast, Error> {
+		let mut entries = Vec::new();
+		loop {
+			let key = match self.advance() {
+				Token::Identifier(key) | Token::QuotedIdentifier(key) => key,
+				_ => {
+					self.pos -= 1;
+					return Err(self.unexpected());
+				}
+			};
+			self.expect(Token::Colon)?;
+			entries.push((key, self.expression(0)?));
+			match self.advance() {
+				Token::Comma => {}
+				Token::RightBrace => return Ok(Ast::MultiSelectHash(entries)),
+				_ => {
+					self.pos -= 1;
+					return Err(self.unexpected());
+				}
+			}
+		}
+	}
+}
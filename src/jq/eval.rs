@@ -0,0 +1,1072 @@
+use std::collections::HashMap;
+
+use super::{BinaryOp, EvalError, Expr, Part};
+use crate::{
+	sort::{compare, sorted_keys},
+	JsonValue,
+};
+
+type Outputs = Result<Vec<JsonValue>, EvalError>;
+
+/// Takes outputs one at a time, returning whether to go on to the next.
+type Emit<'e> = dyn FnMut(JsonValue, &mut Env) -> Result<bool, EvalError> + 'e;
+
+/// The longest string that multiplying one by a number may build.
+const MAX_REPEAT: usize = 1 << 30;
+
+/// Bound variables, innermost last.
+type Env = Vec<(String, JsonValue)>;
+
+fn error<T>(message: impl Into<String>) -> Result<T, EvalError> {
+	Err(EvalError {
+		message: message.into(),
+	})
+}
+
+fn truthy(value: &JsonValue) -> bool {
+	!matches!(
+		value,
+		JsonValue::Null | JsonValue::Boolean(false)
+	)
+}
+
+fn describe(value: &JsonValue) -> String {
+	let text = value.to_string();
+	match text.chars().count() {
+		0..=30 => format!("{} ({text})", value.type_of()),
+		_ => format!(
+			"{} ({}...)",
+			value.type_of(),
+			text.chars().take(27).collect::<String>()
+		),
+	}
+}
+
+fn as_text(value: &JsonValue) -> String {
+	match value {
+		JsonValue::String(s) => s.clone(),
+		value => value.to_string(),
+	}
+}
+
+/// Evaluates `expr` for every output of `a` and `b`, `b` varying slowest
+/// as in jq.
+fn cartesian(
+	a: &Expr,
+	b: &Expr,
+	input: &JsonValue,
+	env: &mut Env,
+	mut f: impl FnMut(JsonValue, JsonValue) -> Result<JsonValue, EvalError>,
+) -> Outputs {
+	let rhs = eval(b, input, env)?;
+	let lhs = eval(a, input, env)?;
+	let mut out = Vec::new();
+	for r in &rhs {
+		for l in &lhs {
+			out.push(f(l.clone(), r.clone())?);
+		}
+	}
+	Ok(out)
+}
+
+pub(super) fn eval(expr: &Expr, input: &JsonValue, env: &mut Env) -> Outputs {
+	match expr {
+		Expr::Identity => Ok(vec![input.clone()]),
+		Expr::Recurse => {
+			let mut out = Vec::new();
+			input.walk(|_, value| out.push(value.clone()));
+			Ok(out)
+		}
+		Expr::Literal(value) => Ok(vec![value.clone()]),
+		Expr::Format(parts) => {
+			let mut strings = vec![String::new()];
+			for part in parts {
+				match part {
+					Part::Literal(s) => {
+						strings.iter_mut().for_each(|string| string.push_str(s))
+					}
+					Part::Expr(expr) => {
+						let values = eval(expr, input, env)?;
+						strings = strings
+							.iter()
+							.flat_map(|string| {
+								values.iter().map(move |value| {
+									string.clone() + &as_text(value)
+								})
+							})
+							.collect();
+					}
+				}
+			}
+			Ok(strings.into_iter().map(JsonValue::String).collect())
+		}
+		Expr::Array(None) => Ok(vec![JsonValue::Array(Vec::new())]),
+		Expr::Array(Some(items)) => Ok(vec![JsonValue::Array(eval(
+			items, input, env,
+		)?)]),
+		Expr::Object(entries) => {
+			let mut objects = vec![HashMap::new()];
+			for (key, value) in entries {
+				let keys = eval(key, input, env)?;
+				let values = eval(value, input, env)?;
+				let mut next = Vec::new();
+				for object in &objects {
+					for key in &keys {
+						let JsonValue::String(key) = key else {
+							return error(format!(
+								"object keys must be strings, found {}",
+								describe(key)
+							));
+						};
+						for value in &values {
+							let mut object = object.clone();
+							object.insert(key.clone(), value.clone());
+							next.push(object);
+						}
+					}
+				}
+				objects = next;
+			}
+			Ok(objects.into_iter().map(JsonValue::from).collect())
+		}
+		Expr::Index(target, index) => cartesian(
+			target,
+			index,
+			input,
+			env,
+			|target, index| self::index(&target, &index),
+		),
+		Expr::Slice(target, from, to) => {
+			let bound = |expr: &Option<Box<Expr>>, env: &mut Env| match expr {
+				Some(expr) => eval(expr, input, env),
+				None => Ok(vec![JsonValue::Null]),
+			};
+			let (froms, tos) = (bound(from, env)?, bound(to, env)?);
+			let mut out = Vec::new();
+			for target in eval(target, input, env)? {
+				for to in &tos {
+					for from in &froms {
+						out.push(slice(&target, from, to)?);
+					}
+				}
+			}
+			Ok(out)
+		}
+		Expr::Iterate(target) => {
+			let mut out = Vec::new();
+			for target in eval(target, input, env)? {
+				match target {
+					JsonValue::Array(items) => out.extend(items),
+					JsonValue::Object(map) => out.extend(map.into_values()),
+					value => {
+						return error(format!(
+							"cannot iterate over {}",
+							describe(&value)
+						))
+					}
+				}
+			}
+			Ok(out)
+		}
+		Expr::Try(expr) => Ok(eval(expr, input, env).unwrap_or_default()),
+		Expr::Pipe(lhs, rhs) => {
+			let mut out = Vec::new();
+			for value in eval(lhs, input, env)? {
+				out.extend(eval(rhs, &value, env)?);
+			}
+			Ok(out)
+		}
+		Expr::Comma(lhs, rhs) => {
+			let mut out = eval(lhs, input, env)?;
+			out.extend(eval(rhs, input, env)?);
+			Ok(out)
+		}
+		Expr::Neg(expr) => eval(expr, input, env)?
+			.into_iter()
+			.map(|value| match value {
+				JsonValue::Number(n) => Ok(JsonValue::Number(-n)),
+				value => error(format!(
+					"{} cannot be negated",
+					describe(&value)
+				)),
+			})
+			.collect(),
+		Expr::Binary(op, lhs, rhs) => {
+			cartesian(lhs, rhs, input, env, |l, r| {
+				binary(*op, l, r)
+			})
+		}
+		Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+			let is_and = matches!(expr, Expr::And(..));
+			let mut out = Vec::new();
+			for l in eval(lhs, input, env)? {
+				if truthy(&l) != is_and {
+					out.push(JsonValue::Boolean(!is_and));
+					continue;
+				}
+				for r in eval(rhs, input, env)? {
+					out.push(JsonValue::Boolean(truthy(&r)));
+				}
+			}
+			Ok(out)
+		}
+		Expr::Alternative(lhs, rhs) => {
+			let values: Vec<_> = eval(lhs, input, env)
+				.unwrap_or_default()
+				.into_iter()
+				.filter(truthy)
+				.collect();
+			if values.is_empty() {
+				eval(rhs, input, env)
+			} else {
+				Ok(values)
+			}
+		}
+		Expr::If(branches, otherwise) => match branches.split_first() {
+			None => match otherwise {
+				Some(otherwise) => eval(otherwise, input, env),
+				None => Ok(vec![input.clone()]),
+			},
+			Some(((condition, then), rest)) => {
+				let mut out = Vec::new();
+				for value in eval(condition, input, env)? {
+					if truthy(&value) {
+						out.extend(eval(then, input, env)?);
+					} else {
+						let rest = Expr::If(rest.to_vec(), otherwise.clone());
+						out.extend(eval(&rest, input, env)?);
+					}
+				}
+				Ok(out)
+			}
+		},
+		Expr::Bind(source, name, body) => {
+			let mut out = Vec::new();
+			for value in eval(source, input, env)? {
+				env.push((name.clone(), value));
+				let result = eval(body, input, env);
+				env.pop();
+				out.extend(result?);
+			}
+			Ok(out)
+		}
+		Expr::Var(name) => match env.iter().rev().find(|(n, _)| n == name) {
+			Some((_, value)) => Ok(vec![value.clone()]),
+			None => error(format!("${name} is not defined")),
+		},
+		Expr::Call(name, args) => call(name, args, input, env),
+	}
+}
+
+/// Passes the outputs of `expr` to `emit` as they are produced, stopping
+/// once it asks to, and returns whether it was left to go on. Pipes,
+/// commas, bindings, iteration and the generators `range`, `limit` and
+/// `first` are produced lazily, so `limit(1; range(1e10))` stops after one
+/// output; anything else is evaluated in full first.
+fn each(
+	expr: &Expr,
+	input: &JsonValue,
+	env: &mut Env,
+	emit: &mut Emit<'_>,
+) -> Result<bool, EvalError> {
+	match expr {
+		Expr::Pipe(lhs, rhs) => each(lhs, input, env, &mut |value, env| {
+			each(rhs, &value, env, emit)
+		}),
+		Expr::Comma(lhs, rhs) => {
+			Ok(each(lhs, input, env, emit)? && each(rhs, input, env, emit)?)
+		}
+		Expr::Bind(source, name, body) => {
+			each(source, input, env, &mut |value, env| {
+				env.push((name.clone(), value));
+				let result = each(body, input, env, emit);
+				env.pop();
+				result
+			})
+		}
+		Expr::Iterate(target) => each(
+			target,
+			input,
+			env,
+			&mut |target, env| {
+				let items: Vec<JsonValue> = match target {
+					JsonValue::Array(items) => items,
+					JsonValue::Object(map) => map.into_values().collect(),
+					value => {
+						return error(format!(
+							"cannot iterate over {}",
+							describe(&value)
+						))
+					}
+				};
+				for item in items {
+					if !emit(item, env)? {
+						return Ok(false);
+					}
+				}
+				Ok(true)
+			},
+		),
+		Expr::Call(name, args) => match (name.as_str(), args.as_slice()) {
+			("first", [f]) => first(f, input, env, emit),
+			("limit", [n, f]) => limit(n, f, input, env, emit),
+			("range", [to]) => range(None, to, input, env, emit),
+			("range", [from, to]) => range(Some(from), to, input, env, emit),
+			_ => all(eval(expr, input, env)?, env, emit),
+		},
+		expr => all(eval(expr, input, env)?, env, emit),
+	}
+}
+
+fn all(
+	values: Vec<JsonValue>,
+	env: &mut Env,
+	emit: &mut Emit<'_>,
+) -> Result<bool, EvalError> {
+	for value in values {
+		if !emit(value, env)? {
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
+/// Collects what a generator passes to its `emit`.
+fn collect(
+	generate: impl FnOnce(&mut Emit<'_>) -> Result<bool, EvalError>,
+) -> Outputs {
+	let mut out = Vec::new();
+	generate(&mut |value, _| {
+		out.push(value);
+		Ok(true)
+	})?;
+	Ok(out)
+}
+
+fn first(
+	f: &Expr,
+	input: &JsonValue,
+	env: &mut Env,
+	emit: &mut Emit<'_>,
+) -> Result<bool, EvalError> {
+	let mut more = true;
+	each(f, input, env, &mut |value, env| {
+		more = emit(value, env)?;
+		Ok(false)
+	})?;
+	Ok(more)
+}
+
+fn limit(
+	n: &Expr,
+	f: &Expr,
+	input: &JsonValue,
+	env: &mut Env,
+	emit: &mut Emit<'_>,
+) -> Result<bool, EvalError> {
+	for n in eval(n, input, env)? {
+		let n = n.as_f64().unwrap_or(0.0).max(0.0) as usize;
+		if n == 0 {
+			continue;
+		}
+		let (mut taken, mut more) = (0, true);
+		each(f, input, env, &mut |value, env| {
+			taken += 1;
+			more = emit(value, env)?;
+			Ok(more && taken < n)
+		})?;
+		if !more {
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
+/// `range(to)` counts up from 0, and `range(from; to)` from `from`.
+fn range(
+	from: Option<&Expr>,
+	to: &Expr,
+	input: &JsonValue,
+	env: &mut Env,
+	emit: &mut Emit<'_>,
+) -> Result<bool, EvalError> {
+	let counting = from.is_none();
+	let froms = match from {
+		Some(from) => eval(from, input, env)?,
+		None => vec![JsonValue::from(0.0)],
+	};
+	for to in eval(to, input, env)? {
+		for from in &froms {
+			let (from, to) = match counting {
+				true => (
+					Some(0.0),
+					Some(to.as_f64().unwrap_or(0.0)),
+				),
+				false => (from.as_f64(), to.as_f64()),
+			};
+			let (Some(mut i), Some(to)) = (from, to) else {
+				return error("range bounds must be numbers");
+			};
+			while i < to {
+				if !emit(i.into(), env)? {
+					return Ok(false);
+				}
+				i += 1.0;
+			}
+		}
+	}
+	Ok(true)
+}
+
+fn index(
+	target: &JsonValue,
+	index: &JsonValue,
+) -> Result<JsonValue, EvalError> {
+	match (target, index) {
+		(JsonValue::Null, JsonValue::String(_) | JsonValue::Number(_)) => {
+			Ok(JsonValue::Null)
+		}
+		(JsonValue::Object(map), JsonValue::String(key)) => {
+			Ok(map.get(key).cloned().unwrap_or(JsonValue::Null))
+		}
+		(JsonValue::Array(items), JsonValue::Number(n)) => {
+			let i = n.floor();
+			let i = if i < 0.0 { i + items.len() as f64 } else { i };
+			Ok(match i >= 0.0 {
+				true => {
+					items.get(i as usize).cloned().unwrap_or(JsonValue::Null)
+				}
+				false => JsonValue::Null,
+			})
+		}
+		(target, index) => error(format!(
+			"cannot index {} with {}",
+			target.type_of(),
+			describe(index)
+		)),
+	}
+}
+
+fn slice(
+	target: &JsonValue,
+	from: &JsonValue,
+	to: &JsonValue,
+) -> Result<JsonValue, EvalError> {
+	let len = match target {
+		JsonValue::Null => return Ok(JsonValue::Null),
+		JsonValue::Array(items) => items.len(),
+		JsonValue::String(s) => s.chars().count(),
+		target => {
+			return error(format!(
+				"cannot slice {}",
+				describe(target)
+			))
+		}
+	};
+	let bound = |value: &JsonValue, default: usize| match value {
+		JsonValue::Null => Ok(default),
+		JsonValue::Number(n) => {
+			let n = n.floor();
+			let n = if n < 0.0 { n + len as f64 } else { n };
+			Ok(n.clamp(0.0, len as f64) as usize)
+		}
+		value => error(format!(
+			"slice bounds must be numbers, found {}",
+			describe(value)
+		)),
+	};
+	let from = bound(from, 0)?;
+	let to = bound(to, len)?.max(from);
+	Ok(match target {
+		JsonValue::Array(items) => JsonValue::Array(items[from..to].to_vec()),
+		JsonValue::String(s) => {
+			JsonValue::String(s.chars().skip(from).take(to - from).collect())
+		}
+		_ => JsonValue::Null,
+	})
+}
+
+fn binary(
+	op: BinaryOp,
+	l: JsonValue,
+	r: JsonValue,
+) -> Result<JsonValue, EvalError> {
+	use JsonValue::{Array, Null, Number, Object, String};
+
+	let order = || compare(&l, &r);
+	let value = match (op, &l, &r) {
+		(BinaryOp::Eq, ..) => (l == r).into(),
+		(BinaryOp::Ne, ..) => (l != r).into(),
+		(BinaryOp::Lt, ..) => order().is_lt().into(),
+		(BinaryOp::Le, ..) => order().is_le().into(),
+		(BinaryOp::Gt, ..) => order().is_gt().into(),
+		(BinaryOp::Ge, ..) => order().is_ge().into(),
+
+		(BinaryOp::Add, Null, _) => r,
+		(BinaryOp::Add, _, Null) => l,
+		(BinaryOp::Add, Number(a), Number(b)) => Number(a + b),
+		(BinaryOp::Add, String(a), String(b)) => String(a.clone() + b),
+		(BinaryOp::Add, Array(a), Array(b)) => {
+			Array(a.iter().chain(b).cloned().collect())
+		}
+		(BinaryOp::Add, Object(a), Object(b)) => {
+			let mut merged = a.clone();
+			merged.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+			Object(merged)
+		}
+
+		(BinaryOp::Sub, Number(a), Number(b)) => Number(a - b),
+		(BinaryOp::Sub, Array(a), Array(b)) => {
+			Array(a.iter().filter(|item| !b.contains(item)).cloned().collect())
+		}
+
+		(BinaryOp::Mul, Number(a), Number(b)) => Number(a * b),
+		(BinaryOp::Mul, String(s), Number(n))
+		| (BinaryOp::Mul, Number(n), String(s))
+			if *n > 0.0 =>
+		{
+			let count = n.ceil() as usize;
+			match s.len().checked_mul(count) {
+				Some(len) if len <= MAX_REPEAT => String(s.repeat(count)),
+				_ => {
+					return error(format!(
+						"{} repeated {n} times is too long",
+						describe(&l)
+					))
+				}
+			}
+		}
+		(BinaryOp::Mul, String(_), Number(_))
+		| (BinaryOp::Mul, Number(_), String(_)) => Null,
+		(BinaryOp::Mul, Object(_), Object(_)) => deep_merge(l, r),
+
+		(BinaryOp::Div, Number(_), Number(b)) if *b == 0.0 => {
+			return error(format!(
+				"{} and {} cannot be divided because the divisor is zero",
+				describe(&l),
+				describe(&r)
+			))
+		}
+		(BinaryOp::Div, Number(a), Number(b)) => Number(a / b),
+		(BinaryOp::Div, String(a), String(b)) => {
+			Array(split(a, b).into_iter().map(String).collect())
+		}
+
+		(BinaryOp::Rem, Number(a), Number(b)) => {
+			let (a, b) = (a.trunc() as i64, b.trunc() as i64);
+			if b == 0 {
+				return error(format!(
+					"{} and {} cannot be divided because the divisor is zero",
+					describe(&l),
+					describe(&r)
+				));
+			}
+			Number((a % b) as f64)
+		}
+
+		_ => {
+			let verb = match op {
+				BinaryOp::Add => "added",
+				BinaryOp::Sub => "subtracted",
+				BinaryOp::Mul => "multiplied",
+				BinaryOp::Div | BinaryOp::Rem => "divided",
+				_ => "compared",
+			};
+			return error(format!(
+				"{} and {} cannot be {verb}",
+				describe(&l),
+				describe(&r)
+			));
+		}
+	};
+	Ok(value)
+}
+
+fn deep_merge(l: JsonValue, r: JsonValue) -> JsonValue {
+	match (l, r) {
+		(JsonValue::Object(mut a), JsonValue::Object(b)) => {
+			for (key, value) in *b {
+				let merged = match a.remove(&key) {
+					Some(existing) => deep_merge(existing, value),
+					None => value,
+				};
+				a.insert(key, merged);
+			}
+			JsonValue::Object(a)
+		}
+		(_, r) => r,
+	}
+}
+
+fn split(s: &str, separator: &str) -> Vec<String> {
+	match s.is_empty() {
+		true => Vec::new(),
+		false => s.split(separator).map(str::to_owned).collect(),
+	}
+}
+
+fn expect_array<'a>(
+	name: &str,
+	value: &'a JsonValue,
+) -> Result<&'a [JsonValue], EvalError> {
+	match value {
+		JsonValue::Array(items) => Ok(items),
+		value => error(format!(
+			"{name} expects an array, found {}",
+			describe(value)
+		)),
+	}
+}
+
+fn expect_str<'a>(
+	name: &str,
+	value: &'a JsonValue,
+) -> Result<&'a str, EvalError> {
+	match value {
+		JsonValue::String(s) => Ok(s),
+		value => error(format!(
+			"{name} expects a string, found {}",
+			describe(value)
+		)),
+	}
+}
+
+/// Sorts by the outputs of `key` for each item, keeping ties in order.
+fn keyed(
+	key: &Expr,
+	items: &[JsonValue],
+	env: &mut Env,
+) -> Result<Vec<(Vec<JsonValue>, JsonValue)>, EvalError> {
+	let mut keyed = items
+		.iter()
+		.map(|item| Ok((eval(key, item, env)?, item.clone())))
+		.collect::<Result<Vec<_>, EvalError>>()?;
+	keyed.sort_by(|(a, _), (b, _)| {
+		compare(
+			&JsonValue::Array(a.clone()),
+			&JsonValue::Array(b.clone()),
+		)
+	});
+	Ok(keyed)
+}
+
+fn call(
+	name: &str,
+	args: &[Expr],
+	input: &JsonValue,
+	env: &mut Env,
+) -> Outputs {
+	let one = |value: JsonValue| Ok(vec![value]);
+	match (name, args) {
+		("empty", []) => Ok(Vec::new()),
+		("not", []) => one((!truthy(input)).into()),
+		("error", []) => error(as_text(input)),
+		("error", [message]) => match eval(message, input, env)?.first() {
+			Some(message) => error(as_text(message)),
+			None => Ok(Vec::new()),
+		},
+		("type", []) => one(input.type_of().as_str().into()),
+		("length", []) => one(match input {
+			JsonValue::Null => 0.0.into(),
+			JsonValue::Boolean(_) => {
+				return error(format!(
+					"{} has no length",
+					describe(input)
+				))
+			}
+			JsonValue::Number(n) => n.abs().into(),
+			JsonValue::String(s) => (s.chars().count() as f64).into(),
+			JsonValue::Array(items) => (items.len() as f64).into(),
+			JsonValue::Object(map) => (map.len() as f64).into(),
+		}),
+		("keys", []) => match input {
+			JsonValue::Object(map) => one(sorted_keys(map)
+				.into_iter()
+				.map(|key| JsonValue::from(key.as_str()))
+				.collect()),
+			JsonValue::Array(items) => {
+				one((0..items.len()).map(JsonValue::from).collect())
+			}
+			value => error(format!(
+				"{} has no keys",
+				describe(value)
+			)),
+		},
+		("values", []) => match input {
+			JsonValue::Null => Ok(Vec::new()),
+			_ => Ok(vec![input.clone()]),
+		},
+		(
+			"nulls" | "booleans" | "numbers" | "strings" | "arrays" | "objects"
+			| "iterables" | "scalars",
+			[],
+		) => {
+			let keep = match (name, input) {
+				("iterables", JsonValue::Array(_) | JsonValue::Object(_)) => {
+					true
+				}
+				("iterables", _) => false,
+				("scalars", JsonValue::Array(_) | JsonValue::Object(_)) => {
+					false
+				}
+				("scalars", _) => true,
+				(name, value) => {
+					name.strip_suffix('s') == Some(value.type_of().as_str())
+				}
+			};
+			Ok(keep.then(|| input.clone()).into_iter().collect())
+		}
+		("has", [key]) => eval(key, input, env)?
+			.iter()
+			.map(|key| match (input, key) {
+				(JsonValue::Object(map), JsonValue::String(key)) => {
+					Ok(map.contains_key(key).into())
+				}
+				(JsonValue::Array(items), JsonValue::Number(i)) => {
+					Ok((*i >= 0.0 && (*i as usize) < items.len()).into())
+				}
+				(input, key) => error(format!(
+					"cannot check whether {} has {}",
+					input.type_of(),
+					describe(key)
+				)),
+			})
+			.collect(),
+		("select", [condition]) => Ok(eval(condition, input, env)?
+			.iter()
+			.filter(|value| truthy(value))
+			.map(|_| input.clone())
+			.collect()),
+		("map", [f]) => {
+			let items: Vec<JsonValue> = match input {
+				JsonValue::Object(map) => map.values().cloned().collect(),
+				value => expect_array("map", value)?.to_vec(),
+			};
+			let mut out = Vec::new();
+			for item in &items {
+				out.extend(eval(f, item, env)?);
+			}
+			one(JsonValue::Array(out))
+		}
+		("map_values", [f]) => match input {
+			JsonValue::Object(map) => {
+				let mut out = HashMap::new();
+				for (key, value) in map.iter() {
+					if let Some(value) = eval(f, value, env)?.into_iter().next()
+					{
+						out.insert(key.clone(), value);
+					}
+				}
+				one(JsonValue::Object(Box::new(out)))
+			}
+			value => {
+				let mut out = Vec::new();
+				for item in expect_array("map_values", value)? {
+					out.extend(eval(f, item, env)?.into_iter().next());
+				}
+				one(JsonValue::Array(out))
+			}
+		},
+		("recurse", []) => eval(&Expr::Recurse, input, env),
+		("add", []) => {
+			let items: Vec<JsonValue> = match input {
+				JsonValue::Object(map) => map.values().cloned().collect(),
+				value => expect_array("add", value)?.to_vec(),
+			};
+			let sum =
+				items.into_iter().try_fold(JsonValue::Null, |sum, item| {
+					binary(BinaryOp::Add, sum, item)
+				})?;
+			one(sum)
+		}
+		("any", []) => {
+			one(expect_array("any", input)?.iter().any(truthy).into())
+		}
+		("all", []) => {
+			one(expect_array("all", input)?.iter().all(truthy).into())
+		}
+		("first", []) => index(input, &0.0.into()).map(|value| vec![value]),
+		("last", []) => index(input, &(-1.0).into()).map(|value| vec![value]),
+		("first", [f]) => collect(|emit| first(f, input, env, emit)),
+		("limit", [n, f]) => collect(|emit| limit(n, f, input, env, emit)),
+		("range", [to]) => collect(|emit| range(None, to, input, env, emit)),
+		("range", [from, to]) => {
+			collect(|emit| range(Some(from), to, input, env, emit))
+		}
+		("sort", []) => {
+			let mut items = expect_array("sort", input)?.to_vec();
+			items.sort_by(compare);
+			one(JsonValue::Array(items))
+		}
+		("sort_by", [key]) => {
+			let items = keyed(
+				key,
+				expect_array("sort_by", input)?,
+				env,
+			)?;
+			one(items.into_iter().map(|(_, item)| item).collect())
+		}
+		("group_by", [key]) => {
+			let mut groups: Vec<(Vec<JsonValue>, Vec<JsonValue>)> = Vec::new();
+			for (key, item) in keyed(
+				key,
+				expect_array("group_by", input)?,
+				env,
+			)? {
+				match groups.last_mut() {
+					Some((last, group)) if *last == key => group.push(item),
+					_ => groups.push((key, vec![item])),
+				}
+			}
+			one(groups
+				.into_iter()
+				.map(|(_, group)| JsonValue::Array(group))
+				.collect())
+		}
+		("unique", []) => {
+			let mut items = expect_array("unique", input)?.to_vec();
+			items.sort_by(compare);
+			items.dedup();
+			one(JsonValue::Array(items))
+		}
+		("unique_by", [key]) => {
+			let mut items = keyed(
+				key,
+				expect_array("unique_by", input)?,
+				env,
+			)?;
+			items.dedup_by(|(a, _), (b, _)| a == b);
+			one(items.into_iter().map(|(_, item)| item).collect())
+		}
+		("min" | "max", []) => {
+			let items = expect_array(name, input)?;
+			let found = match name {
+				"min" => items.iter().min_by(|a, b| compare(a, b)),
+				_ => items.iter().max_by(|a, b| compare(a, b)),
+			};
+			one(found.cloned().unwrap_or(JsonValue::Null))
+		}
+		("reverse", []) => match input {
+			JsonValue::String(s) => {
+				one(s.chars().rev().collect::<String>().into())
+			}
+			JsonValue::Null => one(JsonValue::Array(Vec::new())),
+			value => {
+				let mut items = expect_array("reverse", value)?.to_vec();
+				items.reverse();
+				one(JsonValue::Array(items))
+			}
+		},
+		("flatten", []) => {
+			fn flatten(items: &[JsonValue], out: &mut Vec<JsonValue>) {
+				for item in items {
+					match item {
+						JsonValue::Array(inner) => flatten(inner, out),
+						item => out.push(item.clone()),
+					}
+				}
+			}
+			let mut out = Vec::new();
+			flatten(
+				expect_array("flatten", input)?,
+				&mut out,
+			);
+			one(JsonValue::Array(out))
+		}
+		("to_entries", []) => {
+			let entry = |key: JsonValue, value: &JsonValue| {
+				JsonValue::from(HashMap::from([
+					("key".to_owned(), key),
+					("value".to_owned(), value.clone()),
+				]))
+			};
+			match input {
+				JsonValue::Object(map) => one(sorted_keys(map)
+					.into_iter()
+					.map(|key| entry(key.as_str().into(), &map[key]))
+					.collect()),
+				JsonValue::Array(items) => one(items
+					.iter()
+					.enumerate()
+					.map(|(i, item)| entry(i.into(), item))
+					.collect()),
+				value => error(format!(
+					"{} has no entries",
+					describe(value)
+				)),
+			}
+		}
+		("from_entries", []) => {
+			let mut map = HashMap::new();
+			for entry in expect_array("from_entries", input)? {
+				let key =
+					["key", "k", "name", "Name", "Key", "K"].iter().find_map(
+						|name| entry.get(name).filter(|key| !key.is_null()),
+					);
+				let key = match key {
+					Some(JsonValue::String(key)) => key.clone(),
+					Some(
+						key @ (JsonValue::Number(_) | JsonValue::Boolean(_)),
+					) => key.to_string(),
+					_ => {
+						return error(format!(
+							"invalid entry {}",
+							describe(entry)
+						))
+					}
+				};
+				let value = ["value", "v", "Value", "V"]
+					.iter()
+					.find_map(|name| entry.get(name))
+					.cloned()
+					.unwrap_or(JsonValue::Null);
+				map.insert(key, value);
+			}
+			one(JsonValue::Object(Box::new(map)))
+		}
+		("with_entries", [f]) => {
+			let entries = call("to_entries", &[], input, env)?;
+			let mapped = call(
+				"map",
+				std::slice::from_ref(f),
+				&entries[0],
+				env,
+			)?;
+			call("from_entries", &[], &mapped[0], env)
+		}
+		("tostring", []) => one(as_text(input).into()),
+		("tojson", []) => one(input.to_string().into()),
+		("fromjson", []) => match expect_str("fromjson", input)?.parse() {
+			Ok(value) => one(value),
+			Err(e) => error(format!(
+				"cannot parse {}: {e}",
+				describe(input)
+			)),
+		},
+		("tonumber", []) => match input {
+			JsonValue::Number(_) => one(input.clone()),
+			JsonValue::String(s) => match s.trim().parse::<f64>() {
+				Ok(n) if n.is_finite() => one(n.into()),
+				_ => error(format!(
+					"cannot parse {} as a number",
+					describe(input)
+				)),
+			},
+			value => error(format!(
+				"{} cannot be parsed as a number",
+				describe(value)
+			)),
+		},
+		("ascii_downcase", []) => {
+			one(expect_str(name, input)?.to_ascii_lowercase().into())
+		}
+		("ascii_upcase", []) => {
+			one(expect_str(name, input)?.to_ascii_uppercase().into())
+		}
+		("floor" | "ceil" | "round" | "fabs" | "sqrt", []) => match input {
+			JsonValue::Number(n) => one(match name {
+				"floor" => n.floor(),
+				"ceil" => n.ceil(),
+				"round" => n.round(),
+				"fabs" => n.abs(),
+				_ => n.sqrt(),
+			}
+			.into()),
+			value => error(format!(
+				"{name} expects a number, found {}",
+				describe(value)
+			)),
+		},
+		(
+			"startswith" | "endswith" | "ltrimstr" | "rtrimstr" | "split"
+			| "join" | "contains" | "test",
+			[arg],
+		) => {
+			let mut out = Vec::new();
+			for arg in eval(arg, input, env)? {
+				out.push(string_call(name, input, &arg)?);
+			}
+			Ok(out)
+		}
+		(name, args) => error(format!(
+			"{name}/{} is not defined",
+			args.len()
+		)),
+	}
+}
+
+/// The builtins that take one string-ish argument.
+fn string_call(
+	name: &str,
+	input: &JsonValue,
+	arg: &JsonValue,
+) -> Result<JsonValue, EvalError> {
+	Ok(match name {
+		"join" => {
+			let separator = expect_str(name, arg)?;
+			let mut joined = String::new();
+			for (i, item) in expect_array(name, input)?.iter().enumerate() {
+				if i > 0 {
+					joined.push_str(separator);
+				}
+				match item {
+					JsonValue::Null => {}
+					JsonValue::Array(_) | JsonValue::Object(_) => {
+						return error(format!(
+							"cannot join {}",
+							describe(item)
+						))
+					}
+					item => joined.push_str(&as_text(item)),
+				}
+			}
+			joined.into()
+		}
+		"contains" => contains(input, arg).into(),
+		"ltrimstr" | "rtrimstr" => match (input, arg) {
+			(JsonValue::String(s), JsonValue::String(affix)) => match name {
+				"ltrimstr" => s.strip_prefix(affix.as_str()),
+				_ => s.strip_suffix(affix.as_str()),
+			}
+			.unwrap_or(s)
+			.into(),
+			_ => input.clone(),
+		},
+		_ => {
+			let (s, arg) = (
+				expect_str(name, input)?,
+				expect_str(name, arg)?,
+			);
+			match name {
+				"startswith" => s.starts_with(arg).into(),
+				"endswith" => s.ends_with(arg).into(),
+				"split" => {
+					split(s, arg).into_iter().map(JsonValue::String).collect()
+				}
+				_ => match crate::schema::Regex::new(arg) {
+					Ok(regex) => match regex.is_match(s) {
+						Ok(matched) => matched.into(),
+						Err(e) => return error(format!("{e} {arg:?}")),
+					},
+					Err(e) => {
+						return error(format!("invalid regex {arg:?}: {e}"))
+					}
+				},
+			}
+		}
+	})
+}
+
+/// jq's `contains`: substrings, subsets of arrays and sub-objects.
+fn contains(a: &JsonValue, b: &JsonValue) -> bool {
+	match (a, b) {
+		(JsonValue::String(a), JsonValue::String(b)) => a.contains(b.as_str()),
+		(JsonValue::Array(a), JsonValue::Array(b)) => {
+			b.iter().all(|b| a.iter().any(|a| contains(a, b)))
+		}
+		(JsonValue::Object(a), JsonValue::Object(b)) => b
+			.iter()
+			.all(|(key, b)| a.get(key).is_some_and(|a| contains(a, b))),
+		(a, b) => a == b,
+	}
+}
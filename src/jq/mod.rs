@@ -0,0 +1,351 @@
+//! A jq-like filter language.
+//!
+//! The supported subset covers paths (`.a.b`, `."key"`, `.[0]`, `.[1:3]`,
+//! `.[]`, `..`, `?`), pipes and commas, literals including arrays, objects
+//! and interpolated strings (`"\(.name)!"`), arithmetic, comparisons,
+//! `and`/`or`/`//`, `if ... then ... elif ... else ... end`, variable
+//! bindings (`. as $x | ...`) and a set of common builtins such as
+//! `select`, `map`, `length`, `keys`, `has`, `sort_by` and `to_entries`.
+//!
+//! ```text
+//! .users[] | select(.age >= 18) | "\(.name) is \(.age)"
+//! ```
+
+mod eval;
+mod parse;
+
+use std::{error, fmt, str::FromStr};
+
+use winnow::Parser;
+
+use crate::{Error, JsonValue};
+
+/// A compiled filter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+	expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+	Identity,
+	/// `..`: the input and everything inside it.
+	Recurse,
+	Literal(JsonValue),
+	/// An interpolated string.
+	Format(Vec<Part>),
+	Array(Option<Box<Expr>>),
+	Object(Vec<(Expr, Expr)>),
+	Index(Box<Expr>, Box<Expr>),
+	Slice(
+		Box<Expr>,
+		Option<Box<Expr>>,
+		Option<Box<Expr>>,
+	),
+	Iterate(Box<Expr>),
+	/// `?`: errors produce no output.
+	Try(Box<Expr>),
+	Pipe(Box<Expr>, Box<Expr>),
+	Comma(Box<Expr>, Box<Expr>),
+	Neg(Box<Expr>),
+	Binary(BinaryOp, Box<Expr>, Box<Expr>),
+	And(Box<Expr>, Box<Expr>),
+	Or(Box<Expr>, Box<Expr>),
+	/// `a // b`
+	Alternative(Box<Expr>, Box<Expr>),
+	If(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+	/// `source as $name | body`
+	Bind(Box<Expr>, String, Box<Expr>),
+	Var(String),
+	Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Part {
+	Literal(String),
+	Expr(Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Rem,
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+/// An error raised while running a filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+	pub message: String,
+}
+
+impl fmt::Display for EvalError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.message)
+	}
+}
+
+impl error::Error for EvalError {}
+
+impl Filter {
+	pub fn parse(filter: &str) -> Result<Self, Error> {
+		let input = parse::Stream {
+			input: filter,
+			state: 0,
+		};
+		parse::filter
+			.map(|expr| Self { expr })
+			.parse(input)
+			.map_err(|e| Error::from_trail(filter, e.offset(), e.inner()))
+	}
+
+	/// Runs the filter on `input`, collecting every output.
+	pub fn run(&self, input: &JsonValue) -> Result<Vec<JsonValue>, EvalError> {
+		eval::eval(&self.expr, input, &mut Vec::new())
+	}
+}
+
+impl FromStr for Filter {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::parse;
+
+	use super::*;
+
+	fn run(filter: &str, input: &str) -> Result<Vec<JsonValue>, EvalError> {
+		Filter::parse(filter).unwrap().run(&parse(input).unwrap())
+	}
+
+	fn outputs(filter: &str, input: &str) -> Vec<JsonValue> {
+		run(filter, input).unwrap_or_else(|e| panic!("{filter}: {e}"))
+	}
+
+	fn values(outputs: &[&str]) -> Vec<JsonValue> {
+		outputs
+			.iter()
+			.map(|output| parse(output).unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn paths() {
+		let input = r#"{"a": {"b": [1, 2, 3]}, "c d": true}"#;
+		assert_eq!(outputs(".", "1"), values(&["1"]));
+		assert_eq!(
+			outputs(".a.b", input),
+			values(&["[1, 2, 3]"])
+		);
+		assert_eq!(
+			outputs(".a.b[]", input),
+			values(&["1", "2", "3"])
+		);
+		assert_eq!(
+			outputs(".a.b[-1]", input),
+			values(&["3"])
+		);
+		assert_eq!(
+			outputs(".a.b[1:]", input),
+			values(&["[2, 3]"])
+		);
+		assert_eq!(
+			outputs(r#"."c d""#, input),
+			values(&["true"])
+		);
+		assert_eq!(
+			outputs(".missing", input),
+			values(&["null"])
+		);
+		assert_eq!(
+			outputs("[..] | length", "[[1]]"),
+			values(&["3"])
+		);
+		assert!(run(".a", "[1]").is_err());
+		assert_eq!(outputs(".a?", "[1]"), values(&[]));
+	}
+
+	#[test]
+	fn pipes_and_builtins() {
+		let input = r#"{"users": [
+			{"name": "ann", "age": 31},
+			{"name": "bob", "age": 17},
+			{"name": "cy", "age": 45}
+		]}"#;
+		assert_eq!(
+			outputs(
+				r#".users[] | select(.age >= 18) | "\(.name) is \(.age)""#,
+				input
+			),
+			values(&[r#""ann is 31""#, r#""cy is 45""#])
+		);
+		assert_eq!(
+			outputs(".users | map(.age) | add", input),
+			values(&["93"])
+		);
+		assert_eq!(
+			outputs(
+				".users | sort_by(.age) | map(.name)",
+				input
+			),
+			values(&[r#"["bob", "ann", "cy"]"#])
+		);
+		assert_eq!(
+			outputs(
+				"[.users[].name] | length, first, (keys | last)",
+				input
+			),
+			values(&["3", r#""ann""#, "2"])
+		);
+		assert_eq!(
+			outputs(
+				"to_entries | from_entries",
+				r#"{"a": 1}"#
+			),
+			values(&[r#"{"a": 1}"#])
+		);
+	}
+
+	#[test]
+	fn expressions() {
+		assert_eq!(
+			outputs("1 + 2 * 3 - 4 / 2", "null"),
+			values(&["5"])
+		);
+		assert_eq!(
+			outputs("7 % 3, -.", "2"),
+			values(&["1", "-2"])
+		);
+		assert_eq!(
+			outputs(
+				r#""a" + "b", [1] + [2], {"a": 1} + {"b": 2}"#,
+				"null"
+			),
+			values(&[r#""ab""#, "[1, 2]", r#"{"a": 1, "b": 2}"#])
+		);
+		assert_eq!(
+			outputs(". as $x | [$x, $x * 2]", "3"),
+			values(&["[3, 6]"])
+		);
+		assert_eq!(
+			outputs(
+				r#"if . > 2 then "big" elif . > 1 then "mid" else "small" end"#,
+				"2"
+			),
+			values(&[r#""mid""#])
+		);
+		assert_eq!(
+			outputs(
+				".a // \"default\", (true and false), (null or 1)",
+				"{}"
+			),
+			values(&[r#""default""#, "false", "true"])
+		);
+		assert!(run(r#"1 + "a""#, "null").is_err());
+		assert!(run("error(\"boom\")", "null").is_err());
+	}
+
+	#[test]
+	fn invalid_filters() {
+		for filter in ["", ".[", "if . then 1", ". as x | .", "1 +", "map("] {
+			assert!(
+				Filter::parse(filter).is_err(),
+				"{filter}"
+			);
+		}
+	}
+
+	#[test]
+	fn generators_stop_early() {
+		assert_eq!(
+			outputs("limit(1; range(1e10))", "null"),
+			values(&["0"])
+		);
+		assert_eq!(
+			outputs("first(range(1e10))", "null"),
+			values(&["0"])
+		);
+		assert_eq!(
+			outputs(
+				"[limit(3; range(1e10) as $x | $x * 2)]",
+				"null"
+			),
+			values(&["[0, 2, 4]"])
+		);
+		assert_eq!(
+			outputs(
+				"first(limit(5; range(5; 1e10)))",
+				"null"
+			),
+			values(&["5"])
+		);
+		assert_eq!(
+			outputs("limit(3; .[], range(1e10))", "[7, 8]"),
+			values(&["7", "8", "0"])
+		);
+		assert_eq!(
+			outputs("[limit(0, 2; range(1e10))]", "null"),
+			values(&["[0, 1]"])
+		);
+		assert!(run("first(range(\"a\"; 1))", "null").is_err());
+	}
+
+	#[test]
+	fn long_repeats_are_an_error() {
+		assert_eq!(
+			outputs(r#""ab" * 2.5"#, "null"),
+			values(&[r#""ababab""#])
+		);
+		assert_eq!(
+			outputs(r#""ab" * 0"#, "null"),
+			values(&["null"])
+		);
+		assert!(run(r#""x" * 1e19"#, "null").is_err());
+		assert!(run(r#"1e12 * "xy""#, "null").is_err());
+	}
+
+	#[test]
+	fn deep_filters_are_too_deep() {
+		let filters = |n: usize| {
+			[
+				format!("{}.{}", "(".repeat(n), ")".repeat(n)),
+				format!("{}{}", "[".repeat(n), "]".repeat(n)),
+				format!("{}.", ".|".repeat(n)),
+				format!("{}.", "1 // ".repeat(n)),
+				format!("{}1", "-".repeat(n)),
+				format!(
+					"{}1{}",
+					"\"\\(".repeat(n),
+					")\"".repeat(n)
+				),
+			]
+		};
+		for filter in filters(parse::MAX_DEPTH - 1) {
+			Filter::parse(&filter).unwrap_or_else(|e| panic!("{e}"));
+		}
+		for filter in filters(100_000) {
+			let e = Filter::parse(&filter).unwrap_err();
+			assert_eq!(e.kind(), crate::ErrorKind::TooDeep);
+		}
+		let depth = parse::MAX_DEPTH - 1;
+		let filter = format!(
+			"{}.{}",
+			"(".repeat(depth),
+			")".repeat(depth)
+		);
+		assert_eq!(outputs(&filter, "1"), values(&["1"]));
+	}
+}
@@ -0,0 +1,439 @@
+use winnow::{
+	ascii::digit1,
+	combinator::{
+		alt, cut_err, delimited, not, opt, preceded, repeat, separated,
+		separated_foldl1, terminated,
+	},
+	prelude::*,
+	token::{one_of, take_while},
+	PResult, Stateful,
+};
+
+use super::{BinaryOp, Expr, Part};
+use crate::{
+	error::ContextTrail,
+	options::too_deep,
+	parser::{character, string, ws},
+	JsonValue,
+};
+
+/// How deep filters may nest. Each level takes a pass through every
+/// precedence level of the grammar, so this is well below how deep values
+/// may nest in a document.
+pub(super) const MAX_DEPTH: usize = 64;
+
+/// A filter being parsed, with how many levels deep the parser is in it.
+pub(super) type Stream<'i> = Stateful<&'i str, usize>;
+
+/// Words that cannot name a function.
+const RESERVED: &[&str] =
+	&["and", "as", "elif", "else", "end", "if", "or", "then"];
+
+pub(super) fn filter<'i>(
+	input: &mut Stream<'i>,
+) -> PResult<Expr, ContextTrail> {
+	terminated(pipe, ws).parse_next(input)
+}
+
+fn boxed(expr: Expr) -> Box<Expr> {
+	Box::new(expr)
+}
+
+/// Runs `parser` a level deeper, failing beyond [`MAX_DEPTH`].
+fn nested<'i, O>(
+	mut parser: impl Parser<Stream<'i>, O, ContextTrail>,
+) -> impl Parser<Stream<'i>, O, ContextTrail> {
+	move |input: &mut Stream<'i>| {
+		if input.state >= MAX_DEPTH {
+			return Err(too_deep(MAX_DEPTH));
+		}
+		input.state += 1;
+		let parsed = parser.parse_next(input);
+		input.state -= 1;
+		parsed
+	}
+}
+
+fn symbol<'i>(
+	symbol: &'static str,
+) -> impl Parser<Stream<'i>, &'i str, ContextTrail> {
+	preceded(ws, symbol)
+}
+
+fn keyword<'i>(
+	keyword: &'static str,
+) -> impl Parser<Stream<'i>, &'i str, ContextTrail> {
+	preceded(
+		ws,
+		terminated(
+			keyword,
+			not(one_of(|c: char| {
+				c.is_ascii_alphanumeric() || c == '_'
+			})),
+		),
+	)
+}
+
+fn ident<'i>(input: &mut Stream<'i>) -> PResult<&'i str, ContextTrail> {
+	(
+		one_of(|c: char| c.is_ascii_alphabetic() || c == '_'),
+		take_while(0.., |c: char| {
+			c.is_ascii_alphanumeric() || c == '_'
+		}),
+	)
+		.recognize()
+		.parse_next(input)
+}
+
+/// `a | b`, binding looser than everything else.
+fn pipe<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let lhs = comma(input)?;
+	if opt(keyword("as")).parse_next(input)?.is_some() {
+		let (name, body) = cut_err((
+			preceded(symbol("$"), ident),
+			preceded(symbol("|"), nested(pipe)),
+		))
+		.context("binding")
+		.parse_next(input)?;
+		return Ok(Expr::Bind(
+			boxed(lhs),
+			name.to_owned(),
+			boxed(body),
+		));
+	}
+	match opt(preceded(
+		symbol("|"),
+		cut_err(nested(pipe)),
+	))
+	.parse_next(input)?
+	{
+		Some(rhs) => Ok(Expr::Pipe(boxed(lhs), boxed(rhs))),
+		None => Ok(lhs),
+	}
+}
+
+fn comma<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	separated_foldl1(
+		alternative,
+		symbol(","),
+		|lhs, _, rhs| Expr::Comma(boxed(lhs), boxed(rhs)),
+	)
+	.parse_next(input)
+}
+
+/// `a // b`, which is right-associative.
+fn alternative<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let lhs = or(input)?;
+	match opt(preceded(
+		symbol("//"),
+		cut_err(nested(alternative)),
+	))
+	.parse_next(input)?
+	{
+		Some(rhs) => Ok(Expr::Alternative(
+			boxed(lhs),
+			boxed(rhs),
+		)),
+		None => Ok(lhs),
+	}
+}
+
+fn or<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	separated_foldl1(and, keyword("or"), |lhs, _, rhs| {
+		Expr::Or(boxed(lhs), boxed(rhs))
+	})
+	.parse_next(input)
+}
+
+fn and<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	separated_foldl1(
+		comparison,
+		keyword("and"),
+		|lhs, _, rhs| Expr::And(boxed(lhs), boxed(rhs)),
+	)
+	.parse_next(input)
+}
+
+fn comparison<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let lhs = additive(input)?;
+	let op = opt(preceded(
+		ws,
+		alt((
+			"==".value(BinaryOp::Eq),
+			"!=".value(BinaryOp::Ne),
+			"<=".value(BinaryOp::Le),
+			">=".value(BinaryOp::Ge),
+			"<".value(BinaryOp::Lt),
+			">".value(BinaryOp::Gt),
+		)),
+	))
+	.parse_next(input)?;
+	match op {
+		Some(op) => {
+			let rhs = cut_err(additive).parse_next(input)?;
+			Ok(Expr::Binary(op, boxed(lhs), boxed(rhs)))
+		}
+		None => Ok(lhs),
+	}
+}
+
+fn additive<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let op = preceded(
+		ws,
+		alt((
+			'+'.value(BinaryOp::Add),
+			'-'.value(BinaryOp::Sub),
+		)),
+	);
+	separated_foldl1(multiplicative, op, |lhs, op, rhs| {
+		Expr::Binary(op, boxed(lhs), boxed(rhs))
+	})
+	.parse_next(input)
+}
+
+fn multiplicative<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let op = preceded(
+		ws,
+		alt((
+			'*'.value(BinaryOp::Mul),
+			terminated('/', not('/')).value(BinaryOp::Div),
+			'%'.value(BinaryOp::Rem),
+		)),
+	);
+	separated_foldl1(unary, op, |lhs, op, rhs| {
+		Expr::Binary(op, boxed(lhs), boxed(rhs))
+	})
+	.parse_next(input)
+}
+
+fn unary<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	alt((
+		preceded(symbol("-"), cut_err(nested(unary)))
+			.map(|e| Expr::Neg(boxed(e))),
+		postfix,
+	))
+	.parse_next(input)
+}
+
+#[derive(Clone)]
+enum Suffix {
+	Index(Expr),
+	Slice(Option<Expr>, Option<Expr>),
+	Iterate,
+	Try,
+}
+
+fn postfix<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let term = term(input)?;
+	repeat(0.., suffix)
+		.fold(
+			move || term.clone(),
+			|expr, suffix| match suffix {
+				Suffix::Index(index) => Expr::Index(boxed(expr), boxed(index)),
+				Suffix::Slice(from, to) => Expr::Slice(
+					boxed(expr),
+					from.map(boxed),
+					to.map(boxed),
+				),
+				Suffix::Iterate => Expr::Iterate(boxed(expr)),
+				Suffix::Try => Expr::Try(boxed(expr)),
+			},
+		)
+		.parse_next(input)
+}
+
+fn suffix<'i>(input: &mut Stream<'i>) -> PResult<Suffix, ContextTrail> {
+	alt((
+		preceded('.', field).map(Suffix::Index),
+		'?'.value(Suffix::Try),
+		preceded(
+			'[',
+			cut_err(terminated(brackets, symbol("]"))),
+		),
+	))
+	.parse_next(input)
+}
+
+/// The inside of `[]`, `[i]`, `[from:to]`.
+fn brackets<'i>(input: &mut Stream<'i>) -> PResult<Suffix, ContextTrail> {
+	let from = opt(pipe).parse_next(input)?;
+	if opt(symbol(":")).parse_next(input)?.is_some() {
+		let to = opt(pipe).parse_next(input)?;
+		return Ok(Suffix::Slice(from, to));
+	}
+	Ok(match from {
+		Some(index) => Suffix::Index(index),
+		None => Suffix::Iterate,
+	})
+}
+
+/// The name after `.`, bare or quoted.
+fn field<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	alt((
+		ident.map(|name: &str| Expr::Literal(name.into())),
+		string.map(|name| Expr::Literal(name.into())),
+	))
+	.parse_next(input)
+}
+
+fn term<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	nested(preceded(
+		ws,
+		alt((
+			"..".value(Expr::Recurse),
+			preceded('.', opt(field)).map(|field| match field {
+				Some(field) => Expr::Index(boxed(Expr::Identity), boxed(field)),
+				None => Expr::Identity,
+			}),
+			number.map(|n| Expr::Literal(JsonValue::Number(n))),
+			format,
+			delimited('(', cut_err(pipe), cut_err(symbol(")"))),
+			preceded(
+				'[',
+				cut_err(terminated(opt(pipe), symbol("]"))),
+			)
+			.map(|items| Expr::Array(items.map(boxed)))
+			.context("array"),
+			object,
+			preceded('$', cut_err(ident)).map(|name| Expr::Var(name.into())),
+			conditional,
+			call,
+		)),
+	))
+	.parse_next(input)
+}
+
+fn number<'i>(input: &mut Stream<'i>) -> PResult<f64, ContextTrail> {
+	(
+		digit1,
+		opt(('.', opt(digit1))),
+		opt((
+			one_of(['e', 'E']),
+			opt(one_of(['+', '-'])),
+			digit1,
+		)),
+	)
+		.recognize()
+		.verify_map(|s: &str| s.parse().ok())
+		.parse_next(input)
+}
+
+/// A string literal, possibly with `\(...)` interpolations.
+fn format<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let part = alt((
+		delimited(
+			"\\(",
+			cut_err(pipe),
+			cut_err(symbol(")")),
+		)
+		.map(Part::Expr),
+		character.map(|c: char| Part::Literal(c.to_string())),
+	));
+	let parts = repeat(0.., part).fold(
+		Vec::new,
+		|mut parts: Vec<Part>, part| {
+			match (parts.last_mut(), part) {
+				(Some(Part::Literal(s)), Part::Literal(c)) => s.push_str(&c),
+				(_, part) => parts.push(part),
+			}
+			parts
+		},
+	);
+	preceded('"', cut_err(terminated(parts, '"')))
+		.map(|mut parts| match parts.as_mut_slice() {
+			[] => Expr::Literal("".into()),
+			[Part::Literal(s)] => Expr::Literal(std::mem::take(s).into()),
+			_ => Expr::Format(parts),
+		})
+		.context("string")
+		.parse_next(input)
+}
+
+fn object<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	preceded(
+		'{',
+		cut_err(terminated(
+			separated(0.., entry, symbol(",")),
+			symbol("}"),
+		)),
+	)
+	.map(Expr::Object)
+	.context("object")
+	.parse_next(input)
+}
+
+/// `key: value`, or the shorthands `key` (for `key: .key`) and `$name`.
+fn entry<'i>(input: &mut Stream<'i>) -> PResult<(Expr, Expr), ContextTrail> {
+	let var = preceded(symbol("$"), cut_err(ident)).map(|name: &str| {
+		(
+			Expr::Literal(name.into()),
+			Expr::Var(name.into()),
+		)
+	});
+	let key = preceded(
+		ws,
+		alt((
+			ident.map(|name: &str| Expr::Literal(name.into())),
+			format,
+			delimited('(', cut_err(pipe), cut_err(symbol(")"))),
+		)),
+	);
+	let keyed = (
+		key,
+		opt(preceded(
+			symbol(":"),
+			cut_err(alternative),
+		)),
+	)
+		.map(|(key, value)| {
+			let value = value.unwrap_or_else(|| {
+				Expr::Index(
+					boxed(Expr::Identity),
+					boxed(key.clone()),
+				)
+			});
+			(key, value)
+		});
+	alt((var, keyed)).parse_next(input)
+}
+
+fn conditional<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let branch = |start| {
+		(
+			preceded(keyword(start), cut_err(pipe)),
+			cut_err(preceded(keyword("then"), pipe)),
+		)
+	};
+	let (first, rest, otherwise) = (
+		branch("if"),
+		cut_err(repeat(0.., branch("elif"))),
+		cut_err(opt(preceded(keyword("else"), pipe))),
+	)
+		.parse_next(input)?;
+	cut_err(keyword("end")).context("if").parse_next(input)?;
+	let mut branches = vec![first];
+	branches.extend::<Vec<_>>(rest);
+	Ok(Expr::If(branches, otherwise.map(boxed)))
+}
+
+fn call<'i>(input: &mut Stream<'i>) -> PResult<Expr, ContextTrail> {
+	let name = ident.verify(|name: &str| !RESERVED.contains(&name));
+	let args = opt(delimited(
+		'(',
+		cut_err(separated(1.., pipe, symbol(";"))),
+		cut_err(symbol(")")),
+	));
+	(name, args)
+		.map(
+			|(name, args): (&str, Option<Vec<Expr>>)| match name {
+				"true" => Expr::Literal(true.into()),
+				"false" => Expr::Literal(false.into()),
+				"null" => Expr::Literal(JsonValue::Null),
+				_ => Expr::Call(
+					name.to_owned(),
+					args.unwrap_or_default(),
+				),
+			},
+		)
+		.parse_next(input)
+}
@@ -0,0 +1,332 @@
+//! A lossless tokenizer for editors: every byte of the input belongs to
+//! exactly one [`Token`], whitespace and comments included, so tokens can
+//! be highlighted or reassembled into the exact source.
+
+use std::ops::Range;
+
+/// What a [`Token`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+	/// A run of spaces, tabs, carriage returns and line feeds.
+	Whitespace,
+	/// A `//` comment, up to but not including the line break ending it.
+	LineComment,
+	/// A `/* */` comment.
+	BlockComment,
+	StartObject,
+	EndObject,
+	StartArray,
+	EndArray,
+	Colon,
+	Comma,
+	/// A string literal with its quotes. Its escapes are not checked.
+	String,
+	Number,
+	True,
+	False,
+	Null,
+	/// Text that starts no token, such as a stray character, a misspelled
+	/// literal, or a string or comment that is not terminated.
+	Error,
+}
+
+impl TokenKind {
+	/// Whether the token is whitespace or a comment, which carry no meaning
+	/// in a document.
+	pub fn is_trivia(self) -> bool {
+		matches!(
+			self,
+			TokenKind::Whitespace
+				| TokenKind::LineComment
+				| TokenKind::BlockComment
+		)
+	}
+}
+
+/// A token and the bytes of the input it covers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+	pub kind: TokenKind,
+	pub span: Range<usize>,
+}
+
+/// Splits `input` into tokens, covering all of it in order.
+///
+/// Malformed text becomes [`TokenKind::Error`] tokens and lexing carries
+/// on after them, so this never fails; comments are recognized even
+/// though only [`parse_spanned_with_comments`] of the parsers accepts
+/// them.
+///
+/// [`parse_spanned_with_comments`]: crate::parse_spanned_with_comments
+pub fn tokenize(input: &str) -> Tokens<'_> {
+	Tokens { input, pos: 0 }
+}
+
+/// The tokens of a document, made by [`tokenize`].
+#[derive(Debug, Clone)]
+pub struct Tokens<'i> {
+	input: &'i str,
+	pos: usize,
+}
+
+impl Iterator for Tokens<'_> {
+	type Item = Token;
+
+	fn next(&mut self) -> Option<Token> {
+		let rest = &self.input[self.pos..];
+		let c = rest.chars().next()?;
+		let (kind, len) = match c {
+			' ' | '\t' | '\r' | '\n' => (
+				TokenKind::Whitespace,
+				rest.find(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+					.unwrap_or(rest.len()),
+			),
+			'{' => (TokenKind::StartObject, 1),
+			'}' => (TokenKind::EndObject, 1),
+			'[' => (TokenKind::StartArray, 1),
+			']' => (TokenKind::EndArray, 1),
+			':' => (TokenKind::Colon, 1),
+			',' => (TokenKind::Comma, 1),
+			'"' => string(rest),
+			'/' => comment(rest),
+			'-' | '+' | '.' | '0'..='9' => number(rest),
+			'a'..='z' | 'A'..='Z' => {
+				let len = rest
+					.find(|c: char| !c.is_ascii_alphanumeric())
+					.unwrap_or(rest.len());
+				let kind = match &rest[..len] {
+					"true" => TokenKind::True,
+					"false" => TokenKind::False,
+					"null" => TokenKind::Null,
+					_ => TokenKind::Error,
+				};
+				(kind, len)
+			}
+			c => (TokenKind::Error, c.len_utf8()),
+		};
+		let start = self.pos;
+		self.pos += len;
+		Some(Token {
+			kind,
+			span: start..self.pos,
+		})
+	}
+}
+
+/// Lexes a string, which a line break or the end of the input leaves
+/// unterminated.
+fn string(rest: &str) -> (TokenKind, usize) {
+	let bytes = rest.as_bytes();
+	let mut i = 1;
+	while let Some(&b) = bytes.get(i) {
+		match b {
+			b'"' => return (TokenKind::String, i + 1),
+			b'\\' if bytes.get(i + 1).is_some_and(|&b| b != b'\n') => i += 1,
+			b'\n' => return (TokenKind::Error, i),
+			_ => {}
+		}
+		i += 1;
+	}
+	(TokenKind::Error, rest.len())
+}
+
+fn comment(rest: &str) -> (TokenKind, usize) {
+	if rest.starts_with("//") {
+		let len = rest.find(['\r', '\n']).unwrap_or(rest.len());
+		(TokenKind::LineComment, len)
+	} else if let Some(body) = rest.strip_prefix("/*") {
+		match body.find("*/") {
+			Some(end) => (TokenKind::BlockComment, end + 4),
+			None => (TokenKind::Error, rest.len()),
+		}
+	} else {
+		(TokenKind::Error, 1)
+	}
+}
+
+/// Lexes a number, taking everything that looks like part of one and
+/// making an error token of it unless it matches the JSON grammar. Runs
+/// starting with `+` or `.` are lexed as numbers too, so that they make a
+/// single error token that [`number_error`] can explain.
+fn number(rest: &str) -> (TokenKind, usize) {
+	let len = rest
+		.find(|c: char| {
+			!(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+		})
+		.unwrap_or(rest.len());
+	let kind = if is_number(&rest[..len]) {
+		TokenKind::Number
+	} else {
+		TokenKind::Error
+	};
+	(kind, len)
+}
+
+/// Whether `s` is a number by the JSON grammar:
+/// `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`.
+pub(crate) fn is_number(s: &str) -> bool {
+	check_number(s).is_ok()
+}
+
+/// Checks that `s` is a number by the JSON grammar, saying what is wrong
+/// with it if not.
+pub(crate) fn check_number(s: &str) -> Result<(), &'static str> {
+	if s.starts_with('+') {
+		return Err("numbers cannot start with '+'");
+	}
+	let int = s.strip_prefix('-').unwrap_or(s);
+	let (len, mut s) = digits(int);
+	if len == 0 {
+		return Err(if s.starts_with('.') {
+			"numbers need a digit before the decimal point"
+		} else {
+			"invalid number"
+		});
+	}
+	if len > 1 && int.starts_with('0') {
+		return Err("leading zeros are not allowed");
+	}
+	if let Some(after) = s.strip_prefix('.') {
+		let (fraction, after) = digits(after);
+		if fraction == 0 {
+			return Err("numbers need a digit after the decimal point");
+		}
+		s = after;
+	}
+	if let Some(after) = s.strip_prefix(['e', 'E']) {
+		let after = after.strip_prefix(['+', '-']).unwrap_or(after);
+		let (exponent, after) = digits(after);
+		if exponent == 0 {
+			return Err("exponents need at least one digit");
+		}
+		s = after;
+	}
+	if s.is_empty() {
+		Ok(())
+	} else {
+		Err("invalid number")
+	}
+}
+
+/// Why the text of an error token that starts like a number is not one,
+/// or `None` if it does not start like a number.
+pub(crate) fn number_error(text: &str) -> Option<&'static str> {
+	text.starts_with([
+		'-', '+', '.', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+	])
+	.then(|| check_number(text).err().unwrap_or("invalid number"))
+}
+
+/// The length of the run of characters at the start of `s` that could be
+/// part of a number literal, valid or not.
+pub(crate) fn number_len(s: &str) -> usize {
+	s.find(|c: char| !is_number_char(c)).unwrap_or(s.len())
+}
+
+pub(crate) fn is_number_char(c: char) -> bool {
+	matches!(
+		c,
+		'0'..='9' | '+' | '-' | '.' | 'e' | 'E'
+	)
+}
+
+/// Counts the digits `s` starts with, returning the count and what follows
+/// them.
+fn digits(s: &str) -> (usize, &str) {
+	let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+	(len, &s[len..])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+		tokenize(input)
+			.map(|token| (token.kind, &input[token.span]))
+			.collect()
+	}
+
+	#[test]
+	fn tokens_cover_the_input() {
+		use TokenKind::*;
+		let input = "{\"k\\\"\": [1.5e3, true, null] /* c */ , // end\n\t}";
+		assert_eq!(
+			kinds(input),
+			[
+				(StartObject, "{"),
+				(String, "\"k\\\"\""),
+				(Colon, ":"),
+				(Whitespace, " "),
+				(StartArray, "["),
+				(Number, "1.5e3"),
+				(Comma, ","),
+				(Whitespace, " "),
+				(True, "true"),
+				(Comma, ","),
+				(Whitespace, " "),
+				(Null, "null"),
+				(EndArray, "]"),
+				(Whitespace, " "),
+				(BlockComment, "/* c */"),
+				(Whitespace, " "),
+				(Comma, ","),
+				(Whitespace, " "),
+				(LineComment, "// end"),
+				(Whitespace, "\n\t"),
+				(EndObject, "}"),
+			]
+		);
+		let trivia: Vec<_> = kinds(input)
+			.into_iter()
+			.filter(|(kind, _)| kind.is_trivia())
+			.map(|(_, text)| text)
+			.collect();
+		assert_eq!(
+			trivia.concat(),
+			"    /* c */  // end\n\t"
+		);
+	}
+
+	#[test]
+	fn malformed_text_becomes_error_tokens() {
+		use TokenKind::*;
+		assert_eq!(
+			kinds("[01, +1, nul, é, \"open\n\"x\\é\" /* open"),
+			[
+				(StartArray, "["),
+				(Error, "01"),
+				(Comma, ","),
+				(Whitespace, " "),
+				(Error, "+1"),
+				(Comma, ","),
+				(Whitespace, " "),
+				(Error, "nul"),
+				(Comma, ","),
+				(Whitespace, " "),
+				(Error, "é"),
+				(Comma, ","),
+				(Whitespace, " "),
+				(Error, "\"open"),
+				(Whitespace, "\n"),
+				(String, "\"x\\é\""),
+				(Whitespace, " "),
+				(Error, "/* open"),
+			]
+		);
+		for input in ["\"", "\"\\", "/", "-", "1e", "\\u", "😀😀"] {
+			let tokens: Vec<_> = tokenize(input).collect();
+			assert_eq!(
+				tokens.first().map(|t| t.span.start),
+				Some(0)
+			);
+			assert_eq!(
+				tokens.last().map(|t| t.span.end),
+				Some(input.len())
+			);
+			assert!(tokens
+				.windows(2)
+				.all(|t| t[0].span.end == t[1].span.start));
+		}
+	}
+}
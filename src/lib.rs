@@ -0,0 +1,139 @@
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+mod binary;
+mod builder;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod coerce;
+mod color;
+mod convert;
+mod csv;
+mod cursor;
+mod document;
+mod error;
+mod escape;
+pub mod events;
+mod flatten;
+mod format;
+mod generate;
+mod gron;
+pub mod iter;
+pub mod jmespath;
+pub mod jq;
+pub mod lexer;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod multimap;
+pub mod ndjson;
+mod options;
+mod parallel;
+pub mod parser;
+mod partial;
+mod persistent;
+pub mod pointer;
+mod position;
+mod projection;
+mod query;
+mod read;
+mod redact;
+mod reformat;
+mod rename;
+mod repair;
+mod retain;
+mod reuse;
+pub mod schema;
+mod ser;
+mod sort;
+mod stats;
+mod syntax;
+pub mod testing;
+#[cfg(feature = "toml")]
+mod toml;
+mod truncate;
+mod value;
+mod walk;
+mod writer;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+use std::str::FromStr;
+
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+pub use binary::DecodeError;
+pub use builder::{ArrayBuilder, ObjectBuilder};
+pub use coerce::Coercion;
+pub use color::{highlight, Palette, TokenClass};
+pub use convert::{ConversionError, ExtractError, ExtractErrorKind};
+pub use csv::from_csv;
+pub use cursor::Cursor;
+pub use document::{Diagnostic, Document};
+pub use error::{Error, ErrorKind};
+pub use escape::{escape_json_string, unescape_json_string};
+pub use events::{events_to_value, value_to_events, Event, EventReader};
+pub use flatten::UnflattenError;
+pub use format::{CompactFormatter, Formatter, NumberFormat, PrettyFormatter};
+pub use generate::GenerateLimits;
+pub use lexer::{tokenize, Token, TokenKind};
+#[cfg(all(feature = "mmap", unix))]
+pub use mmap::from_file_mmap;
+pub use multimap::MultiValue;
+pub use options::{
+	Budget, DuplicateKeys, Metrics, NumberLiterals, NumberMode, Overflow,
+	ParseOptions,
+};
+pub use parallel::par_parse;
+pub use parser::json;
+pub use partial::PartialParser;
+pub use persistent::PersistentValue;
+pub use pointer::{JsonPointer, PointerError, RelativePointer, RelativeTarget};
+pub use position::{LineIndex, Position};
+pub use projection::parse_projection;
+pub use query::QueryStringError;
+pub use read::{from_reader, from_slice, ReadError};
+pub use redact::{ReplaceWith, Selector};
+pub use reformat::{minify, reformat};
+pub use rename::KeyCase;
+pub use repair::{parse_lenient_with_fixes, repair_truncated, Fix};
+pub use retain::Prune;
+pub use reuse::Parser;
+pub use ser::TooLarge;
+pub use stats::Stats;
+pub use syntax::{
+	parse_spanned, parse_spanned_with_comments, Comments, Member, Node,
+	NodeKind, TextEdit,
+};
+pub use truncate::Summary;
+pub use value::{JsonType, JsonValue};
+pub use writer::{JsonWriter, WriteError};
+
+/// Parses a complete JSON document.
+///
+/// Arrays and objects may nest [`ParseOptions::DEFAULT_MAX_DEPTH`] levels
+/// deep, as with [`ParseOptions`]' defaults.
+pub fn parse(input: &str) -> Result<JsonValue, Error> {
+	ParseOptions::new().parse(input)
+}
+
+impl FromStr for JsonValue {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse(s)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deep_documents_fail_without_overflowing() {
+		for open in ["[", "{\"a\":"] {
+			let input = open.repeat(100_000);
+			let error = parse(&input).unwrap_err();
+			assert_eq!(error.kind(), ErrorKind::TooDeep);
+			assert!(input.parse::<JsonValue>().is_err());
+		}
+	}
+}
@@ -1,243 +1,204 @@
-use std::collections::HashMap;
-
-use winnow::{
-	ascii::float,
-	combinator::{
-		alt, cut_err, delimited, preceded, repeat, separated, separated_pair,
-		terminated,
-	},
-	error::{AddContext, InputError, ParserError},
-	prelude::*,
-	token::{any, none_of, take, take_while},
-	PResult,
+use std::{
+	env,
+	fs::{self, File},
+	io::{self, IsTerminal, Read},
+	process::ExitCode,
 };
 
-#[derive(Debug, Clone, PartialEq)]
-enum JsonValue {
-	Null,
-	Boolean(bool),
-	Number(f64),
-	String(String),
-	Array(Vec<JsonValue>),
-	Object(HashMap<String, JsonValue>),
-}
-
-fn json<'i, E>(input: &mut &'i str) -> PResult<JsonValue, E>
-where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
-	delimited(ws, json_value, ws).parse_next(input)
-}
-
-fn json_value<'i, E>(input: &mut &'i str) -> PResult<JsonValue, E>
-where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
-	alt((
-		null.value(JsonValue::Null),
-		boolean.map(JsonValue::Boolean),
-		string.map(JsonValue::String),
-		float.map(JsonValue::Number),
-		array.map(JsonValue::Array),
-		object.map(JsonValue::Object),
-	))
-	.parse_next(input)
-}
-
-fn null<'i, E>(input: &mut &'i str) -> PResult<&'i str, E>
-where E: ParserError<&'i str> {
-	"null".parse_next(input)
-}
-
-fn boolean<'i, E>(input: &mut &'i str) -> PResult<bool, E>
-where E: ParserError<&'i str> {
-	let parse_true = "true".value(true);
-	let parse_false = "false".value(false);
-
-	alt((parse_true, parse_false)).parse_next(input)
-}
-
-fn string<'i, E>(input: &mut &'i str) -> PResult<String, E>
-where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
-	preceded(
-		'\"',
-		cut_err(terminated(
-			repeat(0.., character).fold(String::new, |mut string, c| {
-				string.push(c);
-				string
-			}),
-			'\"',
-		)),
-	)
-	.context("string")
-	.parse_next(input)
-}
-
-fn character<'i, E>(input: &mut &'i str) -> PResult<char, E>
-where E: ParserError<&'i str> {
-	let c = none_of('\"').parse_next(input)?;
-
-	if c == '\\' {
-		alt((
-			any.verify_map(|c| {
-				Some(match c {
-					'"' | '\\' | '/' => c,
-					'b' => '\x08',
-					'f' => '\x0C',
-					'n' => '\n',
-					'r' => '\r',
-					't' => '\t',
-					_ => return None,
-				})
-			}),
-			preceded('u', unicode_escape),
-		))
-		.parse_next(input)
-	} else {
-		Ok(c)
+use winnow_json::{jq::Filter, JsonValue, Palette};
+
+const USAGE: &str = "\
+usage: winnow-json [--color[=<when>]] <command> [args] [file]
+
+Reads a JSON document from `file`, or standard input if omitted.
+
+commands:
+    gron             print the document as greppable assignments
+    minify           print the input without whitespace or comments,
+                     streaming it so it may be larger than memory
+    eval <filter>    run a jq-style filter, printing each output on a line
+    convert <from> <to>
+                     convert the input between `json` and `csv`, a table
+                     with a header row read as an array of flat objects
+
+options:
+    --color[=<when>] color JSON output `always`, `never`, or `auto` (the
+                     default) when writing to a terminal and NO_COLOR is
+                     not set";
+
+fn main() -> ExitCode {
+	let args: Vec<String> = env::args().skip(1).collect();
+	match run(&args) {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(message) => {
+			eprintln!("winnow-json: {message}");
+			ExitCode::FAILURE
+		}
 	}
 }
 
-fn unicode_escape<'i, E>(input: &mut &'i str) -> PResult<char, E>
-where E: ParserError<&'i str> {
-	alt((
-		u16_hex
-			.verify(|cp| !(0xD800..0xE000).contains(cp))
-			.map(|cp| cp as u32),
-		separated_pair(u16_hex, "\\u", u16_hex)
-			.verify(|(h, l)| {
-				(0xD800..0xDC00).contains(h) && (0xDC00..0xE000).contains(l)
-			})
-			.map(|(h, l)| {
-				let high_ten = (h as u32) - 0xD800;
-				let low_ten = (l as u32) - 0xDC00;
-				(high_ten << 10) + low_ten + 0x10000
-			}),
-	))
-	.verify_map(std::char::from_u32)
-	.parse_next(input)
-}
-
-fn u16_hex<'i, E>(input: &mut &'i str) -> PResult<u16, E>
-where E: ParserError<&'i str> {
-	take(4usize)
-		.verify_map(|s| u16::from_str_radix(s, 16).ok())
-		.parse_next(input)
+fn run(args: &[String]) -> Result<(), String> {
+	let (color, args) = color_option(args)?;
+	let paint = |json: String| {
+		if color {
+			Palette::default().paint(&json)
+		} else {
+			json
+		}
+	};
+	let Some((command, rest)) = args.split_first() else {
+		return Err(USAGE.to_owned());
+	};
+
+	match command.as_str() {
+		"gron" => print!("{}", read_value(rest)?.to_gron()),
+		"minify" => {
+			let stdout = io::stdout().lock();
+			let minified = match rest {
+				[] => winnow_json::minify(io::stdin().lock(), stdout),
+				[path] => {
+					let file = File::open(path)
+						.map_err(|e| format!("cannot read {path}: {e}"))?;
+					winnow_json::minify(file, stdout)
+				}
+				_ => return Err(format!("too many arguments\n\n{USAGE}")),
+			};
+			minified.map_err(|e| format!("cannot minify: {e}"))?;
+			println!();
+		}
+		"eval" => {
+			let Some((filter, rest)) = rest.split_first() else {
+				return Err(format!(
+					"eval needs a filter\n\n{USAGE}"
+				));
+			};
+			let filter: Filter =
+				filter.parse().map_err(|e| format!("invalid filter: {e}"))?;
+			let outputs =
+				filter.run(&read_value(rest)?).map_err(|e| format!("{e}"))?;
+			for output in outputs {
+				println!("{}", paint(output.to_string()));
+			}
+		}
+		"convert" => {
+			let [from, to, rest @ ..] = rest else {
+				return Err(format!(
+					"convert needs a source and a target format\n\n{USAGE}"
+				));
+			};
+			let input = read_input(rest)?;
+			let value = match from.as_str() {
+				"json" => input.parse().map_err(|e| format!("{e}"))?,
+				"csv" => winnow_json::from_csv(input.as_bytes())
+					.map_err(|e| format!("{e}"))?,
+				_ => return Err(format!("unknown format {from:?}")),
+			};
+			match to.as_str() {
+				"json" => println!("{}", paint(value.to_string_pretty())),
+				"csv" => print!(
+					"{}",
+					value.to_csv().map_err(|e| format!("{e}"))?
+				),
+				_ => return Err(format!("unknown format {to:?}")),
+			}
+		}
+		"help" | "-h" | "--help" => println!("{USAGE}"),
+		_ => {
+			return Err(format!(
+				"unknown command {command:?}\n\n{USAGE}"
+			))
+		}
+	}
+	Ok(())
 }
 
-fn array<'i, E>(input: &mut &'i str) -> PResult<Vec<JsonValue>, E>
-where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
-	preceded(
-		('[', ws),
-		cut_err(terminated(
-			separated(0.., json_value, (ws, ',', ws)),
-			(ws, ']'),
+/// Takes the `--color` option out of the arguments, deciding whether JSON
+/// output is colored.
+fn color_option(args: &[String]) -> Result<(bool, Vec<String>), String> {
+	let mut when = "auto";
+	let mut rest = Vec::new();
+	for arg in args {
+		match arg.strip_prefix("--color") {
+			Some("") => when = "always",
+			Some(value) if value.starts_with('=') => when = &value[1..],
+			_ => rest.push(arg.clone()),
+		}
+	}
+	match when {
+		"always" => Ok((true, rest)),
+		"never" => Ok((false, rest)),
+		"auto" => Ok((
+			io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+			rest,
 		)),
-	)
-	.context("array")
-	.parse_next(input)
-}
-
-fn object<'i, E>(
-	input: &mut &'i str,
-) -> PResult<HashMap<String, JsonValue>, E>
-where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
-	preceded(
-		('{', ws),
-		cut_err(terminated(
-			separated(0.., key_value, (ws, ',', ws)),
-			(ws, '}'),
+		_ => Err(format!(
+			"invalid --color value {when:?}\n\n{USAGE}"
 		)),
-	)
-	.context("object")
-	.parse_next(input)
+	}
 }
 
-fn key_value<'i, E>(input: &mut &'i str) -> PResult<(String, JsonValue), E>
-where E: ParserError<&'i str> + AddContext<&'i str, &'static str> {
-	separated_pair(
-		string,
-		cut_err((ws, ':', ws)),
-		json_value,
-	)
-	.parse_next(input)
+fn read_input(args: &[String]) -> Result<String, String> {
+	match args {
+		[] => {
+			let mut input = String::new();
+			io::stdin()
+				.read_to_string(&mut input)
+				.map_err(|e| format!("cannot read standard input: {e}"))?;
+			Ok(input)
+		}
+		[path] => fs::read_to_string(path)
+			.map_err(|e| format!("cannot read {path}: {e}")),
+		_ => Err(format!("too many arguments\n\n{USAGE}")),
+	}
 }
 
-fn ws<'i, E>(input: &mut &'i str) -> PResult<&'i str, E>
-where E: ParserError<&'i str> {
-	take_while(0.., WS).parse_next(input)
+fn read_value(args: &[String]) -> Result<JsonValue, String> {
+	read_input(args)?.parse().map_err(|e| format!("{e}"))
 }
 
-const WS: &[char] = &[' ', '\t', '\r', '\n'];
-
-fn main() {
-	let input = r#"
-  {
-    "null" : null,
-    "true"  :true ,
-    "false":  false  ,
-    "number" : 123e4 ,
-    "string" : " abc 123 " ,
-    "array" : [ false , 1 , "two" ] ,
-    "object" : { "a" : 1.0 , "b" : "c" } ,
-    "empty_array" : [  ] ,
-    "empty_object" : {   }
-  }
-  "#;
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deep_input_is_an_error() {
+		let path = std::env::temp_dir().join(format!(
+			"winnow-json-deep-{}.json",
+			std::process::id()
+		));
+		fs::write(&path, "[".repeat(100_000)).unwrap();
+		let args = [path.to_string_lossy().into_owned()];
+		let result = read_value(&args);
+		fs::remove_file(&path).unwrap();
+		let error = result.unwrap_err();
+		assert!(
+			error.starts_with("nesting deeper than"),
+			"{error}"
+		);
+	}
 
-	assert_eq!(
-		json::<InputError<&'_ str>>.parse_peek(input),
-		Ok((
-			"",
-			JsonValue::Object(
-				vec![
-					("null".to_string(), JsonValue::Null),
-					(
-						"true".to_string(),
-						JsonValue::Boolean(true)
-					),
-					(
-						"false".to_string(),
-						JsonValue::Boolean(false)
-					),
-					(
-						"number".to_string(),
-						JsonValue::Number(123e4)
-					),
-					(
-						"string".to_string(),
-						JsonValue::String(" abc 123 ".to_string())
-					),
-					(
-						"array".to_string(),
-						JsonValue::Array(vec![
-							JsonValue::Boolean(false),
-							JsonValue::Number(1.0),
-							JsonValue::String("two".to_string())
-						])
-					),
-					(
-						"object".to_string(),
-						JsonValue::Object(
-							vec![
-								("a".to_string(), JsonValue::Number(1.0)),
-								(
-									"b".to_string(),
-									JsonValue::String("c".to_string())
-								),
-							]
-							.into_iter()
-							.collect()
-						)
-					),
-					(
-						"empty_array".to_string(),
-						JsonValue::Array(vec![]),
-					),
-					(
-						"empty_object".to_string(),
-						JsonValue::Object(HashMap::new()),
-					),
-				]
-				.into_iter()
-				.collect()
-			)
-		))
-	);
+	#[test]
+	fn color_option_is_taken_out_of_the_arguments() {
+		let args = |args: &[&str]| -> Vec<String> {
+			args.iter().map(|arg| arg.to_string()).collect()
+		};
+		assert_eq!(
+			color_option(&args(&["--color", "pretty", "f.json"])),
+			Ok((true, args(&["pretty", "f.json"])))
+		);
+		assert_eq!(
+			color_option(&args(&["pretty", "--color=never"])),
+			Ok((false, args(&["pretty"])))
+		);
+		assert_eq!(
+			color_option(&args(&[
+				"--color=always",
+				"--color=never",
+				"x"
+			])),
+			Ok((false, args(&["x"])))
+		);
+		let error = color_option(&args(&["--color=sometimes"])).unwrap_err();
+		assert!(error.starts_with("invalid --color value \"sometimes\""));
+	}
 }
@@ -0,0 +1,104 @@
+//! Parsing files through a memory mapping, behind the `mmap` feature.
+
+use std::{fs::File, io, os::fd::AsRawFd, path::Path, ptr, str};
+
+use crate::{JsonValue, ReadError};
+
+/// Parses the file at `path` straight from a read-only memory mapping, so
+/// the text of a multi-gigabyte document is paged in by the kernel as the
+/// parser reaches it rather than copied into memory up front.
+///
+/// # Safety
+///
+/// The file must not be modified or truncated, by this or any other
+/// process, until the call returns. A change is visible through the
+/// mapping while it is being parsed, breaking the guarantee that the text
+/// is valid UTF-8, and truncation makes reading the missing pages crash
+/// the process with `SIGBUS`.
+pub unsafe fn from_file_mmap(
+	path: impl AsRef<Path>,
+) -> Result<JsonValue, ReadError> {
+	let file = File::open(path)?;
+	let len = usize::try_from(file.metadata()?.len())
+		.map_err(|_| io::Error::other("file is too large to map"))?;
+	if len == 0 {
+		return crate::parse("").map_err(ReadError::Parse);
+	}
+	let map = Mapping::new(&file, len)?;
+	let text = str::from_utf8(map.bytes()).map_err(ReadError::Utf8)?;
+	crate::parse(text).map_err(ReadError::Parse)
+}
+
+/// A read-only mapping of a whole file, unmapped on drop.
+struct Mapping {
+	ptr: *mut libc::c_void,
+	len: usize,
+}
+
+impl Mapping {
+	fn new(file: &File, len: usize) -> io::Result<Self> {
+		// SAFETY: a fresh private read-only mapping aliases no Rust memory.
+		let ptr = unsafe {
+			libc::mmap(
+				ptr::null_mut(),
+				len,
+				libc::PROT_READ,
+				libc::MAP_PRIVATE,
+				file.as_raw_fd(),
+				0,
+			)
+		};
+		if ptr == libc::MAP_FAILED {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(Self { ptr, len })
+	}
+
+	fn bytes(&self) -> &[u8] {
+		// SAFETY: the mapping is `len` readable bytes for as long as `self`
+		// lives; the caller of `from_file_mmap` promises they stay put.
+		unsafe { std::slice::from_raw_parts(self.ptr.cast(), self.len) }
+	}
+}
+
+impl Drop for Mapping {
+	fn drop(&mut self) {
+		// SAFETY: `ptr` and `len` describe a live mapping made by `new`.
+		unsafe {
+			libc::munmap(self.ptr, self.len);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+	use crate::ErrorKind;
+
+	#[test]
+	fn deep_files_are_a_parse_error() {
+		let path = std::env::temp_dir().join(format!(
+			"winnow-json-mmap-{}.json",
+			std::process::id()
+		));
+		fs::write(&path, "{\"a\":".repeat(100_000)).unwrap();
+		// SAFETY: nothing else knows about the file.
+		let result = unsafe { from_file_mmap(&path) };
+		fs::write(&path, "[1, 2]").unwrap();
+		// SAFETY: as above.
+		let small = unsafe { from_file_mmap(&path) };
+		fs::remove_file(&path).unwrap();
+		match result {
+			Err(ReadError::Parse(e)) => {
+				assert_eq!(e.kind(), ErrorKind::TooDeep);
+			}
+			other => panic!("expected a parse error, got {other:?}"),
+		}
+		assert_eq!(
+			small.unwrap(),
+			crate::parse("[1, 2]").unwrap()
+		);
+	}
+}
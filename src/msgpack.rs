@@ -0,0 +1,406 @@
+//! Conversion to and from [MessagePack](https://github.com/msgpack/msgpack/blob/master/spec.md),
+//! behind the `msgpack` feature.
+
+use std::collections::HashMap;
+
+use crate::{
+	binary::{base64url, DecodeError},
+	JsonValue,
+};
+
+impl JsonValue {
+	/// Encodes the value as MessagePack.
+	///
+	/// Integral numbers within the range of an `i64` or `u64` use the
+	/// smallest integer format that holds them, and other numbers `float 32`
+	/// when that loses nothing, `float 64` otherwise. Object members are
+	/// written ordered by key, so equal values always encode to the same
+	/// bytes.
+	pub fn to_msgpack(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode(self, &mut out);
+		out
+	}
+
+	/// Decodes one MessagePack object, which must span all of `bytes`.
+	///
+	/// Binary data becomes a base64url string without padding and map keys
+	/// that are numbers are converted to strings. Other non-string keys and
+	/// extension types have no JSON equivalent and are rejected.
+	pub fn from_msgpack(bytes: &[u8]) -> Result<JsonValue, DecodeError> {
+		let mut decoder = Decoder {
+			bytes,
+			offset: 0,
+			depth: 0,
+		};
+		let value = decoder.item()?;
+		if decoder.offset < bytes.len() {
+			return Err(decoder.error("trailing bytes after the object"));
+		}
+		Ok(value)
+	}
+}
+
+fn encode(value: &JsonValue, out: &mut Vec<u8>) {
+	match value {
+		JsonValue::Null => out.push(0xC0),
+		JsonValue::Boolean(false) => out.push(0xC2),
+		JsonValue::Boolean(true) => out.push(0xC3),
+		JsonValue::Number(n) => encode_number(*n, out),
+		JsonValue::String(s) => encode_str(s, out),
+		JsonValue::Array(items) => {
+			head(items.len(), 0x90, 0xDC, out);
+			for item in items {
+				encode(item, out);
+			}
+		}
+		JsonValue::Object(map) => {
+			let mut members: Vec<_> = map.iter().collect();
+			members.sort_unstable_by_key(|&(key, _)| key);
+			head(members.len(), 0x80, 0xDE, out);
+			for (key, value) in members {
+				encode_str(key, out);
+				encode(value, out);
+			}
+		}
+	}
+}
+
+fn encode_number(n: f64, out: &mut Vec<u8>) {
+	let integral = n.fract() == 0.0 && !(n == 0.0 && n.is_sign_negative());
+	if integral && (0.0..2f64.powi(64)).contains(&n) {
+		match n as u64 {
+			n @ 0..=0x7F => out.push(n as u8),
+			n @ 0..=0xFF => out.extend_from_slice(&[0xCC, n as u8]),
+			n @ 0..=0xFFFF => {
+				out.push(0xCD);
+				out.extend_from_slice(&(n as u16).to_be_bytes());
+			}
+			n @ 0..=0xFFFF_FFFF => {
+				out.push(0xCE);
+				out.extend_from_slice(&(n as u32).to_be_bytes());
+			}
+			n => {
+				out.push(0xCF);
+				out.extend_from_slice(&n.to_be_bytes());
+			}
+		}
+	} else if integral && (-(2f64.powi(63))..0.0).contains(&n) {
+		match n as i64 {
+			n @ -32..=-1 => out.push(n as u8),
+			n @ -0x80..=-1 => out.extend_from_slice(&[0xD0, n as u8]),
+			n @ -0x8000..=-1 => {
+				out.push(0xD1);
+				out.extend_from_slice(&(n as i16).to_be_bytes());
+			}
+			n @ -0x8000_0000..=-1 => {
+				out.push(0xD2);
+				out.extend_from_slice(&(n as i32).to_be_bytes());
+			}
+			n => {
+				out.push(0xD3);
+				out.extend_from_slice(&n.to_be_bytes());
+			}
+		}
+	} else if (n as f32) as f64 == n || n.is_nan() {
+		out.push(0xCA);
+		out.extend_from_slice(&(n as f32).to_be_bytes());
+	} else {
+		out.push(0xCB);
+		out.extend_from_slice(&n.to_be_bytes());
+	}
+}
+
+fn encode_str(s: &str, out: &mut Vec<u8>) {
+	match s.len() {
+		len @ 0..=31 => out.push(0xA0 | len as u8),
+		len @ 32..=0xFF => out.extend_from_slice(&[0xD9, len as u8]),
+		len @ 0x100..=0xFFFF => {
+			out.push(0xDA);
+			out.extend_from_slice(&(len as u16).to_be_bytes());
+		}
+		len => {
+			out.push(0xDB);
+			out.extend_from_slice(&(len as u32).to_be_bytes());
+		}
+	}
+	out.extend_from_slice(s.as_bytes());
+}
+
+/// Writes the length of an array or map, given the first byte of its
+/// `fix` format and of its 16-bit one.
+fn head(len: usize, fix: u8, wide: u8, out: &mut Vec<u8>) {
+	match len {
+		0..=15 => out.push(fix | len as u8),
+		16..=0xFFFF => {
+			out.push(wide);
+			out.extend_from_slice(&(len as u16).to_be_bytes());
+		}
+		_ => {
+			out.push(wide + 1);
+			out.extend_from_slice(&(len as u32).to_be_bytes());
+		}
+	}
+}
+
+/// How deeply arrays and maps may nest, so hostile input cannot exhaust
+/// the stack.
+const MAX_DEPTH: usize = 512;
+
+struct Decoder<'b> {
+	bytes: &'b [u8],
+	offset: usize,
+	/// The number of arrays and maps being decoded.
+	depth: usize,
+}
+
+impl<'b> Decoder<'b> {
+	fn error(&self, message: &'static str) -> DecodeError {
+		DecodeError::new(self.offset, message)
+	}
+
+	fn take(&mut self, len: usize) -> Result<&'b [u8], DecodeError> {
+		let end = self
+			.offset
+			.checked_add(len)
+			.filter(|&end| end <= self.bytes.len())
+			.ok_or_else(|| self.error("unexpected end of input"))?;
+		let taken = &self.bytes[self.offset..end];
+		self.offset = end;
+		Ok(taken)
+	}
+
+	fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+		Ok(self.take(N)?.try_into().unwrap())
+	}
+
+	/// Reads a big-endian length of `width` bytes, which must leave at
+	/// least one byte per element.
+	fn len(&mut self, width: usize) -> Result<usize, DecodeError> {
+		let start = self.offset;
+		let len = self
+			.take(width)?
+			.iter()
+			.fold(0, |len, &b| len << 8 | usize::from(b));
+		if len > self.bytes.len() - self.offset {
+			return Err(DecodeError::new(
+				start,
+				"length exceeds the input",
+			));
+		}
+		Ok(len)
+	}
+
+	fn item(&mut self) -> Result<JsonValue, DecodeError> {
+		if self.depth == MAX_DEPTH {
+			return Err(self.error("nesting too deep"));
+		}
+		self.depth += 1;
+		let item = self.nested();
+		self.depth -= 1;
+		item
+	}
+
+	fn nested(&mut self) -> Result<JsonValue, DecodeError> {
+		let start = self.offset;
+		let format = self.take(1)?[0];
+		let int = |n: i64| JsonValue::Number(n as f64);
+		Ok(match format {
+			0x00..=0x7F => int(format.into()),
+			0xE0..=0xFF => int((format as i8).into()),
+			0xC0 => JsonValue::Null,
+			0xC2 => JsonValue::Boolean(false),
+			0xC3 => JsonValue::Boolean(true),
+			0xCC => int(self.take(1)?[0].into()),
+			0xCD => int(u16::from_be_bytes(self.array()?).into()),
+			0xCE => int(u32::from_be_bytes(self.array()?).into()),
+			0xCF => JsonValue::Number(u64::from_be_bytes(self.array()?) as f64),
+			0xD0 => int((self.take(1)?[0] as i8).into()),
+			0xD1 => int(i16::from_be_bytes(self.array()?).into()),
+			0xD2 => int(i32::from_be_bytes(self.array()?).into()),
+			0xD3 => int(i64::from_be_bytes(self.array()?)),
+			0xCA => JsonValue::Number(f32::from_be_bytes(self.array()?).into()),
+			0xCB => JsonValue::Number(f64::from_be_bytes(self.array()?)),
+			0xA0..=0xBF | 0xD9..=0xDB => {
+				let len = match format {
+					0xD9 => self.len(1)?,
+					0xDA => self.len(2)?,
+					0xDB => self.len(4)?,
+					_ => usize::from(format & 0x1F),
+				};
+				let bytes = self.take(len)?;
+				let string = std::str::from_utf8(bytes).map_err(|_| {
+					DecodeError::new(start, "string is not UTF-8")
+				})?;
+				JsonValue::String(string.to_owned())
+			}
+			0xC4..=0xC6 => {
+				let len = self.len(1 << (format - 0xC4))?;
+				JsonValue::String(base64url(self.take(len)?))
+			}
+			0x90..=0x9F | 0xDC | 0xDD => {
+				let len = match format {
+					0xDC => self.len(2)?,
+					0xDD => self.len(4)?,
+					_ => usize::from(format & 0x0F),
+				};
+				let mut items = Vec::with_capacity(len);
+				for _ in 0..len {
+					items.push(self.item()?);
+				}
+				JsonValue::Array(items)
+			}
+			0x80..=0x8F | 0xDE | 0xDF => {
+				let len = match format {
+					0xDE => self.len(2)?,
+					0xDF => self.len(4)?,
+					_ => usize::from(format & 0x0F),
+				};
+				let mut map = HashMap::with_capacity(len);
+				for _ in 0..len {
+					let key = self.key()?;
+					map.insert(key, self.item()?);
+				}
+				JsonValue::Object(Box::new(map))
+			}
+			0xC7..=0xC9 | 0xD4..=0xD8 => {
+				return Err(DecodeError::new(
+					start,
+					"unsupported extension type",
+				))
+			}
+			_ => {
+				return Err(DecodeError::new(
+					start,
+					"invalid format byte",
+				))
+			}
+		})
+	}
+
+	fn key(&mut self) -> Result<String, DecodeError> {
+		let start = self.offset;
+		match self.item()? {
+			JsonValue::String(key) => Ok(key),
+			JsonValue::Number(n) => Ok(JsonValue::Number(n).to_string()),
+			_ => Err(DecodeError::new(
+				start,
+				"map key is not a string or number",
+			)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn decode(bytes: &[u8]) -> Result<JsonValue, DecodeError> {
+		JsonValue::from_msgpack(bytes)
+	}
+
+	#[test]
+	fn round_trips() {
+		let value = parse(
+			r#"{"null": null, "bools": [true, false], "ints": [0, 127, 128,
+			-32, -33, 65535, -2147483648, 4294967296, 18446744073709549568],
+			"floats": [1.5, -0.0, 0.1, 1e300], "text": ["", "ü€😀"],
+			"nested": {"a": [[], {}]}}"#,
+		)
+		.unwrap();
+		let bytes = value.to_msgpack();
+		assert_eq!(decode(&bytes), Ok(value.clone()));
+		let long = JsonValue::from("x".repeat(70_000));
+		assert_eq!(decode(&long.to_msgpack()), Ok(long));
+		let items = JsonValue::Array(vec![JsonValue::Null; 70_000]);
+		assert_eq!(decode(&items.to_msgpack()), Ok(items));
+	}
+
+	#[test]
+	fn decodes_each_format() {
+		let cases: &[(&[u8], &str)] = &[
+			(
+				&[0x92, 0x01, 0x92, 0x02, 0x03],
+				"[1, [2, 3]]",
+			),
+			(
+				&[0x81, 0xA1, b'a', 0xFF],
+				r#"{"a": -1}"#,
+			),
+			(&[0x81, 0x07, 0xC0], r#"{"7": null}"#),
+			(&[0xDC, 0x00, 0x01, 0xC3], "[true]"),
+			(&[0xD0, 0x80], "-128"),
+			(&[0xCD, 0x01, 0x00], "256"),
+			(&[0xCA, 0x3F, 0xC0, 0x00, 0x00], "1.5"),
+			(&[0xD9, 0x01, b'z'], r#""z""#),
+			(&[0xC4, 0x02, 0xFF, 0xEE], r#""_-4""#),
+		];
+		for (bytes, expected) in cases {
+			assert_eq!(
+				decode(bytes),
+				Ok(parse(expected).unwrap()),
+				"{bytes:02X?}"
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_malformed_input() {
+		let error = |bytes: &[u8]| decode(bytes).unwrap_err();
+		for huge in [
+			&[0xDB, 0xFF, 0xFF, 0xFF, 0xFF][..],
+			&[0xDD, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0],
+			&[0xDF, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0],
+			&[0xC6, 0x7F, 0xFF, 0xFF, 0xFF],
+		] {
+			assert_eq!(
+				error(huge).message(),
+				"length exceeds the input"
+			);
+		}
+		assert_eq!(
+			error(&[0xA3, b'a']).message(),
+			"unexpected end of input"
+		);
+		assert_eq!(
+			error(&[0xCB, 0x00]).message(),
+			"unexpected end of input"
+		);
+		assert_eq!(
+			error(&[0xC0, 0xC0]).message(),
+			"trailing bytes after the object"
+		);
+		assert_eq!(
+			error(&[0xA2, 0xFF, 0xFE]).message(),
+			"string is not UTF-8"
+		);
+		assert_eq!(
+			error(&[0x81, 0x90, 0xC0]).message(),
+			"map key is not a string or number"
+		);
+		assert_eq!(
+			error(&[0xD4, 0x01, 0x00]).message(),
+			"unsupported extension type"
+		);
+		assert_eq!(
+			error(&[0xC1]).message(),
+			"invalid format byte"
+		);
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		for open in [&[0x91][..], &[0xDC, 0x00, 0x01], &[0x81, 0xA1, b'k']] {
+			let mut bytes = open.repeat(100_000);
+			bytes.push(0xC0);
+			assert_eq!(
+				decode(&bytes).unwrap_err().message(),
+				"nesting too deep"
+			);
+		}
+		let mut bytes = vec![0x91; MAX_DEPTH - 1];
+		bytes.push(0xC0);
+		assert!(decode(&bytes).is_ok());
+	}
+}
@@ -0,0 +1,142 @@
+use std::fmt::{self, Write};
+
+use crate::{
+	ser::{write_number, write_string},
+	JsonValue,
+};
+
+/// A JSON value whose objects keep every member in document order,
+/// duplicate keys included.
+///
+/// Built by [`ParseOptions::parse_multimap`](crate::ParseOptions::parse_multimap),
+/// for tools that need to see what a [`JsonValue`] would silently drop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultiValue {
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<MultiValue>),
+	Object(Vec<(String, MultiValue)>),
+}
+
+impl MultiValue {
+	/// Looks up the last occurrence of `key`, the one [`parse`](crate::parse)
+	/// keeps.
+	pub fn get(&self, key: &str) -> Option<&MultiValue> {
+		match self {
+			MultiValue::Object(members) => members
+				.iter()
+				.rev()
+				.find(|(k, _)| k == key)
+				.map(|(_, value)| value),
+			_ => None,
+		}
+	}
+
+	/// Every value stored under `key`, in document order.
+	///
+	/// Empty if this is not an object.
+	pub fn get_all<'a>(
+		&'a self,
+		key: &'a str,
+	) -> impl Iterator<Item = &'a MultiValue> + 'a {
+		let members = match self {
+			MultiValue::Object(members) => members.as_slice(),
+			_ => &[],
+		};
+		members
+			.iter()
+			.filter(move |(k, _)| k == key)
+			.map(|(_, value)| value)
+	}
+
+	/// The keys occurring more than once in this object, each listed once in
+	/// order of first appearance.
+	pub fn duplicate_keys(&self) -> Vec<&str> {
+		let MultiValue::Object(members) = self else {
+			return Vec::new();
+		};
+		let mut duplicates = Vec::new();
+		for (i, (key, _)) in members.iter().enumerate() {
+			if !duplicates.contains(&key.as_str())
+				&& members[i + 1..].iter().any(|(k, _)| k == key)
+			{
+				duplicates.push(key.as_str());
+			}
+		}
+		duplicates
+	}
+}
+
+/// Collapses duplicate keys, keeping the last value like [`parse`](crate::parse).
+impl From<MultiValue> for JsonValue {
+	fn from(value: MultiValue) -> Self {
+		match value {
+			MultiValue::Null => JsonValue::Null,
+			MultiValue::Boolean(b) => JsonValue::Boolean(b),
+			MultiValue::Number(n) => JsonValue::Number(n),
+			MultiValue::String(s) => JsonValue::String(s),
+			MultiValue::Array(items) => {
+				JsonValue::Array(items.into_iter().map(Into::into).collect())
+			}
+			MultiValue::Object(members) => members
+				.into_iter()
+				.map(|(key, value)| (key, value.into()))
+				.collect(),
+		}
+	}
+}
+
+/// Object members come out in the map's iteration order.
+impl From<JsonValue> for MultiValue {
+	fn from(value: JsonValue) -> Self {
+		match value {
+			JsonValue::Null => MultiValue::Null,
+			JsonValue::Boolean(b) => MultiValue::Boolean(b),
+			JsonValue::Number(n) => MultiValue::Number(n),
+			JsonValue::String(s) => MultiValue::String(s),
+			JsonValue::Array(items) => {
+				MultiValue::Array(items.into_iter().map(Into::into).collect())
+			}
+			JsonValue::Object(map) => MultiValue::Object(
+				map.into_iter()
+					.map(|(key, value)| (key, value.into()))
+					.collect(),
+			),
+		}
+	}
+}
+
+/// Formats the value as compact JSON, repeating duplicate keys.
+impl fmt::Display for MultiValue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MultiValue::Null => f.write_str("null"),
+			MultiValue::Boolean(b) => write!(f, "{b}"),
+			MultiValue::Number(n) => write_number(f, *n),
+			MultiValue::String(s) => write_string(f, s),
+			MultiValue::Array(items) => {
+				f.write_char('[')?;
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{item}")?;
+				}
+				f.write_char(']')
+			}
+			MultiValue::Object(members) => {
+				f.write_char('{')?;
+				for (i, (key, value)) in members.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{value}")?;
+				}
+				f.write_char('}')
+			}
+		}
+	}
+}
@@ -0,0 +1,79 @@
+//! [Newline-delimited JSON](https://github.com/ndjson/ndjson-spec): a
+//! document on every line.
+//!
+//! Blank lines are skipped, and errors are located within the whole input
+//! rather than the line they occur on.
+
+use crate::{parallel::par_map, Error, JsonValue, Parser};
+
+/// Parses each line of `input`, in order.
+pub fn parse(
+	input: &str,
+) -> impl Iterator<Item = Result<JsonValue, Error>> + '_ {
+	let mut parser = Parser::new();
+	lines(input)
+		.map(move |(start, line)| parse_line(&mut parser, input, start, line))
+}
+
+/// Like [`parse`], but shares the lines out among as many threads as there
+/// are cores, collecting every result in input order.
+pub fn par_parse(input: &str) -> Vec<Result<JsonValue, Error>> {
+	let lines: Vec<_> = lines(input).collect();
+	par_map(&lines, 0, |parser, &(start, line)| {
+		parse_line(parser, input, start, line)
+	})
+}
+
+/// The non-blank lines of `input`, each with its offset.
+fn lines(input: &str) -> impl Iterator<Item = (usize, &str)> {
+	input
+		.split('\n')
+		.scan(0, |start, line| {
+			let at = *start;
+			*start += line.len() + 1;
+			Some((at, line))
+		})
+		.filter(|(_, line)| {
+			!line.bytes().all(|b| matches!(b, b' ' | b'\t' | b'\r'))
+		})
+}
+
+fn parse_line(
+	parser: &mut Parser,
+	input: &str,
+	start: usize,
+	line: &str,
+) -> Result<JsonValue, Error> {
+	parser.parse(line).map_err(|e| {
+		Error::new(
+			input,
+			start + e.offset(),
+			e.message().to_owned(),
+		)
+		.with_kind(e.kind())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ErrorKind;
+
+	#[test]
+	fn deep_lines_fail_without_overflowing() {
+		let deep = "[".repeat(100_000);
+		let input = format!("1\n{deep}\n{{\"a\": 2}}\n");
+		for results in [parse(&input).collect(), par_parse(&input)] {
+			let results: Vec<_> = results;
+			assert_eq!(results.len(), 3);
+			assert!(results[0].is_ok() && results[2].is_ok());
+			let error = results[1].as_ref().unwrap_err();
+			assert_eq!(error.kind(), ErrorKind::TooDeep);
+			assert_eq!(
+				error.offset(),
+				2 + crate::ParseOptions::DEFAULT_MAX_DEPTH
+			);
+			assert_eq!(error.line(), 2);
+		}
+	}
+}
@@ -0,0 +1,1152 @@
+use std::{
+	borrow::Cow,
+	collections::{HashMap, HashSet},
+	fmt,
+	str::FromStr,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+};
+
+use winnow::{
+	combinator::{
+		cut_err, delimited, eof, preceded, separated, terminated, trace,
+	},
+	error::ErrMode,
+	prelude::*,
+	stream::Stream,
+	PResult, Stateful,
+};
+
+use crate::{
+	error::{ContextTrail, ErrorKind},
+	parser::{number, raw_string, scalar, skip_nested, ws},
+	Error, JsonPointer, JsonValue, MultiValue,
+};
+
+type KeyFilter = dyn Fn(&JsonPointer, &str) -> bool + Send + Sync;
+type KeyNormalizer = dyn Fn(&str) -> String + Send + Sync;
+type Progress = dyn Fn(usize, usize) + Send + Sync;
+
+/// Settings for [`ParseOptions::parse`], for when [`parse`](crate::parse)'s
+/// defaults won't do.
+pub struct ParseOptions {
+	key_filter: Option<Box<KeyFilter>>,
+	key_normalizer: Option<Box<KeyNormalizer>>,
+	overflow: Overflow,
+	number_mode: NumberMode,
+	duplicate_keys: DuplicateKeys,
+	max_depth: usize,
+	budget: Budget,
+	progress: Option<(usize, Box<Progress>)>,
+	cancel: Option<Arc<AtomicBool>>,
+	unwrap_jsonp: bool,
+}
+
+/// Counters describing one parse, from [`ParseOptions::parse_with_metrics`],
+/// for tuning limits and finding out what makes a payload slow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+	/// How far into the input parsing got: all of it, unless it failed.
+	pub bytes: usize,
+	/// Values built into the result; skipped values are not counted.
+	pub nodes: usize,
+	/// Strings and keys copied into the result, or created by the
+	/// [key normalizer](ParseOptions::key_normalizer).
+	pub strings_allocated: usize,
+	/// Keys that were only examined in place, because their member was
+	/// dropped.
+	pub strings_borrowed: usize,
+	/// The deepest nesting of arrays and objects, counted like
+	/// [`ParseOptions::max_depth`].
+	pub max_depth: usize,
+}
+
+/// Limits on how much a parse may build, for [`ParseOptions::budget`].
+///
+/// The default is no limit at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+	/// Values in the result, counting arrays, objects and scalars alike.
+	pub nodes: usize,
+	/// Bytes of string values and keys in the result, once unescaped.
+	pub string_bytes: usize,
+}
+
+impl Default for Budget {
+	fn default() -> Self {
+		Self {
+			nodes: usize::MAX,
+			string_bytes: usize::MAX,
+		}
+	}
+}
+
+/// What to do with a number literal too large for an `f64`, like `1e999`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+	/// Store it as an infinity, as [`parse`](crate::parse) does. Infinities
+	/// are written back out as `null`.
+	#[default]
+	Infinity,
+	/// Store `f64::MAX`, or `f64::MIN` for negative numbers.
+	Clamp,
+	/// Fail with an `invalid number` error.
+	Error,
+}
+
+/// Whether [`ParseOptions`] keeps number literals as they are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberMode {
+	/// Only as the nearest `f64`, as [`parse`](crate::parse) does.
+	#[default]
+	Float,
+	/// As the nearest `f64` in the value, and also as written, in the
+	/// [`NumberLiterals`] that [`ParseOptions::parse_with_literals`]
+	/// returns, for reading into a type with more precision or range than
+	/// an `f64`, such as a decimal type, without rounding through one. The
+	/// literals count against [`Budget::string_bytes`].
+	Literal,
+}
+
+/// The number literals of a document as written, by location, kept by
+/// [`NumberMode::Literal`] and returned by
+/// [`ParseOptions::parse_with_literals`].
+///
+/// ```text
+/// let options = ParseOptions::new().number_mode(NumberMode::Literal);
+/// let (value, literals) = options.parse_with_literals(input)?;
+/// let price: Decimal = literals.number_as(&pointer).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NumberLiterals {
+	literals: HashMap<JsonPointer, String>,
+}
+
+impl NumberLiterals {
+	/// The literal of the number at `pointer`, exactly as written.
+	pub fn get(&self, pointer: &JsonPointer) -> Option<&str> {
+		self.literals.get(pointer).map(String::as_str)
+	}
+
+	/// Reads the number at `pointer` from its literal as a `T`, or `None`
+	/// if there is no number there or `T` does not accept it.
+	pub fn number_as<T: FromStr>(&self, pointer: &JsonPointer) -> Option<T> {
+		self.get(pointer)?.parse().ok()
+	}
+
+	/// Every location and literal, in no particular order.
+	pub fn iter(&self) -> impl Iterator<Item = (&JsonPointer, &str)> {
+		self.literals
+			.iter()
+			.map(|(pointer, literal)| (pointer, literal.as_str()))
+	}
+
+	pub fn len(&self) -> usize {
+		self.literals.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.literals.is_empty()
+	}
+}
+
+/// What to do when an object has the same key more than once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+	/// Keep the last value, as [`parse`](crate::parse) does.
+	#[default]
+	Last,
+	/// Keep the first value, skipping over later ones.
+	First,
+	/// Fail with a `duplicate key` error pointing at the repeated key.
+	Error,
+}
+
+impl fmt::Debug for ParseOptions {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ParseOptions")
+			.field("key_filter", &self.key_filter.is_some())
+			.field(
+				"key_normalizer",
+				&self.key_normalizer.is_some(),
+			)
+			.field("overflow", &self.overflow)
+			.field("number_mode", &self.number_mode)
+			.field("duplicate_keys", &self.duplicate_keys)
+			.field("max_depth", &self.max_depth)
+			.field("budget", &self.budget)
+			.field(
+				"progress",
+				&self.progress.as_ref().map(|(every, _)| every),
+			)
+			.field("cancel", &self.cancel)
+			.field("unwrap_jsonp", &self.unwrap_jsonp)
+			.finish()
+	}
+}
+
+impl Default for ParseOptions {
+	fn default() -> Self {
+		Self {
+			key_filter: None,
+			key_normalizer: None,
+			overflow: Overflow::default(),
+			number_mode: NumberMode::default(),
+			duplicate_keys: DuplicateKeys::default(),
+			max_depth: Self::DEFAULT_MAX_DEPTH,
+			budget: Budget::default(),
+			progress: None,
+			cancel: None,
+			unwrap_jsonp: false,
+		}
+	}
+}
+
+impl ParseOptions {
+	/// How deeply arrays and objects may nest unless
+	/// [`ParseOptions::max_depth`] says otherwise.
+	pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Drops every object member for which `filter`, given the location of
+	/// the object and the member's key, returns `false`. Dropped values are
+	/// skipped over without being built.
+	pub fn key_filter(
+		mut self,
+		filter: impl Fn(&JsonPointer, &str) -> bool + Send + Sync + 'static,
+	) -> Self {
+		self.key_filter = Some(Box::new(filter));
+		self
+	}
+
+	/// Rewrites every object key with `normalize` as it is parsed, before
+	/// the [key filter](ParseOptions::key_filter) sees it.
+	///
+	/// Useful for producers that are inconsistent about key spelling, e.g.
+	/// `str::to_ascii_lowercase`, or Unicode NFC normalization from a crate
+	/// such as `unicode-normalization`. Keys that normalize to the same
+	/// string are treated as [duplicates](ParseOptions::duplicate_keys).
+	pub fn key_normalizer(
+		mut self,
+		normalize: impl Fn(&str) -> String + Send + Sync + 'static,
+	) -> Self {
+		self.key_normalizer = Some(Box::new(normalize));
+		self
+	}
+
+	/// Chooses how number literals beyond the range of an `f64` are
+	/// handled.
+	pub fn overflow(mut self, overflow: Overflow) -> Self {
+		self.overflow = overflow;
+		self
+	}
+
+	/// Chooses whether number literals are kept as written.
+	pub fn number_mode(mut self, mode: NumberMode) -> Self {
+		self.number_mode = mode;
+		self
+	}
+
+	/// Chooses what happens to repeated keys in an object.
+	///
+	/// [`ParseOptions::parse_multimap`] keeps every occurrence unless this is
+	/// [`DuplicateKeys::Error`].
+	pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+		self.duplicate_keys = policy;
+		self
+	}
+
+	/// Fails on arrays and objects nested more than `depth` levels deep,
+	/// counting the outermost one as the first level, so hostile documents
+	/// cannot recurse without bound. The default is
+	/// [`ParseOptions::DEFAULT_MAX_DEPTH`].
+	///
+	/// Values are built recursively, so a limit in the thousands can
+	/// overflow the stack of a thread with little of it.
+	pub fn max_depth(mut self, depth: usize) -> Self {
+		self.max_depth = depth;
+		self
+	}
+
+	/// Fails with a `budget exceeded` error once the result would hold more
+	/// values or string bytes than `budget` allows, so a small payload such
+	/// as a huge array of empty objects cannot make the parse allocate far
+	/// more than the caller is prepared for. Skipped values cost nothing.
+	pub fn budget(mut self, budget: Budget) -> Self {
+		self.budget = budget;
+		self
+	}
+
+	/// Calls `callback` with the number of bytes parsed so far and the
+	/// length of the input each time roughly another `every` bytes have
+	/// been parsed, and once more when a parse succeeds, to drive a
+	/// progress bar or keep a watchdog quiet through a huge document.
+	pub fn progress(
+		mut self,
+		every: usize,
+		callback: impl Fn(usize, usize) + Send + Sync + 'static,
+	) -> Self {
+		self.progress = Some((every.max(1), Box::new(callback)));
+		self
+	}
+
+	/// Makes parsing fail with a `parse cancelled` error soon after `token`
+	/// is set, so another thread can abandon a parse that is taking too
+	/// long without waiting for it to finish.
+	pub fn cancel_on(mut self, token: Arc<AtomicBool>) -> Self {
+		self.cancel = Some(token);
+		self
+	}
+
+	/// Accepts a document wrapped in a JSONP callback, `callback(...);`, or
+	/// behind an anti-XSSI prefix such as `)]}',`, `while(1);` or
+	/// `for(;;);`, as legacy endpoints still send, parsing what is inside.
+	/// Documents without either parse as usual, and errors point into the
+	/// whole input.
+	pub fn unwrap_jsonp(mut self, unwrap: bool) -> Self {
+		self.unwrap_jsonp = unwrap;
+		self
+	}
+
+	/// Parses a complete JSON document with these options.
+	pub fn parse(&self, input: &str) -> Result<JsonValue, Error> {
+		self.parse_into(input).0.map(|(value, _)| value)
+	}
+
+	/// Like [`ParseOptions::parse`], also returning the number literals as
+	/// written when [`ParseOptions::number_mode`] is
+	/// [`NumberMode::Literal`]; otherwise they are empty.
+	pub fn parse_with_literals(
+		&self,
+		input: &str,
+	) -> Result<(JsonValue, NumberLiterals), Error> {
+		self.parse_into(input).0
+	}
+
+	/// Like [`ParseOptions::parse`], but keeps every object member, including
+	/// duplicate keys, in document order.
+	pub fn parse_multimap(&self, input: &str) -> Result<MultiValue, Error> {
+		self.parse_into(input).0.map(|(value, _)| value)
+	}
+
+	/// Like [`ParseOptions::parse`], also returning what the parse did,
+	/// whether or not it succeeded.
+	pub fn parse_with_metrics(
+		&self,
+		input: &str,
+	) -> (Result<JsonValue, Error>, Metrics) {
+		let (parsed, metrics) = self.parse_into(input);
+		(parsed.map(|(value, _)| value), metrics)
+	}
+
+	fn parse_into<N: Node>(
+		&self,
+		input: &str,
+	) -> (
+		Result<(N, NumberLiterals), Error>,
+		Metrics,
+	) {
+		let (offset, body) = if self.unwrap_jsonp {
+			jsonp_body(input)
+		} else {
+			(0, input)
+		};
+		let mut stream = Stateful {
+			input: body,
+			state: Session {
+				options: self,
+				path: JsonPointer::root(),
+				len: body.len(),
+				next_report: self
+					.progress
+					.as_ref()
+					.map_or(0, |&(every, _)| every),
+				metrics: Metrics::default(),
+				spent: Budget {
+					nodes: 0,
+					string_bytes: 0,
+				},
+				literals: NumberLiterals::default(),
+			},
+		};
+		let parsed =
+			terminated(delimited(ws, value, ws), eof).parse_next(&mut stream);
+		let mut metrics = stream.state.metrics;
+		metrics.bytes = offset + body.len() - stream.input.len();
+		let parsed = match parsed {
+			Ok(parsed) => {
+				metrics.bytes = input.len();
+				parsed
+			}
+			Err(e) => {
+				let trail = e.into_inner().unwrap_or_default();
+				let error = Error::from_trail(input, metrics.bytes, &trail);
+				return (Err(error), metrics);
+			}
+		};
+		if let Some((_, callback)) = &self.progress {
+			callback(body.len(), body.len());
+		}
+		(
+			Ok((parsed, stream.state.literals)),
+			metrics,
+		)
+	}
+}
+
+/// Finds the document inside a JSONP callback and after an anti-XSSI
+/// prefix, along with its offset in `input`.
+fn jsonp_body(input: &str) -> (usize, &str) {
+	let mut rest = input.trim_start();
+	for prefix in [")]}',", ")]}'", "while(1);", "for(;;);"] {
+		if let Some(after) = rest.strip_prefix(prefix) {
+			rest = after.trim_start();
+			break;
+		}
+	}
+	// Callbacks are often preceded by an empty comment to defuse content
+	// sniffing.
+	rest = rest.strip_prefix("/**/").unwrap_or(rest).trim_start();
+	let callback = rest
+		.find(|c: char| {
+			!(c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'))
+		})
+		.unwrap_or(rest.len());
+	if callback == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+		return (input.len() - rest.len(), rest);
+	}
+	let Some(args) = rest[callback..].trim_start().strip_prefix('(') else {
+		return (input.len() - rest.len(), rest);
+	};
+	let end = args.trim_end();
+	let end = end.strip_suffix(';').unwrap_or(end).trim_end();
+	match end.strip_suffix(')') {
+		Some(body) => (input.len() - args.len(), body),
+		None => (input.len() - rest.len(), rest),
+	}
+}
+
+/// A tree a [`Session`] can build.
+trait Node: Sized {
+	type Members: Default;
+
+	fn scalar(value: JsonValue) -> Self;
+	fn array(items: Vec<Self>) -> Self;
+	fn object(members: Self::Members) -> Self;
+	fn insert(members: &mut Self::Members, key: String, value: Self);
+	fn contains(members: &mut Self::Members, key: &str) -> bool;
+
+	/// Whether objects keep every occurrence of a key.
+	const KEEPS_DUPLICATES: bool;
+}
+
+impl Node for JsonValue {
+	type Members = HashMap<String, JsonValue>;
+
+	fn scalar(value: JsonValue) -> Self {
+		value
+	}
+
+	fn array(items: Vec<Self>) -> Self {
+		JsonValue::Array(items)
+	}
+
+	fn object(members: Self::Members) -> Self {
+		JsonValue::Object(Box::new(members))
+	}
+
+	fn insert(members: &mut Self::Members, key: String, value: Self) {
+		members.insert(key, value);
+	}
+
+	fn contains(members: &mut Self::Members, key: &str) -> bool {
+		members.contains_key(key)
+	}
+
+	const KEEPS_DUPLICATES: bool = false;
+}
+
+/// The members of an object being built as a [`MultiValue`], in order.
+#[derive(Default)]
+struct Entries {
+	members: Vec<(String, MultiValue)>,
+	/// The keys of the first `indexed` members, gathered only once a key is
+	/// looked up so that duplicates are found without a scan.
+	keys: HashSet<String>,
+	indexed: usize,
+}
+
+impl Node for MultiValue {
+	type Members = Entries;
+
+	fn scalar(value: JsonValue) -> Self {
+		value.into()
+	}
+
+	fn array(items: Vec<Self>) -> Self {
+		MultiValue::Array(items)
+	}
+
+	fn object(entries: Self::Members) -> Self {
+		MultiValue::Object(entries.members)
+	}
+
+	fn insert(entries: &mut Self::Members, key: String, value: Self) {
+		entries.members.push((key, value));
+	}
+
+	fn contains(entries: &mut Self::Members, key: &str) -> bool {
+		for (key, _) in &entries.members[entries.indexed..] {
+			entries.keys.insert(key.clone());
+		}
+		entries.indexed = entries.members.len();
+		entries.keys.contains(key)
+	}
+
+	const KEEPS_DUPLICATES: bool = true;
+}
+
+/// The state of one [`ParseOptions::parse`] call, carried alongside the
+/// input.
+#[derive(Debug, Clone)]
+struct Session<'o> {
+	options: &'o ParseOptions,
+	/// The location of the value being parsed.
+	path: JsonPointer,
+	/// The length of the whole input.
+	len: usize,
+	/// How far into the input the next progress report is due.
+	next_report: usize,
+	metrics: Metrics,
+	/// How much of the [budget](ParseOptions::budget) has been used.
+	spent: Budget,
+	literals: NumberLiterals,
+}
+
+impl Session<'_> {
+	fn keep(&self, key: &str) -> bool {
+		self.options
+			.key_filter
+			.as_ref()
+			.is_none_or(|filter| filter(&self.path, key))
+	}
+
+	/// Counts a key, which is borrowed unless it has been copied into the
+	/// result or replaced by the normalizer.
+	fn count_key(&mut self, allocated: bool) {
+		if allocated {
+			self.metrics.strings_allocated += 1;
+		} else {
+			self.metrics.strings_borrowed += 1;
+		}
+	}
+
+	/// Counts a value about to be built against the budget.
+	fn spend_node(&mut self) -> PResult<(), ContextTrail> {
+		self.spent.nodes += 1;
+		let max = self.options.budget.nodes;
+		if self.spent.nodes > max {
+			return Err(ErrMode::Cut(ContextTrail::custom(
+				ErrorKind::BudgetExceeded,
+				format!("budget exceeded: more than {max} values"),
+			)));
+		}
+		Ok(())
+	}
+
+	/// Counts the bytes of a string or key put in the result against the
+	/// budget.
+	fn spend_string(&mut self, bytes: usize) -> PResult<(), ContextTrail> {
+		self.spent.string_bytes = self.spent.string_bytes.saturating_add(bytes);
+		let max = self.options.budget.string_bytes;
+		if self.spent.string_bytes > max {
+			return Err(ErrMode::Cut(ContextTrail::custom(
+				ErrorKind::BudgetExceeded,
+				format!("budget exceeded: more than {max} bytes of strings"),
+			)));
+		}
+		Ok(())
+	}
+
+	/// Reports progress if it is due, given how much input is left.
+	fn report(&mut self, remaining: usize) {
+		let Some((every, callback)) = &self.options.progress else {
+			return;
+		};
+		let parsed = self.len - remaining;
+		if parsed >= self.next_report {
+			callback(parsed, self.len);
+			self.next_report = parsed + every;
+		}
+	}
+}
+
+type Input<'i, 'o> = Stateful<&'i str, Session<'o>>;
+
+fn value<N: Node>(input: &mut Input<'_, '_>) -> PResult<N, ContextTrail> {
+	let remaining = input.input.len();
+	input.state.report(remaining);
+	let options = input.state.options;
+	if let Some(token) = &options.cancel {
+		if token.load(Ordering::Relaxed) {
+			return Err(ErrMode::Cut(ContextTrail::custom(
+				ErrorKind::Cancelled,
+				"parse cancelled",
+			)));
+		}
+	}
+	input.state.spend_node()?;
+	let next = input.chars().next();
+	if let Some('{' | '[') = next {
+		let depth = input.state.path.tokens().len();
+		if depth >= options.max_depth {
+			return Err(too_deep(options.max_depth));
+		}
+		let metrics = &mut input.state.metrics;
+		metrics.max_depth = metrics.max_depth.max(depth + 1);
+	}
+	let parsed = match next {
+		Some('{') => object::<N>(input).map(N::object),
+		Some('[') => array(input).map(N::array),
+		Some('-' | '0'..='9') => {
+			let start = input.checkpoint();
+			let overflow = options.overflow;
+			let (n, literal) =
+				cut_err(
+					number.verify_map(move |n: f64| match overflow {
+						_ if n.is_finite() => Some(n),
+						Overflow::Infinity => Some(n),
+						Overflow::Clamp => Some(f64::MAX.copysign(n)),
+						Overflow::Error => None,
+					}),
+				)
+				.context("number")
+				.with_recognized()
+				.parse_next(input)?;
+			if options.number_mode == NumberMode::Literal {
+				input.state.metrics.strings_allocated += 1;
+				if let Err(e) = input.state.spend_string(literal.len()) {
+					input.reset(start);
+					return Err(e);
+				}
+				let path = input.state.path.clone();
+				input
+					.state
+					.literals
+					.literals
+					.insert(path, literal.to_owned());
+			}
+			Ok(N::scalar(JsonValue::Number(n)))
+		}
+		_ => {
+			let start = input.checkpoint();
+			let value = scalar.parse_next(input)?;
+			if let JsonValue::String(s) = &value {
+				input.state.metrics.strings_allocated += 1;
+				if let Err(e) = input.state.spend_string(s.len()) {
+					input.reset(start);
+					return Err(e);
+				}
+			}
+			Ok(N::scalar(value))
+		}
+	}?;
+	input.state.metrics.nodes += 1;
+	Ok(parsed)
+}
+
+/// Skips the value of a member that is not kept, which may still nest no
+/// deeper than [`ParseOptions::max_depth`] allows.
+fn skip_member(input: &mut Input<'_, '_>) -> PResult<(), ContextTrail> {
+	let max = input.state.options.max_depth;
+	// The value is a level below the object.
+	let depth = input.state.path.tokens().len() + 1;
+	match skip_nested(input, max.saturating_sub(depth))? {
+		true => Ok(()),
+		false => Err(too_deep(max)),
+	}
+}
+
+pub(crate) fn too_deep(max: usize) -> ErrMode<ContextTrail> {
+	ErrMode::Cut(ContextTrail::custom(
+		ErrorKind::TooDeep,
+		format!("nesting deeper than {max} levels"),
+	))
+}
+
+fn object<N: Node>(
+	input: &mut Input<'_, '_>,
+) -> PResult<N::Members, ContextTrail> {
+	let mut members = N::Members::default();
+	let member = |input: &mut Input<'_, '_>| {
+		let start = input.checkpoint();
+		let mut key = raw_string.parse_next(input)?;
+		let options = input.state.options;
+		if let Some(normalize) = &options.key_normalizer {
+			key = Cow::Owned(normalize(&key));
+		}
+		cut_err((ws, ':', ws)).parse_next(input)?;
+		if !input.state.keep(&key) {
+			input.state.count_key(matches!(key, Cow::Owned(_)));
+			return skip_member(input);
+		}
+		match options.duplicate_keys {
+			DuplicateKeys::Last => {}
+			DuplicateKeys::First if N::KEEPS_DUPLICATES => {}
+			policy if N::contains(&mut members, &key) => {
+				input.state.count_key(matches!(key, Cow::Owned(_)));
+				if policy == DuplicateKeys::First {
+					return skip_member(input);
+				}
+				input.reset(start);
+				return Err(ErrMode::Cut(ContextTrail::custom(
+					ErrorKind::DuplicateKey,
+					format!("duplicate key {key:?}"),
+				)));
+			}
+			_ => {}
+		}
+		input.state.count_key(true);
+		if let Err(e) = input.state.spend_string(key.len()) {
+			input.reset(start);
+			return Err(e);
+		}
+		input.state.path.push(key.as_ref());
+		let parsed = value(input);
+		input.state.path.pop();
+		N::insert(&mut members, key.into_owned(), parsed?);
+		Ok(())
+	};
+	trace(
+		"object",
+		preceded(
+			('{', ws),
+			cut_err(terminated(
+				separated::<_, _, (), _, _, _, _>(0.., member, (ws, ',', ws)),
+				(ws, '}'),
+			)),
+		)
+		.context("object"),
+	)
+	.parse_next(input)?;
+	Ok(members)
+}
+
+fn array<N: Node>(input: &mut Input<'_, '_>) -> PResult<Vec<N>, ContextTrail> {
+	let mut items = Vec::new();
+	let item = |input: &mut Input<'_, '_>| {
+		input.state.path.push(items.len().to_string());
+		let parsed = value(input);
+		input.state.path.pop();
+		items.push(parsed?);
+		Ok(())
+	};
+	trace(
+		"array",
+		preceded(
+			('[', ws),
+			cut_err(terminated(
+				separated::<_, _, (), _, _, _, _>(0.., item, (ws, ',', ws)),
+				(ws, ']'),
+			)),
+		)
+		.context("array"),
+	)
+	.parse_next(input)?;
+	Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn options_are_send_and_sync() {
+		fn assert_send_sync<T: Send + Sync>(_: &T) {}
+		let options = ParseOptions::new()
+			.key_filter(|_, _| true)
+			.key_normalizer(str::to_owned)
+			.progress(1, |_, _| {});
+		assert_send_sync(&options);
+	}
+
+	fn nested(depth: usize) -> String {
+		format!(
+			"{}{}",
+			"[".repeat(depth),
+			"]".repeat(depth)
+		)
+	}
+
+	#[test]
+	fn depth_is_limited_by_default() {
+		let input = "[".repeat(100_000);
+		let error = ParseOptions::new().parse(&input).unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::TooDeep);
+		assert_eq!(
+			error.offset(),
+			ParseOptions::DEFAULT_MAX_DEPTH
+		);
+		let limit = ParseOptions::DEFAULT_MAX_DEPTH;
+		assert!(ParseOptions::new().parse(&nested(limit)).is_ok());
+		assert!(ParseOptions::new().parse(&nested(limit + 1)).is_err());
+	}
+
+	#[test]
+	fn error_kinds() {
+		let kind = |options: ParseOptions, input: &str| {
+			options.parse(input).unwrap_err().kind()
+		};
+		let budget = Budget {
+			nodes: 2,
+			..Budget::default()
+		};
+		let error = ParseOptions::new().budget(budget).parse("[1, 2]");
+		assert!(error.unwrap_err().is_budget_exceeded());
+		let budget = Budget {
+			string_bytes: 2,
+			..Budget::default()
+		};
+		assert_eq!(
+			kind(
+				ParseOptions::new().budget(budget),
+				r#"["abc"]"#
+			),
+			ErrorKind::BudgetExceeded
+		);
+		assert_eq!(
+			kind(
+				ParseOptions::new().max_depth(2),
+				&nested(3)
+			),
+			ErrorKind::TooDeep
+		);
+		assert_eq!(
+			kind(
+				ParseOptions::new().duplicate_keys(DuplicateKeys::Error),
+				r#"{"a": 1, "a": 2}"#
+			),
+			ErrorKind::DuplicateKey
+		);
+		let cancelled = Arc::new(AtomicBool::new(true));
+		assert_eq!(
+			kind(
+				ParseOptions::new().cancel_on(cancelled),
+				"[]"
+			),
+			ErrorKind::Cancelled
+		);
+		let error = ParseOptions::new().parse("[1,]").unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::Syntax);
+		assert!(!error.is_budget_exceeded());
+	}
+
+	#[test]
+	fn skipped_values_count_against_max_depth() {
+		let input = format!(
+			r#"{{"drop": {}, "keep": 1}}"#,
+			nested(3)
+		);
+		let drop = || ParseOptions::new().key_filter(|_, key| key != "drop");
+		assert!(drop().max_depth(4).parse(&input).is_ok());
+		let error = drop().max_depth(3).parse(&input).unwrap_err();
+		assert_eq!(
+			error.message(),
+			"nesting deeper than 3 levels"
+		);
+		assert_eq!(error.offset(), 11);
+
+		let input = format!(r#"{{"a": 1, "a": {}}}"#, nested(3));
+		let first = || ParseOptions::new().duplicate_keys(DuplicateKeys::First);
+		assert!(first().max_depth(4).parse(&input).is_ok());
+		assert!(first().max_depth(3).parse(&input).is_err());
+	}
+
+	#[test]
+	fn skipping_deep_values_does_not_recurse() {
+		let input = format!(r#"{{"drop": {}}}"#, nested(1_000_000));
+		let options = ParseOptions::new()
+			.max_depth(usize::MAX)
+			.key_filter(|_, key| key != "drop");
+		assert!(options
+			.parse(&input)
+			.unwrap()
+			.as_object()
+			.unwrap()
+			.is_empty());
+	}
+
+	#[test]
+	fn skipped_values_are_checked() {
+		let options = ParseOptions::new().key_filter(|_, key| key != "drop");
+		for input in [
+			r#"{"drop": [1,]}"#,
+			r#"{"drop": {"a" 1}}"#,
+			r#"{"drop": [1 2]}"#,
+			r#"{"drop": {"a": 1]}"#,
+			r#"{"drop": [01]}"#,
+		] {
+			assert!(options.parse(input).is_err(), "{input}");
+		}
+		let input = r#"{"drop": [{"a": [true, null, "\"]"]}, {}, []], "b": 2}"#;
+		assert_eq!(
+			options.parse(input).unwrap().to_string(),
+			r#"{"b":2}"#
+		);
+	}
+
+	#[test]
+	fn literal_numbers() {
+		let options = ParseOptions::new().number_mode(NumberMode::Literal);
+		let input =
+			r#"{"price": 0.10000000000000000001, "big": [1e400], "s": "12"}"#;
+		let (value, literals) = options.parse_with_literals(input).unwrap();
+		let pointer = |s| JsonPointer::parse(s).unwrap();
+		assert_eq!(literals.len(), 2);
+		assert_eq!(
+			literals.get(&pointer("/price")),
+			Some("0.10000000000000000001")
+		);
+		assert_eq!(
+			literals.get(&pointer("/big/0")),
+			Some("1e400")
+		);
+		assert_eq!(literals.get(&pointer("/s")), None);
+		assert_eq!(
+			literals.number_as::<u8>(&pointer("/big/0")),
+			None
+		);
+		assert_eq!(
+			value.get("price"),
+			Some(&JsonValue::Number(0.1))
+		);
+		assert_eq!(
+			value.get("s"),
+			Some(&JsonValue::String("12".into()))
+		);
+		assert_eq!(
+			value.get("s").unwrap().number_as::<u8>(),
+			None
+		);
+
+		let (_, literals) =
+			ParseOptions::new().parse_with_literals(input).unwrap();
+		assert!(literals.is_empty());
+		let overflowing = options.overflow(Overflow::Error).parse(input);
+		assert!(overflowing.is_err());
+	}
+
+	#[test]
+	fn multimap_duplicates_are_found_without_a_scan() {
+		let options = ParseOptions::new().duplicate_keys(DuplicateKeys::Error);
+		let members: Vec<String> =
+			(0..100_000).map(|i| format!(r#""k{i}": {i}"#)).collect();
+		let input = format!("{{{}}}", members.join(","));
+		let MultiValue::Object(parsed) =
+			options.parse_multimap(&input).unwrap()
+		else {
+			panic!("not an object");
+		};
+		assert_eq!(parsed.len(), 100_000);
+		assert_eq!(parsed[99_999].0, "k99999");
+
+		let input = format!(r#"{{{}, "k5": 0}}"#, members.join(","));
+		let error = options.parse_multimap(&input).unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::DuplicateKey);
+		assert_eq!(error.message(), r#"duplicate key "k5""#);
+		let nested = r#"{"a": {"b": 1}, "b": {"b": 2, "b": 3}}"#;
+		let error = options.parse_multimap(nested).unwrap_err();
+		assert_eq!(
+			error.offset(),
+			nested.rfind(r#""b""#).unwrap()
+		);
+	}
+
+	#[test]
+	fn keys_are_normalized_before_filtering() {
+		let options = ParseOptions::new()
+			.key_normalizer(str::to_ascii_lowercase)
+			.key_filter(|_, key| key != "drop");
+		assert_eq!(
+			options.parse(r#"{"A": {"B": 1}, "DROP": 2}"#).unwrap(),
+			crate::parse(r#"{"a": {"b": 1}}"#).unwrap()
+		);
+		let error = options
+			.duplicate_keys(DuplicateKeys::Error)
+			.parse(r#"{"k": 1, "K": 2}"#)
+			.unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::DuplicateKey);
+	}
+
+	#[test]
+	fn overflowing_numbers_follow_the_policy() {
+		let input = "[1e400, -1e400, 1.5]";
+		let parse =
+			|overflow| ParseOptions::new().overflow(overflow).parse(input);
+		assert_eq!(
+			parse(Overflow::Infinity).unwrap(),
+			JsonValue::Array(vec![
+				f64::INFINITY.into(),
+				f64::NEG_INFINITY.into(),
+				1.5.into(),
+			])
+		);
+		assert_eq!(
+			parse(Overflow::Clamp).unwrap(),
+			JsonValue::Array(vec![
+				f64::MAX.into(),
+				f64::MIN.into(),
+				1.5.into()
+			])
+		);
+		let error = parse(Overflow::Error).unwrap_err();
+		assert_eq!(error.offset(), 1);
+		assert_eq!(error.kind(), ErrorKind::Syntax);
+		assert!(ParseOptions::new()
+			.overflow(Overflow::Error)
+			.parse("[1e308, 1e-400]")
+			.is_ok());
+	}
+
+	#[test]
+	fn progress_is_reported_as_parsing_goes() {
+		let input = format!("[{}1]", "1, ".repeat(1000));
+		let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let seen = Arc::clone(&reports);
+		ParseOptions::new()
+			.progress(500, move |parsed, len| {
+				seen.lock().unwrap().push((parsed, len))
+			})
+			.parse(&input)
+			.unwrap();
+		let reports = reports.lock().unwrap();
+		assert_eq!(
+			reports.last(),
+			Some(&(input.len(), input.len()))
+		);
+		assert!(reports.len() >= input.len() / 500);
+		assert!(reports.len() <= input.len() / 500 + 2);
+		assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+		assert!(reports.iter().all(|&(_, len)| len == input.len()));
+	}
+
+	#[test]
+	fn parses_stop_soon_after_cancellation() {
+		let input = format!("[{}1]", "1, ".repeat(1000));
+		let token = Arc::new(AtomicBool::new(false));
+		assert!(ParseOptions::new()
+			.cancel_on(Arc::clone(&token))
+			.parse(&input)
+			.is_ok());
+
+		let cancel = Arc::clone(&token);
+		let error = ParseOptions::new()
+			.cancel_on(token)
+			.progress(1000, move |parsed, _| {
+				if parsed > 0 {
+					cancel.store(true, Ordering::Relaxed);
+				}
+			})
+			.parse(&input)
+			.unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::Cancelled);
+		assert_eq!(error.message(), "parse cancelled");
+		assert!((1000..1010).contains(&error.offset()));
+	}
+
+	#[test]
+	fn metrics_describe_the_parse() {
+		let input = r#"{"a": [1, "x", {"b": null}], "drop": "y", "c\n": "z"}"#;
+		let (value, metrics) = ParseOptions::new()
+			.key_filter(|_, key| key != "drop")
+			.parse_with_metrics(input);
+		assert!(value.is_ok());
+		assert_eq!(
+			metrics,
+			Metrics {
+				bytes: input.len(),
+				nodes: 7,
+				strings_allocated: 5,
+				strings_borrowed: 1,
+				max_depth: 3,
+			}
+		);
+
+		let (value, metrics) =
+			ParseOptions::new().parse_with_metrics(r#"[[1], [2, oops]]"#);
+		assert_eq!(
+			metrics.bytes,
+			value.unwrap_err().offset()
+		);
+		assert_eq!(metrics.max_depth, 2);
+	}
+
+	#[test]
+	fn progress_and_metrics_can_instrument_a_parse() {
+		let input = r#"{"items": [1, 2, 3], "name": "n"}"#;
+		let last = Arc::new(std::sync::Mutex::new(None));
+		let report = Arc::clone(&last);
+		let options = ParseOptions::new().progress(8, move |parsed, len| {
+			*report.lock().unwrap() = Some((parsed, len))
+		});
+		let (value, metrics) = options.parse_with_metrics(input);
+		assert!(value.is_ok());
+		assert_eq!(
+			*last.lock().unwrap(),
+			Some((metrics.bytes, input.len()))
+		);
+
+		let (value, metrics) =
+			options.parse_with_metrics(r#"{"items": [1, 2,"#);
+		assert_eq!(
+			metrics.bytes,
+			value.unwrap_err().offset()
+		);
+		let (parsed, _) = last.lock().unwrap().unwrap();
+		assert!(parsed <= metrics.bytes);
+	}
+
+	#[test]
+	fn jsonp_and_xssi_wrappers_are_unwrapped() {
+		let options = ParseOptions::new().unwrap_jsonp(true);
+		let expected = crate::parse(r#"{"a": [1]}"#).unwrap();
+		for input in [
+			r#"cb({"a": [1]});"#,
+			r#" /**/ jQuery.cb_1$ ( {"a": [1]} ) ; "#,
+			r#")]}',
+{"a": [1]}"#,
+			r#")]}'{"a": [1]}"#,
+			r#"while(1);{"a": [1]}"#,
+			r#"for(;;);cb({"a": [1]})"#,
+			r#"{"a": [1]}"#,
+		] {
+			assert_eq!(
+				options.parse(input).unwrap(),
+				expected,
+				"{input}"
+			);
+		}
+		assert_eq!(
+			options.parse("true").unwrap(),
+			JsonValue::Boolean(true)
+		);
+		assert_eq!(
+			options.parse("12").unwrap(),
+			JsonValue::Number(12.0)
+		);
+
+		let error = options.parse("cb([1,]);").unwrap_err();
+		let plain = crate::parse("[1,]").unwrap_err();
+		assert_eq!(
+			error.offset(),
+			plain.offset() + "cb(".len()
+		);
+		assert!(options.parse("cb([1]").is_err());
+		assert!(ParseOptions::new().parse("cb([1])").is_err());
+	}
+}
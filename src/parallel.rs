@@ -0,0 +1,131 @@
+use std::{num::NonZeroUsize, panic, thread};
+
+use crate::{
+	parser::{skip_value, ws},
+	Error, JsonValue, Parser,
+};
+
+/// Parses a document whose top level is a large array using every core:
+/// a first pass finds where each item starts and ends without building
+/// anything, then the items are parsed in parallel.
+///
+/// Any other document is parsed by [`parse`](crate::parse) on the calling
+/// thread, and the results and errors are always the same as its. The
+/// threads only pay for themselves on inputs of several megabytes.
+pub fn par_parse(input: &str) -> Result<JsonValue, Error> {
+	let Some(items) = array_items(input) else {
+		return crate::parse(input);
+	};
+	par_map(&items, 1, |parser, &(start, item)| {
+		parser.parse(item).map_err(|e| {
+			Error::new(
+				input,
+				start + e.offset(),
+				e.message().to_owned(),
+			)
+			.with_kind(e.kind())
+		})
+	})
+	.into_iter()
+	.collect()
+}
+
+/// Finds the items of a top-level array, each with its offset, or `None` if
+/// `input` is not a well-formed array.
+fn array_items(input: &str) -> Option<Vec<(usize, &str)>> {
+	let mut rest = input;
+	let offset = |rest: &str| input.len() - rest.len();
+	let _ = ws::<_, ()>(&mut rest);
+	rest = rest.strip_prefix('[')?;
+	let _ = ws::<_, ()>(&mut rest);
+	let mut items = Vec::new();
+	if let Some(after) = rest.strip_prefix(']') {
+		rest = after;
+	} else {
+		loop {
+			let start = offset(rest);
+			skip_value::<_, ()>(&mut rest).ok()?;
+			items.push((start, &input[start..offset(rest)]));
+			let _ = ws::<_, ()>(&mut rest);
+			if let Some(after) = rest.strip_prefix(',') {
+				rest = after;
+				let _ = ws::<_, ()>(&mut rest);
+				continue;
+			}
+			rest = rest.strip_prefix(']')?;
+			break;
+		}
+	}
+	let _ = ws::<_, ()>(&mut rest);
+	rest.is_empty().then_some(items)
+}
+
+/// Applies `f` to every item on as many threads as there are cores, each
+/// with its own [`Parser`] for documents `depth` levels down, returning the
+/// results in order.
+pub(crate) fn par_map<T: Sync, R: Send>(
+	items: &[T],
+	depth: usize,
+	f: impl Fn(&mut Parser, &T) -> R + Sync,
+) -> Vec<R> {
+	let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+	let chunk = items.len().div_ceil(threads).max(1);
+	let f = &f;
+	thread::scope(|scope| {
+		let workers: Vec<_> = items
+			.chunks(chunk)
+			.map(|items| {
+				scope.spawn(move || {
+					let mut parser = Parser::nested(depth);
+					items
+						.iter()
+						.map(|item| f(&mut parser, item))
+						.collect::<Vec<_>>()
+				})
+			})
+			.collect();
+		workers
+			.into_iter()
+			.flat_map(|worker| {
+				worker.join().unwrap_or_else(|e| panic::resume_unwind(e))
+			})
+			.collect()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ErrorKind;
+
+	#[test]
+	fn deep_items_fail_like_parse() {
+		let deep = 100_000;
+		let nested = format!(
+			"{}{}",
+			"[".repeat(deep),
+			"]".repeat(deep)
+		);
+		for input in [
+			format!("[1, {nested}, 2]"),
+			format!("[{}", "{\"a\":[".repeat(deep)),
+		] {
+			let error = par_parse(&input).unwrap_err();
+			let expected = crate::parse(&input).unwrap_err();
+			assert_eq!(error.kind(), ErrorKind::TooDeep);
+			assert_eq!(
+				(error.offset(), error.message()),
+				(expected.offset(), expected.message())
+			);
+		}
+		let limit = crate::ParseOptions::DEFAULT_MAX_DEPTH - 1;
+		let item = format!(
+			"{}{}",
+			"[".repeat(limit),
+			"]".repeat(limit)
+		);
+		let input = format!("[{item}, {item}]");
+		assert_eq!(par_parse(&input), crate::parse(&input));
+		assert!(par_parse(&input).is_ok());
+	}
+}
@@ -0,0 +1,612 @@
+//! The [winnow] parsers behind [`parse`](crate::parse), for embedding JSON
+//! in other winnow grammars.
+//!
+//! They work on any [`Input`] and are generic over the error type, which
+//! must accept `&'static str` contexts: `"string"`, `"number"`, `"array"`
+//! and `"object"` are attached once parsing has committed to one of those,
+//! and failures past that point are
+//! [`ErrMode::Cut`](winnow::error::ErrMode::Cut). None of them skip leading
+//! or trailing whitespace except [`json`].
+//!
+//! Each parser is wrapped in winnow's [`trace`], so building with the `debug`
+//! feature prints a trace of every attempt to stderr, which helps when a
+//! grammar built on these misbehaves. Without the feature it costs nothing.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use winnow::{
+	combinator::{
+		alt, cut_err, delimited, opt, peek, preceded, repeat, separated,
+		separated_pair, terminated, trace,
+	},
+	error::{AddContext, ParserError},
+	prelude::*,
+	stream::{AsBStr, AsChar, Compare, ParseSlice, Stream, StreamIsPartial},
+	token::{any, none_of, one_of, take, take_till, take_while},
+	PResult,
+};
+
+use crate::{
+	lexer::{is_number, is_number_char},
+	JsonValue,
+};
+
+/// The streams the parsers read: `&str` and `&[u8]`, and winnow's wrappers
+/// around them such as `Located`, `Stateful` and `Partial`.
+///
+/// Byte input is checked to be UTF-8 inside strings, the only place where
+/// JSON allows anything but ASCII.
+pub trait Input:
+	Stream<
+		Token: AsChar + Clone,
+		Slice: AsBStr + ParseSlice<f64>,
+		IterOffsets: Clone,
+	> + StreamIsPartial
+	+ Compare<&'static str>
+	+ AsBStr
+{
+}
+
+impl<I> Input for I where I: Stream<
+			Token: AsChar + Clone,
+			Slice: AsBStr + ParseSlice<f64>,
+			IterOffsets: Clone,
+		> + StreamIsPartial
+		+ Compare<&'static str>
+		+ AsBStr
+{
+}
+
+/// Parses a complete value surrounded by optional whitespace.
+pub fn json<I, E>(input: &mut I) -> PResult<JsonValue, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace("json", delimited(ws, json_value, ws)).parse_next(input)
+}
+
+/// Parses any value.
+pub fn json_value<I, E>(input: &mut I) -> PResult<JsonValue, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"json_value",
+		alt((
+			null.value(JsonValue::Null),
+			boolean.map(JsonValue::Boolean),
+			string.map(JsonValue::String),
+			number.map(JsonValue::Number),
+			array.map(JsonValue::Array),
+			object.map(JsonValue::from),
+		)),
+	)
+	.parse_next(input)
+}
+
+/// Parses a JSON value other than an array or object.
+pub(crate) fn scalar<I, E>(input: &mut I) -> PResult<JsonValue, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"scalar",
+		alt((
+			null.value(JsonValue::Null),
+			boolean.map(JsonValue::Boolean),
+			string.map(JsonValue::String),
+			number.map(JsonValue::Number),
+		)),
+	)
+	.parse_next(input)
+}
+
+fn null<I, E>(input: &mut I) -> PResult<I::Slice, E>
+where
+	I: Input,
+	E: ParserError<I>,
+{
+	trace("null", "null").parse_next(input)
+}
+
+fn boolean<I, E>(input: &mut I) -> PResult<bool, E>
+where
+	I: Input,
+	E: ParserError<I>,
+{
+	let parse_true = "true".value(true);
+	let parse_false = "false".value(false);
+
+	trace(
+		"boolean",
+		alt((parse_true, parse_false)),
+	)
+	.parse_next(input)
+}
+
+/// Part of a string's contents.
+pub(crate) enum Fragment<S> {
+	/// UTF-8 text without escapes.
+	Run(S),
+	Escaped(char),
+}
+
+/// Parses a quoted string, decoding its escapes.
+pub fn string<I, E>(input: &mut I) -> PResult<String, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	let fragment = alt((
+		take_till(1.., ['"', '\\'])
+			.verify(|run: &I::Slice| std::str::from_utf8(run.as_bstr()).is_ok())
+			.map(Fragment::Run),
+		preceded('\\', escape).map(Fragment::Escaped),
+	));
+	trace(
+		"string",
+		preceded(
+			'\"',
+			cut_err(terminated(
+				repeat(0.., fragment).fold(
+					String::new,
+					|mut string, fragment: Fragment<I::Slice>| {
+						match fragment {
+							Fragment::Run(run) => string.push_str(
+								std::str::from_utf8(run.as_bstr())
+									.unwrap_or_default(),
+							),
+							Fragment::Escaped(c) => string.push(c),
+						}
+						string
+					},
+				),
+				'\"',
+			)),
+		)
+		.context("string"),
+	)
+	.parse_next(input)
+}
+
+/// Like [`string`], but borrows the contents from the input when they have
+/// no escapes, so only strings that need decoding allocate.
+pub fn raw_string<'i, I, E>(input: &mut I) -> PResult<Cow<'i, str>, E>
+where
+	I: Input<Slice = &'i str>,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"raw_string",
+		alt((
+			delimited('\"', take_till(0.., ['"', '\\']), '\"')
+				.map(Cow::Borrowed),
+			string.map(Cow::Owned),
+		)),
+	)
+	.parse_next(input)
+}
+
+/// Parses one character of a string's contents, decoding an escape.
+pub(crate) fn character<I, E>(input: &mut I) -> PResult<char, E>
+where
+	I: Input + Stream<Token = char>,
+	E: ParserError<I>,
+{
+	let c = none_of('\"').parse_next(input)?;
+
+	if c == '\\' {
+		escape.parse_next(input)
+	} else {
+		Ok(c)
+	}
+}
+
+/// Parses what follows the backslash of an escape sequence.
+pub(crate) fn escape<I, E>(input: &mut I) -> PResult<char, E>
+where
+	I: Input,
+	E: ParserError<I>,
+{
+	trace(
+		"escape",
+		alt((
+			any.verify_map(|c: I::Token| {
+				Some(match c.as_char() {
+					c @ ('"' | '\\' | '/') => c,
+					'b' => '\x08',
+					'f' => '\x0C',
+					'n' => '\n',
+					'r' => '\r',
+					't' => '\t',
+					_ => return None,
+				})
+			}),
+			preceded('u', unicode_escape),
+		)),
+	)
+	.parse_next(input)
+}
+
+/// Parses the four hex digits following a `\u` escape, along with the
+/// `\uXXXX` low half when they are the high half of a surrogate pair.
+pub fn unicode_escape<I, E>(input: &mut I) -> PResult<char, E>
+where
+	I: Input,
+	E: ParserError<I>,
+{
+	trace(
+		"unicode_escape",
+		alt((
+			u16_hex
+				.verify(|cp| !(0xD800..0xE000).contains(cp))
+				.map(|cp| cp as u32),
+			separated_pair(u16_hex, "\\u", u16_hex)
+				.verify(|(h, l)| {
+					(0xD800..0xDC00).contains(h) && (0xDC00..0xE000).contains(l)
+				})
+				.map(|(h, l)| {
+					let high_ten = (h as u32) - 0xD800;
+					let low_ten = (l as u32) - 0xDC00;
+					(high_ten << 10) + low_ten + 0x10000
+				}),
+		))
+		.verify_map(std::char::from_u32),
+	)
+	.parse_next(input)
+}
+
+/// Parses a number; literals beyond the range of an `f64` become infinite.
+///
+/// The literal must follow the JSON grammar to the letter: `01`, `+1`,
+/// `.5` and `1.` fail under the `"number"` context, as does anything else
+/// that starts like a number and isn't one.
+pub fn number<I, E>(input: &mut I) -> PResult<f64, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"number",
+		preceded(
+			peek(one_of(('0'..='9', '-', '+', '.'))),
+			cut_err(
+				take_while(1.., |c: I::Token| {
+					is_number_char(c.as_char())
+				})
+				.verify(|literal: &I::Slice| {
+					std::str::from_utf8(literal.as_bstr()).is_ok_and(is_number)
+				})
+				.parse_to(),
+			)
+			.context("number"),
+		),
+	)
+	.parse_next(input)
+}
+
+fn u16_hex<I, E>(input: &mut I) -> PResult<u16, E>
+where
+	I: Input,
+	E: ParserError<I>,
+{
+	trace(
+		"u16_hex",
+		take(4usize).verify_map(|s: I::Slice| {
+			let s = std::str::from_utf8(s.as_bstr()).ok()?;
+			u16::from_str_radix(s, 16).ok()
+		}),
+	)
+	.parse_next(input)
+}
+
+/// Parses an array, allowing whitespace around its items.
+pub fn array<I, E>(input: &mut I) -> PResult<Vec<JsonValue>, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"array",
+		preceded(
+			('[', ws),
+			cut_err(terminated(
+				separated(0.., json_value, (ws, ',', ws)),
+				(ws, ']'),
+			)),
+		)
+		.context("array"),
+	)
+	.parse_next(input)
+}
+
+/// Parses an object, allowing whitespace around its keys and values.
+/// Duplicate keys keep their last value.
+pub fn object<I, E>(input: &mut I) -> PResult<HashMap<String, JsonValue>, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"object",
+		preceded(
+			('{', ws),
+			cut_err(terminated(
+				separated(0.., key_value, (ws, ',', ws)),
+				(ws, '}'),
+			)),
+		)
+		.context("object"),
+	)
+	.parse_next(input)
+}
+
+fn key_value<I, E>(input: &mut I) -> PResult<(String, JsonValue), E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace(
+		"key_value",
+		separated_pair(
+			string,
+			cut_err((ws, ':', ws)),
+			json_value,
+		),
+	)
+	.parse_next(input)
+}
+
+/// Recognizes a value without building it, for fast-forwarding past parts
+/// of a document nobody asked for.
+pub(crate) fn skip_value<I, E>(input: &mut I) -> PResult<(), E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	trace("skip_value", |input: &mut I| {
+		skip_nested(input, usize::MAX).map(|_| ())
+	})
+	.parse_next(input)
+}
+
+/// Like [`skip_value`], but stops before the bracket of an array or object
+/// more than `max_depth` levels deep, counting the value itself as the
+/// first, and returns `false`.
+///
+/// The open containers are kept on a stack rather than recursed into, so
+/// no depth of nesting overflows the call stack.
+pub(crate) fn skip_nested<I, E>(
+	input: &mut I,
+	max_depth: usize,
+) -> PResult<bool, E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	// Whether each container open around the position is an object.
+	let mut open: Vec<bool> = Vec::new();
+	let context = |object| if object { "object" } else { "array" };
+	loop {
+		// A value starts here.
+		let start = input.checkpoint();
+		let bracket =
+			opt(one_of(['[', '{']).map(AsChar::as_char)).parse_next(input)?;
+		if let Some(bracket) = bracket {
+			if open.len() == max_depth {
+				input.reset(start);
+				return Ok(false);
+			}
+			let object = bracket == '{';
+			let close = if object { '}' } else { ']' };
+			ws.parse_next(input)?;
+			if opt(close).parse_next(input)?.is_none() {
+				open.push(object);
+				if object {
+					skip_key.context("object").parse_next(input)?;
+				}
+				continue;
+			}
+		} else {
+			let mut scalar = alt((
+				null.void(),
+				boolean.void(),
+				skip_string,
+				number.void(),
+			));
+			match open.last() {
+				None => scalar.parse_next(input)?,
+				Some(&object) => cut_err(scalar)
+					.context(context(object))
+					.parse_next(input)?,
+			}
+		}
+
+		// A value has ended, and with it perhaps the containers around it.
+		loop {
+			let Some(&object) = open.last() else {
+				return Ok(true);
+			};
+			let close = if object { '}' } else { ']' };
+			let more = cut_err(preceded(
+				ws,
+				alt((','.value(true), close.value(false))),
+			))
+			.context(context(object))
+			.parse_next(input)?;
+			if more {
+				ws.parse_next(input)?;
+				if object {
+					skip_key.context("object").parse_next(input)?;
+				}
+				break;
+			}
+			open.pop();
+		}
+	}
+}
+
+/// Recognizes an object key and the colon after it, up to the value.
+fn skip_key<I, E>(input: &mut I) -> PResult<(), E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	cut_err((skip_string, ws, ':', ws)).void().parse_next(input)
+}
+
+fn skip_string<I, E>(input: &mut I) -> PResult<(), E>
+where
+	I: Input,
+	E: ParserError<I> + AddContext<I, &'static str>,
+{
+	let run = alt((
+		take_till(1.., ['"', '\\']).void(),
+		preceded('\\', escape).void(),
+	));
+	trace(
+		"skip_string",
+		preceded(
+			'\"',
+			cut_err(terminated(repeat(0.., run), '\"')),
+		)
+		.context("string"),
+	)
+	.parse_next(input)
+}
+
+/// Skips any JSON whitespace: spaces, tabs, carriage returns and line
+/// feeds.
+pub fn ws<I, E>(input: &mut I) -> PResult<I::Slice, E>
+where
+	I: Input,
+	E: ParserError<I>,
+{
+	trace("ws", take_while(0.., WS)).parse_next(input)
+}
+
+const WS: &[char] = &[' ', '\t', '\r', '\n'];
+
+#[cfg(test)]
+mod tests {
+	use winnow::{
+		ascii::alpha1,
+		combinator::separated_pair,
+		error::{ContextError, ErrMode},
+		stream::{Located, Location, Partial},
+	};
+
+	use super::*;
+
+	type Error = ContextError<&'static str>;
+
+	#[test]
+	fn parsers_embed_in_other_grammars() {
+		let mut assignment = separated_pair(
+			alpha1::<&str, Error>,
+			(ws, '=', ws),
+			json_value,
+		);
+		let mut input = r#"x = {"a": [1, "b"]}; rest"#;
+		let (name, value) = assignment.parse_next(&mut input).unwrap();
+		assert_eq!(name, "x");
+		assert_eq!(
+			value,
+			crate::parse(r#"{"a": [1, "b"]}"#).unwrap()
+		);
+		assert_eq!(input, "; rest");
+
+		let mut input = r#""aé\n" tail"#;
+		assert_eq!(
+			string::<_, Error>(&mut input).unwrap(),
+			"aé\n"
+		);
+		assert_eq!(input, " tail");
+		let mut input = "-1.5e3,";
+		assert_eq!(
+			number::<_, Error>(&mut input).unwrap(),
+			-1500.0
+		);
+	}
+
+	#[test]
+	fn committed_failures_are_cut_with_a_context() {
+		for (input, context) in [
+			(r#""abc"#, "string"),
+			("01", "number"),
+			("[1,", "array"),
+			(r#"{"a" 1}"#, "object"),
+		] {
+			match json::<_, Error>.parse_next(&mut &*input) {
+				Err(ErrMode::Cut(error)) => assert!(
+					error.context().any(|c| *c == context),
+					"{input}"
+				),
+				other => panic!("{input}: {other:?}"),
+			}
+		}
+		assert!(matches!(
+			json_value::<_, Error>.parse_next(&mut "nope"),
+			Err(ErrMode::Backtrack(_))
+		));
+	}
+
+	#[test]
+	fn parsers_read_other_streams() {
+		let expected = crate::parse(r#"{"k": ["é", 1, null]}"#).unwrap();
+
+		let mut bytes = r#" {"k": ["é", 1, null]} "#.as_bytes();
+		assert_eq!(
+			json::<_, Error>(&mut bytes).unwrap(),
+			expected
+		);
+		assert!(bytes.is_empty());
+		let mut invalid: &[u8] = b"\"\xff\"";
+		assert!(json::<_, Error>(&mut invalid).is_err());
+
+		let mut located = Located::new(r#"{"k": ["é", 1, null]} x"#);
+		assert_eq!(
+			json::<_, Error>(&mut located).unwrap(),
+			expected
+		);
+		assert_eq!(located.location(), 23);
+
+		let mut partial = Partial::new(r#"{"k": ["é", 1"#);
+		assert!(matches!(
+			json::<_, Error>(&mut partial),
+			Err(ErrMode::Incomplete(_))
+		));
+		let mut complete = Partial::new(r#"{"k": ["é", 1, null]} "#);
+		let _ = complete.complete();
+		assert_eq!(
+			json::<_, Error>(&mut complete).unwrap(),
+			expected
+		);
+	}
+
+	#[test]
+	fn raw_strings_borrow_unless_escaped() {
+		let mut input = r#""plain é" "a\tb" "" "open"#;
+		let plain = raw_string::<_, Error>(&mut input).unwrap();
+		assert!(matches!(
+			plain,
+			Cow::Borrowed("plain é")
+		));
+		ws::<_, Error>(&mut input).unwrap();
+		let escaped = raw_string::<_, Error>(&mut input).unwrap();
+		assert!(matches!(&escaped, Cow::Owned(s) if s == "a\tb"));
+		ws::<_, Error>(&mut input).unwrap();
+		assert!(matches!(
+			raw_string::<_, Error>(&mut input).unwrap(),
+			Cow::Borrowed("")
+		));
+		ws::<_, Error>(&mut input).unwrap();
+		assert!(matches!(
+			raw_string::<_, Error>(&mut input),
+			Err(ErrMode::Cut(_))
+		));
+	}
+}
@@ -0,0 +1,548 @@
+//! Parsing of a document while it arrives, such as the output of a
+//! language model being streamed, to show what it holds so far.
+
+use std::mem;
+
+use winnow::Parser as _;
+
+use crate::{error::ContextTrail, parser, Error, ErrorKind, JsonValue};
+
+/// How deeply arrays and objects may nest, so hostile input cannot exhaust
+/// memory.
+const MAX_DEPTH: usize = 512;
+
+/// Builds a value from a document fed to it piece by piece, keeping the
+/// part read so far up to date after each piece.
+///
+/// Arrays and objects appear as soon as they open and strings grow as
+/// their characters arrive, while numbers and literals appear once what
+/// follows them shows they are complete. Each piece is read only once, so
+/// following a long document costs no more than parsing it whole.
+///
+/// ```text
+/// let mut parser = PartialParser::new();
+/// for chunk in stream {
+///     render(parser.feed(&chunk)?);
+/// }
+/// let value = parser.finish()?;
+/// ```
+#[derive(Debug)]
+pub struct PartialParser {
+	/// Everything fed so far, for locating errors.
+	text: String,
+	/// How much of `text` has been read.
+	pos: usize,
+	value: JsonValue,
+	/// The open arrays and objects, outermost first.
+	open: Vec<Open>,
+	state: State,
+	/// The key, number or literal being read.
+	token: String,
+	/// What follows the backslash of the escape sequence being read.
+	escape: String,
+	/// Where the number, literal or escape sequence being read starts.
+	token_start: usize,
+	/// The first error, which every later call reports again.
+	error: Option<Error>,
+}
+
+#[derive(Debug)]
+enum Open {
+	Array,
+	/// An object, with the key of the member read last.
+	Object(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+	/// Before a value.
+	Value,
+	/// After `[`.
+	FirstItem,
+	/// After `{`.
+	FirstMember,
+	/// After a comma in an object.
+	Key,
+	/// After a key.
+	Colon,
+	/// After an item or member, before the comma or closing bracket.
+	Separator,
+	/// After the document, where only whitespace may follow.
+	Done,
+	/// Inside a string, which is a key or a value.
+	String { key: bool },
+	/// Inside an escape sequence of a string.
+	Escape { key: bool },
+	/// Inside a number or literal.
+	Token,
+}
+
+impl Default for PartialParser {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl PartialParser {
+	pub fn new() -> Self {
+		Self {
+			text: String::new(),
+			pos: 0,
+			value: JsonValue::Null,
+			open: Vec::new(),
+			state: State::Value,
+			token: String::new(),
+			escape: String::new(),
+			token_start: 0,
+			error: None,
+		}
+	}
+
+	/// Reads the next piece of the document, returning the value of the
+	/// part read so far: `null` until one starts.
+	///
+	/// Fails once what has arrived is not the start of valid JSON, and on
+	/// every call after that.
+	pub fn feed(&mut self, chunk: &str) -> Result<&JsonValue, Error> {
+		if let Some(error) = &self.error {
+			return Err(error.clone());
+		}
+		self.text.push_str(chunk);
+		if let Err(error) = self.read() {
+			self.error = Some(error.clone());
+			return Err(error);
+		}
+		Ok(&self.value)
+	}
+
+	/// The value of the part of the document read so far.
+	pub fn value(&self) -> &JsonValue {
+		&self.value
+	}
+
+	/// Everything fed so far.
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Ends the document, giving its value.
+	///
+	/// Fails if what was fed is not a complete document.
+	pub fn finish(mut self) -> Result<JsonValue, Error> {
+		if let Some(error) = self.error {
+			return Err(error);
+		}
+		if self.state == State::Token {
+			self.end_token()?;
+		}
+		if self.state != State::Done {
+			return Err(Error::new(
+				&self.text,
+				self.text.len(),
+				"unexpected end of input".to_owned(),
+			));
+		}
+		Ok(self.value)
+	}
+
+	fn error_at(&self, offset: usize, message: impl Into<String>) -> Error {
+		Error::new(&self.text, offset, message.into())
+	}
+
+	fn unexpected(&self, offset: usize) -> Error {
+		match self.text[offset..].chars().next() {
+			Some(c) => self.error_at(
+				offset,
+				format!("unexpected character {c:?}"),
+			),
+			None => self.error_at(offset, "unexpected end of input"),
+		}
+	}
+
+	fn read(&mut self) -> Result<(), Error> {
+		while let Some(c) = self.text[self.pos..].chars().next() {
+			let start = self.pos;
+			self.pos += c.len_utf8();
+			match self.state {
+				State::String { key } => self.string(key, c, start)?,
+				State::Escape { key } => self.escape(key, c)?,
+				State::Token
+					if c.is_ascii_alphanumeric()
+						|| matches!(c, '+' | '-' | '.') =>
+				{
+					self.token.push(c);
+				}
+				State::Token => {
+					// Whatever ends the token is read again after it.
+					self.pos = start;
+					self.end_token()?;
+				}
+				_ if matches!(c, ' ' | '\t' | '\r' | '\n') => {}
+				State::Value | State::FirstItem => {
+					if self.state == State::FirstItem && c == ']' {
+						self.close();
+					} else {
+						self.start_value(c, start)?;
+					}
+				}
+				State::FirstMember if c == '}' => self.close(),
+				State::FirstMember | State::Key if c == '"' => {
+					self.token.clear();
+					self.state = State::String { key: true };
+				}
+				State::Colon if c == ':' => self.state = State::Value,
+				State::Separator => match (c, self.open.last()) {
+					(',', Some(Open::Array)) => self.state = State::Value,
+					(',', Some(Open::Object(_))) => self.state = State::Key,
+					(']', Some(Open::Array)) | ('}', Some(Open::Object(_))) => {
+						self.close()
+					}
+					_ => return Err(self.unexpected(start)),
+				},
+				_ => return Err(self.unexpected(start)),
+			}
+		}
+		Ok(())
+	}
+
+	fn start_value(&mut self, c: char, start: usize) -> Result<(), Error> {
+		match c {
+			'"' => {
+				self.place(JsonValue::String(String::new()));
+				self.state = State::String { key: false };
+			}
+			'[' | '{' if self.open.len() == MAX_DEPTH => {
+				return Err(self
+					.error_at(start, "nesting too deep")
+					.with_kind(ErrorKind::TooDeep))
+			}
+			'[' => {
+				self.place(JsonValue::Array(Vec::new()));
+				self.open.push(Open::Array);
+				self.state = State::FirstItem;
+			}
+			'{' => {
+				self.place(JsonValue::Object(Box::default()));
+				self.open.push(Open::Object(String::new()));
+				self.state = State::FirstMember;
+			}
+			'-' | '0'..='9' | 'a'..='z' => {
+				self.token.clear();
+				self.token.push(c);
+				self.token_start = start;
+				self.state = State::Token;
+			}
+			_ => return Err(self.unexpected(start)),
+		}
+		Ok(())
+	}
+
+	/// Reads the character `c` of a string, along with the run of plain
+	/// characters after it.
+	fn string(
+		&mut self,
+		key: bool,
+		c: char,
+		start: usize,
+	) -> Result<(), Error> {
+		match c {
+			'"' if key => {
+				if let Some(Open::Object(key)) = self.open.last_mut() {
+					*key = mem::take(&mut self.token);
+				}
+				self.state = State::Colon;
+			}
+			'"' => self.state = self.after_value(),
+			'\\' => {
+				self.escape.clear();
+				self.token_start = start;
+				self.state = State::Escape { key };
+			}
+			c if c < ' ' => {
+				return Err(self.error_at(start, "control character in string"))
+			}
+			_ => {
+				let run = &self.text[start..];
+				let len = run
+					.find(|c: char| c == '"' || c == '\\' || c < ' ')
+					.unwrap_or(run.len());
+				self.pos = start + len;
+				let run = &self.text[start..self.pos];
+				if key {
+					self.token.push_str(run);
+				} else if let JsonValue::String(s) =
+					descend(&mut self.value, &self.open)
+				{
+					s.push_str(run);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn escape(&mut self, key: bool, c: char) -> Result<(), Error> {
+		self.escape.push(c);
+		let escape = self.escape.as_bytes();
+		let high_surrogate = escape
+			.get(1..5)
+			.and_then(|hex| std::str::from_utf8(hex).ok())
+			.and_then(|hex| u16::from_str_radix(hex, 16).ok())
+			.is_some_and(|unit| (0xD800..0xDC00).contains(&unit));
+		// The high half of a surrogate pair waits for its `\\uXXXX` low half.
+		let pending = escape[0] == b'u'
+			&& (escape.len() < 5
+				|| high_surrogate
+					&& escape.len() < 11
+					&& escape.get(5).is_none_or(|&b| b == b'\\')
+					&& escape.get(6).is_none_or(|&b| b == b'u'));
+		if pending {
+			return Ok(());
+		}
+		let mut rest = self.escape.as_str();
+		let c = match parser::escape::<_, ContextTrail>.parse_next(&mut rest) {
+			Ok(c) if rest.is_empty() => c,
+			_ => {
+				return Err(self.error_at(
+					self.token_start,
+					"invalid escape sequence",
+				))
+			}
+		};
+		if key {
+			self.token.push(c);
+		} else if let JsonValue::String(s) =
+			descend(&mut self.value, &self.open)
+		{
+			s.push(c);
+		}
+		self.state = State::String { key };
+		Ok(())
+	}
+
+	/// Ends the number or literal in `token`.
+	fn end_token(&mut self) -> Result<(), Error> {
+		let token = mem::take(&mut self.token);
+		let value = match token.as_str() {
+			"null" => Some(JsonValue::Null),
+			"true" => Some(JsonValue::Boolean(true)),
+			"false" => Some(JsonValue::Boolean(false)),
+			_ if token
+				.starts_with(|c: char| c == '-' || c.is_ascii_digit()) =>
+			{
+				let mut rest = token.as_str();
+				parser::number::<_, ContextTrail>
+					.parse_next(&mut rest)
+					.ok()
+					.filter(|_| rest.is_empty())
+					.map(JsonValue::Number)
+			}
+			_ => None,
+		};
+		let value = value.ok_or_else(|| {
+			self.error_at(
+				self.token_start,
+				format!("invalid value {token:?}"),
+			)
+		})?;
+		self.place(value);
+		self.state = self.after_value();
+		Ok(())
+	}
+
+	fn after_value(&self) -> State {
+		if self.open.is_empty() {
+			State::Done
+		} else {
+			State::Separator
+		}
+	}
+
+	/// Closes the innermost array or object.
+	fn close(&mut self) {
+		self.open.pop();
+		self.state = self.after_value();
+	}
+
+	/// Adds a value to the innermost array or object, or makes it the
+	/// document.
+	fn place(&mut self, value: JsonValue) {
+		let Some((last, outer)) = self.open.split_last() else {
+			self.value = value;
+			return;
+		};
+		match (descend(&mut self.value, outer), last) {
+			(JsonValue::Array(items), Open::Array) => items.push(value),
+			(JsonValue::Object(map), Open::Object(key)) => {
+				map.insert(key.clone(), value);
+			}
+			_ => unreachable!("open containers match the values built"),
+		}
+	}
+}
+
+/// The value that the open containers lead to from `value`, through the
+/// last item of each array and the member read last of each object.
+fn descend<'v>(
+	mut value: &'v mut JsonValue,
+	open: &[Open],
+) -> &'v mut JsonValue {
+	for open in open {
+		value = match (value, open) {
+			(JsonValue::Array(items), Open::Array) => items.last_mut().unwrap(),
+			(JsonValue::Object(map), Open::Object(key)) => {
+				map.get_mut(key).unwrap()
+			}
+			_ => unreachable!("open containers match the values built"),
+		};
+	}
+	value
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	/// The value after each of `chunks` is fed in turn, and the final one.
+	fn feed_all(chunks: &[&str]) -> (Vec<JsonValue>, JsonValue) {
+		let mut parser = PartialParser::new();
+		let snapshots = chunks
+			.iter()
+			.map(|chunk| {
+				parser
+					.feed(chunk)
+					.unwrap_or_else(|e| panic!("{chunk:?}: {e}"))
+					.clone()
+			})
+			.collect();
+		(snapshots, parser.finish().unwrap())
+	}
+
+	fn values(values: &[&str]) -> Vec<JsonValue> {
+		values.iter().map(|value| parse(value).unwrap()).collect()
+	}
+
+	#[test]
+	fn values_grow_as_chunks_arrive() {
+		let (snapshots, value) = feed_all(&[
+			r#"{"name": "Ad"#,
+			r#"a", "tags": [1"#,
+			r#"2, tr"#,
+			r#"ue, {"#,
+			r#""k": null}], "n": -1.5e"#,
+			"2}",
+		]);
+		assert_eq!(
+			snapshots,
+			values(&[
+				r#"{"name": "Ad"}"#,
+				r#"{"name": "Ada", "tags": []}"#,
+				r#"{"name": "Ada", "tags": [12]}"#,
+				r#"{"name": "Ada", "tags": [12, true, {}]}"#,
+				r#"{"name": "Ada", "tags": [12, true, {"k": null}]}"#,
+				r#"{"name": "Ada", "tags": [12, true, {"k": null}], "n": -150}"#,
+			])
+		);
+		assert_eq!(snapshots[5], value);
+
+		let (snapshots, value) = feed_all(&[" ", "4", "2", " "]);
+		assert_eq!(
+			snapshots,
+			values(&["null", "null", "null", "42"])
+		);
+		assert_eq!(value, parse("42").unwrap());
+		let mut parser = PartialParser::new();
+		parser.feed("42").unwrap();
+		assert_eq!(
+			parser.finish(),
+			Ok(parse("42").unwrap())
+		);
+	}
+
+	#[test]
+	fn escapes_may_be_split_across_chunks() {
+		let (snapshots, value) = feed_all(&[
+			r#"["a\"#,
+			r#"u00"#,
+			r#"e9\uD8"#,
+			r#"3D\u"#,
+			r#"DE00"]"#,
+		]);
+		assert_eq!(
+			snapshots,
+			values(&[
+				r#"["a"]"#,
+				r#"["a"]"#,
+				r#"["aé"]"#,
+				r#"["aé"]"#,
+				r#"["aé😀"]"#,
+			])
+		);
+		assert_eq!(value, parse(r#"["aé😀"]"#).unwrap());
+
+		let (_, value) = feed_all(&[r#"{"k\"#, r#"n": "\"#, r#"""}"#]);
+		assert_eq!(
+			value,
+			parse(r#"{"k\n": "\""}"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn malformed_input_fails() {
+		for input in [
+			"[1,]",
+			"{\"a\" 1}",
+			"[1 2]",
+			"nul]",
+			"\"\\uD800\\n\"",
+			"\"\\uDC00\"",
+			"\"\\x\"",
+			"1 2",
+			"{1: 2}",
+		] {
+			assert!(parse(input).is_err(), "{input}");
+			let mut parser = PartialParser::new();
+			let failed = input
+				.split_inclusive(|_| true)
+				.any(|chunk| parser.feed(chunk).is_err())
+				|| parser.finish().is_err();
+			assert!(failed, "{input}");
+		}
+		let mut parser = PartialParser::new();
+		let error = parser.feed("\"a\nb\"").unwrap_err();
+		assert_eq!(
+			error.message(),
+			"control character in string"
+		);
+		let mut parser = PartialParser::new();
+		let error = parser.feed("[1,]").unwrap_err();
+		assert_eq!(parser.feed("2]"), Err(error.clone()));
+		assert_eq!(parser.finish(), Err(error));
+	}
+
+	#[test]
+	fn incomplete_documents_fail_to_finish() {
+		for input in ["", "[", r#"{"a": "#, r#""open"#, r#""\u12"#] {
+			let mut parser = PartialParser::new();
+			parser.feed(input).unwrap();
+			let error = parser.finish().unwrap_err();
+			assert_eq!(
+				error.message(),
+				"unexpected end of input",
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let mut parser = PartialParser::new();
+		let error = parser.feed(&"[".repeat(100_000)).unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::TooDeep);
+		assert_eq!(error.offset(), MAX_DEPTH);
+		let mut parser = PartialParser::new();
+		parser.feed(&"[".repeat(MAX_DEPTH)).unwrap();
+		parser.feed(&"]".repeat(MAX_DEPTH)).unwrap();
+		assert!(parser.finish().is_ok());
+	}
+}
@@ -0,0 +1,265 @@
+//! An immutable tree for keeping many versions of a document, as an undo
+//! stack does, without copying all of it for each one.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{pointer::parse_index, JsonPointer, JsonValue};
+
+/// An immutable [`JsonValue`] whose strings, arrays and objects are
+/// reference-counted.
+///
+/// Edits give a new tree and leave the old one as it was. Only the arrays
+/// and objects on the path to the edit are copied, and shallowly at that;
+/// every subtree beside the path is shared between the two versions, so
+/// keeping both costs little more than keeping one.
+///
+/// ```text
+/// let before = PersistentValue::from(&value);
+/// let after = before.set(&"/a/b".parse()?, PersistentValue::Null)?;
+/// undo.push(before);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum PersistentValue {
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(Rc<str>),
+	Array(Rc<Vec<PersistentValue>>),
+	Object(Rc<HashMap<String, PersistentValue>>),
+}
+
+impl PersistentValue {
+	pub fn get(&self, pointer: &JsonPointer) -> Option<&PersistentValue> {
+		pointer
+			.tokens()
+			.iter()
+			.try_fold(self, |node, token| node.child(token))
+	}
+
+	/// A tree with the value at `pointer` replaced by `value`, or added if
+	/// it is a new key of an object or the index just past the end (or `-`)
+	/// of an array. `None` if the container it goes in does not exist.
+	pub fn set(
+		&self,
+		pointer: &JsonPointer,
+		value: PersistentValue,
+	) -> Option<Self> {
+		match pointer.tokens() {
+			[] => Some(value),
+			tokens => self.rebuild(tokens, |parent, token| {
+				parent.with_entry(token, Some(value))
+			}),
+		}
+	}
+
+	/// A tree without the value at `pointer`, with later array items moved
+	/// down. `None` if there is no such value or `pointer` is the root.
+	pub fn remove(&self, pointer: &JsonPointer) -> Option<Self> {
+		self.rebuild(pointer.tokens(), |parent, token| {
+			parent.child(token)?;
+			parent.with_entry(token, None)
+		})
+	}
+
+	/// A mutable copy of the tree.
+	pub fn to_value(&self) -> JsonValue {
+		match self {
+			PersistentValue::Null => JsonValue::Null,
+			PersistentValue::Boolean(b) => JsonValue::Boolean(*b),
+			PersistentValue::Number(n) => JsonValue::Number(*n),
+			PersistentValue::String(s) => JsonValue::String(s.to_string()),
+			PersistentValue::Array(items) => JsonValue::Array(
+				items.iter().map(PersistentValue::to_value).collect(),
+			),
+			PersistentValue::Object(map) => map
+				.iter()
+				.map(|(key, value)| (key.clone(), value.to_value()))
+				.collect(),
+		}
+	}
+
+	/// Whether the two are the same node in memory rather than merely equal,
+	/// as subtrees that an edit left alone are.
+	pub fn ptr_eq(&self, other: &PersistentValue) -> bool {
+		match (self, other) {
+			(PersistentValue::String(a), PersistentValue::String(b)) => {
+				Rc::ptr_eq(a, b)
+			}
+			(PersistentValue::Array(a), PersistentValue::Array(b)) => {
+				Rc::ptr_eq(a, b)
+			}
+			(PersistentValue::Object(a), PersistentValue::Object(b)) => {
+				Rc::ptr_eq(a, b)
+			}
+			_ => false,
+		}
+	}
+
+	fn child(&self, token: &str) -> Option<&PersistentValue> {
+		match self {
+			PersistentValue::Object(map) => map.get(token),
+			PersistentValue::Array(items) => items.get(parse_index(token)?),
+			_ => None,
+		}
+	}
+
+	/// Copies the path down to the container the last of `tokens` is in,
+	/// replacing that container by what `edit` makes of it.
+	fn rebuild(
+		&self,
+		tokens: &[String],
+		edit: impl FnOnce(&Self, &str) -> Option<Self>,
+	) -> Option<Self> {
+		match tokens {
+			[] => None,
+			[token] => edit(self, token),
+			[token, rest @ ..] => {
+				let child = self.child(token)?.rebuild(rest, edit)?;
+				self.with_entry(token, Some(child))
+			}
+		}
+	}
+
+	/// A shallow copy of this array or object with the entry `token` set to
+	/// `child`, or removed if it is `None`.
+	fn with_entry(&self, token: &str, child: Option<Self>) -> Option<Self> {
+		match self {
+			PersistentValue::Object(map) => {
+				let mut map = HashMap::clone(map);
+				match child {
+					Some(child) => map.insert(token.to_owned(), child),
+					None => map.remove(token),
+				};
+				Some(PersistentValue::Object(Rc::new(map)))
+			}
+			PersistentValue::Array(items) => {
+				let index = match token {
+					"-" => items.len(),
+					_ => parse_index(token)?,
+				};
+				let mut items = Vec::clone(items);
+				match child {
+					Some(child) if index < items.len() => items[index] = child,
+					Some(child) if index == items.len() => items.push(child),
+					None if index < items.len() => {
+						items.remove(index);
+					}
+					_ => return None,
+				}
+				Some(PersistentValue::Array(Rc::new(items)))
+			}
+			_ => None,
+		}
+	}
+}
+
+impl From<&JsonValue> for PersistentValue {
+	fn from(value: &JsonValue) -> Self {
+		match value {
+			JsonValue::Null => PersistentValue::Null,
+			JsonValue::Boolean(b) => PersistentValue::Boolean(*b),
+			JsonValue::Number(n) => PersistentValue::Number(*n),
+			JsonValue::String(s) => PersistentValue::String(s.as_str().into()),
+			JsonValue::Array(items) => PersistentValue::Array(Rc::new(
+				items.iter().map(PersistentValue::from).collect(),
+			)),
+			JsonValue::Object(map) => PersistentValue::Object(Rc::new(
+				map.iter()
+					.map(|(key, value)| (key.clone(), value.into()))
+					.collect(),
+			)),
+		}
+	}
+}
+
+impl From<JsonValue> for PersistentValue {
+	fn from(value: JsonValue) -> Self {
+		(&value).into()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn at(pointer: &str) -> JsonPointer {
+		JsonPointer::parse(pointer).unwrap()
+	}
+
+	#[test]
+	fn edits_share_what_they_leave_alone() {
+		let before = PersistentValue::from(
+			parse(r#"{"a": {"b": 1, "c": [1, 2]}, "d": {"e": "text"}}"#)
+				.unwrap(),
+		);
+		let after = before.set(&at("/a/b"), PersistentValue::Null).unwrap();
+
+		assert_eq!(
+			before.to_value(),
+			parse(r#"{"a": {"b": 1, "c": [1, 2]}, "d": {"e": "text"}}"#)
+				.unwrap()
+		);
+		assert_eq!(
+			after.to_value(),
+			parse(r#"{"a": {"b": null, "c": [1, 2]}, "d": {"e": "text"}}"#)
+				.unwrap()
+		);
+		let shared = |pointer| {
+			before
+				.get(&at(pointer))
+				.unwrap()
+				.ptr_eq(after.get(&at(pointer)).unwrap())
+		};
+		assert!(shared("/d"));
+		assert!(shared("/d/e"));
+		assert!(shared("/a/c"));
+		assert!(!shared("/a"));
+		assert!(!shared(""));
+		assert!(!PersistentValue::Null.ptr_eq(&PersistentValue::Null));
+	}
+
+	#[test]
+	fn set_and_remove_follow_pointer_rules() {
+		let value =
+			PersistentValue::from(parse(r#"{"list": [1, 2]}"#).unwrap());
+		let three = PersistentValue::Number(3.0);
+		let set = |pointer| {
+			value.set(&at(pointer), three.clone()).map(|v| v.to_value())
+		};
+		assert_eq!(
+			set("/list/-"),
+			Some(parse(r#"{"list": [1, 2, 3]}"#).unwrap())
+		);
+		assert_eq!(
+			set("/list/2"),
+			Some(parse(r#"{"list": [1, 2, 3]}"#).unwrap())
+		);
+		assert_eq!(
+			set("/list/0"),
+			Some(parse(r#"{"list": [3, 2]}"#).unwrap())
+		);
+		assert_eq!(
+			set("/new"),
+			Some(parse(r#"{"list": [1, 2], "new": 3}"#).unwrap())
+		);
+		assert_eq!(set(""), Some(parse("3").unwrap()));
+		assert_eq!(set("/list/3"), None);
+		assert_eq!(set("/missing/x"), None);
+		assert_eq!(set("/list/0/x"), None);
+
+		let remove = |pointer| value.remove(&at(pointer)).map(|v| v.to_value());
+		assert_eq!(
+			remove("/list/0"),
+			Some(parse(r#"{"list": [2]}"#).unwrap())
+		);
+		assert_eq!(
+			remove("/list"),
+			Some(parse("{}").unwrap())
+		);
+		assert_eq!(remove("/list/2"), None);
+		assert_eq!(remove("/list/-"), None);
+		assert_eq!(remove("/missing"), None);
+		assert_eq!(remove(""), None);
+	}
+}
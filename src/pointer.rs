@@ -0,0 +1,617 @@
+use std::{cmp::Ordering, error, fmt, str::FromStr};
+
+use winnow::{
+	combinator::{alt, preceded, repeat},
+	error::{ContextError, ParserError},
+	prelude::*,
+	token::none_of,
+	PResult,
+};
+
+use crate::JsonValue;
+
+/// An RFC 6901 JSON Pointer, stored as its unescaped reference tokens.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct JsonPointer {
+	tokens: Vec<String>,
+}
+
+impl JsonPointer {
+	/// The empty pointer, which refers to the whole document.
+	pub fn root() -> Self {
+		Self::default()
+	}
+
+	pub fn parse(pointer: &str) -> Result<Self, PointerError> {
+		repeat(
+			0..,
+			preceded('/', reference_token::<ContextError>),
+		)
+		.map(|tokens| Self { tokens })
+		.parse(pointer)
+		.map_err(|_| PointerError::Syntax(pointer.to_owned()))
+	}
+
+	pub fn tokens(&self) -> &[String] {
+		&self.tokens
+	}
+
+	pub fn is_root(&self) -> bool {
+		self.tokens.is_empty()
+	}
+
+	pub fn push(&mut self, token: impl Into<String>) {
+		self.tokens.push(token.into());
+	}
+
+	pub fn pop(&mut self) -> Option<String> {
+		self.tokens.pop()
+	}
+
+	/// Returns a new pointer with `token` appended.
+	pub fn join(&self, token: impl Into<String>) -> Self {
+		let mut pointer = self.clone();
+		pointer.push(token);
+		pointer
+	}
+
+	fn prefix(&self, len: usize) -> Self {
+		Self {
+			tokens: self.tokens[..len].to_vec(),
+		}
+	}
+}
+
+impl fmt::Display for JsonPointer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for token in &self.tokens {
+			write!(
+				f,
+				"/{}",
+				token.replace('~', "~0").replace('/', "~1")
+			)?;
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for JsonPointer {
+	type Err = PointerError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s)
+	}
+}
+
+impl<S: Into<String>> FromIterator<S> for JsonPointer {
+	fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+		Self {
+			tokens: iter.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+fn reference_token<'i, E>(input: &mut &'i str) -> PResult<String, E>
+where E: ParserError<&'i str> {
+	repeat(
+		0..,
+		alt((
+			"~0".value('~'),
+			"~1".value('/'),
+			none_of(['~', '/']),
+		)),
+	)
+	.parse_next(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerError {
+	/// The pointer text is not a valid JSON Pointer.
+	Syntax(String),
+	/// A token addressing an array is neither an index nor `-`.
+	InvalidIndex(JsonPointer),
+	/// A scalar value sits where a container was needed.
+	NotContainer(JsonPointer),
+	/// An array index is past the end of the array, where `-` or the
+	/// array's length would append.
+	OutOfBounds(JsonPointer),
+}
+
+impl fmt::Display for PointerError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Syntax(pointer) => {
+				write!(f, "invalid JSON pointer {pointer:?}")
+			}
+			Self::InvalidIndex(pointer) => {
+				write!(
+					f,
+					"invalid array index at \"{pointer}\""
+				)
+			}
+			Self::NotContainer(pointer) => {
+				write!(
+					f,
+					"value at \"{pointer}\" is not a container"
+				)
+			}
+			Self::OutOfBounds(pointer) => {
+				write!(
+					f,
+					"array index past the end at \"{pointer}\""
+				)
+			}
+		}
+	}
+}
+
+impl error::Error for PointerError {}
+
+/// Parses an array index token, rejecting leading zeros as RFC 6901 does.
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+	if token.is_empty()
+		|| (token.len() > 1 && token.starts_with('0'))
+		|| !token.bytes().all(|b| b.is_ascii_digit())
+	{
+		return None;
+	}
+	token.parse().ok()
+}
+
+impl JsonValue {
+	/// Looks up a value by JSON Pointer text, e.g. `"/a/b/0"`.
+	pub fn pointer(&self, pointer: &str) -> Option<&JsonValue> {
+		self.resolve(&JsonPointer::parse(pointer).ok()?)
+	}
+
+	pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JsonValue> {
+		self.resolve_mut(&JsonPointer::parse(pointer).ok()?)
+	}
+
+	pub fn resolve(&self, pointer: &JsonPointer) -> Option<&JsonValue> {
+		pointer
+			.tokens()
+			.iter()
+			.try_fold(self, |node, token| match node {
+				JsonValue::Object(map) => map.get(token),
+				JsonValue::Array(items) => items.get(parse_index(token)?),
+				_ => None,
+			})
+	}
+
+	pub fn resolve_mut(
+		&mut self,
+		pointer: &JsonPointer,
+	) -> Option<&mut JsonValue> {
+		pointer
+			.tokens()
+			.iter()
+			.try_fold(self, |node, token| match node {
+				JsonValue::Object(map) => map.get_mut(token),
+				JsonValue::Array(items) => items.get_mut(parse_index(token)?),
+				_ => None,
+			})
+	}
+
+	/// Like [`JsonValue::pointer_mut`], but creates any missing values on
+	/// the way.
+	///
+	/// `null`s along the path become arrays when the next token is an index
+	/// (or `-`) and objects otherwise. `-` or an array's length appends a
+	/// new element; an index past that fails rather than padding the array,
+	/// as RFC 6901 allows nothing further. A call that fails creates
+	/// nothing.
+	pub fn ensure_pointer(
+		&mut self,
+		pointer: &str,
+	) -> Result<&mut JsonValue, PointerError> {
+		self.ensure(&JsonPointer::parse(pointer)?)
+	}
+
+	pub fn ensure(
+		&mut self,
+		pointer: &JsonPointer,
+	) -> Result<&mut JsonValue, PointerError> {
+		self.check_ensure(pointer)?;
+		let mut node = self;
+
+		for (depth, token) in pointer.tokens().iter().enumerate() {
+			if let JsonValue::Null = node {
+				*node = if token == "-" || parse_index(token).is_some() {
+					JsonValue::Array(Vec::new())
+				} else {
+					JsonValue::Object(Box::default())
+				};
+			}
+
+			node = match node {
+				JsonValue::Object(map) => {
+					map.entry(token.clone()).or_insert(JsonValue::Null)
+				}
+				JsonValue::Array(items) => {
+					let index = match token.as_str() {
+						"-" => items.len(),
+						_ => parse_index(token).ok_or_else(|| {
+							PointerError::InvalidIndex(
+								pointer.prefix(depth + 1),
+							)
+						})?,
+					};
+					match index.cmp(&items.len()) {
+						Ordering::Less => {}
+						Ordering::Equal => items.push(JsonValue::Null),
+						Ordering::Greater => {
+							return Err(PointerError::OutOfBounds(
+								pointer.prefix(depth + 1),
+							))
+						}
+					}
+					&mut items[index]
+				}
+				_ => {
+					return Err(PointerError::NotContainer(
+						pointer.prefix(depth),
+					))
+				}
+			};
+		}
+
+		Ok(node)
+	}
+
+	/// Fails as [`JsonValue::ensure`] would, without changing anything, so
+	/// that a failing call leaves the tree as it was.
+	fn check_ensure(&self, pointer: &JsonPointer) -> Result<(), PointerError> {
+		// `None` once the path has left the tree, into values `ensure`
+		// would create.
+		let mut node = Some(self);
+
+		for (depth, token) in pointer.tokens().iter().enumerate() {
+			let index = || match token.as_str() {
+				"-" => Some(None),
+				token => parse_index(token).map(Some),
+			};
+			node = match node {
+				Some(JsonValue::Object(map)) => map.get(token),
+				Some(JsonValue::Array(items)) => {
+					let index = index().ok_or_else(|| {
+						PointerError::InvalidIndex(pointer.prefix(depth + 1))
+					})?;
+					match index.map(|index| index.cmp(&items.len())) {
+						Some(Ordering::Less) => index.map(|i| &items[i]),
+						None | Some(Ordering::Equal) => None,
+						Some(Ordering::Greater) => {
+							return Err(PointerError::OutOfBounds(
+								pointer.prefix(depth + 1),
+							))
+						}
+					}
+				}
+				// A new array is empty, so only its first item can be added.
+				Some(JsonValue::Null) | None => match index() {
+					Some(Some(index)) if index > 0 => {
+						return Err(PointerError::OutOfBounds(
+							pointer.prefix(depth + 1),
+						))
+					}
+					_ => None,
+				},
+				Some(_) => {
+					return Err(PointerError::NotContainer(
+						pointer.prefix(depth),
+					))
+				}
+			};
+		}
+
+		Ok(())
+	}
+}
+
+/// A Relative JSON Pointer, such as `1/foo` or `0#`, which is followed from
+/// a location in a tree rather than from the root: up some number of
+/// levels, then optionally over to a neighbouring array item, and then
+/// either down a JSON Pointer or, with `#`, to the key or index of the
+/// value reached.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RelativePointer {
+	up: usize,
+	/// The index manipulation, added to the array index reached by going
+	/// up.
+	shift: Option<isize>,
+	/// `None` for `#`.
+	then: Option<JsonPointer>,
+}
+
+/// What a [`RelativePointer`] leads to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelativeTarget<'a> {
+	Value(&'a JsonValue),
+	/// For a pointer ending in `#`, the key of the value reached as a
+	/// string, or its index as a number if it is an array item.
+	Name(JsonValue),
+}
+
+impl RelativePointer {
+	pub fn parse(pointer: &str) -> Result<Self, PointerError> {
+		let syntax = || PointerError::Syntax(pointer.to_owned());
+		let (up, rest) = leading_integer(pointer).ok_or_else(syntax)?;
+		let (shift, rest) = match rest.strip_prefix(['+', '-']) {
+			Some(after) => {
+				let (n, after) = leading_integer(after).ok_or_else(syntax)?;
+				let n = isize::try_from(n).map_err(|_| syntax())?;
+				let n = if rest.starts_with('-') { -n } else { n };
+				(Some(n), after)
+			}
+			None => (None, rest),
+		};
+		let then = match rest {
+			"#" => None,
+			_ => Some(JsonPointer::parse(rest).map_err(|_| syntax())?),
+		};
+		Ok(Self { up, shift, then })
+	}
+}
+
+/// Splits a non-negative integer without leading zeros off the front of
+/// `s`.
+fn leading_integer(s: &str) -> Option<(usize, &str)> {
+	let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+	Some((parse_index(&s[..len])?, &s[len..]))
+}
+
+impl fmt::Display for RelativePointer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.up)?;
+		if let Some(shift) = self.shift {
+			write!(f, "{shift:+}")?;
+		}
+		match &self.then {
+			Some(pointer) => write!(f, "{pointer}"),
+			None => f.write_str("#"),
+		}
+	}
+}
+
+impl FromStr for RelativePointer {
+	type Err = PointerError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s)
+	}
+}
+
+impl JsonValue {
+	/// Follows `pointer` from the value at `location` in this tree. `None`
+	/// if it leads nowhere: above the root, to a missing value, beyond
+	/// either end of an array, or to the name of the root.
+	pub fn resolve_relative(
+		&self,
+		location: &JsonPointer,
+		pointer: &RelativePointer,
+	) -> Option<RelativeTarget<'_>> {
+		let len = location.tokens().len().checked_sub(pointer.up)?;
+		let mut base = location.prefix(len);
+		if let Some(shift) = pointer.shift {
+			let index = parse_index(&base.pop()?)?;
+			let JsonValue::Array(items) = self.resolve(&base)? else {
+				return None;
+			};
+			let index = index.checked_add_signed(shift)?;
+			if index >= items.len() {
+				return None;
+			}
+			base.push(index.to_string());
+		}
+		let value = self.resolve(&base)?;
+		match &pointer.then {
+			Some(then) => value.resolve(then).map(RelativeTarget::Value),
+			None => {
+				let token = base.pop()?;
+				let name = match self.resolve(&base)? {
+					JsonValue::Array(_) => {
+						JsonValue::Number(parse_index(&token)? as f64)
+					}
+					_ => JsonValue::String(token),
+				};
+				Some(RelativeTarget::Name(name))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn ensure_appends_only_at_the_end() {
+		let mut value = parse(r#"{"a": [1]}"#).unwrap();
+		*value.ensure_pointer("/a/1").unwrap() = 2.0.into();
+		*value.ensure_pointer("/a/-").unwrap() = 3.0.into();
+		*value.ensure_pointer("/a/0").unwrap() = 0.0.into();
+		*value.ensure_pointer("/b/0/c").unwrap() = true.into();
+		assert_eq!(
+			value,
+			parse(r#"{"a": [0, 2, 3], "b": [{"c": true}]}"#).unwrap()
+		);
+
+		assert_eq!(
+			value.ensure_pointer("/a/4"),
+			Err(PointerError::OutOfBounds(
+				JsonPointer::parse("/a/4").unwrap()
+			)),
+		);
+		let huge = format!("/a/{}", usize::MAX);
+		assert!(matches!(
+			value.ensure_pointer(&huge),
+			Err(PointerError::OutOfBounds(_))
+		));
+		assert!(matches!(
+			value.ensure_pointer("/a/x"),
+			Err(PointerError::InvalidIndex(_))
+		));
+	}
+
+	#[test]
+	fn failed_ensure_leaves_the_value_unchanged() {
+		let original =
+			parse(r#"{"a": [1, {"b": null}], "s": "text", "n": null}"#)
+				.unwrap();
+		let at = |pointer| JsonPointer::parse(pointer).unwrap();
+		for (pointer, error) in [
+			(
+				"/new/x/3",
+				PointerError::OutOfBounds(at("/new/x/3")),
+			),
+			(
+				"/n/0/1",
+				PointerError::OutOfBounds(at("/n/0/1")),
+			),
+			(
+				"/a/-/2",
+				PointerError::OutOfBounds(at("/a/-/2")),
+			),
+			(
+				"/a/1/c/y/7",
+				PointerError::OutOfBounds(at("/a/1/c/y/7")),
+			),
+			(
+				"/a/y",
+				PointerError::InvalidIndex(at("/a/y")),
+			),
+			(
+				"/a/0/x",
+				PointerError::NotContainer(at("/a/0")),
+			),
+			(
+				"/s/x",
+				PointerError::NotContainer(at("/s")),
+			),
+		] {
+			let mut value = original.clone();
+			assert_eq!(
+				value.ensure_pointer(pointer),
+				Err(error),
+				"{pointer}"
+			);
+			assert_eq!(value, original, "{pointer}");
+		}
+		let mut value = original.clone();
+		*value.ensure_pointer("/n/0/0/k").unwrap() = true.into();
+		*value.ensure_pointer("/a/1/b/-/c").unwrap() = 1.0.into();
+		assert_eq!(
+			value,
+			parse(
+				r#"{"a": [1, {"b": [{"c": 1}]}], "s": "text",
+				"n": [[{"k": true}]]}"#
+			)
+			.unwrap()
+		);
+	}
+
+	fn relative<'a>(
+		value: &'a JsonValue,
+		location: &str,
+		pointer: &str,
+	) -> Option<RelativeTarget<'a>> {
+		let location = JsonPointer::parse(location).unwrap();
+		let pointer = RelativePointer::parse(pointer).unwrap();
+		value.resolve_relative(&location, &pointer)
+	}
+
+	#[test]
+	fn relative_pointers_follow_the_draft_examples() {
+		let value = parse(
+			r#"{"foo": ["bar", "baz"], "highly": {"nested": {"objects": true}}}"#,
+		)
+		.unwrap();
+		let bar = JsonValue::from("bar");
+		let baz = JsonValue::from("baz");
+		let yes = JsonValue::Boolean(true);
+		let name = |name: JsonValue| Some(RelativeTarget::Name(name));
+		for (pointer, expected) in [
+			("0", Some(RelativeTarget::Value(&baz))),
+			("1/0", Some(RelativeTarget::Value(&bar))),
+			("0-1", Some(RelativeTarget::Value(&bar))),
+			(
+				"2/highly/nested/objects",
+				Some(RelativeTarget::Value(&yes)),
+			),
+			("0#", name(1.0.into())),
+			("0-1#", name(0.0.into())),
+			("1#", name("foo".into())),
+		] {
+			assert_eq!(
+				relative(&value, "/foo/1", pointer),
+				expected,
+				"{pointer}"
+			);
+		}
+		for (pointer, expected) in [
+			(
+				"0/objects",
+				Some(RelativeTarget::Value(&yes)),
+			),
+			(
+				"1/nested/objects",
+				Some(RelativeTarget::Value(&yes)),
+			),
+			(
+				"2/foo/0",
+				Some(RelativeTarget::Value(&bar)),
+			),
+			("0#", name("nested".into())),
+			("1#", name("highly".into())),
+		] {
+			assert_eq!(
+				relative(&value, "/highly/nested", pointer),
+				expected,
+				"{pointer}"
+			);
+		}
+	}
+
+	#[test]
+	fn relative_pointers_may_lead_nowhere() {
+		let value =
+			parse(r#"{"foo": ["bar", "baz"], "obj": {"a": 1}}"#).unwrap();
+		for (location, pointer) in [
+			("/foo/1", "3"),
+			("/foo/1", "0+1"),
+			("/foo/0", "0-1"),
+			("/foo/1", "0/x"),
+			("", "0#"),
+			("/foo/1", "2#"),
+			("/obj/a", "0-1"),
+			("/obj/a", "1-1/x"),
+			("/foo/1", "1-1/x"),
+		] {
+			assert_eq!(
+				relative(&value, location, pointer),
+				None,
+				"{location} {pointer}"
+			);
+		}
+	}
+
+	#[test]
+	fn relative_pointers_parse_strictly() {
+		for pointer in ["0", "1/a~1b", "0#", "2+3/x", "1-1#", "0/"] {
+			let parsed = RelativePointer::parse(pointer).unwrap();
+			assert_eq!(parsed.to_string(), pointer);
+		}
+		for pointer in
+			["", "#", "01", "00#", "0-01", "0+", "-1", "0x", "0#/a", "1a"]
+		{
+			assert_eq!(
+				RelativePointer::parse(pointer),
+				Err(PointerError::Syntax(pointer.to_owned())),
+				"{pointer}"
+			);
+		}
+		let huge = format!("0+{}", usize::MAX);
+		assert!(RelativePointer::parse(&huge).is_err());
+	}
+}
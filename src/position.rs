@@ -0,0 +1,163 @@
+//! Conversion between byte offsets and the line/character positions of the
+//! Language Server Protocol, which counts characters in UTF-16 code units.
+
+use std::ops::Range;
+
+use crate::Node;
+
+/// A place in a text as the Language Server Protocol gives it: a 0-based
+/// line, and a 0-based count of UTF-16 code units into that line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+	pub line: u32,
+	pub character: u32,
+}
+
+/// Where the lines of a text start, for converting between byte offsets
+/// and [`Position`]s in it.
+///
+/// Lines end in `\n`, `\r\n` or `\r`. The index holds no copy of the text,
+/// so each conversion is given the text it was built from again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+	/// The offset at which each line starts.
+	starts: Vec<usize>,
+}
+
+impl LineIndex {
+	pub fn new(text: &str) -> Self {
+		let bytes = text.as_bytes();
+		let mut starts = vec![0];
+		for (i, &b) in bytes.iter().enumerate() {
+			let ends_line =
+				b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+			if ends_line {
+				starts.push(i + 1);
+			}
+		}
+		Self { starts }
+	}
+
+	/// The number of lines, which is one more than the number of line
+	/// breaks.
+	pub fn line_count(&self) -> usize {
+		self.starts.len()
+	}
+
+	/// The position of `offset`, which is clamped to the text and moved
+	/// back to the start of the character it falls inside.
+	pub fn position(&self, text: &str, offset: usize) -> Position {
+		let mut offset = offset.min(text.len());
+		while !text.is_char_boundary(offset) {
+			offset -= 1;
+		}
+		let line = self.starts.partition_point(|&start| start <= offset) - 1;
+		let character = text[self.starts[line]..offset]
+			.chars()
+			.map(char::len_utf16)
+			.sum::<usize>();
+		Position {
+			line: line as u32,
+			character: character as u32,
+		}
+	}
+
+	/// The offset of `position`.
+	///
+	/// As the protocol asks, a character past the end of its line means
+	/// the end of the line, and a line past the last means the end of the
+	/// text. A position between the two halves of a surrogate pair gives the
+	/// start of the character.
+	pub fn offset(&self, text: &str, position: Position) -> usize {
+		let Some(&start) = self.starts.get(position.line as usize) else {
+			return text.len();
+		};
+		let end = self
+			.starts
+			.get(position.line as usize + 1)
+			.map_or(text.len(), |&next| next);
+		let line = text[start..end].trim_end_matches(['\r', '\n']);
+		let mut units = 0;
+		for (i, c) in line.char_indices() {
+			units += c.len_utf16();
+			if units > position.character as usize {
+				return start + i;
+			}
+		}
+		start + line.len()
+	}
+
+	/// The positions of the start and end of `span`.
+	pub fn range(&self, text: &str, span: &Range<usize>) -> Range<Position> {
+		self.position(text, span.start)..self.position(text, span.end)
+	}
+}
+
+impl Node {
+	/// The positions of the start and end of the node in `text`, the source
+	/// it was parsed from and that `lines` indexes.
+	pub fn range(&self, text: &str, lines: &LineIndex) -> Range<Position> {
+		lines.range(text, &self.span)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn at(line: u32, character: u32) -> Position {
+		Position { line, character }
+	}
+
+	#[test]
+	fn positions_count_utf16_units() {
+		let text = "a😀é\r\nb\rc\n";
+		let lines = LineIndex::new(text);
+		assert_eq!(lines.line_count(), 4);
+		for (offset, position) in [
+			(0, at(0, 0)),
+			(1, at(0, 1)),
+			(5, at(0, 3)),
+			(7, at(0, 4)),
+			(9, at(1, 0)),
+			(11, at(2, 0)),
+			(13, at(3, 0)),
+		] {
+			assert_eq!(
+				lines.position(text, offset),
+				position,
+				"{offset}"
+			);
+			assert_eq!(
+				lines.offset(text, position),
+				offset,
+				"{offset}"
+			);
+		}
+		// Inside the emoji, and past the end.
+		assert_eq!(lines.position(text, 3), at(0, 1));
+		assert_eq!(lines.position(text, 100), at(3, 0));
+	}
+
+	#[test]
+	fn offsets_are_clamped_as_the_protocol_asks() {
+		let text = "a😀é\r\nb\rc\n";
+		let lines = LineIndex::new(text);
+		assert_eq!(lines.offset(text, at(0, 2)), 1);
+		assert_eq!(lines.offset(text, at(0, 99)), 7);
+		assert_eq!(lines.offset(text, at(1, 99)), 10);
+		assert_eq!(lines.offset(text, at(9, 0)), text.len());
+		assert_eq!(
+			lines.range(text, &(9..12)),
+			at(1, 0)..at(2, 1)
+		);
+
+		let node = crate::parse_spanned("{\r\n  \"k\": [1]\r\n}").unwrap();
+		let text = "{\r\n  \"k\": [1]\r\n}";
+		let lines = LineIndex::new(text);
+		assert_eq!(
+			node.range(text, &lines),
+			at(0, 0)..at(2, 1)
+		);
+	}
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use winnow::{
+	combinator::{cut_err, delimited, preceded, separated, terminated},
+	prelude::*,
+	stream::Stream,
+	PResult,
+};
+
+use crate::{
+	error::ContextTrail,
+	options::too_deep,
+	parser::{json_value, skip_nested, string, ws},
+	Error, JsonPointer, JsonValue, ParseOptions,
+};
+
+/// Parses only the parts of `input` that `pointers` refer to.
+///
+/// Everything off the requested paths is checked for syntax but skipped
+/// without being built. The result keeps the document's shape around the
+/// selected values: objects hold just the members on some path, and arrays
+/// are padded with `null` before a selected element so indices still
+/// match. Pointers that resolve to nothing are ignored, and a document
+/// with no selected values projects to `null`. Nesting is limited as in
+/// [`parse`](crate::parse), selected or not.
+pub fn parse_projection(
+	input: &str,
+	pointers: &[JsonPointer],
+) -> Result<JsonValue, Error> {
+	let mut path = JsonPointer::root();
+	let projection = delimited(
+		ws,
+		|input: &mut &str| project(input, pointers, &mut path),
+		ws,
+	)
+	.parse(input);
+	projection
+		.map(|value| value.unwrap_or(JsonValue::Null))
+		.map_err(|e| Error::from_parse(input, e))
+}
+
+fn project(
+	input: &mut &str,
+	pointers: &[JsonPointer],
+	path: &mut JsonPointer,
+) -> PResult<Option<JsonValue>, ContextTrail> {
+	let max = ParseOptions::DEFAULT_MAX_DEPTH;
+	let depth = path.tokens().len();
+	let selected = pointers.iter().any(|pointer| pointer == path);
+	let on_path = pointers
+		.iter()
+		.any(|pointer| pointer.tokens().starts_with(path.tokens()));
+	match input.chars().next() {
+		Some('{' | '[') if depth >= max => Err(too_deep(max)),
+		Some('{') if on_path && !selected => object(input, pointers, path),
+		Some('[') if on_path && !selected => array(input, pointers, path),
+		_ => {
+			// Checked without recursing first, so that building a selected
+			// value can't go too deep either.
+			let start = input.checkpoint();
+			if !skip_nested(input, max - depth)? {
+				return Err(too_deep(max));
+			}
+			if !selected {
+				return Ok(None);
+			}
+			input.reset(start);
+			json_value.map(Some).parse_next(input)
+		}
+	}
+}
+
+fn object(
+	input: &mut &str,
+	pointers: &[JsonPointer],
+	path: &mut JsonPointer,
+) -> PResult<Option<JsonValue>, ContextTrail> {
+	let mut map = HashMap::new();
+	let member = |input: &mut &str| {
+		let key = string.parse_next(input)?;
+		cut_err((ws, ':', ws)).parse_next(input)?;
+		path.push(key.as_str());
+		let value = project(input, pointers, path);
+		path.pop();
+		if let Some(value) = value? {
+			map.insert(key, value);
+		}
+		Ok(())
+	};
+	preceded(
+		('{', ws),
+		cut_err(terminated(
+			separated::<_, _, (), _, _, _, _>(0.., member, (ws, ',', ws)),
+			(ws, '}'),
+		)),
+	)
+	.context("object")
+	.parse_next(input)?;
+	Ok((!map.is_empty()).then_some(JsonValue::Object(Box::new(map))))
+}
+
+fn array(
+	input: &mut &str,
+	pointers: &[JsonPointer],
+	path: &mut JsonPointer,
+) -> PResult<Option<JsonValue>, ContextTrail> {
+	let mut items = Vec::new();
+	let mut index = 0;
+	let item = |input: &mut &str| {
+		path.push(index.to_string());
+		let value = project(input, pointers, path);
+		path.pop();
+		if let Some(value) = value? {
+			items.resize(index, JsonValue::Null);
+			items.push(value);
+		}
+		index += 1;
+		Ok(())
+	};
+	preceded(
+		('[', ws),
+		cut_err(terminated(
+			separated::<_, _, (), _, _, _, _>(0.., item, (ws, ',', ws)),
+			(ws, ']'),
+		)),
+	)
+	.context("array")
+	.parse_next(input)?;
+	Ok((!items.is_empty()).then_some(JsonValue::Array(items)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ErrorKind;
+
+	#[test]
+	fn deep_input_is_too_deep_whether_selected_or_not() {
+		let deep = "[".repeat(100_000);
+		let input = format!(r#"{{"a": {deep}, "b": 1}}"#);
+		let mut long = JsonPointer::root();
+		long.push("a");
+		for _ in 0..1_000 {
+			long.push("0");
+		}
+		for pointers in [
+			vec!["/a".parse().unwrap()],
+			vec!["/b".parse().unwrap()],
+			vec![long],
+		] {
+			let error = parse_projection(&input, &pointers).unwrap_err();
+			assert_eq!(
+				error.kind(),
+				ErrorKind::TooDeep,
+				"{pointers:?}"
+			);
+			assert_eq!(
+				error.offset(),
+				crate::parse(&input).unwrap_err().offset()
+			);
+		}
+		let pointers = ["/a/0".parse().unwrap()];
+		assert_eq!(
+			parse_projection(
+				r#"{"a": [[1]], "b": [[[]]]}"#,
+				&pointers
+			),
+			crate::parse(r#"{"a": [[1]]}"#)
+		);
+	}
+}
@@ -0,0 +1,353 @@
+//! Conversion between objects and URL query strings, as sent by HTML forms,
+//! with bracket notation such as `user[name]=x` for nesting.
+
+use std::{
+	error,
+	fmt::{self, Write},
+	mem,
+};
+
+use crate::{ConversionError, JsonValue};
+
+/// How many bracketed segments a key may nest; the rest of a deeper key
+/// is taken as one literal segment.
+const MAX_DEPTH: usize = 32;
+
+impl JsonValue {
+	/// Encodes an object as a query string, such as `a=1&b[c]=x&d[0]=y`.
+	///
+	/// Nested objects are written with their keys in brackets and arrays
+	/// with their indices, and members are ordered by key. Strings are
+	/// written as they are, other scalars as JSON and `null` as an empty
+	/// value; empty arrays and objects have no representation and are left
+	/// out. Names and values are percent-encoded, with `+` for spaces.
+	///
+	/// Fails if the value is not an object.
+	pub fn to_query_string(&self) -> Result<String, ConversionError> {
+		let JsonValue::Object(map) = self else {
+			return Err(ConversionError {
+				expected: "object",
+				found: self.type_of(),
+			});
+		};
+		let mut members: Vec<_> = map.iter().collect();
+		members.sort_unstable_by_key(|&(key, _)| key);
+		let mut out = String::new();
+		for (key, value) in members {
+			let mut name = String::new();
+			encode(key, &mut name);
+			write_pairs(value, &mut name, &mut out);
+		}
+		Ok(out)
+	}
+
+	/// Decodes a query string, with or without its leading `?`, into an
+	/// object whose values are all strings.
+	///
+	/// Bracketed segments in a name nest into objects, or into arrays when
+	/// they are empty (`a[]`, appending) or indices (`a[0]`, which appends
+	/// too unless an item is already there). A name given more than once
+	/// collects its values into an array, as form fields with several
+	/// selected options do.
+	///
+	/// Fails on a percent escape that is malformed or does not decode to
+	/// UTF-8, and on a name that would both hold a value and nest others,
+	/// such as `a` in `a[b]=1&a=2`, rather than dropping either.
+	pub fn from_query_string(
+		query: &str,
+	) -> Result<JsonValue, QueryStringError> {
+		let query = query.strip_prefix('?').unwrap_or(query);
+		let mut root = JsonValue::Object(Box::default());
+		for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+			let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+			insert(
+				&mut root,
+				name,
+				&segments(name)?,
+				decode(value)?,
+			)?;
+		}
+		Ok(root)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryStringError {
+	/// The text has a `%` not followed by two hex digits, or escapes bytes
+	/// that are not UTF-8.
+	InvalidEscape(String),
+	/// The name nests under a name that holds a value, or holds a value
+	/// where other names nest.
+	Conflict(String),
+}
+
+impl fmt::Display for QueryStringError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidEscape(text) => {
+				write!(f, "invalid percent escape in {text:?}")
+			}
+			Self::Conflict(name) => write!(f, "conflicting value at {name:?}"),
+		}
+	}
+}
+
+impl error::Error for QueryStringError {}
+
+impl JsonValue {}
+
+fn write_pairs(value: &JsonValue, name: &mut String, out: &mut String) {
+	let len = name.len();
+	match value {
+		JsonValue::Array(items) => {
+			for (i, item) in items.iter().enumerate() {
+				let _ = write!(name, "[{i}]");
+				write_pairs(item, name, out);
+				name.truncate(len);
+			}
+		}
+		JsonValue::Object(map) => {
+			let mut members: Vec<_> = map.iter().collect();
+			members.sort_unstable_by_key(|&(key, _)| key);
+			for (key, value) in members {
+				name.push('[');
+				encode(key, name);
+				name.push(']');
+				write_pairs(value, name, out);
+				name.truncate(len);
+			}
+		}
+		_ => {
+			if !out.is_empty() {
+				out.push('&');
+			}
+			out.push_str(name);
+			out.push('=');
+			match value {
+				JsonValue::Null => {}
+				JsonValue::String(s) => encode(s, out),
+				_ => encode(&value.to_string(), out),
+			}
+		}
+	}
+}
+
+/// Percent-encodes everything but unreserved characters, writing spaces
+/// as `+`.
+fn encode(s: &str, out: &mut String) {
+	for b in s.bytes() {
+		match b {
+			b'A'..=b'Z'
+			| b'a'..=b'z'
+			| b'0'..=b'9'
+			| b'-'
+			| b'.'
+			| b'_'
+			| b'~' => out.push(b.into()),
+			b' ' => out.push('+'),
+			_ => {
+				let _ = write!(out, "%{b:02X}");
+			}
+		}
+	}
+}
+
+fn decode(s: &str) -> Result<String, QueryStringError> {
+	let invalid = || QueryStringError::InvalidEscape(s.to_owned());
+	let bytes = s.as_bytes();
+	let mut out = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'%' => {
+				let hex = s
+					.get(i + 1..i + 3)
+					.filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+					.ok_or_else(invalid)?;
+				out.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+				i += 3;
+				continue;
+			}
+			b'+' => out.push(b' '),
+			b => out.push(b),
+		}
+		i += 1;
+	}
+	String::from_utf8(out).map_err(|_| invalid())
+}
+
+/// Splits a name such as `a[b][]` into its decoded segments.
+fn segments(name: &str) -> Result<Vec<String>, QueryStringError> {
+	let (base, mut rest) = match name.find('[') {
+		Some(bracket) if bracket > 0 => name.split_at(bracket),
+		_ => return Ok(vec![decode(name)?]),
+	};
+	let mut segments = vec![decode(base)?];
+	while segments.len() <= MAX_DEPTH {
+		let Some((segment, after)) = rest
+			.strip_prefix('[')
+			.and_then(|inner| inner.split_once(']'))
+		else {
+			break;
+		};
+		segments.push(decode(segment)?);
+		rest = after;
+	}
+	match rest {
+		"" => Ok(segments),
+		// Brackets that do not pair up are just part of the name.
+		_ if segments.len() == 1 => Ok(vec![decode(name)?]),
+		_ => {
+			segments.push(decode(rest)?);
+			Ok(segments)
+		}
+	}
+}
+
+/// Whether a segment addresses an array item rather than an object member.
+fn is_index(segment: &str) -> bool {
+	segment.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn insert(
+	root: &mut JsonValue,
+	name: &str,
+	segments: &[String],
+	value: String,
+) -> Result<(), QueryStringError> {
+	let conflict = || QueryStringError::Conflict(name.to_owned());
+	let mut node = root;
+	for (i, segment) in segments.iter().enumerate() {
+		node = child(node, segment);
+		if let Some(next) = segments.get(i + 1) {
+			match node {
+				JsonValue::Array(_) | JsonValue::Object(_) => {}
+				JsonValue::Null => {
+					*node = if is_index(next) {
+						JsonValue::Array(Vec::new())
+					} else {
+						JsonValue::Object(Box::default())
+					};
+				}
+				_ => return Err(conflict()),
+			}
+		}
+	}
+	match node {
+		JsonValue::Null => *node = JsonValue::String(value),
+		JsonValue::String(first) => {
+			let first = mem::take(first);
+			*node = JsonValue::Array(vec![
+				JsonValue::String(first),
+				JsonValue::String(value),
+			]);
+		}
+		JsonValue::Array(items) => items.push(JsonValue::String(value)),
+		_ => return Err(conflict()),
+	}
+	Ok(())
+}
+
+/// The item or member of a collection that `segment` addresses, added as
+/// `null` if missing. An array given a name that is not an index becomes
+/// an object keyed by the indices of its items.
+fn child<'v>(node: &'v mut JsonValue, segment: &str) -> &'v mut JsonValue {
+	if let JsonValue::Array(items) = node {
+		if !is_index(segment) {
+			let items = mem::take(items);
+			*node = JsonValue::Object(Box::new(
+				items
+					.into_iter()
+					.enumerate()
+					.map(|(i, item)| (i.to_string(), item))
+					.collect(),
+			));
+		}
+	}
+	match node {
+		JsonValue::Array(items) => {
+			let index = segment
+				.parse::<usize>()
+				.ok()
+				.filter(|&index| index < items.len());
+			match index {
+				Some(index) => &mut items[index],
+				None => {
+					items.push(JsonValue::Null);
+					items.last_mut().unwrap()
+				}
+			}
+		}
+		JsonValue::Object(map) => {
+			map.entry(segment.to_owned()).or_insert(JsonValue::Null)
+		}
+		_ => unreachable!("only collections have children"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn decoded(query: &str) -> Result<JsonValue, QueryStringError> {
+		JsonValue::from_query_string(query)
+	}
+
+	#[test]
+	fn nesting_and_repeats() {
+		assert_eq!(
+			decoded("?a=1&b[c]=x+y&b[d][]=1&b[d][]=2&e=3&e=4&f[0]=p&f[1]=q"),
+			Ok(parse(
+				r#"{"a": "1", "b": {"c": "x y", "d": ["1", "2"]},
+				"e": ["3", "4"], "f": ["p", "q"]}"#
+			)
+			.unwrap())
+		);
+		assert_eq!(
+			decoded("a%5Bb%5D=%C3%A9&c[=1"),
+			Ok(parse(r#"{"a[b]": "é", "c[": "1"}"#).unwrap())
+		);
+		assert_eq!(decoded(""), Ok(parse("{}").unwrap()));
+	}
+
+	#[test]
+	fn invalid_escapes() {
+		for query in ["a=%ZZ", "a=%4", "%=1", "a=%FF", "a[%G0]=1"] {
+			assert!(
+				matches!(
+					decoded(query),
+					Err(QueryStringError::InvalidEscape(_))
+				),
+				"{query}"
+			);
+		}
+	}
+
+	#[test]
+	fn conflicts() {
+		for query in ["a[b]=1&a=2", "a=2&a[b]=1", "a[b]=1&a[b][c]=2"] {
+			assert!(
+				matches!(
+					decoded(query),
+					Err(QueryStringError::Conflict(_))
+				),
+				"{query}"
+			);
+		}
+		assert_eq!(
+			decoded("a[]=1&a=2"),
+			Ok(parse(r#"{"a": ["1", "2"]}"#).unwrap())
+		);
+	}
+
+	#[test]
+	fn round_trip() {
+		let value = parse(
+			r#"{"a": "x&y=z", "b": {"c": ["1", "2"], "d e": "%"}, "f": "é"}"#,
+		)
+		.unwrap();
+		let query = value.to_query_string().unwrap();
+		assert_eq!(decoded(&query), Ok(value));
+	}
+}
@@ -0,0 +1,120 @@
+//! Parsing straight from files, sockets and other readers.
+
+use std::{
+	error, fmt,
+	io::{self, Read},
+	str,
+};
+
+use crate::{Error, JsonValue};
+
+/// An error from [`from_reader`], [`from_slice`], [`from_csv`](crate::from_csv), or
+/// `from_file_mmap` with the `mmap` feature.
+#[derive(Debug)]
+pub enum ReadError {
+	/// The input could not be read.
+	Io(io::Error),
+	/// The input is not UTF-8.
+	Utf8(str::Utf8Error),
+	Parse(Error),
+}
+
+impl fmt::Display for ReadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "failed to read input: {e}"),
+			Self::Utf8(e) => write!(f, "input is not UTF-8: {e}"),
+			Self::Parse(e) => e.fmt(f),
+		}
+	}
+}
+
+impl error::Error for ReadError {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Utf8(e) => Some(e),
+			Self::Parse(e) => Some(e),
+		}
+	}
+}
+
+impl From<io::Error> for ReadError {
+	fn from(e: io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+/// Parses a document from bytes, which must be UTF-8, such as a request
+/// body.
+pub fn from_slice(bytes: &[u8]) -> Result<JsonValue, ReadError> {
+	let text = str::from_utf8(bytes).map_err(ReadError::Utf8)?;
+	crate::parse(text).map_err(ReadError::Parse)
+}
+
+/// Reads `reader` to the end and parses what it held as one document.
+///
+/// Compressed input needs no special support: wrap the reader in a
+/// decompressor first, such as `flate2::read::GzDecoder` or
+/// `zstd::Decoder`.
+///
+/// ```text
+/// let value = from_reader(GzDecoder::new(File::open("data.json.gz")?))?;
+/// ```
+pub fn from_reader(mut reader: impl Read) -> Result<JsonValue, ReadError> {
+	let mut bytes = Vec::new();
+	reader.read_to_end(&mut bytes)?;
+	from_slice(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ErrorKind;
+
+	#[test]
+	fn deep_input_is_a_parse_error() {
+		let input = "[".repeat(100_000);
+		for result in
+			[from_slice(input.as_bytes()), from_reader(input.as_bytes())]
+		{
+			match result {
+				Err(ReadError::Parse(e)) => {
+					assert_eq!(e.kind(), ErrorKind::TooDeep);
+				}
+				other => panic!("expected a parse error, got {other:?}"),
+			}
+		}
+		assert!(matches!(
+			from_slice(b"[\xff]"),
+			Err(ReadError::Utf8(_))
+		));
+	}
+
+	#[test]
+	fn values_own_their_strings() {
+		let buffer = br#"{"body": "shared?", "list": ["a", "b"]}"#.to_vec();
+		let value = from_slice(&buffer).unwrap();
+		drop(buffer);
+		assert_eq!(
+			value,
+			crate::parse(r#"{"body": "shared?", "list": ["a", "b"]}"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn from_slice_checks_utf8_then_syntax() {
+		assert_eq!(
+			from_slice(br#"{"a": [1, "\u00e9"]}"#).unwrap(),
+			crate::parse(r#"{"a": [1, "é"]}"#).unwrap()
+		);
+		match from_slice(b"[\"\xff\"]") {
+			Err(ReadError::Utf8(e)) => assert_eq!(e.valid_up_to(), 2),
+			other => panic!("expected a UTF-8 error, got {other:?}"),
+		}
+		match from_slice(b"[1,]") {
+			Err(ReadError::Parse(e)) => assert_eq!(e.kind(), ErrorKind::Syntax),
+			other => panic!("expected a parse error, got {other:?}"),
+		}
+	}
+}
@@ -0,0 +1,292 @@
+//! Masking secrets in a document before it is logged.
+
+use crate::{JsonPointer, JsonValue, PointerError};
+
+/// Which values [`JsonValue::redact`] hides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+	/// Values at locations matching a JSON Pointer in which a `*` token
+	/// stands for any one key or index and `**` for any number of them, as
+	/// in `/users/*/password` or `/**/token`.
+	Path(JsonPointer),
+	/// The values of members anywhere in the tree whose key matches a
+	/// pattern, ignoring ASCII case, in which `*` stands for any run of
+	/// characters, as in `*password*` or `api_key`.
+	Key(String),
+}
+
+impl Selector {
+	/// A [`Selector::Path`] from its pointer text.
+	pub fn path(pattern: &str) -> Result<Self, PointerError> {
+		JsonPointer::parse(pattern).map(Selector::Path)
+	}
+
+	pub fn key(pattern: impl Into<String>) -> Self {
+		Selector::Key(pattern.into())
+	}
+
+	/// Whether the value at `path` is selected, given whether it is the
+	/// value of an object member.
+	fn matches(&self, path: &[String], member: bool) -> bool {
+		match self {
+			Selector::Path(pattern) => path_matches(pattern.tokens(), path),
+			Selector::Key(pattern) => {
+				member
+					&& path.last().is_some_and(|key| {
+						glob_matches(pattern.as_bytes(), key.as_bytes())
+					})
+			}
+		}
+	}
+}
+
+/// What [`JsonValue::redact`] does with a value it hides.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplaceWith {
+	/// Replaces it with a fixed value, such as `"[REDACTED]"` or `null`.
+	Value(JsonValue),
+	/// Replaces it with a string of its first `visible` characters, if it
+	/// is a string long enough to keep some hidden, followed by `****`, as
+	/// in `"sk-****"`. The mask has the same length whatever the secret's.
+	Mask { visible: usize },
+	/// Removes it from the object or array holding it. A matching root is
+	/// replaced with `null`.
+	Remove,
+}
+
+impl JsonValue {
+	/// Hides every value that one of `selectors` matches, returning how
+	/// many were hidden. A hidden value's contents are not looked at, so
+	/// the values under it are not counted.
+	///
+	/// Array items matched by index are matched at their indices before
+	/// any removal.
+	pub fn redact(
+		&mut self,
+		selectors: &[Selector],
+		replace: &ReplaceWith,
+	) -> usize {
+		let mut path = Vec::new();
+		if selectors
+			.iter()
+			.any(|selector| selector.matches(&path, false))
+		{
+			*self = match replace {
+				ReplaceWith::Remove => JsonValue::Null,
+				replace => masked(self, replace),
+			};
+			return 1;
+		}
+		redact(self, selectors, replace, &mut path)
+	}
+}
+
+fn redact(
+	node: &mut JsonValue,
+	selectors: &[Selector],
+	replace: &ReplaceWith,
+	path: &mut Vec<String>,
+) -> usize {
+	let mut count = 0;
+	let member = matches!(node, JsonValue::Object(_));
+	let mut visit = |token: String, child: &mut JsonValue| {
+		path.push(token);
+		let hidden = selectors
+			.iter()
+			.any(|selector| selector.matches(path, member));
+		if hidden {
+			count += 1;
+			if *replace != ReplaceWith::Remove {
+				*child = masked(child, replace);
+			}
+		} else {
+			count += redact(child, selectors, replace, path);
+		}
+		path.pop();
+		// Whether to keep the child.
+		!hidden || *replace != ReplaceWith::Remove
+	};
+	match node {
+		JsonValue::Array(items) => {
+			let mut index = 0;
+			items.retain_mut(|item| {
+				index += 1;
+				visit((index - 1).to_string(), item)
+			});
+		}
+		JsonValue::Object(map) => {
+			map.retain(|key, value| visit(key.clone(), value));
+		}
+		_ => {}
+	}
+	count
+}
+
+fn masked(value: &JsonValue, replace: &ReplaceWith) -> JsonValue {
+	match replace {
+		ReplaceWith::Value(value) => value.clone(),
+		ReplaceWith::Mask { visible } => {
+			let shown: String = match value {
+				JsonValue::String(s) if s.chars().count() > *visible => {
+					s.chars().take(*visible).collect()
+				}
+				_ => String::new(),
+			};
+			JsonValue::String(shown + "****")
+		}
+		ReplaceWith::Remove => JsonValue::Null,
+	}
+}
+
+fn path_matches(pattern: &[String], path: &[String]) -> bool {
+	wildcard(
+		pattern,
+		path,
+		|token| token == "**",
+		|token, key| token == "*" || token == key,
+	)
+}
+
+/// Whether `text` matches `pattern`, in which `*` matches any run of bytes,
+/// ignoring ASCII case.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+	wildcard(
+		pattern,
+		text,
+		|&b| b == b'*',
+		|b, c| b.eq_ignore_ascii_case(c),
+	)
+}
+
+/// Whether `text` matches `pattern`, in which a `star` matches any run of
+/// items and anything else matches one item it `matches`.
+///
+/// A failed match resumes from the last star, taking one more item into
+/// it. Earlier stars need not be revisited, since whatever a later star
+/// is retried with an earlier one could have been too, so this takes time
+/// proportional to the two lengths multiplied at worst and no recursion.
+fn wildcard<P, T>(
+	pattern: &[P],
+	text: &[T],
+	star: impl Fn(&P) -> bool,
+	matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+	let (mut p, mut t) = (0, 0);
+	// The position after the last star, and where in the text it ends.
+	let mut resume = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some(token) if star(token) => {
+				p += 1;
+				resume = Some((p, t));
+			}
+			Some(token) if matches(token, &text[t]) => {
+				p += 1;
+				t += 1;
+			}
+			_ => match resume {
+				Some((after, end)) => {
+					p = after;
+					t = end + 1;
+					resume = Some((after, t));
+				}
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(star)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tokens(path: &str) -> Vec<String> {
+		JsonPointer::parse(path).unwrap().tokens().to_vec()
+	}
+
+	#[test]
+	fn globs() {
+		let glob = |pattern: &str, text: &str| {
+			glob_matches(pattern.as_bytes(), text.as_bytes())
+		};
+		assert!(glob("api_key", "API_KEY"));
+		assert!(glob("*password*", "user_Password_hash"));
+		assert!(glob("*", ""));
+		assert!(glob("a*b*c", "aXbYbZc"));
+		assert!(!glob("a*b*c", "aXbYbZ"));
+		assert!(!glob("token", "tokens"));
+		assert!(!glob("*token", "tokens"));
+		assert!(glob("**a", "a"));
+	}
+
+	#[test]
+	fn paths() {
+		let matches = |pattern: &str, path: &str| {
+			path_matches(&tokens(pattern), &tokens(path))
+		};
+		assert!(matches(
+			"/users/*/password",
+			"/users/0/password"
+		));
+		assert!(!matches(
+			"/users/*/password",
+			"/users/password"
+		));
+		assert!(!matches(
+			"/users/*/password",
+			"/users/0/1/password"
+		));
+		assert!(matches("/**/token", "/token"));
+		assert!(matches("/**/token", "/a/b/c/token"));
+		assert!(!matches("/**/token", "/a/token/b"));
+		assert!(matches("/a/**", "/a"));
+		assert!(matches("/**/*/x/**", "/q/w/x/e/r"));
+		assert!(!matches("/**/*/x/**", "/x"));
+		assert!(matches("", ""));
+		assert!(!matches("", "/a"));
+	}
+
+	#[test]
+	fn hostile_patterns_do_not_backtrack() {
+		let text = "a".repeat(10_000);
+		let pattern = format!("{}b", "*a".repeat(5_000));
+		assert!(!glob_matches(
+			pattern.as_bytes(),
+			text.as_bytes()
+		));
+
+		let pattern = tokens(&"/**/a".repeat(1_000));
+		let path = vec!["a".to_owned(); 2_000];
+		assert!(path_matches(&pattern, &path));
+		let mut pattern = pattern;
+		pattern.push("b".to_owned());
+		assert!(!path_matches(&pattern, &path));
+	}
+
+	#[test]
+	fn redacts_what_selectors_match() {
+		let mut value = crate::parse(
+			r#"{"users": [{"name": "a", "Password": "hunter2"}],
+			"auth": {"token": "sk-123456"}}"#,
+		)
+		.unwrap();
+		let selectors = [
+			Selector::key("*password*"),
+			Selector::path("/**/token").unwrap(),
+		];
+		let count = value.redact(
+			&selectors,
+			&ReplaceWith::Mask { visible: 3 },
+		);
+		assert_eq!(count, 2);
+		assert_eq!(
+			value,
+			crate::parse(
+				r#"{"users": [{"name": "a", "Password": "hun****"}],
+				"auth": {"token": "sk-****"}}"#
+			)
+			.unwrap()
+		);
+	}
+}
@@ -0,0 +1,487 @@
+//! Rewriting the layout of JSON as it streams past, token by token, for
+//! files too large to hold as a [`JsonValue`](crate::JsonValue).
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::{lexer::is_number, Formatter, PrettyFormatter, TokenKind};
+
+/// Copies the JSON read from `reader` to `writer` without whitespace or
+/// comments.
+///
+/// Only one token is held at a time, so memory use is bounded by the
+/// longest string or number in the input rather than its size. Values in a
+/// stream of several documents, such as JSON Lines, are written one per
+/// line. The tokens are not checked to form a document; a token that is
+/// not JSON, such as a misspelled literal or an unterminated string, fails
+/// with [`io::ErrorKind::InvalidData`] once everything before it has been
+/// written.
+pub fn minify(reader: impl Read, mut writer: impl Write) -> io::Result<()> {
+	let mut tokens = StreamTokens::new(reader);
+	let mut after_value = false;
+	while let Some((kind, text)) = tokens.next_token()? {
+		if kind.is_trivia() {
+			continue;
+		}
+		if after_value && starts_value(kind) {
+			writer.write_all(b"\n")?;
+		}
+		after_value = ends_value(kind);
+		writer.write_all(&text)?;
+	}
+	writer.flush()
+}
+
+/// Copies the JSON read from `reader` to `writer` laid out by `formatter`,
+/// as [`JsonValue::write_with`](crate::JsonValue::write_with) would write
+/// it, without parsing it into a value.
+///
+/// Memory use is bounded by the longest string or number in the input,
+/// plus a line's worth with [`PrettyFormatter::max_width`]. Strings and
+/// numbers are copied as they are spelled, so escapes and digits beyond
+/// an `f64`'s precision are kept and [`PrettyFormatter::numbers`] has no
+/// effect. Comments are dropped. Each document in a stream of several,
+/// such as JSON Lines, is formatted on its own and followed by a line
+/// break.
+///
+/// Input that is not JSON fails with [`io::ErrorKind::InvalidData`] once
+/// everything before the offending token has been written.
+pub fn reformat(
+	reader: impl Read,
+	mut writer: impl Write,
+	formatter: PrettyFormatter,
+) -> io::Result<()> {
+	let mut tokens = StreamTokens::new(reader);
+	let mut f = formatter.clone();
+	// The containers open around the next token: whether each is an
+	// object, and whether it is still empty.
+	let mut open: Vec<(bool, bool)> = Vec::new();
+	let mut expect = Expect::Value;
+	loop {
+		let start = tokens.offset;
+		let Some((kind, text)) = tokens.next_token()? else {
+			if open.is_empty() && expect == Expect::Value {
+				return writer.flush();
+			}
+			return Err(unexpected("end of input", start));
+		};
+		let out = &mut writer;
+		match (expect, kind) {
+			(_, kind) if kind.is_trivia() => continue,
+			(Expect::ValueOrEnd | Expect::CommaOrEnd, TokenKind::EndArray)
+				if open.last().is_some_and(|&(object, _)| !object) =>
+			{
+				open.pop();
+				f.end_array(out)?;
+			}
+			(Expect::KeyOrEnd | Expect::CommaOrEnd, TokenKind::EndObject)
+				if open.last().is_some_and(|&(object, _)| object) =>
+			{
+				open.pop();
+				f.end_object(out)?;
+			}
+			(Expect::Value | Expect::ValueOrEnd, kind)
+				if starts_value(kind) =>
+			{
+				if let Some((false, first)) = open.last_mut() {
+					f.begin_array_value(out, std::mem::take(first))?;
+				}
+				match kind {
+					TokenKind::StartArray => {
+						f.begin_array(out)?;
+						open.push((false, true));
+						expect = Expect::ValueOrEnd;
+						continue;
+					}
+					TokenKind::StartObject => {
+						f.begin_object(out)?;
+						open.push((true, true));
+						expect = Expect::KeyOrEnd;
+						continue;
+					}
+					_ => f.write_raw(out, &text)?,
+				}
+			}
+			(Expect::Key | Expect::KeyOrEnd, TokenKind::String) => {
+				if let Some((true, first)) = open.last_mut() {
+					f.begin_object_key(out, std::mem::take(first))?;
+				}
+				f.write_raw(out, &text)?;
+				f.end_object_key(out)?;
+				expect = Expect::Colon;
+				continue;
+			}
+			(Expect::Colon, TokenKind::Colon) => {
+				f.begin_object_value(out)?;
+				expect = Expect::Value;
+				continue;
+			}
+			(Expect::CommaOrEnd, TokenKind::Comma) => {
+				expect = match open.last() {
+					Some((true, _)) => Expect::Key,
+					_ => Expect::Value,
+				};
+				continue;
+			}
+			_ => return Err(unexpected("token", start)),
+		}
+		// A value has just ended.
+		match open.last() {
+			Some((false, _)) => {
+				f.end_array_value(out)?;
+				expect = Expect::CommaOrEnd;
+			}
+			Some((true, _)) => {
+				f.end_object_value(out)?;
+				expect = Expect::CommaOrEnd;
+			}
+			None => {
+				out.write_all(b"\n")?;
+				f = formatter.clone();
+				expect = Expect::Value;
+			}
+		}
+	}
+}
+
+/// What [`reformat`] accepts next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+	Value,
+	/// A value or the end of the array just opened.
+	ValueOrEnd,
+	Key,
+	/// A key or the end of the object just opened.
+	KeyOrEnd,
+	Colon,
+	/// A comma or the end of the innermost container.
+	CommaOrEnd,
+}
+
+fn unexpected(what: &str, offset: usize) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("unexpected {what} at byte {offset}"),
+	)
+}
+
+fn starts_value(kind: TokenKind) -> bool {
+	!matches!(
+		kind,
+		TokenKind::EndObject
+			| TokenKind::EndArray
+			| TokenKind::Colon
+			| TokenKind::Comma
+	)
+}
+
+fn ends_value(kind: TokenKind) -> bool {
+	!matches!(
+		kind,
+		TokenKind::StartObject
+			| TokenKind::StartArray
+			| TokenKind::Colon
+			| TokenKind::Comma
+	)
+}
+
+/// The tokens of a reader, lexed as [`tokenize`](crate::tokenize) lexes a
+/// string, except that text that starts no token is an error.
+struct StreamTokens<R> {
+	reader: BufReader<R>,
+	/// The number of bytes consumed so far.
+	offset: usize,
+}
+
+impl<R: Read> StreamTokens<R> {
+	fn new(reader: R) -> Self {
+		Self {
+			reader: BufReader::new(reader),
+			offset: 0,
+		}
+	}
+
+	fn peek(&mut self) -> io::Result<Option<u8>> {
+		Ok(self.reader.fill_buf()?.first().copied())
+	}
+
+	/// Moves the next byte into `text`.
+	fn take(&mut self, text: &mut Vec<u8>) -> io::Result<Option<u8>> {
+		let b = self.peek()?;
+		if let Some(b) = b {
+			self.reader.consume(1);
+			self.offset += 1;
+			text.push(b);
+		}
+		Ok(b)
+	}
+
+	/// Moves bytes into `text` as long as `more` accepts them.
+	fn take_while(
+		&mut self,
+		text: &mut Vec<u8>,
+		more: impl Fn(u8) -> bool,
+	) -> io::Result<()> {
+		while self.peek()?.is_some_and(&more) {
+			self.take(text)?;
+		}
+		Ok(())
+	}
+
+	fn next_token(&mut self) -> io::Result<Option<(TokenKind, Vec<u8>)>> {
+		let start = self.offset;
+		let mut text = Vec::new();
+		let Some(b) = self.take(&mut text)? else {
+			return Ok(None);
+		};
+		let kind = match b {
+			b' ' | b'\t' | b'\r' | b'\n' => {
+				self.take_while(&mut text, |b| {
+					matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+				})?;
+				TokenKind::Whitespace
+			}
+			b'{' => TokenKind::StartObject,
+			b'}' => TokenKind::EndObject,
+			b'[' => TokenKind::StartArray,
+			b']' => TokenKind::EndArray,
+			b':' => TokenKind::Colon,
+			b',' => TokenKind::Comma,
+			b'"' => self.string(&mut text)?,
+			b'/' => self.comment(&mut text)?,
+			b'-' | b'+' | b'.' | b'0'..=b'9' => {
+				self.take_while(&mut text, |b| {
+					b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.')
+				})?;
+				match std::str::from_utf8(&text) {
+					Ok(number) if is_number(number) => TokenKind::Number,
+					_ => TokenKind::Error,
+				}
+			}
+			b'a'..=b'z' | b'A'..=b'Z' => {
+				self.take_while(&mut text, |b| b.is_ascii_alphanumeric())?;
+				match &text[..] {
+					b"true" => TokenKind::True,
+					b"false" => TokenKind::False,
+					b"null" => TokenKind::Null,
+					_ => TokenKind::Error,
+				}
+			}
+			_ => TokenKind::Error,
+		};
+		if kind == TokenKind::Error {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("invalid JSON at byte {start}"),
+			));
+		}
+		Ok(Some((kind, text)))
+	}
+
+	/// Lexes the rest of a string, which a line break or the end of the
+	/// input leaves unterminated.
+	fn string(&mut self, text: &mut Vec<u8>) -> io::Result<TokenKind> {
+		loop {
+			match self.peek()? {
+				None | Some(b'\n') => return Ok(TokenKind::Error),
+				Some(b'"') => {
+					self.take(text)?;
+					return Ok(TokenKind::String);
+				}
+				Some(b'\\') => {
+					self.take(text)?;
+					if self.peek()?.is_some_and(|b| b != b'\n') {
+						self.take(text)?;
+					}
+				}
+				Some(_) => {
+					self.take(text)?;
+				}
+			}
+		}
+	}
+
+	fn comment(&mut self, text: &mut Vec<u8>) -> io::Result<TokenKind> {
+		match self.peek()? {
+			Some(b'/') => {
+				self.take_while(text, |b| !matches!(b, b'\r' | b'\n'))?;
+				Ok(TokenKind::LineComment)
+			}
+			Some(b'*') => {
+				self.take(text)?;
+				while self.take(text)?.is_some() {
+					if text.len() >= 4 && text.ends_with(b"*/") {
+						return Ok(TokenKind::BlockComment);
+					}
+				}
+				Ok(TokenKind::Error)
+			}
+			_ => Ok(TokenKind::Error),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn minified(input: &str) -> (String, Option<io::Error>) {
+		let mut out = Vec::new();
+		let error = minify(input.as_bytes(), &mut out).err();
+		(String::from_utf8(out).unwrap(), error)
+	}
+
+	#[test]
+	fn minify_drops_whitespace_and_comments() {
+		let (out, error) = minified(
+			"{\n  \"a\\u0041\": [1.50, -0e1, true], // note\n  \"b\" /* x */ : null\n}\n",
+		);
+		assert!(error.is_none());
+		assert_eq!(
+			out,
+			r#"{"a\u0041":[1.50,-0e1,true],"b":null}"#
+		);
+	}
+
+	#[test]
+	fn minify_puts_each_document_on_a_line() {
+		let (out, error) =
+			minified("{\"a\": 1}\n\n{\"b\": [2, 3]}\n\"c\" 4 [] null\n");
+		assert!(error.is_none());
+		assert_eq!(
+			out,
+			"{\"a\":1}\n{\"b\":[2,3]}\n\"c\"\n4\n[]\nnull"
+		);
+	}
+
+	#[test]
+	fn minify_fails_at_the_first_bad_token() {
+		let (out, error) = minified("[1, tru, 3]");
+		let error = error.unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+		assert_eq!(
+			error.to_string(),
+			"invalid JSON at byte 4"
+		);
+		assert_eq!(out, "[1,");
+
+		let (out, error) = minified("[\"open\n\"]");
+		assert_eq!(
+			error.unwrap().to_string(),
+			"invalid JSON at byte 1"
+		);
+		assert_eq!(out, "[");
+		assert!(minified("1 /* open").1.is_some());
+	}
+
+	fn reformatted(
+		input: &str,
+		formatter: PrettyFormatter,
+	) -> (String, Option<io::Error>) {
+		let mut out = Vec::new();
+		let error = reformat(input.as_bytes(), &mut out, formatter).err();
+		(String::from_utf8(out).unwrap(), error)
+	}
+
+	#[test]
+	fn reformat_matches_write_with() {
+		for input in [
+			"[]",
+			"{}",
+			r#"[1, [2, []], {"a": {"b": [true, null]}}, {}]"#,
+			r#"{"k": [{"x": "a long enough string to break the line"}]}"#,
+		] {
+			let value = crate::parse(input).unwrap();
+			for formatter in [
+				PrettyFormatter::new(),
+				PrettyFormatter::new().tabs().newline("\r\n"),
+				PrettyFormatter::new().space_in_empty(true),
+				PrettyFormatter::new().max_width(30),
+			] {
+				let mut expected = Vec::new();
+				value.write_with(&mut expected, formatter.clone()).unwrap();
+				let (out, error) = reformatted(input, formatter);
+				assert!(error.is_none(), "{input}");
+				assert_eq!(
+					out,
+					format!(
+						"{}\n",
+						String::from_utf8(expected).unwrap()
+					)
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn reformat_keeps_spelling_and_drops_comments() {
+		let (out, error) = reformatted(
+			"// header\n[1.50, \"\\u0041\" /* a */, 1e400]",
+			PrettyFormatter::new(),
+		);
+		assert!(error.is_none());
+		assert_eq!(
+			out,
+			"[\n  1.50,\n  \"\\u0041\",\n  1e400\n]\n"
+		);
+	}
+
+	#[test]
+	fn reformat_formats_each_document() {
+		let (out, error) = reformatted(
+			"{\"a\": [1]}\n[]\n2",
+			PrettyFormatter::new(),
+		);
+		assert!(error.is_none());
+		assert_eq!(
+			out,
+			"{\n  \"a\": [\n    1\n  ]\n}\n[]\n2\n"
+		);
+	}
+
+	#[test]
+	fn reformat_checks_the_structure() {
+		for (input, message, written) in [
+			(
+				"[1 2]",
+				"unexpected token at byte 3",
+				"[\n  1",
+			),
+			(
+				"[1,]",
+				"unexpected token at byte 3",
+				"[\n  1",
+			),
+			(
+				r#"{"a" 1}"#,
+				"unexpected token at byte 5",
+				"{\n  \"a\"",
+			),
+			(
+				"{1: 2}",
+				"unexpected token at byte 1",
+				"{",
+			),
+			(
+				"[1}",
+				"unexpected token at byte 2",
+				"[\n  1",
+			),
+			(
+				"[1",
+				"unexpected end of input at byte 2",
+				"[\n  1",
+			),
+			("nul", "invalid JSON at byte 0", ""),
+		] {
+			let (out, error) = reformatted(input, PrettyFormatter::new());
+			let error = error.unwrap();
+			assert_eq!(
+				error.kind(),
+				io::ErrorKind::InvalidData,
+				"{input}"
+			);
+			assert_eq!(error.to_string(), message, "{input}");
+			assert_eq!(out, written, "{input}");
+		}
+	}
+}
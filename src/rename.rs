@@ -0,0 +1,235 @@
+//! Converting object keys between naming conventions.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::JsonValue;
+
+/// A naming convention for [`JsonValue::rename_keys`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCase {
+	/// `snake_case`
+	Snake,
+	/// `camelCase`
+	Camel,
+	/// `kebab-case`
+	Kebab,
+}
+
+impl KeyCase {
+	/// Writes `key` in this convention.
+	///
+	/// Words are separated by `_`, `-` and spaces, by a lower-case letter
+	/// or digit followed by an upper-case one, and at the end of a run of
+	/// capitals before a capitalized word, so `HTTPServer_id` is `http`,
+	/// `server` and `id`. Digits belong to the word they follow.
+	pub fn convert(self, key: &str) -> String {
+		let words = words(key);
+		match self {
+			KeyCase::Snake => lower(&words).join("_"),
+			KeyCase::Kebab => lower(&words).join("-"),
+			KeyCase::Camel => words
+				.iter()
+				.enumerate()
+				.map(|(i, word)| {
+					let word = word.to_lowercase();
+					if i == 0 {
+						return word;
+					}
+					let mut chars = word.chars();
+					match chars.next() {
+						Some(first) => {
+							first.to_uppercase().chain(chars).collect()
+						}
+						None => word,
+					}
+				})
+				.collect(),
+		}
+	}
+}
+
+fn lower(words: &[&str]) -> Vec<String> {
+	words.iter().map(|word| word.to_lowercase()).collect()
+}
+
+fn words(key: &str) -> Vec<&str> {
+	let mut words = Vec::new();
+	let chars: Vec<(usize, char)> = key.char_indices().collect();
+	let mut start = None;
+	for (i, &(at, c)) in chars.iter().enumerate() {
+		if matches!(c, '_' | '-' | ' ') {
+			if let Some(start) = start.take() {
+				words.push(&key[start..at]);
+			}
+			continue;
+		}
+		let boundary = i > 0 && c.is_uppercase() && {
+			let previous = chars[i - 1].1;
+			let next = chars.get(i + 1).map(|&(_, c)| c);
+			previous.is_lowercase()
+				|| previous.is_ascii_digit()
+				|| (previous.is_uppercase()
+					&& next.is_some_and(char::is_lowercase))
+		};
+		match start {
+			Some(word) if boundary => {
+				words.push(&key[word..at]);
+				start = Some(at);
+			}
+			Some(_) => {}
+			None => start = Some(at),
+		}
+	}
+	if let Some(start) = start {
+		words.push(&key[start..]);
+	}
+	words
+}
+
+impl JsonValue {
+	/// Rewrites the keys of every object in the tree in the `case`
+	/// convention, as [`KeyCase::convert`] does.
+	///
+	/// Members whose key is in `exclude` are left as they are, value and
+	/// all, for keys that are data rather than names, such as the keys of
+	/// a map of user IDs. If two keys of an object come out the same, the
+	/// member kept is the one whose key was excluded or already in the
+	/// `case` convention, or failing that the one whose key sorts first.
+	pub fn rename_keys(&mut self, case: KeyCase, exclude: &[&str]) {
+		match self {
+			JsonValue::Array(items) => items
+				.iter_mut()
+				.for_each(|item| item.rename_keys(case, exclude)),
+			JsonValue::Object(map) => {
+				let mut renamed = HashMap::with_capacity(map.len());
+				for (key, mut value) in std::mem::take(&mut **map) {
+					let converted = match exclude.contains(&key.as_str()) {
+						true => key.clone(),
+						false => {
+							value.rename_keys(case, exclude);
+							case.convert(&key)
+						}
+					};
+					// Members that keep their key rank first.
+					let rank = (key != converted, key);
+					match renamed.entry(converted) {
+						Entry::Vacant(entry) => {
+							entry.insert((rank, value));
+						}
+						Entry::Occupied(mut entry) if rank < entry.get().0 => {
+							entry.insert((rank, value));
+						}
+						Entry::Occupied(_) => {}
+					}
+				}
+				**map = renamed
+					.into_iter()
+					.map(|(key, (_, value))| (key, value))
+					.collect();
+			}
+			_ => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[track_caller]
+	fn check(key: &str, snake: &str, camel: &str, kebab: &str) {
+		assert_eq!(KeyCase::Snake.convert(key), snake);
+		assert_eq!(KeyCase::Camel.convert(key), camel);
+		assert_eq!(KeyCase::Kebab.convert(key), kebab);
+	}
+
+	#[test]
+	fn converts_keys() {
+		check(
+			"HTTPServer_id",
+			"http_server_id",
+			"httpServerId",
+			"http-server-id",
+		);
+		check("userID", "user_id", "userId", "user-id");
+		check(
+			"already_snake",
+			"already_snake",
+			"alreadySnake",
+			"already-snake",
+		);
+		check(
+			"  spaced--out__",
+			"spaced_out",
+			"spacedOut",
+			"spaced-out",
+		);
+		check("", "", "", "");
+	}
+
+	#[test]
+	fn digits_belong_to_the_word_they_follow() {
+		check("v2Api", "v2_api", "v2Api", "v2-api");
+		check("utf8", "utf8", "utf8", "utf8");
+		check(
+			"area51Base",
+			"area51_base",
+			"area51Base",
+			"area51-base",
+		);
+		check(
+			"2fa_code", "2fa_code", "2faCode", "2fa-code",
+		);
+	}
+
+	#[test]
+	fn converts_non_ascii_keys() {
+		check(
+			"straßeName",
+			"straße_name",
+			"straßeName",
+			"straße-name",
+		);
+		check(
+			"ÄpfelBaum",
+			"äpfel_baum",
+			"äpfelBaum",
+			"äpfel-baum",
+		);
+		check(
+			"élan_vital",
+			"élan_vital",
+			"élanVital",
+			"élan-vital",
+		);
+		check("名前", "名前", "名前", "名前");
+	}
+
+	#[test]
+	fn colliding_keys_keep_a_chosen_member() {
+		let mut value =
+			parse(r#"{"userId": 1, "user_id": 2, "user-id": 3}"#).unwrap();
+		value.rename_keys(KeyCase::Snake, &[]);
+		assert_eq!(
+			value,
+			parse(r#"{"user_id": 2}"#).unwrap()
+		);
+
+		let mut value = parse(r#"{"userId": 1, "user-id": 3}"#).unwrap();
+		value.rename_keys(KeyCase::Snake, &[]);
+		assert_eq!(
+			value,
+			parse(r#"{"user_id": 3}"#).unwrap()
+		);
+
+		let mut value = parse(r#"{"a_b": {"cD": 1}, "aB": 2}"#).unwrap();
+		value.rename_keys(KeyCase::Camel, &["a_b"]);
+		assert_eq!(
+			value,
+			parse(r#"{"a_b": {"cD": 1}, "aB": 2}"#).unwrap()
+		);
+		value.rename_keys(KeyCase::Camel, &[]);
+		assert_eq!(value, parse(r#"{"aB": 2}"#).unwrap());
+	}
+}
@@ -0,0 +1,754 @@
+//! Recovery of documents that are not quite JSON: hand-written text with
+//! the usual mistakes in it, and documents cut off partway through.
+
+use std::{collections::HashMap, ops::Range};
+
+use winnow::Parser as _;
+
+use crate::{
+	error::ContextTrail,
+	escape_json_string,
+	lexer::{check_number, is_number, number_len},
+	parser, Error, ErrorKind, JsonValue,
+};
+
+/// How deeply arrays and objects may nest, so hostile input cannot exhaust
+/// the stack.
+const MAX_DEPTH: usize = 512;
+
+/// An edit to the input of [`parse_lenient_with_fixes`] that corrects one
+/// mistake in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+	/// The bytes of the input to replace, empty when the fix only inserts.
+	pub span: Range<usize>,
+	/// What was wrong, such as `"trailing comma"`.
+	pub description: &'static str,
+	/// The text to put in place of the span.
+	pub replacement: String,
+}
+
+/// Parses a document that may contain the mistakes commonly made when
+/// writing JSON by hand, giving its value and the fixes that would turn
+/// the input into valid JSON, ordered by position.
+///
+/// The mistakes accepted are strings and keys in single quotes, keys
+/// without quotes that are identifiers, a comma after the last item or
+/// member, a missing comma between items or members that whitespace,
+/// a quote or a bracket keeps apart, and numbers spelled with leading
+/// zeros, a plus sign, or no digits on one side of the decimal point, as in
+/// `01`, `+1`, `.5` and `1.`. Everything else must be valid JSON.
+pub fn parse_lenient_with_fixes(
+	input: &str,
+) -> Result<(JsonValue, Vec<Fix>), Error> {
+	let mut lenient = Lenient {
+		input,
+		pos: 0,
+		depth: 0,
+		fixes: Vec::new(),
+	};
+	lenient.skip_ws();
+	let value = lenient.value()?;
+	lenient.skip_ws();
+	if lenient.pos < input.len() {
+		return Err(lenient.unexpected());
+	}
+	Ok((value, lenient.fixes))
+}
+
+/// Spells a number literal that only [`parse_lenient_with_fixes`] accepts
+/// the way JSON does, with a description of the first thing changed, or
+/// `None` if it is not a number even so.
+fn normalize_number(literal: &str) -> Option<(String, &'static str)> {
+	let mut description = None;
+	let mut note = |what| {
+		description.get_or_insert(what);
+	};
+	let mut normalized = String::new();
+	let mut rest = literal;
+	if let Some(after) = rest.strip_prefix('+') {
+		note("plus sign before number");
+		rest = after;
+	} else if let Some(after) = rest.strip_prefix('-') {
+		normalized.push('-');
+		rest = after;
+	}
+	let len = rest
+		.find(|c: char| !c.is_ascii_digit())
+		.unwrap_or(rest.len());
+	let (int, after) = rest.split_at(len);
+	rest = after;
+	if int.is_empty() {
+		if !rest
+			.strip_prefix('.')?
+			.starts_with(|c: char| c.is_ascii_digit())
+		{
+			return None;
+		}
+		note("number without a digit before the decimal point");
+		normalized.push('0');
+	} else if int.len() > 1 && int.starts_with('0') {
+		note("number with leading zeros");
+		match int.trim_start_matches('0') {
+			"" => normalized.push('0'),
+			trimmed => normalized.push_str(trimmed),
+		}
+	} else {
+		normalized.push_str(int);
+	}
+	if let Some(after) = rest.strip_prefix('.') {
+		let len = after
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(after.len());
+		if len == 0 {
+			note("number without a digit after the decimal point");
+		} else {
+			normalized.push('.');
+			normalized.push_str(&after[..len]);
+		}
+		rest = &after[len..];
+	}
+	if !rest.is_empty() && !rest.starts_with(['e', 'E']) {
+		return None;
+	}
+	normalized.push_str(rest);
+	let description = description?;
+	is_number(&normalized).then_some((normalized, description))
+}
+
+struct Lenient<'i> {
+	input: &'i str,
+	pos: usize,
+	/// The number of arrays and objects being parsed.
+	depth: usize,
+	fixes: Vec<Fix>,
+}
+
+impl<'i> Lenient<'i> {
+	fn error_at(&self, offset: usize, message: impl Into<String>) -> Error {
+		Error::new(self.input, offset, message.into())
+	}
+
+	fn error(&self, message: impl Into<String>) -> Error {
+		self.error_at(self.pos, message)
+	}
+
+	fn unexpected(&self) -> Error {
+		match self.rest().chars().next() {
+			Some(c) => self.error(format!("unexpected character {c:?}")),
+			None => self.error("unexpected end of input"),
+		}
+	}
+
+	fn byte(&self) -> Option<u8> {
+		self.input.as_bytes().get(self.pos).copied()
+	}
+
+	fn rest(&self) -> &'i str {
+		&self.input[self.pos..]
+	}
+
+	fn skip_ws(&mut self) {
+		while matches!(
+			self.byte(),
+			Some(b' ' | b'\t' | b'\r' | b'\n')
+		) {
+			self.pos += 1;
+		}
+	}
+
+	fn fix(
+		&mut self,
+		span: Range<usize>,
+		description: &'static str,
+		replacement: impl Into<String>,
+	) {
+		self.fixes.push(Fix {
+			span,
+			description,
+			replacement: replacement.into(),
+		});
+	}
+
+	fn value(&mut self) -> Result<JsonValue, Error> {
+		if self.depth == MAX_DEPTH {
+			return Err(self
+				.error("nesting too deep")
+				.with_kind(ErrorKind::TooDeep));
+		}
+		self.depth += 1;
+		let value = self.nested();
+		self.depth -= 1;
+		value
+	}
+
+	fn nested(&mut self) -> Result<JsonValue, Error> {
+		match self.byte() {
+			Some(b'[') => self.array(),
+			Some(b'{') => self.object(),
+			Some(b'"' | b'\'') => self.string().map(JsonValue::String),
+			Some(b'-' | b'+' | b'.' | b'0'..=b'9') => self.number(),
+			_ => self.literal(),
+		}
+	}
+
+	fn literal(&mut self) -> Result<JsonValue, Error> {
+		for (word, value) in [
+			("null", JsonValue::Null),
+			("true", JsonValue::Boolean(true)),
+			("false", JsonValue::Boolean(false)),
+		] {
+			if self.rest().starts_with(word) {
+				self.pos += word.len();
+				return Ok(value);
+			}
+		}
+		Err(self.unexpected())
+	}
+
+	/// Reads a number, which may be spelled `01`, `+1`, `.5` or `1.`.
+	fn number(&mut self) -> Result<JsonValue, Error> {
+		let start = self.pos;
+		let literal = &self.rest()[..number_len(self.rest())];
+		let (normalized, description) = match check_number(literal) {
+			Ok(()) => (literal.to_owned(), None),
+			Err(message) => {
+				let (normalized, description) = normalize_number(literal)
+					.ok_or_else(|| self.error(message))?;
+				(normalized, Some(description))
+			}
+		};
+		let n = normalized
+			.parse()
+			.map_err(|_| self.error("invalid number"))?;
+		self.pos += literal.len();
+		if let Some(description) = description {
+			self.fix(start..self.pos, description, normalized);
+		}
+		Ok(JsonValue::Number(n))
+	}
+
+	/// Reads a string in double or single quotes, which may escape a single
+	/// quote too.
+	fn string(&mut self) -> Result<String, Error> {
+		let start = self.pos;
+		let quote = char::from(self.input.as_bytes()[start]);
+		self.pos += 1;
+		let mut text = String::new();
+		loop {
+			match self.rest().chars().next() {
+				Some(c) if c == quote => break,
+				Some('\\') => {
+					let mut rest = &self.rest()[1..];
+					let c = match rest.strip_prefix('\'') {
+						Some(after) if quote == '\'' => {
+							rest = after;
+							'\''
+						}
+						_ => parser::escape::<_, ContextTrail>
+							.parse_next(&mut rest)
+							.map_err(|_| {
+								self.error("invalid escape sequence")
+							})?,
+					};
+					text.push(c);
+					self.pos = self.input.len() - rest.len();
+				}
+				Some(c) if c < ' ' => {
+					return Err(self.error("control character in string"))
+				}
+				Some(c) => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+				None => return Err(self.error_at(start, "unterminated string")),
+			}
+		}
+		self.pos += 1;
+		if quote == '\'' {
+			self.fix(
+				start..self.pos,
+				"single-quoted string",
+				quoted(&text),
+			);
+		}
+		Ok(text)
+	}
+
+	/// Reads a key, which may also be an identifier without quotes.
+	fn key(&mut self) -> Result<String, Error> {
+		if matches!(self.byte(), Some(b'"' | b'\'')) {
+			return self.string();
+		}
+		let start = self.pos;
+		let rest = self.rest();
+		let len = rest
+			.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+			.unwrap_or(rest.len());
+		let key = &rest[..len];
+		if !key.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$')
+		{
+			return Err(self.unexpected());
+		}
+		self.pos += len;
+		self.fix(
+			start..self.pos,
+			"unquoted key",
+			quoted(key),
+		);
+		Ok(key.to_owned())
+	}
+
+	fn array(&mut self) -> Result<JsonValue, Error> {
+		self.pos += 1;
+		self.skip_ws();
+		let mut items = Vec::new();
+		if self.byte() != Some(b']') {
+			loop {
+				items.push(self.value()?);
+				if !self.separator(b']', starts_value)? {
+					break;
+				}
+			}
+		}
+		self.pos += 1;
+		Ok(JsonValue::Array(items))
+	}
+
+	fn object(&mut self) -> Result<JsonValue, Error> {
+		self.pos += 1;
+		self.skip_ws();
+		let mut map = HashMap::new();
+		if self.byte() != Some(b'}') {
+			loop {
+				let key = self.key()?;
+				self.skip_ws();
+				if self.byte() != Some(b':') {
+					return Err(self.unexpected());
+				}
+				self.pos += 1;
+				self.skip_ws();
+				let value = self.value()?;
+				map.insert(key, value);
+				if !self.separator(b'}', starts_key)? {
+					break;
+				}
+			}
+		}
+		self.pos += 1;
+		Ok(JsonValue::Object(Box::new(map)))
+	}
+
+	/// Reads what follows an item or member up to the next one, or up to
+	/// the `close` bracket, returning whether another comes next.
+	fn separator(
+		&mut self,
+		close: u8,
+		starts_next: fn(u8) -> bool,
+	) -> Result<bool, Error> {
+		let end = self.pos;
+		self.skip_ws();
+		match self.byte() {
+			Some(b',') => {
+				let comma = self.pos;
+				self.pos += 1;
+				self.skip_ws();
+				if self.byte() == Some(close) {
+					self.fix(comma..comma + 1, "trailing comma", "");
+					return Ok(false);
+				}
+				Ok(true)
+			}
+			Some(b) if b == close => Ok(false),
+			// Without anything between them, `1-2` or `truex` could be
+			// meant as one token.
+			Some(b)
+				if starts_next(b)
+					&& (self.pos > end
+						|| is_delimiter(self.input.as_bytes()[end - 1])
+						|| is_delimiter(b)) =>
+			{
+				self.fix(end..end, "missing comma", ",");
+				Ok(true)
+			}
+			_ => Err(self.unexpected()),
+		}
+	}
+}
+
+fn starts_value(b: u8) -> bool {
+	matches!(
+		b,
+		b'[' | b'{' | b'"' | b'\'' | b'-' | b'0'..=b'9' | b't' | b'f' | b'n'
+	)
+}
+
+fn starts_key(b: u8) -> bool {
+	matches!(b, b'"' | b'\'' | b'_' | b'$')
+		|| b.is_ascii_alphabetic()
+		|| b >= 0x80
+}
+
+/// Whether `b` is a quote or bracket, which cannot run into a neighbouring
+/// token.
+fn is_delimiter(b: u8) -> bool {
+	matches!(
+		b,
+		b'"' | b'\'' | b'[' | b']' | b'{' | b'}'
+	)
+}
+
+fn quoted(s: &str) -> String {
+	format!("\"{}\"", escape_json_string(s))
+}
+
+/// Recovers what a document cut off partway through, as by an interrupted
+/// write, holds so far, closing the strings, arrays and objects left open
+/// at the end of the input.
+///
+/// Parsing stops at the end of the input or at the first thing that is
+/// not valid JSON there. An item or member that is incomplete at that
+/// point, such as a key without its value or a literal cut short, is left
+/// out, while a string keeps the characters read. Returns the value along
+/// with the length of the prefix of the input it was built from, which is
+/// `null` and 0 if not even the start of a value was found.
+pub fn repair_truncated(input: &str) -> (JsonValue, usize) {
+	let mut truncated = Truncated {
+		input,
+		pos: 0,
+		end: 0,
+		depth: 0,
+		stopped: false,
+	};
+	let value = truncated.value().unwrap_or(JsonValue::Null);
+	(value, truncated.end)
+}
+
+struct Truncated<'i> {
+	input: &'i str,
+	pos: usize,
+	/// The end of what has been taken into the value.
+	end: usize,
+	/// The number of arrays and objects being parsed.
+	depth: usize,
+	/// Whether the end of what can be parsed was reached, so that every
+	/// array and object still open closes there.
+	stopped: bool,
+}
+
+impl Truncated<'_> {
+	fn byte(&self) -> Option<u8> {
+		self.input.as_bytes().get(self.pos).copied()
+	}
+
+	fn skip_ws(&mut self) {
+		while matches!(
+			self.byte(),
+			Some(b' ' | b'\t' | b'\r' | b'\n')
+		) {
+			self.pos += 1;
+		}
+	}
+
+	fn stop<T>(&mut self) -> Option<T> {
+		self.stopped = true;
+		None
+	}
+
+	/// Reads a value, or as much of one as there is; `None` if there is
+	/// nothing to keep of it.
+	fn value(&mut self) -> Option<JsonValue> {
+		self.skip_ws();
+		let value = match self.byte() {
+			Some(b'[' | b'{') if self.depth == MAX_DEPTH => self.stop()?,
+			Some(b'[') => {
+				self.depth += 1;
+				let array = self.array();
+				self.depth -= 1;
+				array
+			}
+			Some(b'{') => {
+				self.depth += 1;
+				let object = self.object();
+				self.depth -= 1;
+				object
+			}
+			Some(b'"') => {
+				let string = self.string();
+				self.end = self.pos;
+				JsonValue::String(string)
+			}
+			Some(b'-' | b'0'..=b'9') => {
+				let mut rest = &self.input[self.pos..];
+				let n = parser::number::<_, ContextTrail>
+					.parse_next(&mut rest)
+					.ok()
+					.or_else(|| self.stop())?;
+				self.pos = self.input.len() - rest.len();
+				self.end = self.pos;
+				JsonValue::Number(n)
+			}
+			_ => {
+				let rest = &self.input[self.pos..];
+				let (word, value) = [
+					("null", JsonValue::Null),
+					("true", JsonValue::Boolean(true)),
+					("false", JsonValue::Boolean(false)),
+				]
+				.into_iter()
+				.find(|(word, _)| rest.starts_with(word))
+				.or_else(|| self.stop())?;
+				self.pos += word.len();
+				self.end = self.pos;
+				value
+			}
+		};
+		Some(value)
+	}
+
+	fn array(&mut self) -> JsonValue {
+		self.pos += 1;
+		self.end = self.pos;
+		let mut items = Vec::new();
+		while self.next_item(b']', !items.is_empty()) {
+			match self.value() {
+				Some(item) => items.push(item),
+				None => break,
+			}
+		}
+		JsonValue::Array(items)
+	}
+
+	fn object(&mut self) -> JsonValue {
+		self.pos += 1;
+		self.end = self.pos;
+		let mut map = HashMap::new();
+		while self.next_item(b'}', !map.is_empty()) {
+			if self.byte() != Some(b'"') {
+				self.stopped = true;
+				break;
+			}
+			let key = self.string();
+			self.skip_ws();
+			if self.stopped || self.byte() != Some(b':') {
+				self.stopped = true;
+				break;
+			}
+			self.pos += 1;
+			match self.value() {
+				Some(value) => {
+					map.insert(key, value);
+				}
+				None => break,
+			}
+		}
+		JsonValue::Object(Box::new(map))
+	}
+
+	/// Moves to the next item or member unless the `close` bracket comes
+	/// first, returning whether there is one to read.
+	fn next_item(&mut self, close: u8, after_item: bool) -> bool {
+		if self.stopped {
+			return false;
+		}
+		self.skip_ws();
+		if self.byte() == Some(close) {
+			self.pos += 1;
+			self.end = self.pos;
+			return false;
+		}
+		if after_item {
+			if self.byte() != Some(b',') {
+				self.stopped = true;
+				return false;
+			}
+			self.pos += 1;
+			self.skip_ws();
+		}
+		true
+	}
+
+	/// Reads a string up to its closing quote, or as far as it goes.
+	fn string(&mut self) -> String {
+		self.pos += 1;
+		let mut text = String::new();
+		loop {
+			let rest = &self.input[self.pos..];
+			match rest.chars().next() {
+				Some('"') => {
+					self.pos += 1;
+					return text;
+				}
+				Some('\\') => {
+					let mut escaped = &rest[1..];
+					match parser::escape::<_, ContextTrail>
+						.parse_next(&mut escaped)
+					{
+						Ok(c) => text.push(c),
+						Err(_) => break,
+					}
+					self.pos = self.input.len() - escaped.len();
+				}
+				Some(c) if c >= ' ' => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+				_ => break,
+			}
+		}
+		self.stopped = true;
+		text
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	/// `input` with `fixes` made to it.
+	fn apply(input: &str, fixes: &[Fix]) -> String {
+		let mut fixed = input.to_owned();
+		for fix in fixes.iter().rev() {
+			fixed.replace_range(fix.span.clone(), &fix.replacement);
+		}
+		fixed
+	}
+
+	#[track_caller]
+	fn fixes(input: &str, expected: &str) -> Vec<&'static str> {
+		let (value, fixes) = parse_lenient_with_fixes(input)
+			.unwrap_or_else(|e| panic!("{input}: {e}"));
+		assert_eq!(
+			value,
+			parse(expected).unwrap(),
+			"{input}"
+		);
+		let fixed = apply(input, &fixes);
+		assert_eq!(parse(&fixed), Ok(value), "{fixed}");
+		fixes.iter().map(|fix| fix.description).collect()
+	}
+
+	#[test]
+	fn lenient_parsing_fixes_common_mistakes() {
+		assert_eq!(
+			fixes(
+				"{a: 'x', \"b\": [1, 2,], c: .5 d: 01, e: +1, $f: 1.}",
+				r#"{"a": "x", "b": [1, 2], "c": 0.5, "d": 1, "e": 1, "$f": 1}"#
+			),
+			[
+				"unquoted key",
+				"single-quoted string",
+				"trailing comma",
+				"unquoted key",
+				"number without a digit before the decimal point",
+				"missing comma",
+				"unquoted key",
+				"number with leading zeros",
+				"unquoted key",
+				"plus sign before number",
+				"unquoted key",
+				"number without a digit after the decimal point",
+			]
+		);
+		assert_eq!(
+			fixes(
+				r#"['it\'s' "a"[]{}]"#,
+				r#"["it's", "a", [], {}]"#
+			),
+			[
+				"single-quoted string",
+				"missing comma",
+				"missing comma",
+				"missing comma"
+			]
+		);
+		assert_eq!(
+			fixes("[1 2]", "[1, 2]"),
+			["missing comma"]
+		);
+		assert!(fixes(
+			r#"{"valid": [true]}"#,
+			r#"{"valid": [true]}"#
+		)
+		.is_empty());
+	}
+
+	#[test]
+	fn lenient_parsing_rejects_the_rest() {
+		for input in [
+			"[1-2]",
+			"[truex]",
+			r#"{"a" 1}"#,
+			"[1,,2]",
+			"{1: 2}",
+			"'open",
+			"[1] 2",
+			"[.]",
+			"\"\\x\"",
+			"NaN",
+		] {
+			assert!(
+				parse_lenient_with_fixes(input).is_err(),
+				"{input}"
+			);
+		}
+		let e = parse_lenient_with_fixes(&"[".repeat(100_000)).unwrap_err();
+		assert_eq!(e.kind(), ErrorKind::TooDeep);
+		let deep = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+		assert!(parse_lenient_with_fixes(&deep).is_ok());
+	}
+
+	#[track_caller]
+	fn truncated(input: &str, expected: &str, len: usize) {
+		let expected = match expected {
+			"" => JsonValue::Null,
+			expected => parse(expected).unwrap(),
+		};
+		assert_eq!(
+			repair_truncated(input),
+			(expected, len),
+			"{input}"
+		);
+	}
+
+	#[test]
+	fn truncated_documents_are_closed() {
+		let input = r#"{"a": [1, 2, {"b": "hel"#;
+		truncated(
+			input,
+			r#"{"a": [1, 2, {"b": "hel"}]}"#,
+			input.len(),
+		);
+		truncated(r#"{"a": 1, "b"#, r#"{"a": 1}"#, 7);
+		truncated(r#"{"a": "#, "{}", 1);
+		truncated("[1, tr", "[1]", 2);
+		truncated("[1.", "[]", 1);
+		truncated("[1 2", "[1]", 2);
+		truncated("[[], {}] trailing", "[[], {}]", 8);
+		truncated("  x", "", 0);
+		truncated("", "", 0);
+	}
+
+	#[test]
+	fn truncated_strings_keep_what_was_read() {
+		truncated(r#"["ab\u12"#, r#"["ab"]"#, 4);
+		truncated(r#"["ab\"#, r#"["ab"]"#, 4);
+		truncated(r#""\uD83D"#, r#""""#, 1);
+		truncated(r#""😀 ok"#, r#""😀 ok""#, 8);
+		truncated("\"tab\there\"", r#""tab""#, 4);
+	}
+
+	#[test]
+	fn truncated_nesting_is_limited() {
+		let (mut value, len) = repair_truncated(&"[".repeat(100_000));
+		assert_eq!(len, MAX_DEPTH);
+		for _ in 0..MAX_DEPTH {
+			let JsonValue::Array(mut items) = value else {
+				panic!("not an array");
+			};
+			value = items.pop().unwrap_or(JsonValue::Null);
+		}
+		assert_eq!(value, JsonValue::Null);
+	}
+}
@@ -0,0 +1,241 @@
+use crate::JsonValue;
+
+/// What [`JsonValue::prune_empty`] removes. The default removes `null`s and
+/// empty arrays and objects, from objects and arrays alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prune {
+	pub nulls: bool,
+	pub empty_arrays: bool,
+	pub empty_objects: bool,
+	pub empty_strings: bool,
+	/// Whether array items are removed as well as object members. Removing
+	/// an item moves the ones after it to other indices.
+	pub array_items: bool,
+}
+
+impl Default for Prune {
+	fn default() -> Self {
+		Self {
+			nulls: true,
+			empty_arrays: true,
+			empty_objects: true,
+			empty_strings: false,
+			array_items: true,
+		}
+	}
+}
+
+impl Prune {
+	fn removes(&self, value: &JsonValue) -> bool {
+		match value {
+			JsonValue::Null => self.nulls,
+			JsonValue::Array(items) => self.empty_arrays && items.is_empty(),
+			JsonValue::Object(map) => self.empty_objects && map.is_empty(),
+			JsonValue::String(s) => self.empty_strings && s.is_empty(),
+			_ => false,
+		}
+	}
+}
+
+impl JsonValue {
+	/// Keeps only the object entries for which `f` returns `true`.
+	///
+	/// Does nothing if the value is not an object.
+	pub fn retain_keys(&mut self, mut f: impl FnMut(&str, &JsonValue) -> bool) {
+		if let JsonValue::Object(map) = self {
+			map.retain(|k, v| f(k, v));
+		}
+	}
+
+	/// Keeps only the array elements for which `f` returns `true`.
+	///
+	/// Does nothing if the value is not an array.
+	pub fn retain_elements(&mut self, f: impl FnMut(&JsonValue) -> bool) {
+		if let JsonValue::Array(items) = self {
+			items.retain(f);
+		}
+	}
+
+	/// Applies [`JsonValue::retain_keys`] to every object in the tree.
+	///
+	/// Children are filtered before their parent, so `f` sees containers
+	/// that have already been pruned.
+	pub fn retain_keys_deep(
+		&mut self,
+		mut f: impl FnMut(&str, &JsonValue) -> bool,
+	) {
+		retain_keys_deep(self, &mut f)
+	}
+
+	/// Applies [`JsonValue::retain_elements`] to every array in the tree.
+	///
+	/// Children are filtered before their parent, so `f` sees containers
+	/// that have already been pruned.
+	pub fn retain_elements_deep(
+		&mut self,
+		mut f: impl FnMut(&JsonValue) -> bool,
+	) {
+		retain_elements_deep(self, &mut f)
+	}
+
+	/// Removes every object member whose value is `null`, throughout the
+	/// tree. Array items are kept, as their positions mean something.
+	pub fn strip_nulls(&mut self) {
+		self.retain_keys_deep(|_, value| !value.is_null());
+	}
+
+	/// Removes what `prune` asks for throughout the tree, children before
+	/// their parents, so a container left empty by pruning is removed too.
+	/// The value itself stays, however empty.
+	pub fn prune_empty(&mut self, prune: &Prune) {
+		match self {
+			JsonValue::Array(items) => {
+				items.iter_mut().for_each(|item| item.prune_empty(prune));
+				if prune.array_items {
+					items.retain(|item| !prune.removes(item));
+				}
+			}
+			JsonValue::Object(map) => {
+				map.values_mut().for_each(|value| value.prune_empty(prune));
+				map.retain(|_, value| !prune.removes(value));
+			}
+			_ => {}
+		}
+	}
+}
+
+fn retain_keys_deep<F>(node: &mut JsonValue, f: &mut F)
+where F: FnMut(&str, &JsonValue) -> bool {
+	match node {
+		JsonValue::Array(items) => {
+			items.iter_mut().for_each(|item| retain_keys_deep(item, f))
+		}
+		JsonValue::Object(map) => {
+			map.values_mut()
+				.for_each(|value| retain_keys_deep(value, f));
+			map.retain(|k, v| f(k, v));
+		}
+		_ => {}
+	}
+}
+
+fn retain_elements_deep<F>(node: &mut JsonValue, f: &mut F)
+where F: FnMut(&JsonValue) -> bool {
+	match node {
+		JsonValue::Array(items) => {
+			items
+				.iter_mut()
+				.for_each(|item| retain_elements_deep(item, f));
+			items.retain(|item| f(item));
+		}
+		JsonValue::Object(map) => map
+			.values_mut()
+			.for_each(|value| retain_elements_deep(value, f)),
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn shallow_retain_keeps_children_untouched() {
+		let mut value = parse(r#"{"a": 1, "b": {"a": 2, "c": 3}}"#).unwrap();
+		value.retain_keys(|key, _| key != "a");
+		assert_eq!(
+			value,
+			parse(r#"{"b": {"a": 2, "c": 3}}"#).unwrap()
+		);
+
+		let mut value = parse("[1, [2, 3], 4]").unwrap();
+		value.retain_elements(|item| item != &JsonValue::from(4));
+		assert_eq!(value, parse("[1, [2, 3]]").unwrap());
+
+		let mut scalar = parse("1").unwrap();
+		scalar.retain_keys(|_, _| false);
+		scalar.retain_elements(|_| false);
+		assert_eq!(scalar, parse("1").unwrap());
+	}
+
+	#[test]
+	fn deep_retain_filters_children_first() {
+		let mut value =
+			parse(r#"{"secret": 1, "list": [{"secret": 2, "ok": 3}]}"#)
+				.unwrap();
+		value.retain_keys_deep(|key, _| key != "secret");
+		assert_eq!(
+			value,
+			parse(r#"{"list": [{"ok": 3}]}"#).unwrap()
+		);
+
+		let mut value = parse(r#"[[1, "a"], {"k": ["b", 2]}, "c"]"#).unwrap();
+		value
+			.retain_elements_deep(|item| !matches!(item, JsonValue::String(_)));
+		assert_eq!(
+			value,
+			parse(r#"[[1], {"k": [2]}]"#).unwrap()
+		);
+
+		let mut value = parse(r#"[["a"], ["a", 1]]"#).unwrap();
+		value.retain_elements_deep(|item| match item {
+			JsonValue::String(_) => false,
+			JsonValue::Array(items) => !items.is_empty(),
+			_ => true,
+		});
+		assert_eq!(value, parse("[[1]]").unwrap());
+	}
+
+	#[test]
+	fn strip_nulls_keeps_array_items() {
+		let mut value =
+			parse(r#"{"a": null, "b": [null, {"c": null, "d": 1}], "e": {}}"#)
+				.unwrap();
+		value.strip_nulls();
+		assert_eq!(
+			value,
+			parse(r#"{"b": [null, {"d": 1}], "e": {}}"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn pruning_removes_what_it_leaves_empty() {
+		let text =
+			r#"{"a": {"b": [null, {}]}, "c": [1, [], ""], "d": "", "e": 0}"#;
+		let mut value = parse(text).unwrap();
+		value.prune_empty(&Prune::default());
+		assert_eq!(
+			value,
+			parse(r#"{"c": [1, ""], "d": "", "e": 0}"#).unwrap()
+		);
+
+		let mut value = parse(text).unwrap();
+		value.prune_empty(&Prune {
+			empty_strings: true,
+			array_items: false,
+			..Prune::default()
+		});
+		assert_eq!(
+			value,
+			parse(r#"{"a": {"b": [null, {}]}, "c": [1, [], ""], "e": 0}"#)
+				.unwrap()
+		);
+
+		let mut value = parse(text).unwrap();
+		value.prune_empty(&Prune {
+			nulls: false,
+			empty_objects: false,
+			..Prune::default()
+		});
+		assert_eq!(
+			value,
+			parse(r#"{"a": {"b": [null, {}]}, "c": [1, ""], "d": "", "e": 0}"#)
+				.unwrap()
+		);
+
+		let mut root = parse(r#"{"a": null}"#).unwrap();
+		root.prune_empty(&Prune::default());
+		assert_eq!(root, parse("{}").unwrap());
+	}
+}
@@ -0,0 +1,376 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use winnow::Parser as _;
+
+use crate::{
+	lexer::{check_number, number_len},
+	parser::{escape, number, ws},
+	Error, ErrorKind, JsonValue, ParseOptions,
+};
+
+/// Parses document after document, keeping its working memory between
+/// calls.
+///
+/// Decoding a string or collecting the members of an array or object
+/// happens in buffers that are cleared rather than freed, so once they have
+/// grown to fit the largest message, each value in the result is allocated
+/// exactly once at its final size. Worth it when parsing many small
+/// documents in a loop; for a single one use [`parse`](crate::parse).
+///
+/// Values are built with the global allocator, as `std` collections don't
+/// yet take an allocator parameter on stable Rust. To track or pool parse
+/// allocations, install a [`#[global_allocator]`](std::alloc::GlobalAlloc)
+/// that does so. Once its buffers have grown, a `Parser` allocates only
+/// the values it returns.
+///
+/// ```text
+/// let mut parser = Parser::new();
+/// for line in input.lines() {
+///     handle(parser.parse(line)?);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Parser {
+	/// Where strings with escapes are decoded.
+	string: String,
+	/// The items of every open container, innermost last.
+	values: Vec<JsonValue>,
+	/// The keys of the members in `values` that belong to objects.
+	keys: Vec<String>,
+	/// The open containers.
+	frames: Vec<Frame>,
+	/// How many containers enclose the documents being parsed, for items
+	/// split out of a larger one.
+	depth: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+	object: bool,
+	/// Where its items start in `values`, and in `keys` for an object.
+	values: usize,
+	keys: usize,
+}
+
+impl Parser {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A parser for documents nested `depth` levels inside another, which
+	/// count towards the nesting limit.
+	pub(crate) fn nested(depth: usize) -> Self {
+		Self {
+			depth,
+			..Self::default()
+		}
+	}
+
+	/// Parses a complete document, accepting and rejecting exactly what
+	/// [`parse`](crate::parse) does, nesting limit included. Errors carry
+	/// the same message and kind, at the offset where this parser stopped.
+	pub fn parse(&mut self, input: &str) -> Result<JsonValue, Error> {
+		let parsed = self.document(input);
+		self.values.clear();
+		self.keys.clear();
+		self.frames.clear();
+		parsed.map_err(|failure| {
+			let offset = input.len() - failure.rest;
+			Error::new(
+				input,
+				offset,
+				failure.message.into_owned(),
+			)
+			.with_kind(failure.kind)
+		})
+	}
+
+	fn document(&mut self, input: &str) -> Result<JsonValue, Failure> {
+		let mut rest = input;
+		let value = self.value(&mut rest)?;
+		skip_ws(&mut rest);
+		match rest.chars().next() {
+			None => Ok(value),
+			Some(c) => Err(Failure::syntax(
+				rest,
+				format!("unexpected character {c:?}"),
+			)),
+		}
+	}
+
+	/// The failure for input that can't continue the innermost open
+	/// container, or the document if none is open.
+	fn unexpected(&self, rest: &str) -> Failure {
+		match self.frames.last() {
+			Some(frame) if frame.object => {
+				Failure::syntax(rest, "invalid object")
+			}
+			Some(_) => Failure::syntax(rest, "invalid array"),
+			None => match rest.chars().next() {
+				Some(c) => Failure::syntax(
+					rest,
+					format!("unexpected character {c:?}"),
+				),
+				None => Failure::syntax(rest, "unexpected end of input"),
+			},
+		}
+	}
+
+	/// Parses a value, working through nested containers iteratively.
+	fn value(&mut self, rest: &mut &str) -> Result<JsonValue, Failure> {
+		loop {
+			skip_ws(rest);
+			let mut value = match rest.as_bytes().first() {
+				None => return Err(self.unexpected(rest)),
+				Some(b'[' | b'{') => {
+					let max = ParseOptions::DEFAULT_MAX_DEPTH;
+					if self.depth + self.frames.len() >= max {
+						return Err(Failure {
+							rest: rest.len(),
+							kind: ErrorKind::TooDeep,
+							message: format!(
+								"nesting deeper than {max} levels"
+							)
+							.into(),
+						});
+					}
+					let object = rest.starts_with('{');
+					*rest = &rest[1..];
+					skip_ws(rest);
+					if eat(rest, if object { '}' } else { ']' }) {
+						empty(object)
+					} else {
+						self.frames.push(Frame {
+							object,
+							values: self.values.len(),
+							keys: self.keys.len(),
+						});
+						if object {
+							self.key(rest)?;
+						}
+						continue;
+					}
+				}
+				_ => self.scalar(rest)?,
+			};
+			// Store the value, closing every container it completes.
+			loop {
+				let Some(&frame) = self.frames.last() else {
+					return Ok(value);
+				};
+				self.values.push(value);
+				skip_ws(rest);
+				if eat(rest, ',') {
+					if frame.object {
+						self.key(rest)?;
+					}
+					break;
+				}
+				if !eat(
+					rest,
+					if frame.object { '}' } else { ']' },
+				) {
+					return Err(self.unexpected(rest));
+				}
+				self.frames.pop();
+				value = self.close(frame);
+			}
+		}
+	}
+
+	/// Parses an object key and the colon after it.
+	fn key(&mut self, rest: &mut &str) -> Result<(), Failure> {
+		skip_ws(rest);
+		if !rest.starts_with('"') {
+			return Err(self.unexpected(rest));
+		}
+		let key = self.string(rest)?;
+		skip_ws(rest);
+		if !eat(rest, ':') {
+			return Err(self.unexpected(rest));
+		}
+		self.keys.push(key);
+		Ok(())
+	}
+
+	fn scalar(&mut self, rest: &mut &str) -> Result<JsonValue, Failure> {
+		for (literal, value) in [
+			("null", JsonValue::Null),
+			("true", JsonValue::Boolean(true)),
+			("false", JsonValue::Boolean(false)),
+		] {
+			if let Some(after) = rest.strip_prefix(literal) {
+				*rest = after;
+				return Ok(value);
+			}
+		}
+		match rest.as_bytes()[0] {
+			b'"' => self.string(rest).map(JsonValue::String),
+			b'-' | b'0'..=b'9' => {
+				let start = *rest;
+				number::<_, ()>
+					.parse_next(rest)
+					.map(JsonValue::Number)
+					.map_err(|_| {
+						let literal = &start[..number_len(start)];
+						let message = check_number(literal)
+							.err()
+							.unwrap_or("invalid number");
+						Failure::syntax(start, message)
+					})
+			}
+			_ => Err(self.unexpected(rest)),
+		}
+	}
+
+	/// Decodes the string `rest` starts with.
+	fn string(&mut self, rest: &mut &str) -> Result<String, Failure> {
+		*rest = &rest[1..];
+		self.string.clear();
+		loop {
+			let Some(end) = rest.find(['"', '\\']) else {
+				return Err(Failure::syntax("", "invalid string"));
+			};
+			let (run, after) = rest.split_at(end);
+			*rest = &after[1..];
+			if after.starts_with('"') {
+				// Without escapes, the contents are copied straight from
+				// the input.
+				if self.string.is_empty() {
+					return Ok(run.to_owned());
+				}
+				self.string.push_str(run);
+				return Ok(self.string.as_str().to_owned());
+			}
+			self.string.push_str(run);
+			let escaped = *rest;
+			let c = escape::<_, ()>
+				.parse_next(rest)
+				.map_err(|_| Failure::syntax(escaped, "invalid string"))?;
+			self.string.push(c);
+		}
+	}
+
+	/// Builds a finished container from its items.
+	fn close(&mut self, frame: Frame) -> JsonValue {
+		let items = self.values.drain(frame.values..);
+		if frame.object {
+			let mut map = HashMap::with_capacity(items.len());
+			map.extend(self.keys.drain(frame.keys..).zip(items));
+			JsonValue::Object(Box::new(map))
+		} else {
+			JsonValue::Array(items.collect())
+		}
+	}
+}
+
+/// Why a document was rejected.
+struct Failure {
+	/// How much of the input was left where it was found.
+	rest: usize,
+	kind: ErrorKind,
+	message: Cow<'static, str>,
+}
+
+impl Failure {
+	fn syntax(rest: &str, message: impl Into<Cow<'static, str>>) -> Self {
+		Self {
+			rest: rest.len(),
+			kind: ErrorKind::Syntax,
+			message: message.into(),
+		}
+	}
+}
+
+fn empty(object: bool) -> JsonValue {
+	if object {
+		JsonValue::Object(Box::default())
+	} else {
+		JsonValue::Array(Vec::new())
+	}
+}
+
+fn skip_ws(rest: &mut &str) {
+	let _ = ws::<_, ()>(rest);
+}
+
+/// Consumes `c` if it comes next.
+fn eat(rest: &mut &str, c: char) -> bool {
+	match rest.strip_prefix(c) {
+		Some(after) => {
+			*rest = after;
+			true
+		}
+		None => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_what_parse_rejects_with_the_same_error() {
+		let mut parser = Parser::new();
+		for input in [
+			"",
+			" ",
+			"[",
+			"[1,]",
+			"[1 2]",
+			"[}",
+			"{",
+			"{\"a\"",
+			"{\"a\":",
+			"{\"a\":1,}",
+			"{1:2}",
+			"{\"a\":tru}",
+			"\"abc",
+			"\"a\\x\"",
+			"[\"\\ud800\"]",
+			"01",
+			"[1.e3]",
+			"[-]",
+			"1e",
+			"tru",
+			"1 2",
+			"[1]]",
+			"{\"a\":[1,}",
+		] {
+			let expected = crate::parse(input).unwrap_err();
+			let error = parser.parse(input).unwrap_err();
+			assert_eq!(
+				(error.message(), error.kind()),
+				(expected.message(), expected.kind()),
+				"{input:?}"
+			);
+		}
+		let input = r#"{"a": [1, 2.5e3, "x\n", true, null, {}], "b": []}"#;
+		assert_eq!(parser.parse(input), crate::parse(input));
+	}
+
+	#[test]
+	fn deep_input_fails_without_overflowing() {
+		let mut parser = Parser::new();
+		for input in ["[".repeat(100_000), "{\"a\":".repeat(100_000)] {
+			let error = parser.parse(&input).unwrap_err();
+			assert_eq!(error.kind(), ErrorKind::TooDeep);
+			assert_eq!(
+				error.message(),
+				crate::parse(&input).unwrap_err().message()
+			);
+		}
+		let limit = ParseOptions::DEFAULT_MAX_DEPTH;
+		let nested = format!(
+			"{}{}",
+			"[".repeat(limit),
+			"]".repeat(limit)
+		);
+		assert!(parser.parse(&nested).is_ok());
+		let deeper = format!("[{nested}]");
+		assert_eq!(
+			parser.parse(&deeper).unwrap_err().kind(),
+			ErrorKind::TooDeep
+		);
+	}
+}
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+
+use super::{InstanceType, KeywordKind, Schema};
+use crate::{
+	coerce::{coerced, Coercion},
+	JsonType, JsonValue,
+};
+
+impl Schema {
+	/// Converts each string in `value` whose schema rules strings out with
+	/// `type` into a type it allows, if the string spells one: a number
+	/// for `number`, or `integer` if it is whole, `true` or `false` for
+	/// `boolean`, and `""` or `"null"` for `null`. Returns how many were
+	/// converted.
+	///
+	/// The schemas of the parts of `value` are found through `properties`,
+	/// `patternProperties`, `additionalProperties`, `prefixItems`, `items`,
+	/// `allOf`, `$ref` and `$dynamicRef`, whose anchor is looked for only
+	/// among the schemas applying to the same part of `value`; other
+	/// applicators are not followed. Nothing is validated, so
+	/// [`Schema::validate`] the result.
+	pub fn coerce(&self, value: &mut JsonValue) -> usize {
+		self.coerce_at(&self.expand(vec![self.root]), value)
+	}
+
+	fn coerce_at(&self, schemas: &[usize], value: &mut JsonValue) -> usize {
+		match value {
+			JsonValue::String(s) => {
+				let Some(types) = self.allowed_types(schemas) else {
+					return 0;
+				};
+				if types.contains(&InstanceType::Type(JsonType::String)) {
+					return 0;
+				}
+				let converted = coerced(s, &Coercion::default())
+					.filter(|value| types.iter().any(|ty| ty.matches(value)));
+				match converted {
+					Some(converted) => {
+						*value = converted;
+						1
+					}
+					None => 0,
+				}
+			}
+			JsonValue::Array(items) => items
+				.iter_mut()
+				.enumerate()
+				.map(|(i, item)| {
+					let schemas = self.item_schemas(schemas, i);
+					self.coerce_at(&schemas, item)
+				})
+				.sum(),
+			JsonValue::Object(map) => map
+				.iter_mut()
+				.map(|(key, value)| {
+					let schemas = self.member_schemas(schemas, key);
+					self.coerce_at(&schemas, value)
+				})
+				.sum(),
+			_ => 0,
+		}
+	}
+
+	/// The types every `type` keyword of `schemas` allows, or `None` if
+	/// they have none.
+	fn allowed_types(&self, schemas: &[usize]) -> Option<Vec<InstanceType>> {
+		let mut allowed: Option<Vec<InstanceType>> = None;
+		for keyword in self.schema_keywords(schemas) {
+			if let KeywordKind::Type(types) = keyword {
+				allowed = Some(match allowed {
+					None => types.clone(),
+					Some(allowed) => allowed
+						.into_iter()
+						.filter(|ty| types.contains(ty))
+						.collect(),
+				});
+			}
+		}
+		allowed
+	}
+
+	fn item_schemas(&self, schemas: &[usize], index: usize) -> Vec<usize> {
+		let mut found = Vec::new();
+		for keyword in self.schema_keywords(schemas) {
+			match keyword {
+				KeywordKind::PrefixItems(items) => {
+					found.extend(items.get(index))
+				}
+				KeywordKind::Items { schema, skip } if index >= *skip => {
+					found.push(*schema)
+				}
+				_ => {}
+			}
+		}
+		self.expand(found)
+	}
+
+	fn member_schemas(&self, schemas: &[usize], key: &str) -> Vec<usize> {
+		let mut found = Vec::new();
+		for keyword in self.schema_keywords(schemas) {
+			match keyword {
+				KeywordKind::Properties(properties) => {
+					found.extend(properties.iter().filter_map(
+						|(name, schema)| (name == key).then_some(*schema),
+					));
+				}
+				// A key too complex to match against a pattern is left for
+				// validation to report, and coerced by neither keyword.
+				KeywordKind::PatternProperties(patterns) => {
+					found.extend(patterns.iter().filter_map(
+						|(regex, schema)| {
+							(regex.is_match(key) == Ok(true)).then_some(*schema)
+						},
+					));
+				}
+				KeywordKind::AdditionalProperties {
+					schema,
+					known,
+					patterns,
+				} if !known.iter().any(|name| name == key)
+					&& patterns
+						.iter()
+						.all(|regex| regex.is_match(key) == Ok(false)) =>
+				{
+					found.push(*schema)
+				}
+				_ => {}
+			}
+		}
+		self.expand(found)
+	}
+
+	/// Adds every schema reachable through `allOf` and `$ref`, which apply
+	/// to the same instance.
+	fn expand(&self, mut schemas: Vec<usize>) -> Vec<usize> {
+		let mut seen: HashSet<usize> = schemas.iter().copied().collect();
+		let mut scope = Vec::new();
+		let mut i = 0;
+		while let Some(&node) = schemas.get(i) {
+			i += 1;
+			if let Some(id) = self.nodes[node].scope {
+				if !scope.contains(&id) {
+					scope.push(id);
+				}
+			}
+			let mut next = Vec::new();
+			for keyword in self.schema_keywords(&[node]) {
+				match keyword {
+					KeywordKind::AllOf(all) => next.extend(all),
+					KeywordKind::Ref(target) => next.push(*target),
+					KeywordKind::DynamicRef { schema, anchor } => {
+						next.push(self.dynamic_target(*schema, anchor, &scope))
+					}
+					_ => {}
+				}
+			}
+			schemas.extend(next.into_iter().filter(|s| seen.insert(*s)));
+		}
+		schemas
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{parse, schema::Schema, JsonValue};
+
+	fn coerce(schema: &str, instance: &str) -> (JsonValue, usize) {
+		let schema = Schema::compile(&parse(schema).unwrap()).unwrap();
+		let mut value = parse(instance).unwrap();
+		let converted = schema.coerce(&mut value);
+		(value, converted)
+	}
+
+	#[test]
+	fn converts_to_the_type_asked_for() {
+		let schema = r#"{"properties": {
+			"n": {"type": "number"},
+			"i": {"type": "integer"},
+			"b": {"type": "boolean"},
+			"z": {"type": "null"},
+			"e": {"type": ["null", "integer"]}
+		}}"#;
+		let (value, converted) = coerce(
+			schema,
+			r#"{"n": "1.5", "i": "3", "b": "false", "z": "null", "e": ""}"#,
+		);
+		assert_eq!(converted, 5);
+		assert_eq!(
+			value,
+			parse(r#"{"n": 1.5, "i": 3, "b": false, "z": null, "e": null}"#)
+				.unwrap()
+		);
+	}
+
+	#[test]
+	fn leaves_strings_the_schema_allows_or_cannot_take() {
+		let schema = r#"{"properties": {
+			"s": {"type": ["string", "number"]},
+			"i": {"type": "integer"},
+			"b": {"type": "boolean"},
+			"any": {}
+		}}"#;
+		let instance = r#"{"s": "1", "i": "1.5", "b": "1", "any": "2",
+			"other": "3"}"#;
+		let (value, converted) = coerce(schema, instance);
+		assert_eq!(converted, 0);
+		assert_eq!(value, parse(instance).unwrap());
+	}
+
+	#[test]
+	fn follows_applicators() {
+		let schema = r##"{
+			"type": "object",
+			"properties": {"list": {"items": {"$ref": "#/$defs/count"}}},
+			"patternProperties": {"^x-": {"type": "boolean"}},
+			"additionalProperties": {"allOf": [{"type": "null"}]},
+			"$defs": {"count": {"type": "integer"}}
+		}"##;
+		let (value, converted) = coerce(
+			schema,
+			r#"{"list": ["1", "2"], "x-on": "true", "rest": ""}"#,
+		);
+		assert_eq!(converted, 4);
+		assert_eq!(
+			value,
+			parse(r#"{"list": [1, 2], "x-on": true, "rest": null}"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn prefix_items_come_before_items() {
+		let schema = r#"{
+			"prefixItems": [{"type": "string"}, {"type": "boolean"}],
+			"items": {"type": "number"}
+		}"#;
+		let (value, converted) =
+			coerce(schema, r#"["1", "true", "2", "1e400"]"#);
+		assert_eq!(converted, 2);
+		assert_eq!(
+			value,
+			parse(r#"["1", true, 2, "1e400"]"#).unwrap()
+		);
+	}
+}
@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::JsonValue;
+
+/// Infers a JSON Schema describing every sample.
+///
+/// The result records the observed types (`integer` when every number was
+/// integral), the properties of objects with those present in all of them
+/// marked `required`, a merged `items` schema for arrays, and a `format`
+/// when every string sample looks like the same well-known format.
+pub fn infer_schema(samples: &[JsonValue]) -> JsonValue {
+	let mut shape = Shape::default();
+	for sample in samples {
+		shape.observe(sample);
+	}
+	let mut schema = shape.to_schema();
+	if let JsonValue::Object(map) = &mut schema {
+		map.insert(
+			"$schema".to_owned(),
+			"https://json-schema.org/draft/2020-12/schema".into(),
+		);
+	}
+	schema
+}
+
+#[derive(Debug, Default)]
+struct Shape {
+	null: bool,
+	boolean: bool,
+	integer: bool,
+	number: bool,
+	/// The formats every string seen so far satisfies.
+	strings: Option<BTreeSet<&'static str>>,
+	items: Option<Box<Shape>>,
+	objects: usize,
+	properties: BTreeMap<String, (usize, Shape)>,
+	array: bool,
+}
+
+impl Shape {
+	fn observe(&mut self, value: &JsonValue) {
+		match value {
+			JsonValue::Null => self.null = true,
+			JsonValue::Boolean(_) => self.boolean = true,
+			JsonValue::Number(n) if n.fract() == 0.0 => self.integer = true,
+			JsonValue::Number(_) => self.number = true,
+			JsonValue::String(s) => {
+				let formats = formats(s);
+				self.strings = Some(match self.strings.take() {
+					Some(seen) => {
+						seen.intersection(&formats).copied().collect()
+					}
+					None => formats,
+				});
+			}
+			JsonValue::Array(items) => {
+				self.array = true;
+				for item in items {
+					self.items
+						.get_or_insert_with(Default::default)
+						.observe(item);
+				}
+			}
+			JsonValue::Object(map) => {
+				self.objects += 1;
+				for (key, value) in map.iter() {
+					let (count, shape) =
+						self.properties.entry(key.clone()).or_default();
+					*count += 1;
+					shape.observe(value);
+				}
+			}
+		}
+	}
+
+	fn to_schema(&self) -> JsonValue {
+		let mut types = Vec::new();
+		let mut schema = BTreeMap::new();
+
+		if self.null {
+			types.push("null");
+		}
+		if self.boolean {
+			types.push("boolean");
+		}
+		if self.number {
+			types.push("number");
+		} else if self.integer {
+			types.push("integer");
+		}
+		if let Some(formats) = &self.strings {
+			types.push("string");
+			if let Some(format) = formats.iter().next() {
+				schema.insert("format", JsonValue::from(*format));
+			}
+		}
+		if self.array {
+			types.push("array");
+			if let Some(items) = &self.items {
+				schema.insert("items", items.to_schema());
+			}
+		}
+		if self.objects > 0 {
+			types.push("object");
+			let properties: JsonValue = self
+				.properties
+				.iter()
+				.map(|(key, (_, shape))| (key.clone(), shape.to_schema()))
+				.collect();
+			let required: Vec<JsonValue> = self
+				.properties
+				.iter()
+				.filter(|(_, (count, _))| *count == self.objects)
+				.map(|(key, _)| key.as_str().into())
+				.collect();
+			schema.insert("properties", properties);
+			if !required.is_empty() {
+				schema.insert("required", JsonValue::Array(required));
+			}
+		}
+
+		match types.as_slice() {
+			[] => {}
+			[ty] => {
+				schema.insert("type", (*ty).into());
+			}
+			_ => {
+				schema.insert("type", types.into());
+			}
+		}
+		schema.into_iter().collect()
+	}
+}
+
+/// The well-known `format`s that `s` satisfies.
+fn formats(s: &str) -> BTreeSet<&'static str> {
+	[
+		("date-time", is_date_time(s)),
+		("date", is_date(s)),
+		("time", is_time(s)),
+		("uuid", is_uuid(s)),
+		("email", is_email(s)),
+		("ipv4", is_ipv4(s)),
+		("uri", is_uri(s)),
+	]
+	.into_iter()
+	.filter_map(|(format, matches)| matches.then_some(format))
+	.collect()
+}
+
+fn is_digits(s: &str, len: usize) -> bool {
+	s.len() == len && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_date(s: &str) -> bool {
+	let parts: Vec<_> = s.split('-').collect();
+	matches!(parts.as_slice(), [y, m, d]
+		if is_digits(y, 4) && is_digits(m, 2) && is_digits(d, 2)
+			&& (1..=12).contains(&m.parse::<u8>().unwrap_or(0))
+			&& (1..=31).contains(&d.parse::<u8>().unwrap_or(0)))
+}
+
+fn is_time(s: &str) -> bool {
+	let (time, offset) = match s.find(['Z', 'z', '+', '-']) {
+		Some(i) => s.split_at(i),
+		None => return false,
+	};
+	let offset_ok = match offset {
+		"Z" | "z" => true,
+		_ => matches!(offset[1..].split_once(':'), Some((h, m))
+			if is_digits(h, 2) && is_digits(m, 2)),
+	};
+	let time = match time.split_once('.') {
+		Some((time, fraction))
+			if !fraction.is_empty()
+				&& fraction.bytes().all(|b| b.is_ascii_digit()) =>
+		{
+			time
+		}
+		Some(_) => return false,
+		None => time,
+	};
+	let parts: Vec<_> = time.split(':').collect();
+	offset_ok
+		&& matches!(parts.as_slice(), [h, m, s]
+			if is_digits(h, 2) && is_digits(m, 2) && is_digits(s, 2))
+}
+
+fn is_date_time(s: &str) -> bool {
+	s.split_once(['T', 't'])
+		.is_some_and(|(date, time)| is_date(date) && is_time(time))
+}
+
+fn is_uuid(s: &str) -> bool {
+	let parts: Vec<_> = s.split('-').collect();
+	parts.len() == 5
+		&& parts.iter().zip([8, 4, 4, 4, 12]).all(|(part, len)| {
+			part.len() == len && part.bytes().all(|b| b.is_ascii_hexdigit())
+		})
+}
+
+fn is_email(s: &str) -> bool {
+	s.split_once('@').is_some_and(|(local, domain)| {
+		!local.is_empty()
+			&& !domain.contains('@')
+			&& domain.split('.').count() >= 2
+			&& domain.split('.').all(|label| !label.is_empty())
+			&& !s.contains(char::is_whitespace)
+	})
+}
+
+fn is_ipv4(s: &str) -> bool {
+	let parts: Vec<_> = s.split('.').collect();
+	parts.len() == 4
+		&& parts.iter().all(|part| {
+			!part.is_empty()
+				&& part.len() <= 3
+				&& part.bytes().all(|b| b.is_ascii_digit())
+				&& (part.len() == 1 || !part.starts_with('0'))
+				&& part.parse::<u16>().is_ok_and(|n| n <= 255)
+		})
+}
+
+fn is_uri(s: &str) -> bool {
+	s.split_once("://").is_some_and(|(scheme, rest)| {
+		scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+			&& scheme
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+			&& !rest.is_empty()
+			&& !s.contains(char::is_whitespace)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{parse, schema::Schema};
+
+	fn infer(samples: &[&str]) -> JsonValue {
+		let samples: Vec<_> = samples
+			.iter()
+			.map(|sample| parse(sample).unwrap())
+			.collect();
+		let schema = infer_schema(&samples);
+		let compiled = Schema::compile(&schema).unwrap();
+		for sample in &samples {
+			assert!(compiled.is_valid(sample), "{sample}");
+		}
+		schema
+	}
+
+	#[test]
+	fn describes_the_samples() {
+		let schema = infer(&[
+			r#"{"id": 1, "tags": ["a"], "at": "2024-01-02T03:04:05Z"}"#,
+			r#"{"id": 2, "tags": [], "at": "2024-05-06T07:08:09.5+01:00",
+				"note": null}"#,
+		]);
+		assert_eq!(
+			schema,
+			parse(
+				r#"{
+				"$schema": "https://json-schema.org/draft/2020-12/schema",
+				"type": "object",
+				"properties": {
+					"id": {"type": "integer"},
+					"tags": {"type": "array", "items": {"type": "string"}},
+					"at": {"type": "string", "format": "date-time"},
+					"note": {"type": "null"}
+				},
+				"required": ["at", "id", "tags"]
+			}"#
+			)
+			.unwrap()
+		);
+	}
+
+	#[test]
+	fn mixed_samples_widen_the_type() {
+		let schema = infer(&["1", "2.5", "null", r#""a@b.c""#, r#""x""#]);
+		assert_eq!(
+			schema,
+			parse(
+				r#"{
+				"$schema": "https://json-schema.org/draft/2020-12/schema",
+				"type": ["null", "number", "string"]
+			}"#
+			)
+			.unwrap()
+		);
+		assert_eq!(
+			infer(&[r#""a@b.c""#, r#""me@example.org""#]).get("format"),
+			Some(&"email".into())
+		);
+		assert_eq!(
+			infer(&[r#""10.0.0.1""#, r#""010.0.0.1""#]).get("format"),
+			None
+		);
+	}
+}
@@ -0,0 +1,1437 @@
+//! JSON Schema (draft 2020-12) validation.
+//!
+//! A schema document is compiled once with [`Schema::compile`] and can then
+//! check any number of instances, reporting every violation with both the
+//! instance location and the location of the failing keyword in the schema.
+//!
+//! All assertion and applicator keywords are supported, including
+//! `unevaluatedProperties` and `unevaluatedItems`. `$ref` is resolved
+//! against the base URI that `$id` sets, may use a JSON Pointer fragment
+//! (`#/$defs/node`) or an `$anchor`, and can name other documents, which a
+//! [`SchemaLoader`] given to [`Schema::compile_with_loader`] supplies.
+//! `$dynamicRef` resolves to the `$dynamicAnchor` of the outermost schema
+//! resource the validation has passed through, as in
+//! `{"$dynamicRef": "#node"}` extending a recursive schema.
+//! `format` is treated as an annotation and not asserted.
+//!
+//! Large documents can be checked while they are parsed, without building
+//! them, with [`Schema::validate_str`] or a [`StreamValidator`], or parsed
+//! into the typed shapes the schema describes with [`Schema::parse_typed`].
+//!
+//! The schemas in an OpenAPI description, 3.0 or 3.1, can be compiled
+//! where they are with [`Schema::compile_openapi`], to check API examples
+//! and responses against them.
+//!
+//! [`infer_schema`] goes the other way, describing a set of sample
+//! documents.
+
+mod coerce;
+mod infer;
+mod openapi;
+mod regex;
+mod stream;
+mod typed;
+mod validate;
+
+use std::{collections::HashMap, error, fmt, ops::Deref, rc::Rc};
+
+pub use self::infer::infer_schema;
+pub(crate) use self::regex::Regex;
+pub use self::stream::{StreamError, StreamValidator};
+pub use self::typed::TypedValue;
+use crate::{
+	pointer::parse_index, ErrorKind, JsonPointer, JsonType, JsonValue,
+};
+
+/// A compiled JSON Schema.
+#[derive(Debug, Clone)]
+pub struct Schema {
+	nodes: Vec<Node>,
+	root: usize,
+	/// The `$dynamicAnchor`s of each schema resource that declares any, by
+	/// name, with the schema each names.
+	scopes: Vec<Vec<(String, usize)>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+	/// The location of the schema in the document.
+	pub(crate) path: JsonPointer,
+	pub(crate) kind: NodeKind,
+	/// The entry in [`Schema::scopes`] of the resource the schema is in,
+	/// if that resource declares `$dynamicAnchor`s.
+	pub(crate) scope: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum NodeKind {
+	Bool(bool),
+	Keywords(Vec<Keyword>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Keyword {
+	/// The location of the keyword in the schema document.
+	pub(crate) path: JsonPointer,
+	pub(crate) kind: KeywordKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InstanceType {
+	Type(JsonType),
+	Integer,
+}
+
+impl InstanceType {
+	pub(crate) fn matches(self, value: &JsonValue) -> bool {
+		match (self, value) {
+			(InstanceType::Integer, JsonValue::Number(n)) => n.fract() == 0.0,
+			(InstanceType::Integer, _) => false,
+			(InstanceType::Type(ty), value) => value.type_of() == ty,
+		}
+	}
+}
+
+impl fmt::Display for InstanceType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			InstanceType::Type(ty) => ty.fmt(f),
+			InstanceType::Integer => f.write_str("integer"),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum KeywordKind {
+	Type(Vec<InstanceType>),
+	Enum(Vec<JsonValue>),
+	Const(JsonValue),
+	MultipleOf(f64),
+	Maximum(f64),
+	ExclusiveMaximum(f64),
+	Minimum(f64),
+	ExclusiveMinimum(f64),
+	MaxLength(usize),
+	MinLength(usize),
+	Pattern(String, Regex),
+	PrefixItems(Vec<usize>),
+	Items {
+		schema: usize,
+		skip: usize,
+	},
+	Contains {
+		schema: usize,
+		min: usize,
+		max: Option<usize>,
+	},
+	MaxItems(usize),
+	MinItems(usize),
+	UniqueItems,
+	Properties(Vec<(String, usize)>),
+	PatternProperties(Vec<(Regex, usize)>),
+	AdditionalProperties {
+		schema: usize,
+		known: Vec<String>,
+		patterns: Vec<Regex>,
+	},
+	Required(Vec<String>),
+	MaxProperties(usize),
+	MinProperties(usize),
+	DependentRequired(Vec<(String, Vec<String>)>),
+	DependentSchemas(Vec<(String, usize)>),
+	PropertyNames(usize),
+	AllOf(Vec<usize>),
+	AnyOf(Vec<usize>),
+	OneOf(Vec<usize>),
+	Not(usize),
+	If {
+		condition: usize,
+		then: Option<usize>,
+		otherwise: Option<usize>,
+	},
+	Ref(usize),
+	/// A `$dynamicRef` to `schema`, which the outermost resource in the
+	/// dynamic scope with a `$dynamicAnchor` named `anchor` overrides.
+	DynamicRef {
+		schema: usize,
+		anchor: String,
+	},
+	UnevaluatedItems(usize),
+	UnevaluatedProperties(usize),
+}
+
+/// An error in the schema document itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+	pub path: JsonPointer,
+	pub message: String,
+	/// [`ErrorKind::TooDeep`] for a chain of subschemas longer than
+	/// [`Schema::MAX_DEPTH`], and [`ErrorKind::Syntax`] for any other.
+	pub kind: ErrorKind,
+}
+
+impl fmt::Display for SchemaError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} at \"{}\"",
+			self.message, self.path
+		)
+	}
+}
+
+impl error::Error for SchemaError {}
+
+/// A single way in which an instance fails to satisfy a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+	/// The location of the offending value in the instance.
+	pub instance_path: JsonPointer,
+	/// The location of the failing keyword in the schema.
+	pub schema_path: JsonPointer,
+	pub message: String,
+	/// [`ErrorKind::TooDeep`] for an instance nested deeper than
+	/// [`Schema::MAX_DEPTH`], and [`ErrorKind::Syntax`] for any other
+	/// violation.
+	pub kind: ErrorKind,
+}
+
+impl fmt::Display for ValidationError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} at \"{}\" (schema \"{}\")",
+			self.message, self.instance_path, self.schema_path
+		)
+	}
+}
+
+impl error::Error for ValidationError {}
+
+/// Fetches the schema documents that `$ref`s name, for
+/// [`Schema::compile_with_loader`]: from the filesystem, over HTTP, or from
+/// an embedded map, which implements it.
+///
+/// Each document is loaded at most once per compilation, however many
+/// references name it.
+pub trait SchemaLoader {
+	/// The document at `uri`, which has no fragment. It is absolute when
+	/// the referring schema has an absolute base URI, and otherwise as
+	/// relative as the `$ref` itself.
+	fn load(&mut self, uri: &str) -> Result<JsonValue, String>;
+}
+
+impl<F: FnMut(&str) -> Result<JsonValue, String>> SchemaLoader for F {
+	fn load(&mut self, uri: &str) -> Result<JsonValue, String> {
+		self(uri)
+	}
+}
+
+impl SchemaLoader for HashMap<String, JsonValue> {
+	fn load(&mut self, uri: &str) -> Result<JsonValue, String> {
+		self.get(uri)
+			.cloned()
+			.ok_or_else(|| "no such schema".to_owned())
+	}
+}
+
+impl Schema {
+	/// How many subschemas may apply to the same value one inside the
+	/// other, as in a chain of `not`s or of `$ref`s to `$ref`s, and how
+	/// deeply the values of an instance may nest where subschemas apply.
+	pub const MAX_DEPTH: usize = 128;
+
+	/// Compiles a schema whose `$ref`s all point into the document itself.
+	///
+	/// Fails with [`ErrorKind::TooDeep`] if a chain of subschemas applying
+	/// to the same value is longer than [`Schema::MAX_DEPTH`].
+	pub fn compile(schema: &JsonValue) -> Result<Self, SchemaError> {
+		Self::compile_from(schema, &JsonPointer::root(), None)
+	}
+
+	/// Compiles a schema, fetching the other documents its `$ref`s name
+	/// with `loader`.
+	pub fn compile_with_loader(
+		schema: &JsonValue,
+		loader: &mut dyn SchemaLoader,
+	) -> Result<Self, SchemaError> {
+		Self::compile_from(
+			schema,
+			&JsonPointer::root(),
+			Some(loader),
+		)
+	}
+
+	/// Compiles the schema at `start` in `schema`, which is the document
+	/// its references resolve against.
+	fn compile_from(
+		schema: &JsonValue,
+		start: &JsonPointer,
+		loader: Option<&mut dyn SchemaLoader>,
+	) -> Result<Self, SchemaError> {
+		let mut compiler = Compiler {
+			loader,
+			documents: Vec::new(),
+			resources: HashMap::new(),
+			anchors: HashMap::new(),
+			dynamic_anchors: HashMap::new(),
+			scopes: Vec::new(),
+			scope_ids: HashMap::new(),
+			document: 0,
+			nodes: Vec::new(),
+			compiled: HashMap::new(),
+			pending: Vec::new(),
+		};
+		compiler.add_document(String::new(), Document::Given(schema));
+		let root = compiler.compile(start)?;
+		compiler.finish()?;
+		compiler.check_cycles()?;
+		Ok(Self {
+			nodes: compiler.nodes,
+			root,
+			scopes: compiler.scopes,
+		})
+	}
+
+	/// Checks `instance`, returning every violation found. Values nested
+	/// deeper than [`Schema::MAX_DEPTH`] are violations of kind
+	/// [`ErrorKind::TooDeep`] wherever a subschema applies to them.
+	pub fn validate(
+		&self,
+		instance: &JsonValue,
+	) -> Result<(), Vec<ValidationError>> {
+		let mut errors = Vec::new();
+		self.validate_node(
+			self.root,
+			instance,
+			&mut JsonPointer::root(),
+			&mut Vec::new(),
+			&mut errors,
+		);
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(errors)
+		}
+	}
+
+	pub fn is_valid(&self, instance: &JsonValue) -> bool {
+		self.validate(instance).is_ok()
+	}
+}
+
+struct Compiler<'l, 'd> {
+	loader: Option<&'l mut dyn SchemaLoader>,
+	/// Every document loaded so far, the root schema first, with the URI
+	/// it was loaded from.
+	documents: Vec<(String, Document<'d>)>,
+	/// Where the schema each absolute URI names is, by the index of its
+	/// document and its location in that document.
+	resources: HashMap<String, (usize, JsonPointer)>,
+	/// Where each `$anchor` is, by its URI with the anchor as the fragment.
+	anchors: HashMap<String, (usize, JsonPointer)>,
+	/// Where each `$dynamicAnchor` is, by the base URI of its resource and
+	/// its name.
+	dynamic_anchors: HashMap<String, Vec<(String, usize, JsonPointer)>>,
+	scopes: Vec<Vec<(String, usize)>>,
+	/// The entry in `scopes` of each resource compiled so far that has
+	/// `$dynamicAnchor`s, by its base URI.
+	scope_ids: HashMap<String, usize>,
+	/// The document being compiled.
+	document: usize,
+	nodes: Vec<Node>,
+	compiled: HashMap<(usize, JsonPointer), usize>,
+	/// The nodes whose keywords are yet to be compiled, with the index of
+	/// their document.
+	pending: Vec<(usize, usize)>,
+}
+
+fn error(path: &JsonPointer, message: impl Into<String>) -> SchemaError {
+	SchemaError {
+		path: path.clone(),
+		message: message.into(),
+		kind: ErrorKind::Syntax,
+	}
+}
+
+fn too_deep(path: &JsonPointer) -> SchemaError {
+	SchemaError {
+		kind: ErrorKind::TooDeep,
+		..error(
+			path,
+			format!(
+				"schemas applying to the same value nest deeper than {} \
+				 levels",
+				Schema::MAX_DEPTH
+			),
+		)
+	}
+}
+
+/// A schema document, borrowed if it is the one being compiled, so that
+/// it needn't be copied.
+#[derive(Clone)]
+enum Document<'d> {
+	Given(&'d JsonValue),
+	Loaded(Rc<JsonValue>),
+}
+
+impl Deref for Document<'_> {
+	type Target = JsonValue;
+
+	fn deref(&self) -> &JsonValue {
+		match self {
+			Document::Given(document) => document,
+			Document::Loaded(document) => document,
+		}
+	}
+}
+
+impl<'d> Compiler<'_, 'd> {
+	/// Adds a document loaded from `uri`, recording the resources and
+	/// anchors in it.
+	fn add_document(&mut self, uri: String, document: Document<'d>) {
+		let index = self.documents.len();
+		self.documents.push((uri.clone(), document.clone()));
+		self.resources.insert(
+			uri.clone(),
+			(index, JsonPointer::root()),
+		);
+		self.index(index, &document, &uri);
+	}
+
+	/// Records the resources and anchors anywhere in `value`. It is walked
+	/// with a stack, as the values of keywords like `const` may nest as
+	/// deeply as they like.
+	fn index(&mut self, document: usize, value: &JsonValue, base: &str) {
+		// Each value is entered with the token it adds to `path`, which the
+		// `None` step after its children removes again.
+		let mut path = JsonPointer::root();
+		let mut steps = vec![Some((None, value, Rc::<str>::from(base)))];
+		while let Some(step) = steps.pop() {
+			let Some((token, value, base)) = step else {
+				path.pop();
+				continue;
+			};
+			if let Some(token) = token {
+				path.push(token);
+				steps.push(None);
+			}
+			match value {
+				JsonValue::Object(map) => {
+					let base = rebase(&base, map);
+					if map.contains_key("$id") {
+						self.resources
+							.insert(base.clone(), (document, path.clone()));
+					}
+					if let Some(anchor) =
+						map.get("$anchor").and_then(JsonValue::as_str)
+					{
+						self.anchors.insert(
+							format!("{base}#{anchor}"),
+							(document, path.clone()),
+						);
+					}
+					// A dynamic anchor is also a plain one, for `$ref`.
+					if let Some(anchor) =
+						map.get("$dynamicAnchor").and_then(JsonValue::as_str)
+					{
+						self.anchors.insert(
+							format!("{base}#{anchor}"),
+							(document, path.clone()),
+						);
+						self.dynamic_anchors
+							.entry(base.clone())
+							.or_default()
+							.push((
+								anchor.to_owned(),
+								document,
+								path.clone(),
+							));
+					}
+					let base = Rc::<str>::from(base);
+					steps.extend(map.iter().map(|(key, value)| {
+						Some((
+							Some(key.clone()),
+							value,
+							Rc::clone(&base),
+						))
+					}));
+				}
+				JsonValue::Array(items) => {
+					steps.extend(
+						items.iter().enumerate().map(|(i, item)| {
+							Some((
+								Some(i.to_string()),
+								item,
+								Rc::clone(&base),
+							))
+						}),
+					);
+				}
+				_ => {}
+			}
+		}
+	}
+
+	/// The base URI of the schema at `path` in the current document.
+	fn base(&self, path: &JsonPointer) -> String {
+		let (uri, document) = &self.documents[self.document];
+		let mut base = uri.clone();
+		let mut node = Some(&**document);
+		let mut tokens = path.tokens().iter();
+		while let Some(value) = node {
+			let token = tokens.next();
+			node = match value {
+				JsonValue::Object(map) => {
+					base = rebase(&base, map);
+					token.and_then(|token| map.get(token))
+				}
+				JsonValue::Array(items) => token
+					.and_then(|token| parse_index(token))
+					.and_then(|i| items.get(i)),
+				_ => None,
+			};
+		}
+		base
+	}
+
+	/// The node for the schema at `path` in the current document. A new
+	/// one is only registered here, and its keywords compiled later by
+	/// [`Compiler::finish`], so that neither recursive nor deeply nested
+	/// schemas make compiling recurse.
+	fn compile(&mut self, path: &JsonPointer) -> Result<usize, SchemaError> {
+		let key = (self.document, path.clone());
+		if let Some(&index) = self.compiled.get(&key) {
+			return Ok(index);
+		}
+		if self.documents[self.document].1.resolve(path).is_none() {
+			return Err(error(
+				path,
+				"no schema at this location",
+			));
+		}
+		let index = self.nodes.len();
+		self.nodes.push(Node {
+			path: path.clone(),
+			kind: NodeKind::Bool(true),
+			scope: None,
+		});
+		self.compiled.insert(key, index);
+		self.pending.push((self.document, index));
+		Ok(index)
+	}
+
+	/// Compiles the keywords of every node registered so far, and of those
+	/// they register in turn.
+	fn finish(&mut self) -> Result<(), SchemaError> {
+		while let Some((document, index)) = self.pending.pop() {
+			self.document = document;
+			let path = self.nodes[index].path.clone();
+			let value = self.documents[document].1.clone();
+			let value = value.resolve(&path).expect("checked by compile");
+			self.nodes[index].scope = self.scope(&path)?;
+			self.nodes[index].kind = match value {
+				JsonValue::Boolean(b) => NodeKind::Bool(*b),
+				JsonValue::Object(map) => {
+					NodeKind::Keywords(self.keywords(&path, map)?)
+				}
+				_ => {
+					return Err(error(
+						&path,
+						"schema must be an object or boolean",
+					))
+				}
+			};
+		}
+		Ok(())
+	}
+
+	/// The entry in `scopes` of the resource around `path` in the current
+	/// document, compiling its `$dynamicAnchor`s the first time.
+	fn scope(
+		&mut self,
+		path: &JsonPointer,
+	) -> Result<Option<usize>, SchemaError> {
+		let base = self.base(path);
+		if let Some(&id) = self.scope_ids.get(&base) {
+			return Ok(Some(id));
+		}
+		let Some(anchors) = self.dynamic_anchors.get(&base).cloned() else {
+			return Ok(None);
+		};
+		let id = self.scopes.len();
+		self.scopes.push(Vec::new());
+		self.scope_ids.insert(base, id);
+		for (name, document, target) in anchors {
+			let outer = std::mem::replace(&mut self.document, document);
+			let compiled = self.compile(&target);
+			self.document = outer;
+			self.scopes[id].push((name, compiled?));
+		}
+		Ok(Some(id))
+	}
+
+	fn keywords(
+		&mut self,
+		path: &JsonPointer,
+		map: &HashMap<String, JsonValue>,
+	) -> Result<Vec<Keyword>, SchemaError> {
+		let mut keywords = Vec::new();
+		let mut push = |name: &str, kind| {
+			keywords.push(Keyword {
+				path: path.join(name),
+				kind,
+			})
+		};
+
+		if let Some(value) = map.get("$ref") {
+			let base = self.base(path);
+			let (document, target) =
+				self.reference(&path.join("$ref"), value, &base)?;
+			let outer = std::mem::replace(&mut self.document, document);
+			let compiled = self.compile(&target);
+			self.document = outer;
+			push("$ref", KeywordKind::Ref(compiled?));
+		}
+		if let Some(value) = map.get("$dynamicRef") {
+			let base = self.base(path);
+			let (document, target) =
+				self.reference(&path.join("$dynamicRef"), value, &base)?;
+			// The reference is only dynamic if it names a dynamic anchor
+			// where it first resolves; otherwise it is a plain `$ref`.
+			let anchor = value
+				.as_str()
+				.and_then(|reference| reference.split_once('#'))
+				.map(|(_, fragment)| fragment)
+				.filter(|&fragment| {
+					self.documents[document]
+						.1
+						.resolve(&target)
+						.and_then(|schema| schema.get("$dynamicAnchor"))
+						.and_then(JsonValue::as_str)
+						== Some(fragment)
+				})
+				.map(str::to_owned);
+			let outer = std::mem::replace(&mut self.document, document);
+			let compiled = self.compile(&target);
+			self.document = outer;
+			let schema = compiled?;
+			push(
+				"$dynamicRef",
+				match anchor {
+					Some(anchor) => KeywordKind::DynamicRef { schema, anchor },
+					None => KeywordKind::Ref(schema),
+				},
+			);
+		}
+		if let Some(value) = map.get("type") {
+			push(
+				"type",
+				KeywordKind::Type(types(&path.join("type"), value)?),
+			);
+		}
+		if let Some(value) = map.get("enum") {
+			let values = value.as_array().ok_or_else(|| {
+				error(&path.join("enum"), "expected an array")
+			})?;
+			push(
+				"enum",
+				KeywordKind::Enum(values.clone()),
+			);
+		}
+		if let Some(value) = map.get("const") {
+			push(
+				"const",
+				KeywordKind::Const(value.clone()),
+			);
+		}
+
+		let number = |name: &str| -> Result<Option<f64>, SchemaError> {
+			map.get(name)
+				.map(|value| {
+					value.as_f64().ok_or_else(|| {
+						error(&path.join(name), "expected a number")
+					})
+				})
+				.transpose()
+		};
+		let count = |name: &str| -> Result<Option<usize>, SchemaError> {
+			map.get(name)
+				.map(|value| {
+					value.as_u64().map(|n| n as usize).ok_or_else(|| {
+						error(
+							&path.join(name),
+							"expected a non-negative integer",
+						)
+					})
+				})
+				.transpose()
+		};
+
+		if let Some(m) = number("multipleOf")? {
+			if m <= 0.0 {
+				return Err(error(
+					&path.join("multipleOf"),
+					"expected a positive number",
+				));
+			}
+			push("multipleOf", KeywordKind::MultipleOf(m));
+		}
+		if let Some(n) = number("maximum")? {
+			push("maximum", KeywordKind::Maximum(n));
+		}
+		if let Some(n) = number("exclusiveMaximum")? {
+			push(
+				"exclusiveMaximum",
+				KeywordKind::ExclusiveMaximum(n),
+			);
+		}
+		if let Some(n) = number("minimum")? {
+			push("minimum", KeywordKind::Minimum(n));
+		}
+		if let Some(n) = number("exclusiveMinimum")? {
+			push(
+				"exclusiveMinimum",
+				KeywordKind::ExclusiveMinimum(n),
+			);
+		}
+		if let Some(n) = count("maxLength")? {
+			push("maxLength", KeywordKind::MaxLength(n));
+		}
+		if let Some(n) = count("minLength")? {
+			push("minLength", KeywordKind::MinLength(n));
+		}
+		if let Some(value) = map.get("pattern") {
+			let pattern = value.as_str().ok_or_else(|| {
+				error(
+					&path.join("pattern"),
+					"expected a string",
+				)
+			})?;
+			let regex = regex(&path.join("pattern"), pattern)?;
+			push(
+				"pattern",
+				KeywordKind::Pattern(pattern.to_owned(), regex),
+			);
+		}
+
+		let mut prefix = 0;
+		if map.contains_key("prefixItems") {
+			let schemas = self.schema_array(path, "prefixItems")?;
+			prefix = schemas.len();
+			push(
+				"prefixItems",
+				KeywordKind::PrefixItems(schemas),
+			);
+		}
+		if map.contains_key("items") {
+			let schema = self.compile(&path.join("items"))?;
+			push(
+				"items",
+				KeywordKind::Items {
+					schema,
+					skip: prefix,
+				},
+			);
+		}
+		if map.contains_key("contains") {
+			let schema = self.compile(&path.join("contains"))?;
+			let min = count("minContains")?.unwrap_or(1);
+			let max = count("maxContains")?;
+			push(
+				"contains",
+				KeywordKind::Contains { schema, min, max },
+			);
+		}
+		if let Some(n) = count("maxItems")? {
+			push("maxItems", KeywordKind::MaxItems(n));
+		}
+		if let Some(n) = count("minItems")? {
+			push("minItems", KeywordKind::MinItems(n));
+		}
+		if let Some(value) = map.get("uniqueItems") {
+			let unique = value.as_bool().ok_or_else(|| {
+				error(
+					&path.join("uniqueItems"),
+					"expected a boolean",
+				)
+			})?;
+			if unique {
+				push("uniqueItems", KeywordKind::UniqueItems);
+			}
+		}
+
+		let properties = if map.contains_key("properties") {
+			let schemas = self.schema_map(path, "properties")?;
+			push(
+				"properties",
+				KeywordKind::Properties(schemas.clone()),
+			);
+			schemas.into_iter().map(|(name, _)| name).collect()
+		} else {
+			Vec::new()
+		};
+		let mut patterns = Vec::new();
+		if map.contains_key("patternProperties") {
+			let schemas = self.schema_map(path, "patternProperties")?;
+			let mut compiled = Vec::new();
+			for (pattern, schema) in schemas {
+				let location = path.join("patternProperties").join(&*pattern);
+				let regex = regex(&location, &pattern)?;
+				patterns.push(regex.clone());
+				compiled.push((regex, schema));
+			}
+			push(
+				"patternProperties",
+				KeywordKind::PatternProperties(compiled),
+			);
+		}
+		if map.contains_key("additionalProperties") {
+			let schema = self.compile(&path.join("additionalProperties"))?;
+			push(
+				"additionalProperties",
+				KeywordKind::AdditionalProperties {
+					schema,
+					known: properties,
+					patterns,
+				},
+			);
+		}
+		if let Some(value) = map.get("required") {
+			let names = strings(&path.join("required"), value)?;
+			push("required", KeywordKind::Required(names));
+		}
+		if let Some(n) = count("maxProperties")? {
+			push(
+				"maxProperties",
+				KeywordKind::MaxProperties(n),
+			);
+		}
+		if let Some(n) = count("minProperties")? {
+			push(
+				"minProperties",
+				KeywordKind::MinProperties(n),
+			);
+		}
+		if let Some(value) = map.get("dependentRequired") {
+			let location = path.join("dependentRequired");
+			let dependencies = value
+				.as_object()
+				.ok_or_else(|| error(&location, "expected an object"))?
+				.iter()
+				.map(|(name, value)| {
+					Ok((
+						name.clone(),
+						strings(&location.join(&**name), value)?,
+					))
+				})
+				.collect::<Result<_, SchemaError>>()?;
+			push(
+				"dependentRequired",
+				KeywordKind::DependentRequired(dependencies),
+			);
+		}
+		if map.contains_key("dependentSchemas") {
+			let schemas = self.schema_map(path, "dependentSchemas")?;
+			push(
+				"dependentSchemas",
+				KeywordKind::DependentSchemas(schemas),
+			);
+		}
+		if map.contains_key("propertyNames") {
+			let schema = self.compile(&path.join("propertyNames"))?;
+			push(
+				"propertyNames",
+				KeywordKind::PropertyNames(schema),
+			);
+		}
+
+		if map.contains_key("allOf") {
+			let schemas = self.schema_array(path, "allOf")?;
+			push("allOf", KeywordKind::AllOf(schemas));
+		}
+		if map.contains_key("anyOf") {
+			let schemas = self.schema_array(path, "anyOf")?;
+			push("anyOf", KeywordKind::AnyOf(schemas));
+		}
+		if map.contains_key("oneOf") {
+			let schemas = self.schema_array(path, "oneOf")?;
+			push("oneOf", KeywordKind::OneOf(schemas));
+		}
+		if map.contains_key("not") {
+			let schema = self.compile(&path.join("not"))?;
+			push("not", KeywordKind::Not(schema));
+		}
+		if map.contains_key("if") {
+			let condition = self.compile(&path.join("if"))?;
+			let then = match map.contains_key("then") {
+				true => Some(self.compile(&path.join("then"))?),
+				false => None,
+			};
+			let otherwise = match map.contains_key("else") {
+				true => Some(self.compile(&path.join("else"))?),
+				false => None,
+			};
+			push(
+				"if",
+				KeywordKind::If {
+					condition,
+					then,
+					otherwise,
+				},
+			);
+		}
+
+		// These depend on the annotations of every other keyword, so they
+		// must be evaluated last.
+		if map.contains_key("unevaluatedItems") {
+			let schema = self.compile(&path.join("unevaluatedItems"))?;
+			push(
+				"unevaluatedItems",
+				KeywordKind::UnevaluatedItems(schema),
+			);
+		}
+		if map.contains_key("unevaluatedProperties") {
+			let schema = self.compile(&path.join("unevaluatedProperties"))?;
+			push(
+				"unevaluatedProperties",
+				KeywordKind::UnevaluatedProperties(schema),
+			);
+		}
+
+		Ok(keywords)
+	}
+
+	/// Resolves a `$ref` value to the document and location of its target,
+	/// loading the document if it is not loaded yet.
+	fn reference(
+		&mut self,
+		path: &JsonPointer,
+		value: &JsonValue,
+		base: &str,
+	) -> Result<(usize, JsonPointer), SchemaError> {
+		let reference = value
+			.as_str()
+			.ok_or_else(|| error(path, "expected a string"))?;
+		let unresolved = || {
+			error(
+				path,
+				format!("cannot resolve {reference:?}"),
+			)
+		};
+
+		let target = resolve_uri(base, reference);
+		let (uri, fragment) = target.split_once('#').unwrap_or((&target, ""));
+		if !self.resources.contains_key(uri) {
+			let loader = self.loader.as_mut().ok_or_else(unresolved)?;
+			let document = loader.load(uri).map_err(|message| {
+				error(
+					path,
+					format!("cannot load {uri:?}: {message}"),
+				)
+			})?;
+			self.add_document(
+				uri.to_owned(),
+				Document::Loaded(Rc::new(document)),
+			);
+		}
+		let (document, root) = &self.resources[uri];
+		let target = if fragment.is_empty() || fragment.starts_with('/') {
+			JsonPointer::parse(&percent_decode(fragment))
+				.ok()
+				.map(|pointer| {
+					root.tokens().iter().chain(pointer.tokens()).collect()
+				})
+				.map(|pointer| (*document, pointer))
+		} else {
+			self.anchors.get(&target).cloned()
+		};
+		target
+			.filter(|(document, target)| {
+				self.documents[*document].1.resolve(target).is_some()
+			})
+			.ok_or_else(unresolved)
+	}
+
+	/// Fails if a schema can apply itself to the same instance again
+	/// without looking into any part of it, as `{"$ref": "#"}` does, which
+	/// would never stop.
+	fn check_cycles(&self) -> Result<(), SchemaError> {
+		#[derive(Clone, Copy, PartialEq)]
+		enum Mark {
+			New,
+			Visiting,
+			Done,
+		}
+
+		fn visit(
+			nodes: &[Node],
+			scopes: &[Vec<(String, usize)>],
+			marks: &mut [Mark],
+			index: usize,
+			depth: usize,
+		) -> Result<(), SchemaError> {
+			if depth > Schema::MAX_DEPTH {
+				return Err(too_deep(&nodes[index].path));
+			}
+			match marks[index] {
+				Mark::Done => return Ok(()),
+				Mark::Visiting => {
+					return Err(error(
+						&nodes[index].path,
+						"schema refers to itself in a cycle",
+					))
+				}
+				Mark::New => {}
+			}
+			marks[index] = Mark::Visiting;
+			if let NodeKind::Keywords(keywords) = &nodes[index].kind {
+				for keyword in keywords {
+					let mut next = in_place(&keyword.kind);
+					// Any schema with the anchor's name might be the one a
+					// dynamic reference reaches.
+					if let KeywordKind::DynamicRef { anchor, .. } =
+						&keyword.kind
+					{
+						next.extend(scopes.iter().flatten().filter_map(
+							|(name, schema)| {
+								(name == anchor).then_some(*schema)
+							},
+						));
+					}
+					for next in next {
+						visit(nodes, scopes, marks, next, depth + 1)?;
+					}
+				}
+			}
+			marks[index] = Mark::Done;
+			Ok(())
+		}
+
+		let mut marks = vec![Mark::New; self.nodes.len()];
+		(0..self.nodes.len()).try_for_each(|index| {
+			visit(
+				&self.nodes,
+				&self.scopes,
+				&mut marks,
+				index,
+				0,
+			)
+		})
+	}
+
+	fn schema_array(
+		&mut self,
+		path: &JsonPointer,
+		name: &str,
+	) -> Result<Vec<usize>, SchemaError> {
+		let location = path.join(name);
+		let len = match self.documents[self.document].1.resolve(&location) {
+			Some(JsonValue::Array(items)) if !items.is_empty() => items.len(),
+			_ => {
+				return Err(error(
+					&location,
+					"expected a non-empty array",
+				))
+			}
+		};
+		(0..len)
+			.map(|i| self.compile(&location.join(i.to_string())))
+			.collect()
+	}
+
+	fn schema_map(
+		&mut self,
+		path: &JsonPointer,
+		name: &str,
+	) -> Result<Vec<(String, usize)>, SchemaError> {
+		let location = path.join(name);
+		let mut names: Vec<String> =
+			match self.documents[self.document].1.resolve(&location) {
+				Some(JsonValue::Object(map)) => map.keys().cloned().collect(),
+				_ => return Err(error(&location, "expected an object")),
+			};
+		names.sort();
+		names
+			.into_iter()
+			.map(|name| {
+				let schema = self.compile(&location.join(&*name))?;
+				Ok((name, schema))
+			})
+			.collect()
+	}
+}
+
+fn types(
+	path: &JsonPointer,
+	value: &JsonValue,
+) -> Result<Vec<InstanceType>, SchemaError> {
+	let names = match value {
+		JsonValue::String(name) => vec![name.clone()],
+		_ => strings(path, value)?,
+	};
+	names
+		.iter()
+		.map(|name| {
+			Ok(match name.as_str() {
+				"null" => InstanceType::Type(JsonType::Null),
+				"boolean" => InstanceType::Type(JsonType::Bool),
+				"number" => InstanceType::Type(JsonType::Number),
+				"string" => InstanceType::Type(JsonType::String),
+				"array" => InstanceType::Type(JsonType::Array),
+				"object" => InstanceType::Type(JsonType::Object),
+				"integer" => InstanceType::Integer,
+				_ => {
+					return Err(error(
+						path,
+						format!("unknown type {name:?}"),
+					))
+				}
+			})
+		})
+		.collect()
+}
+
+fn strings(
+	path: &JsonPointer,
+	value: &JsonValue,
+) -> Result<Vec<String>, SchemaError> {
+	value
+		.as_array()
+		.and_then(|items| {
+			items
+				.iter()
+				.map(|item| item.as_str().map(str::to_owned))
+				.collect()
+		})
+		.ok_or_else(|| error(path, "expected an array of strings"))
+}
+
+fn regex(path: &JsonPointer, pattern: &str) -> Result<Regex, SchemaError> {
+	Regex::new(pattern).map_err(|message| {
+		error(
+			path,
+			format!("invalid pattern: {message}"),
+		)
+	})
+}
+
+/// The schemas a keyword applies to the same instance as its own schema.
+fn in_place(keyword: &KeywordKind) -> Vec<usize> {
+	match keyword {
+		KeywordKind::Ref(schema)
+		| KeywordKind::DynamicRef { schema, .. }
+		| KeywordKind::Not(schema) => vec![*schema],
+		KeywordKind::AllOf(schemas)
+		| KeywordKind::AnyOf(schemas)
+		| KeywordKind::OneOf(schemas) => schemas.clone(),
+		KeywordKind::DependentSchemas(schemas) => {
+			schemas.iter().map(|&(_, schema)| schema).collect()
+		}
+		KeywordKind::If {
+			condition,
+			then,
+			otherwise,
+		} => [Some(*condition), *then, *otherwise]
+			.into_iter()
+			.flatten()
+			.collect(),
+		_ => Vec::new(),
+	}
+}
+
+/// The base URI inside a schema object, which its `$id` changes.
+fn rebase(base: &str, map: &HashMap<String, JsonValue>) -> String {
+	match map.get("$id").and_then(JsonValue::as_str) {
+		Some(id) => {
+			let uri = resolve_uri(base, id);
+			match uri.split_once('#') {
+				Some((uri, _)) => uri.to_owned(),
+				None => uri,
+			}
+		}
+		None => base.to_owned(),
+	}
+}
+
+/// Resolves a URI reference against a base URI, as RFC 3986 section 5.2
+/// does. A relative base gives a result as relative.
+fn resolve_uri(base: &str, reference: &str) -> String {
+	let base = UriParts::parse(base);
+	let r = UriParts::parse(reference);
+	let (scheme, authority, path, query) = if r.scheme.is_some() {
+		(
+			r.scheme,
+			r.authority,
+			remove_dot_segments(r.path),
+			r.query,
+		)
+	} else if r.authority.is_some() {
+		(
+			base.scheme,
+			r.authority,
+			remove_dot_segments(r.path),
+			r.query,
+		)
+	} else if r.path.is_empty() {
+		(
+			base.scheme,
+			base.authority,
+			base.path.to_owned(),
+			r.query.or(base.query),
+		)
+	} else {
+		let path = if r.path.starts_with('/') {
+			r.path.to_owned()
+		} else if base.authority.is_some() && base.path.is_empty() {
+			format!("/{}", r.path)
+		} else {
+			let directory = base.path.rfind('/').map_or(0, |slash| slash + 1);
+			format!("{}{}", &base.path[..directory], r.path)
+		};
+		(
+			base.scheme,
+			base.authority,
+			remove_dot_segments(&path),
+			r.query,
+		)
+	};
+
+	let mut uri = String::new();
+	if let Some(scheme) = scheme {
+		uri.push_str(scheme);
+		uri.push(':');
+	}
+	if let Some(authority) = authority {
+		uri.push_str("//");
+		uri.push_str(authority);
+	}
+	uri.push_str(&path);
+	if let Some(query) = query {
+		uri.push('?');
+		uri.push_str(query);
+	}
+	if let Some(fragment) = r.fragment {
+		uri.push('#');
+		uri.push_str(fragment);
+	}
+	uri
+}
+
+/// The components of a URI reference.
+struct UriParts<'a> {
+	scheme: Option<&'a str>,
+	authority: Option<&'a str>,
+	path: &'a str,
+	query: Option<&'a str>,
+	fragment: Option<&'a str>,
+}
+
+impl<'a> UriParts<'a> {
+	fn parse(uri: &'a str) -> Self {
+		let (rest, fragment) = match uri.split_once('#') {
+			Some((rest, fragment)) => (rest, Some(fragment)),
+			None => (uri, None),
+		};
+		let (mut rest, query) = match rest.split_once('?') {
+			Some((rest, query)) => (rest, Some(query)),
+			None => (rest, None),
+		};
+		let scheme = rest.split_once(':').and_then(|(scheme, after)| {
+			let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+				&& scheme.chars().all(|c| {
+					c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')
+				});
+			valid.then(|| {
+				rest = after;
+				scheme
+			})
+		});
+		let authority = rest.strip_prefix("//").map(|after| {
+			let end = after.find('/').unwrap_or(after.len());
+			rest = &after[end..];
+			&after[..end]
+		});
+		Self {
+			scheme,
+			authority,
+			path: rest,
+			query,
+			fragment,
+		}
+	}
+}
+
+/// Removes the `.` and `..` segments of a URI path.
+fn remove_dot_segments(path: &str) -> String {
+	let root = usize::from(path.starts_with('/'));
+	let mut output = Vec::new();
+	let mut segments = path.split('/').peekable();
+	while let Some(segment) = segments.next() {
+		let last = segments.peek().is_none();
+		match segment {
+			"." | ".." => {
+				if segment == ".." && output.len() > root {
+					output.pop();
+				}
+				if last {
+					output.push("");
+				}
+			}
+			_ => output.push(segment),
+		}
+	}
+	output.join("/")
+}
+
+/// Decodes `%XX` escapes in a URI fragment.
+fn percent_decode(fragment: &str) -> String {
+	let bytes = fragment.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let hex = bytes
+			.get(i + 1..i + 3)
+			.and_then(|hex| std::str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match (bytes[i], hex) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 3;
+			}
+			(byte, _) => {
+				decoded.push(byte);
+				i += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn compile(schema: &str) -> Result<Schema, SchemaError> {
+		Schema::compile(&parse(schema).unwrap())
+	}
+
+	#[test]
+	fn resolves_uris() {
+		let base = "http://a/b/c/d;p?q";
+		for (reference, resolved) in [
+			("g", "http://a/b/c/g"),
+			("./g/", "http://a/b/c/g/"),
+			("/g", "http://a/g"),
+			("//g", "http://g"),
+			("?y", "http://a/b/c/d;p?y"),
+			("#s", "http://a/b/c/d;p?q#s"),
+			("", "http://a/b/c/d;p?q"),
+			("../..", "http://a/"),
+			("../../../g", "http://a/g"),
+			("g:h", "g:h"),
+		] {
+			assert_eq!(
+				resolve_uri(base, reference),
+				resolved,
+				"{reference}"
+			);
+		}
+		assert_eq!(
+			resolve_uri("dir/root.json", "item.json"),
+			"dir/item.json"
+		);
+	}
+
+	#[test]
+	fn refs_resolve_against_ids() {
+		let schema = compile(
+			r##"{
+				"$id": "https://example.com/schemas/root",
+				"$defs": {
+					"item": {"$id": "item", "type": "integer"},
+					"named": {"$anchor": "name", "type": "string"}
+				},
+				"properties": {
+					"items": {"items": {"$ref": "item"}},
+					"name": {"$ref": "#name"},
+					"absolute": {"$ref": "https://example.com/schemas/item"}
+				}
+			}"##,
+		)
+		.unwrap();
+		let valid = |instance: &str| schema.is_valid(&parse(instance).unwrap());
+		assert!(valid(
+			r#"{"items": [1, 2], "name": "a", "absolute": 3}"#
+		));
+		assert!(!valid(r#"{"items": [1, "2"]}"#));
+		assert!(!valid(r#"{"name": 1}"#));
+		assert!(!valid(r#"{"absolute": 1.5}"#));
+	}
+
+	#[test]
+	fn loader_supplies_other_documents() {
+		let mut documents = HashMap::new();
+		documents.insert(
+			"https://example.com/point".to_owned(),
+			parse(
+				r##"{
+					"type": "object",
+					"properties": {"x": {"$ref": "#/$defs/coordinate"}},
+					"$defs": {"coordinate": {"type": "number"}}
+				}"##,
+			)
+			.unwrap(),
+		);
+		let root = parse(
+			r#"{
+				"$id": "https://example.com/root",
+				"items": {"$ref": "point"}
+			}"#,
+		)
+		.unwrap();
+		let schema =
+			Schema::compile_with_loader(&root, &mut documents).unwrap();
+		assert!(schema.is_valid(&parse(r#"[{"x": 1}]"#).unwrap()));
+		assert!(!schema.is_valid(&parse(r#"[{"x": "1"}]"#).unwrap()));
+
+		let mut loads = Vec::new();
+		let twice = parse(
+			r#"{
+				"$id": "https://example.com/root",
+				"properties": {"a": {"$ref": "other"}, "b": {"$ref": "other"}}
+			}"#,
+		)
+		.unwrap();
+		let mut loader = |uri: &str| {
+			loads.push(uri.to_owned());
+			Ok(JsonValue::Boolean(true))
+		};
+		Schema::compile_with_loader(&twice, &mut loader).unwrap();
+		assert_eq!(loads, ["https://example.com/other"]);
+
+		let error = Schema::compile_with_loader(&twice, &mut |_: &str| {
+			Err("offline".to_owned())
+		})
+		.unwrap_err();
+		assert!(
+			error.message.ends_with(": offline"),
+			"{}",
+			error.message
+		);
+	}
+
+	#[test]
+	fn unresolved_refs_fail_to_compile() {
+		let error =
+			compile(r#"{"$ref": "https://example.com/other"}"#).unwrap_err();
+		assert_eq!(
+			error.message,
+			r#"cannot resolve "https://example.com/other""#
+		);
+		assert!(compile(r##"{"$ref": "#/$defs/missing"}"##).is_err());
+		assert!(compile(r##"{"$ref": "#nowhere"}"##).is_err());
+	}
+}
@@ -0,0 +1,179 @@
+//! Compiling the schemas embedded in OpenAPI descriptions.
+
+use super::{Schema, SchemaError};
+use crate::{JsonPointer, JsonValue};
+
+impl Schema {
+	/// Compiles the schema at `location` in the OpenAPI description
+	/// `document`, such as `/components/schemas/Pet` or the `schema` of a
+	/// response. References such as `#/components/schemas/Tag` resolve
+	/// against the whole description, and errors give schema locations in
+	/// it.
+	///
+	/// OpenAPI 3.1 schemas are JSON Schema 2020-12 and are compiled as
+	/// they are. When the description's `openapi` version is 3.0, the
+	/// keywords in which that version's dialect differs are translated
+	/// first: `nullable: true` also allows `null`, and the boolean forms of
+	/// `exclusiveMinimum` and `exclusiveMaximum` apply to `minimum` and
+	/// `maximum`. Annotations such as `example` and `discriminator` are
+	/// ignored.
+	///
+	/// ```text
+	/// let pet = Schema::compile_openapi(&spec, &"/components/schemas/Pet".parse()?)?;
+	/// assert_json_matches_schema!(response_body, pet);
+	/// ```
+	pub fn compile_openapi(
+		document: &JsonValue,
+		location: &JsonPointer,
+	) -> Result<Self, SchemaError> {
+		let version = document.get("openapi").and_then(JsonValue::as_str);
+		if !version.is_some_and(|version| version.starts_with("3.0")) {
+			return Self::compile_from(document, location, None);
+		}
+		let mut document = document.clone();
+		translate(&mut document);
+		Self::compile_from(&document, location, None)
+	}
+}
+
+/// Rewrites the OpenAPI 3.0 keywords in `value` as JSON Schema 2020-12
+/// ones.
+fn translate(value: &mut JsonValue) {
+	match value {
+		JsonValue::Object(map) => {
+			if map.get("nullable") == Some(&JsonValue::Boolean(true)) {
+				map.remove("nullable");
+				let null = JsonValue::String("null".to_owned());
+				match map.get_mut("type") {
+					Some(JsonValue::String(name)) => {
+						let name = JsonValue::String(std::mem::take(name));
+						map.insert(
+							"type".to_owned(),
+							JsonValue::Array(vec![name, null]),
+						);
+					}
+					Some(JsonValue::Array(names)) if !names.contains(&null) => {
+						names.push(null);
+					}
+					_ => {}
+				}
+				if let Some(JsonValue::Array(values)) = map.get_mut("enum") {
+					if !values.contains(&JsonValue::Null) {
+						values.push(JsonValue::Null);
+					}
+				}
+			}
+			for (exclusive, bound) in [
+				("exclusiveMinimum", "minimum"),
+				("exclusiveMaximum", "maximum"),
+			] {
+				if let Some(&JsonValue::Boolean(b)) = map.get(exclusive) {
+					map.remove(exclusive);
+					if b {
+						if let Some(bound) = map.remove(bound) {
+							map.insert(exclusive.to_owned(), bound);
+						}
+					}
+				}
+			}
+			map.values_mut().for_each(translate);
+		}
+		JsonValue::Array(items) => items.iter_mut().for_each(translate),
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn compile(document: &str, location: &str) -> Result<Schema, SchemaError> {
+		Schema::compile_openapi(
+			&parse(document).unwrap(),
+			&JsonPointer::parse(location).unwrap(),
+		)
+	}
+
+	#[test]
+	fn refs_resolve_against_the_description() {
+		let pet = compile(
+			r##"{
+				"openapi": "3.1.0",
+				"components": {"schemas": {
+					"Pet": {
+						"type": "object",
+						"required": ["name"],
+						"properties": {
+							"name": {"type": "string"},
+							"tags": {"items": {"$ref": "#/components/schemas/Tag"}}
+						}
+					},
+					"Tag": {"type": ["string", "null"]}
+				}}
+			}"##,
+			"/components/schemas/Pet",
+		)
+		.unwrap();
+		let valid = |body: &str| pet.is_valid(&parse(body).unwrap());
+		assert!(valid(
+			r#"{"name": "Rex", "tags": ["a", null]}"#
+		));
+		assert!(!valid(
+			r#"{"name": "Rex", "tags": [1]}"#
+		));
+		assert!(!valid(r#"{"tags": []}"#));
+	}
+
+	#[test]
+	fn version_3_0_keywords_are_translated() {
+		let document = r#"{
+			"openapi": "3.0.3",
+			"components": {"schemas": {
+				"Age": {
+					"type": "integer",
+					"nullable": true,
+					"minimum": 0,
+					"exclusiveMinimum": true,
+					"maximum": 150,
+					"exclusiveMaximum": false
+				},
+				"Colour": {"enum": ["red", "blue"], "nullable": true}
+			}}
+		}"#;
+		let age = compile(document, "/components/schemas/Age").unwrap();
+		let valid = |body: &str| age.is_valid(&parse(body).unwrap());
+		assert!(valid("null"));
+		assert!(valid("1"));
+		assert!(valid("150"));
+		assert!(!valid("0"));
+		assert!(!valid("151"));
+		assert!(!valid("\"1\""));
+
+		let colour = compile(document, "/components/schemas/Colour").unwrap();
+		assert!(colour.is_valid(&JsonValue::Null));
+		assert!(!colour.is_valid(&parse(r#""green""#).unwrap()));
+
+		// 3.1 takes the keywords as JSON Schema does, where nullable is
+		// not one.
+		let age = compile(
+			r#"{"openapi": "3.1.0", "Age": {"type": "integer", "nullable": true}}"#,
+			"/Age",
+		)
+		.unwrap();
+		assert!(!age.is_valid(&JsonValue::Null));
+	}
+
+	#[test]
+	fn errors_locate_the_schema_in_the_description() {
+		let error = compile(
+			r#"{"openapi": "3.1.0", "paths": {}, "components": {"schemas": {"Bad": {"minLength": "1"}}}}"#,
+			"/components/schemas/Bad",
+		)
+		.unwrap_err();
+		assert_eq!(
+			error.path.to_string(),
+			"/components/schemas/Bad/minLength"
+		);
+	}
+}
@@ -0,0 +1,662 @@
+//! A small matcher for the subset of ECMA-262 regular expressions that
+//! schemas use in `pattern` and `patternProperties`.
+//!
+//! Supported: literals, `.`, classes (`[a-z]`, `[^...]`), `\d \w \s` and
+//! their negations, `^`, `$`, `\b`, groups (`(...)`, `(?:...)`), lookahead
+//! (`(?=...)`, `(?!...)`), alternation and greedy or lazy quantifiers.
+//! Backreferences and Unicode property escapes are rejected.
+//!
+//! Patterns are compiled to a Thompson NFA and simulated one character at
+//! a time without recursion, so nested quantifiers such as `(a+)+$` take
+//! time linear in the input rather than exponential. Lookaheads run a
+//! second simulation at each position they are reached; a limit on the
+//! total number of steps keeps those from running away, and a match that
+//! reaches it fails with [`TooComplex`].
+
+/// The most instructions a compiled pattern may have; `{n,m}` copies its
+/// operand up to `m` times.
+const MAX_INSTS: usize = 100_000;
+
+/// The most states a single match may visit.
+const MAX_STEPS: usize = 1 << 24;
+
+/// The most groups and lookaheads a pattern may nest.
+const MAX_NESTING: usize = 64;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Regex {
+	insts: Vec<Inst>,
+}
+
+/// A match that gave up after [`MAX_STEPS`] steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TooComplex;
+
+impl std::fmt::Display for TooComplex {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("pattern too complex to match")
+	}
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+	Empty,
+	Char(char),
+	Any,
+	Class(Class),
+	Start,
+	End,
+	WordBoundary(bool),
+	Look(Box<Node>, bool),
+	Concat(Vec<Node>),
+	Alt(Vec<Node>),
+	/// Whether the repetition is greedy does not change whether a string
+	/// matches, so it is not kept.
+	Repeat {
+		node: Box<Node>,
+		min: usize,
+		max: Option<usize>,
+	},
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+	ranges: Vec<(char, char)>,
+	negated: bool,
+}
+
+impl Class {
+	fn matches(&self, c: char) -> bool {
+		self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+	}
+}
+
+/// An instruction of a compiled pattern. Those that consume a character
+/// move on to the next instruction.
+#[derive(Debug, Clone)]
+enum Inst {
+	Char(char),
+	Any,
+	Class(Class),
+	Start,
+	End,
+	WordBoundary(bool),
+	/// Goes on to `next` if the lookahead that follows, up to its `Match`,
+	/// matching here is `expected`.
+	Look {
+		expected: bool,
+		next: usize,
+	},
+	Split(usize, usize),
+	Jmp(usize),
+	Match,
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[
+	('\t', '\r'),
+	(' ', ' '),
+	('\u{a0}', '\u{a0}'),
+	('\u{1680}', '\u{1680}'),
+	('\u{2000}', '\u{200a}'),
+	('\u{2028}', '\u{2029}'),
+	('\u{202f}', '\u{202f}'),
+	('\u{205f}', '\u{205f}'),
+	('\u{3000}', '\u{3000}'),
+	('\u{feff}', '\u{feff}'),
+];
+
+impl Regex {
+	pub(crate) fn new(pattern: &str) -> Result<Self, String> {
+		let mut parser = RegexParser {
+			chars: pattern.chars().collect(),
+			pos: 0,
+			depth: 0,
+		};
+		let node = parser.alternation()?;
+		if let Some(c) = parser.peek() {
+			return Err(format!("unexpected {c:?} in pattern"));
+		}
+		let mut insts = Vec::new();
+		compile(&node, &mut insts)?;
+		push(&mut insts, Inst::Match)?;
+		Ok(Self { insts })
+	}
+
+	/// Searches for a match anywhere in `haystack`, as ECMA-262's `test`
+	/// does.
+	pub(crate) fn is_match(&self, haystack: &str) -> Result<bool, TooComplex> {
+		let chars: Vec<char> = haystack.chars().collect();
+		let mut steps = MAX_STEPS;
+		self.run(&chars, 0, 0, false, &mut steps)
+	}
+
+	/// Simulates the program from instruction `start` at position `from`,
+	/// trying every later position too unless `anchored`.
+	fn run(
+		&self,
+		input: &[char],
+		start: usize,
+		from: usize,
+		anchored: bool,
+		steps: &mut usize,
+	) -> Result<bool, TooComplex> {
+		let mut current = Threads::new(self.insts.len());
+		let mut next = Threads::new(self.insts.len());
+		for pos in from..=input.len() {
+			if (!anchored || pos == from)
+				&& self.add(&mut current, start, input, pos, steps)?
+			{
+				return Ok(true);
+			}
+			if current.list.is_empty() && anchored {
+				return Ok(false);
+			}
+			next.clear();
+			for &pc in &current.list {
+				spend(steps)?;
+				let consumed = match (&self.insts[pc], input.get(pos)) {
+					(Inst::Char(c), Some(d)) => c == d,
+					(Inst::Any, Some(&c)) => c != '\n' && c != '\r',
+					(Inst::Class(class), Some(&c)) => class.matches(c),
+					_ => false,
+				};
+				if consumed
+					&& self.add(&mut next, pc + 1, input, pos + 1, steps)?
+				{
+					return Ok(true);
+				}
+			}
+			std::mem::swap(&mut current, &mut next);
+		}
+		Ok(false)
+	}
+
+	/// Adds the thread at `pc` to `threads`, following every jump and
+	/// assertion that holds at `pos`, and returns whether one reached
+	/// `Match`.
+	fn add(
+		&self,
+		threads: &mut Threads,
+		pc: usize,
+		input: &[char],
+		pos: usize,
+		steps: &mut usize,
+	) -> Result<bool, TooComplex> {
+		let mut stack = vec![pc];
+		while let Some(pc) = stack.pop() {
+			if !threads.visit(pc) {
+				continue;
+			}
+			spend(steps)?;
+			match self.insts[pc] {
+				Inst::Match => return Ok(true),
+				Inst::Char(_) | Inst::Any | Inst::Class(_) => {
+					threads.list.push(pc)
+				}
+				Inst::Start if pos == 0 => stack.push(pc + 1),
+				Inst::End if pos == input.len() => stack.push(pc + 1),
+				Inst::WordBoundary(expected) => {
+					let before = pos > 0 && is_word(input, pos - 1);
+					if (before != is_word(input, pos)) == expected {
+						stack.push(pc + 1);
+					}
+				}
+				Inst::Look { expected, next } => {
+					if self.run(input, pc + 1, pos, true, steps)? == expected {
+						stack.push(next);
+					}
+				}
+				Inst::Split(first, second) => {
+					stack.push(second);
+					stack.push(first);
+				}
+				Inst::Jmp(to) => stack.push(to),
+				Inst::Start | Inst::End => {}
+			}
+		}
+		Ok(false)
+	}
+}
+
+/// The threads of a simulation at one position, each waiting to consume a
+/// character, and which instructions have been visited there.
+struct Threads {
+	list: Vec<usize>,
+	visited: Vec<usize>,
+	generation: usize,
+}
+
+impl Threads {
+	fn new(len: usize) -> Self {
+		Self {
+			list: Vec::new(),
+			visited: vec![0; len],
+			generation: 1,
+		}
+	}
+
+	fn clear(&mut self) {
+		self.list.clear();
+		self.generation += 1;
+	}
+
+	/// Marks `pc` visited, returning whether it was not already.
+	fn visit(&mut self, pc: usize) -> bool {
+		let fresh = self.visited[pc] != self.generation;
+		self.visited[pc] = self.generation;
+		fresh
+	}
+}
+
+fn spend(steps: &mut usize) -> Result<(), TooComplex> {
+	*steps = steps.checked_sub(1).ok_or(TooComplex)?;
+	Ok(())
+}
+
+fn is_word(chars: &[char], pos: usize) -> bool {
+	chars
+		.get(pos)
+		.is_some_and(|&c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn push(insts: &mut Vec<Inst>, inst: Inst) -> Result<usize, String> {
+	if insts.len() >= MAX_INSTS {
+		return Err("pattern is too large".to_owned());
+	}
+	insts.push(inst);
+	Ok(insts.len() - 1)
+}
+
+fn compile(node: &Node, insts: &mut Vec<Inst>) -> Result<(), String> {
+	match node {
+		Node::Empty => {}
+		Node::Char(c) => _ = push(insts, Inst::Char(*c))?,
+		Node::Any => _ = push(insts, Inst::Any)?,
+		Node::Class(class) => _ = push(insts, Inst::Class(class.clone()))?,
+		Node::Start => _ = push(insts, Inst::Start)?,
+		Node::End => _ = push(insts, Inst::End)?,
+		Node::WordBoundary(expected) => {
+			push(insts, Inst::WordBoundary(*expected))?;
+		}
+		Node::Look(inner, expected) => {
+			let look = push(
+				insts,
+				Inst::Look {
+					expected: *expected,
+					next: 0,
+				},
+			)?;
+			compile(inner, insts)?;
+			push(insts, Inst::Match)?;
+			let end = insts.len();
+			if let Inst::Look { next, .. } = &mut insts[look] {
+				*next = end;
+			}
+		}
+		Node::Concat(nodes) => {
+			for node in nodes {
+				compile(node, insts)?;
+			}
+		}
+		Node::Alt(nodes) => {
+			let mut jumps = Vec::new();
+			let (last, rest) = nodes.split_last().ok_or("empty alternation")?;
+			for node in rest {
+				let split = push(insts, Inst::Split(0, 0))?;
+				compile(node, insts)?;
+				jumps.push(push(insts, Inst::Jmp(0))?);
+				insts[split] = Inst::Split(split + 1, insts.len());
+			}
+			compile(last, insts)?;
+			let end = insts.len();
+			for jump in jumps {
+				insts[jump] = Inst::Jmp(end);
+			}
+		}
+		Node::Repeat { node, min, max } => {
+			for _ in 0..*min {
+				compile(node, insts)?;
+			}
+			match max {
+				None => {
+					let split = push(insts, Inst::Split(0, 0))?;
+					compile(node, insts)?;
+					push(insts, Inst::Jmp(split))?;
+					insts[split] = Inst::Split(split + 1, insts.len());
+				}
+				Some(max) => {
+					let mut splits = Vec::new();
+					for _ in *min..*max {
+						splits.push(push(insts, Inst::Split(0, 0))?);
+						compile(node, insts)?;
+					}
+					let end = insts.len();
+					for split in splits {
+						insts[split] = Inst::Split(split + 1, end);
+					}
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+struct RegexParser {
+	chars: Vec<char>,
+	pos: usize,
+	/// How many groups and lookaheads enclose the position.
+	depth: usize,
+}
+
+impl RegexParser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).copied()
+	}
+
+	fn eat(&mut self, c: char) -> bool {
+		let found = self.peek() == Some(c);
+		if found {
+			self.pos += 1;
+		}
+		found
+	}
+
+	fn next(&mut self) -> Result<char, String> {
+		let c = self.peek().ok_or("unexpected end of pattern")?;
+		self.pos += 1;
+		Ok(c)
+	}
+
+	fn alternation(&mut self) -> Result<Node, String> {
+		let mut branches = vec![self.concatenation()?];
+		while self.eat('|') {
+			branches.push(self.concatenation()?);
+		}
+		Ok(if branches.len() == 1 {
+			branches.pop().unwrap_or(Node::Empty)
+		} else {
+			Node::Alt(branches)
+		})
+	}
+
+	fn concatenation(&mut self) -> Result<Node, String> {
+		let mut nodes = Vec::new();
+		while !matches!(
+			self.peek(),
+			None | Some('|') | Some(')')
+		) {
+			let atom = self.atom()?;
+			nodes.push(self.quantified(atom)?);
+		}
+		Ok(Node::Concat(nodes))
+	}
+
+	fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+		let (min, max) = match self.peek() {
+			Some('{') => match self.braces() {
+				Some(bounds) => bounds,
+				None => return Ok(atom),
+			},
+			Some(c @ ('*' | '+' | '?')) => {
+				self.pos += 1;
+				match c {
+					'*' => (0, None),
+					'+' => (1, None),
+					_ => (0, Some(1)),
+				}
+			}
+			_ => return Ok(atom),
+		};
+		if matches!(
+			atom,
+			Node::Start | Node::End | Node::WordBoundary(_) | Node::Look(..)
+		) {
+			return Err("nothing to repeat".to_owned());
+		}
+		self.eat('?');
+		Ok(Node::Repeat {
+			node: Box::new(atom),
+			min,
+			max,
+		})
+	}
+
+	/// Parses `{n}`, `{n,}` or `{n,m}`; anything else is a literal brace.
+	fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+		let rest: String = self.chars[self.pos..].iter().collect();
+		let end = rest.find('}')?;
+		let body = &rest[1..end];
+		let (min, max) = match body.split_once(',') {
+			None => {
+				let n = body.parse().ok()?;
+				(n, Some(n))
+			}
+			Some((min, "")) => (min.parse().ok()?, None),
+			Some((min, max)) => (
+				min.parse().ok()?,
+				Some(max.parse().ok()?),
+			),
+		};
+		if max.is_some_and(|max| max < min) {
+			return None;
+		}
+		self.pos += rest[..=end].chars().count();
+		Some((min, max))
+	}
+
+	fn atom(&mut self) -> Result<Node, String> {
+		Ok(match self.next()? {
+			'.' => Node::Any,
+			'^' => Node::Start,
+			'$' => Node::End,
+			'[' => Node::Class(self.class()?),
+			'(' => {
+				if self.depth == MAX_NESTING {
+					return Err("pattern nests too deeply".to_owned());
+				}
+				self.depth += 1;
+				let node = if self.eat('?') {
+					match self.next()? {
+						':' => self.alternation()?,
+						'=' => Node::Look(Box::new(self.alternation()?), true),
+						'!' => Node::Look(Box::new(self.alternation()?), false),
+						c => return Err(format!("unsupported group (?{c}")),
+					}
+				} else {
+					self.alternation()?
+				};
+				if !self.eat(')') {
+					return Err("unterminated group".to_owned());
+				}
+				self.depth -= 1;
+				node
+			}
+			')' => return Err("unmatched ')'".to_owned()),
+			'*' | '+' | '?' => return Err("nothing to repeat".to_owned()),
+			'\\' => match self.next()? {
+				'b' => Node::WordBoundary(true),
+				'B' => Node::WordBoundary(false),
+				c => match self.class_escape(c)? {
+					Ok(class) => Node::Class(class),
+					Err(c) => Node::Char(c),
+				},
+			},
+			c => Node::Char(c),
+		})
+	}
+
+	fn class(&mut self) -> Result<Class, String> {
+		let negated = self.eat('^');
+		let mut ranges = Vec::new();
+		while !self.eat(']') {
+			let lo = match self.class_atom()? {
+				Ok(class) => {
+					push_class(&mut ranges, class);
+					continue;
+				}
+				Err(c) => c,
+			};
+			if self.peek() == Some('-')
+				&& self.chars.get(self.pos + 1).is_some_and(|&c| c != ']')
+			{
+				self.pos += 1;
+				let hi = self
+					.class_atom()?
+					.err()
+					.filter(|&hi| hi >= lo)
+					.ok_or("invalid class range")?;
+				ranges.push((lo, hi));
+			} else {
+				ranges.push((lo, lo));
+			}
+		}
+		Ok(Class { ranges, negated })
+	}
+
+	fn class_atom(&mut self) -> Result<Result<Class, char>, String> {
+		match self.next().map_err(|_| "unterminated class")? {
+			'\\' => match self.next()? {
+				'b' => Ok(Err('\x08')),
+				c => self.class_escape(c),
+			},
+			c => Ok(Err(c)),
+		}
+	}
+
+	/// Interprets the character after a backslash, returning either a class
+	/// (`\d`, `\W`, ...) or the literal character it stands for.
+	fn class_escape(&mut self, c: char) -> Result<Result<Class, char>, String> {
+		let class = |ranges: &[(char, char)], negated| {
+			Ok(Ok(Class {
+				ranges: ranges.to_vec(),
+				negated,
+			}))
+		};
+		match c {
+			'd' => class(DIGIT, false),
+			'D' => class(DIGIT, true),
+			'w' => class(WORD, false),
+			'W' => class(WORD, true),
+			's' => class(SPACE, false),
+			'S' => class(SPACE, true),
+			'n' => Ok(Err('\n')),
+			'r' => Ok(Err('\r')),
+			't' => Ok(Err('\t')),
+			'f' => Ok(Err('\x0C')),
+			'v' => Ok(Err('\x0B')),
+			'0' => Ok(Err('\0')),
+			'x' => self.hex(2).map(Err),
+			'u' => self.hex(4).map(Err),
+			'1'..='9' => Err("backreferences are not supported".to_owned()),
+			'p' | 'P' => {
+				Err("unicode property escapes are not supported".to_owned())
+			}
+			c if c.is_ascii_alphanumeric() => {
+				Err(format!("unknown escape \\{c}"))
+			}
+			c => Ok(Err(c)),
+		}
+	}
+
+	fn hex(&mut self, len: usize) -> Result<char, String> {
+		let digits: String =
+			(0..len).map(|_| self.next()).collect::<Result<_, _>>()?;
+		u32::from_str_radix(&digits, 16)
+			.ok()
+			.and_then(char::from_u32)
+			.ok_or_else(|| format!("invalid hex escape {digits:?}"))
+	}
+}
+
+fn push_class(ranges: &mut Vec<(char, char)>, class: Class) {
+	if !class.negated {
+		ranges.extend(class.ranges);
+		return;
+	}
+	// Complement the (sorted, disjoint) ranges of the negated class.
+	let mut next = '\0';
+	for (lo, hi) in class.ranges {
+		if lo > next {
+			ranges.push((
+				next,
+				char::from_u32(lo as u32 - 1).unwrap_or(next),
+			));
+		}
+		next = char::from_u32(hi as u32 + 1).unwrap_or(char::MAX);
+	}
+	ranges.push((next, char::MAX));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn is_match(pattern: &str, haystack: &str) -> bool {
+		Regex::new(pattern).unwrap().is_match(haystack).unwrap()
+	}
+
+	#[test]
+	fn syntax() {
+		assert!(is_match("b", "abc"));
+		assert!(!is_match("^b", "abc"));
+		assert!(is_match("^a.c$", "abc"));
+		assert!(!is_match("^a.c$", "a\nc"));
+		assert!(is_match("^[a-c]+$", "abcabc"));
+		assert!(!is_match("^[^a-c]", "abc"));
+		assert!(is_match(r"^\d{3}-\d{4}$", "555-1234"));
+		assert!(!is_match(r"^\d{3}-\d{4}$", "5555-1234"));
+		assert!(is_match(r"^\w+\s\W$", "word !"));
+		assert!(is_match(r"\bcat\b", "a cat sat"));
+		assert!(!is_match(r"\bcat\b", "concatenate"));
+		assert!(is_match("^(?:ab|cd)*$", "abcdab"));
+		assert!(!is_match("^(?:ab|cd)*$", "abc"));
+		assert!(is_match("^a{2,}?$", "aaa"));
+		assert!(!is_match("^a{2,3}$", "aaaa"));
+		assert!(is_match(
+			"^(?=.*[0-9])(?!.*x).+$",
+			"abc1"
+		));
+		assert!(!is_match(
+			"^(?=.*[0-9])(?!.*x).+$",
+			"abc1x"
+		));
+		assert!(is_match("^(a*)*$", "aaa"));
+		assert!(is_match("^$", ""));
+	}
+
+	#[test]
+	fn invalid_patterns() {
+		assert!(Regex::new("(a").is_err());
+		assert!(Regex::new("a)").is_err());
+		assert!(Regex::new("*a").is_err());
+		assert!(Regex::new("^*").is_err());
+		assert!(Regex::new(&"(".repeat(MAX_NESTING + 1)).is_err());
+		assert!(Regex::new("(?:a{1000}){1000}").is_err());
+	}
+
+	#[test]
+	fn long_input() {
+		let haystack = "a".repeat(200_000);
+		assert!(is_match("^a+$", &haystack));
+		assert!(is_match("^(a|b)*$", &haystack));
+		assert!(!is_match("^a*b", &haystack));
+	}
+
+	#[test]
+	fn nested_quantifiers() {
+		let haystack = format!("{}!", "a".repeat(10_000));
+		assert!(!is_match("^(a+)+$", &haystack));
+		assert!(!is_match("^(a|aa)*$", &haystack));
+	}
+
+	#[test]
+	fn too_complex() {
+		let regex = Regex::new("(?=a*b)").unwrap();
+		assert_eq!(
+			regex.is_match(&"a".repeat(200_000)),
+			Err(TooComplex)
+		);
+	}
+}
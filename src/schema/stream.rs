@@ -0,0 +1,620 @@
+use std::{collections::HashSet, error, fmt, mem};
+
+use super::{
+	validate::{too_deep, Evaluated},
+	Keyword, KeywordKind, NodeKind, Schema, ValidationError,
+};
+use crate::{
+	events::{Event, EventReader, ValueBuilder},
+	Error, ErrorKind, JsonPointer, JsonValue,
+};
+
+/// Why [`Schema::validate_str`] rejected a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamError {
+	Syntax(Error),
+	Invalid(ValidationError),
+}
+
+impl fmt::Display for StreamError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StreamError::Syntax(e) => e.fmt(f),
+			StreamError::Invalid(e) => e.fmt(f),
+		}
+	}
+}
+
+impl error::Error for StreamError {}
+
+/// Validates a document one [`Event`] at a time, stopping at the first
+/// violation.
+///
+/// Only the open containers are remembered. A value is buffered in full
+/// only when a keyword applying to it needs to see all of it at once:
+/// `enum` and `const` on containers, `uniqueItems`, `contains`,
+/// `dependentSchemas`, `anyOf`, `oneOf`, `not`, `if` and the `unevaluated`
+/// keywords.
+#[derive(Debug)]
+pub struct StreamValidator<'s> {
+	schema: &'s Schema,
+	stack: Vec<Frame>,
+	buffer: Option<Buffer>,
+	/// The location of the innermost value being validated, which every
+	/// frame extends by a token while it is open.
+	path: JsonPointer,
+	/// The dynamic scope of the innermost open container: the resources
+	/// with dynamic anchors that the containers are in, the outermost first.
+	scope: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Frame {
+	/// The schemas applying to the container, with `allOf` and `$ref`
+	/// expanded.
+	schemas: Vec<usize>,
+	/// How long `scope` was before the container added its resources.
+	scope: usize,
+	kind: FrameKind,
+}
+
+#[derive(Debug)]
+enum FrameKind {
+	Array {
+		len: usize,
+	},
+	Object {
+		len: usize,
+		/// The keys seen so far, kept only when `required` or
+		/// `dependentRequired` need them.
+		keys: Option<HashSet<String>>,
+		key: Option<String>,
+	},
+}
+
+#[derive(Debug)]
+struct Buffer {
+	schemas: Vec<usize>,
+	builder: ValueBuilder,
+}
+
+fn error(
+	path: &JsonPointer,
+	keyword: &Keyword,
+	message: String,
+) -> ValidationError {
+	ValidationError {
+		instance_path: path.clone(),
+		schema_path: keyword.path.clone(),
+		message,
+		kind: ErrorKind::Syntax,
+	}
+}
+
+impl<'s> StreamValidator<'s> {
+	pub fn new(schema: &'s Schema) -> Self {
+		Self {
+			schema,
+			stack: Vec::new(),
+			buffer: None,
+			path: JsonPointer::root(),
+			scope: Vec::new(),
+		}
+	}
+
+	/// Checks the next event of the document.
+	pub fn feed(&mut self, event: &Event) -> Result<(), ValidationError> {
+		if let Some(mut buffer) = self.buffer.take() {
+			match buffer.builder.push(event.clone()) {
+				None => self.buffer = Some(buffer),
+				Some(value) => {
+					self.check_here(&buffer.schemas, &value)?;
+					self.end_child();
+				}
+			}
+			return Ok(());
+		}
+
+		match event {
+			Event::Key(key) => self.key(key),
+			Event::EndObject | Event::EndArray => {
+				if let Some(frame) = self.stack.pop() {
+					self.close(&frame)?;
+					self.scope.truncate(frame.scope);
+				}
+				self.end_child();
+				Ok(())
+			}
+			event => {
+				let (schemas, token) = self.child();
+				if let Some(token) = token {
+					self.path.push(token);
+				}
+				self.open(self.expand(schemas), event)
+			}
+		}
+	}
+
+	/// The schemas, with `allOf` and `$ref` expanded, that apply to the
+	/// value the next event starts, or `None` inside a value being buffered.
+	pub(super) fn upcoming(&self) -> Option<Vec<usize>> {
+		if self.buffer.is_some() {
+			return None;
+		}
+		Some(self.expand(self.child().0))
+	}
+
+	fn keywords<'a>(
+		&'a self,
+		schemas: &'a [usize],
+	) -> impl Iterator<Item = &'s Keyword> + 'a {
+		let nodes = &self.schema.nodes;
+		schemas
+			.iter()
+			.flat_map(move |&node| match &nodes[node].kind {
+				NodeKind::Keywords(keywords) => keywords.as_slice(),
+				NodeKind::Bool(_) => &[],
+			})
+	}
+
+	/// Adds every schema reachable through `allOf` and `$ref`, which apply
+	/// to the same instance.
+	fn expand(&self, mut schemas: Vec<usize>) -> Vec<usize> {
+		let mut seen: HashSet<usize> = schemas.iter().copied().collect();
+		let mut scope = self.scope();
+		let mut i = 0;
+		while let Some(&node) = schemas.get(i) {
+			i += 1;
+			if let Some(id) = self.schema.nodes[node].scope {
+				if !scope.contains(&id) {
+					scope.push(id);
+				}
+			}
+			let mut next = Vec::new();
+			for keyword in self.keywords(&[node]) {
+				match &keyword.kind {
+					KeywordKind::AllOf(all) => next.extend(all),
+					KeywordKind::Ref(target) => next.push(*target),
+					KeywordKind::DynamicRef { schema, anchor } => next.push(
+						self.schema.dynamic_target(*schema, anchor, &scope),
+					),
+					_ => {}
+				}
+			}
+			schemas.extend(next.into_iter().filter(|s| seen.insert(*s)));
+		}
+		schemas
+	}
+
+	/// The schemas of the value about to start, and the token it adds to
+	/// the path unless it is the document.
+	fn child(&self) -> (Vec<usize>, Option<String>) {
+		let Some(frame) = self.stack.last() else {
+			return (vec![self.schema.root], None);
+		};
+		let token = match &frame.kind {
+			FrameKind::Array { len } => len.to_string(),
+			FrameKind::Object { key, .. } => key.clone().unwrap_or_default(),
+		};
+		let mut schemas = Vec::new();
+		for keyword in self.keywords(&frame.schemas) {
+			match (&keyword.kind, &frame.kind) {
+				(KeywordKind::PrefixItems(items), FrameKind::Array { len }) => {
+					schemas.extend(items.get(*len));
+				}
+				(
+					KeywordKind::Items { schema, skip },
+					FrameKind::Array { len },
+				) if len >= skip => schemas.push(*schema),
+				(
+					KeywordKind::Properties(properties),
+					FrameKind::Object { .. },
+				) => {
+					schemas.extend(properties.iter().filter_map(
+						|(name, schema)| (*name == token).then_some(*schema),
+					));
+				}
+				(
+					KeywordKind::PatternProperties(patterns),
+					FrameKind::Object { .. },
+				) => {
+					// Keys too complex to match were rejected by `key`.
+					schemas.extend(patterns.iter().filter_map(
+						|(regex, schema)| {
+							(regex.is_match(&token) == Ok(true))
+								.then_some(*schema)
+						},
+					));
+				}
+				(
+					KeywordKind::AdditionalProperties {
+						schema,
+						known,
+						patterns,
+					},
+					FrameKind::Object { .. },
+				) if !known.contains(&token)
+					&& patterns
+						.iter()
+						.all(|regex| regex.is_match(&token) == Ok(false)) =>
+				{
+					schemas.push(*schema)
+				}
+				_ => {}
+			}
+		}
+		(schemas, Some(token))
+	}
+
+	/// The dynamic scope of the value about to start: the resources with
+	/// dynamic anchors that the containers around it are in, the outermost
+	/// first.
+	fn scope(&self) -> Vec<usize> {
+		self.scope.clone()
+	}
+
+	/// Like [`Self::check`], at the location of the innermost value.
+	fn check_here(
+		&mut self,
+		schemas: &[usize],
+		value: &JsonValue,
+	) -> Result<(), ValidationError> {
+		let mut path = mem::take(&mut self.path);
+		let checked = self.check(schemas, value, &mut path);
+		self.path = path;
+		checked
+	}
+
+	/// Validates `value` in full against `schemas`.
+	fn check(
+		&self,
+		schemas: &[usize],
+		value: &JsonValue,
+		path: &mut JsonPointer,
+	) -> Result<(), ValidationError> {
+		let mut errors = Vec::new();
+		let mut scope = self.scope();
+		for &node in schemas {
+			self.schema.validate_node(
+				node,
+				value,
+				path,
+				&mut scope,
+				&mut errors,
+			);
+			if let Some(error) = errors.pop() {
+				return Err(error);
+			}
+		}
+		Ok(())
+	}
+
+	/// Starts the value `event` begins, whose token is already on the path.
+	fn open(
+		&mut self,
+		schemas: Vec<usize>,
+		event: &Event,
+	) -> Result<(), ValidationError> {
+		if self.path.tokens().len() > Schema::MAX_DEPTH {
+			if let Some(&node) = schemas.first() {
+				let schema_path = &self.schema.nodes[node].path;
+				return Err(too_deep(&self.path, schema_path));
+			}
+		}
+		let empty = match event {
+			Event::StartObject => JsonValue::Object(Box::default()),
+			Event::StartArray => JsonValue::Array(Vec::new()),
+			event => {
+				let value = event.scalar().unwrap_or(JsonValue::Null);
+				self.check_here(&schemas, &value)?;
+				self.end_child();
+				return Ok(());
+			}
+		};
+
+		let buffered = self.keywords(&schemas).any(|keyword| {
+			matches!(
+				keyword.kind,
+				KeywordKind::Enum(_)
+					| KeywordKind::Const(_)
+					| KeywordKind::UniqueItems
+					| KeywordKind::Contains { .. }
+					| KeywordKind::DependentSchemas(_)
+					| KeywordKind::AnyOf(_)
+					| KeywordKind::OneOf(_)
+					| KeywordKind::Not(_)
+					| KeywordKind::If { .. }
+					| KeywordKind::UnevaluatedItems(_)
+					| KeywordKind::UnevaluatedProperties(_)
+			)
+		});
+		if buffered {
+			let mut builder = ValueBuilder::default();
+			builder.push(event.clone());
+			self.buffer = Some(Buffer { schemas, builder });
+			return Ok(());
+		}
+
+		// Apart from false schemas, only `type` can fail before the
+		// container's contents are known.
+		let mut errors = Vec::new();
+		let mut path = mem::take(&mut self.path);
+		for &node in &schemas {
+			let node = &self.schema.nodes[node];
+			match &node.kind {
+				NodeKind::Bool(false) => {
+					errors.push(ValidationError {
+						instance_path: path.clone(),
+						schema_path: node.path.clone(),
+						message: "no value is allowed here".to_owned(),
+						kind: ErrorKind::Syntax,
+					});
+				}
+				NodeKind::Bool(true) => {}
+				NodeKind::Keywords(keywords) => {
+					for keyword in keywords {
+						if let KeywordKind::Type(_) = keyword.kind {
+							self.schema.keyword(
+								keyword,
+								&empty,
+								&mut path,
+								&mut Vec::new(),
+								&mut errors,
+								&mut Evaluated::default(),
+							);
+						}
+					}
+				}
+			}
+			if let Some(error) = errors.pop() {
+				self.path = path;
+				return Err(error);
+			}
+		}
+		self.path = path;
+
+		let kind = match empty {
+			JsonValue::Array(_) => FrameKind::Array { len: 0 },
+			_ => {
+				let track = self.keywords(&schemas).any(|keyword| {
+					matches!(
+						keyword.kind,
+						KeywordKind::Required(_)
+							| KeywordKind::DependentRequired(_)
+					)
+				});
+				FrameKind::Object {
+					len: 0,
+					keys: track.then(HashSet::new),
+					key: None,
+				}
+			}
+		};
+		let scope = self.scope.len();
+		for &node in &schemas {
+			if let Some(id) = self.schema.nodes[node].scope {
+				if !self.scope.contains(&id) {
+					self.scope.push(id);
+				}
+			}
+		}
+		self.stack.push(Frame {
+			schemas,
+			scope,
+			kind,
+		});
+		Ok(())
+	}
+
+	fn key(&mut self, name: &str) -> Result<(), ValidationError> {
+		let Some(frame) = self.stack.last() else {
+			return Ok(());
+		};
+		let value = JsonValue::String(name.to_owned());
+		let mut names = Vec::new();
+		for keyword in self.keywords(&frame.schemas) {
+			let patterns: Vec<_> = match &keyword.kind {
+				KeywordKind::PropertyNames(schema) => {
+					names.push(*schema);
+					continue;
+				}
+				KeywordKind::PatternProperties(patterns) => {
+					patterns.iter().map(|(regex, _)| regex).collect()
+				}
+				KeywordKind::AdditionalProperties { patterns, .. } => {
+					patterns.iter().collect()
+				}
+				_ => continue,
+			};
+			for regex in patterns {
+				if let Err(e) = regex.is_match(name) {
+					let message = format!("{e} against key {name:?}");
+					return Err(error(&self.path, keyword, message));
+				}
+			}
+		}
+		self.check_here(&names, &value)?;
+
+		if let Some(Frame {
+			kind: FrameKind::Object { len, keys, key },
+			..
+		}) = self.stack.last_mut()
+		{
+			*len += 1;
+			if let Some(keys) = keys {
+				keys.insert(name.to_owned());
+			}
+			*key = Some(name.to_owned());
+		}
+		Ok(())
+	}
+
+	/// Marks the end of a value in the innermost container, taking its
+	/// token off the path.
+	fn end_child(&mut self) {
+		match self.stack.last_mut().map(|frame| &mut frame.kind) {
+			Some(FrameKind::Array { len }) => *len += 1,
+			Some(FrameKind::Object { key, .. }) => *key = None,
+			None => return,
+		}
+		self.path.pop();
+	}
+
+	/// Checks the keywords that depend on a container's size or keys.
+	fn close(&self, frame: &Frame) -> Result<(), ValidationError> {
+		let path = &self.path;
+		for keyword in self.keywords(&frame.schemas) {
+			match (&keyword.kind, &frame.kind) {
+				(KeywordKind::MaxItems(max), FrameKind::Array { len })
+					if len > max =>
+				{
+					let message = format!(
+						"array has {len} items, expected at most {max}"
+					);
+					return Err(error(path, keyword, message));
+				}
+				(KeywordKind::MinItems(min), FrameKind::Array { len })
+					if len < min =>
+				{
+					let message = format!(
+						"array has {len} items, expected at least {min}"
+					);
+					return Err(error(path, keyword, message));
+				}
+				(
+					KeywordKind::MaxProperties(max),
+					FrameKind::Object { len, .. },
+				) if len > max => {
+					let message = format!(
+						"object has {len} properties, expected at most {max}"
+					);
+					return Err(error(path, keyword, message));
+				}
+				(
+					KeywordKind::MinProperties(min),
+					FrameKind::Object { len, .. },
+				) if len < min => {
+					let message = format!(
+						"object has {len} properties, expected at least {min}"
+					);
+					return Err(error(path, keyword, message));
+				}
+				(
+					KeywordKind::Required(names),
+					FrameKind::Object {
+						keys: Some(keys), ..
+					},
+				) => {
+					if let Some(name) =
+						names.iter().find(|name| !keys.contains(*name))
+					{
+						let message =
+							format!("missing required property {name:?}");
+						return Err(error(path, keyword, message));
+					}
+				}
+				(
+					KeywordKind::DependentRequired(dependencies),
+					FrameKind::Object {
+						keys: Some(keys), ..
+					},
+				) => {
+					for (name, required) in dependencies {
+						if !keys.contains(name) {
+							continue;
+						}
+						if let Some(dependency) =
+							required.iter().find(|dep| !keys.contains(*dep))
+						{
+							let message = format!(
+								"property {name:?} requires property \
+								 {dependency:?}"
+							);
+							return Err(error(path, keyword, message));
+						}
+					}
+				}
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Schema {
+	/// Parses and validates `input` in a single pass without building the
+	/// document, rejecting it at the first syntax error or violation.
+	pub fn validate_str(&self, input: &str) -> Result<(), StreamError> {
+		let mut validator = StreamValidator::new(self);
+		for event in EventReader::new(input) {
+			let event = event.map_err(StreamError::Syntax)?;
+			validator.feed(&event).map_err(StreamError::Invalid)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{parse, schema::Schema, JsonPointer};
+
+	fn error(schema: &str, instance: &str) -> (JsonPointer, String) {
+		let schema = Schema::compile(&parse(schema).unwrap()).unwrap();
+		match schema.validate_str(instance) {
+			Err(super::StreamError::Invalid(e)) => (e.instance_path, e.message),
+			other => panic!("expected a violation, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn paths_follow_the_open_containers() {
+		let schema = r#"{
+			"properties": {
+				"a": {"items": {"type": "number"}},
+				"b": {"propertyNames": {"maxLength": 1}}
+			}
+		}"#;
+		let (path, _) = error(
+			schema,
+			r#"{"b": {"c": [1]}, "a": [1, 2, "x"]}"#,
+		);
+		assert_eq!(path, "/a/2".parse().unwrap());
+		let (path, _) = error(
+			schema,
+			r#"{"a": [1, 2], "b": {"cd": 1}}"#,
+		);
+		assert_eq!(path, "/b".parse().unwrap());
+	}
+
+	#[test]
+	fn deep_documents_take_linear_time() {
+		// Every value down to the innermost has its own frame, so keeping
+		// a whole path or scope per frame would take minutes here.
+		let depth = 50_000;
+		let instance = format!(
+			"{}\"x\"{}",
+			"[".repeat(depth),
+			"]".repeat(depth)
+		);
+		let schema = parse(r#"{"type": "array"}"#).unwrap();
+		let schema = Schema::compile(&schema).unwrap();
+		assert_eq!(schema.validate_str(&instance), Ok(()));
+
+		let schema = r##"{
+			"$id": "https://example.com/tree",
+			"$dynamicAnchor": "node",
+			"type": ["array", "number"],
+			"items": {"$dynamicRef": "#node"}
+		}"##;
+		let (path, message) = error(schema, &instance);
+		assert_eq!(
+			path.tokens().len(),
+			Schema::MAX_DEPTH + 1
+		);
+		assert!(path.tokens().iter().all(|token| token == "0"));
+		assert!(
+			message.starts_with("value nested deeper"),
+			"{message}"
+		);
+	}
+}
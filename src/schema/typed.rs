@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use super::{
+	InstanceType, KeywordKind, NodeKind, Schema, StreamError, StreamValidator,
+};
+use crate::{
+	events::{Event, EventReader},
+	JsonType, JsonValue,
+};
+
+/// A document parsed under a [`Schema`] by [`Schema::parse_typed`], in the
+/// shapes the schema promises.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedValue<'s> {
+	Null,
+	Boolean(bool),
+	/// A number whose schema has `"type": "integer"`.
+	Integer(i64),
+	Number(f64),
+	String(String),
+	/// A string listed in its schema's `enum` or given by its `const`,
+	/// borrowed from the schema instead of allocated for every occurrence.
+	Symbol(&'s str),
+	Array(Vec<TypedValue<'s>>),
+	Object(Box<HashMap<String, TypedValue<'s>>>),
+}
+
+impl TypedValue<'_> {
+	pub fn to_value(&self) -> JsonValue {
+		match self {
+			TypedValue::Null => JsonValue::Null,
+			TypedValue::Boolean(b) => JsonValue::Boolean(*b),
+			TypedValue::Integer(n) => JsonValue::Number(*n as f64),
+			TypedValue::Number(n) => JsonValue::Number(*n),
+			TypedValue::String(s) => JsonValue::String(s.clone()),
+			TypedValue::Symbol(s) => JsonValue::String((*s).to_owned()),
+			TypedValue::Array(items) => JsonValue::Array(
+				items.iter().map(TypedValue::to_value).collect(),
+			),
+			TypedValue::Object(map) => map
+				.iter()
+				.map(|(key, value)| (key.clone(), value.to_value()))
+				.collect(),
+		}
+	}
+}
+
+impl Schema {
+	/// Parses `input` into a [`TypedValue`] while validating it as
+	/// [`Schema::validate_str`] does, so a violation stops the parse at the
+	/// token that commits it instead of after the whole tree is built.
+	///
+	/// Numbers become [`TypedValue::Integer`]s where the schema allows
+	/// integers but not other numbers, and strings from an `enum` or
+	/// `const` become [`TypedValue::Symbol`]s. Inside a value that must be
+	/// checked whole, such as one under `anyOf`, the schemas of its parts
+	/// are not known as they are parsed, and they are kept as plain numbers
+	/// and strings.
+	pub fn parse_typed(
+		&self,
+		input: &str,
+	) -> Result<TypedValue<'_>, StreamError> {
+		let mut validator = StreamValidator::new(self);
+		// The open containers, each with the key it will be stored under.
+		let mut stack: Vec<(TypedValue, Option<String>)> = Vec::new();
+		let mut key = None;
+		let mut root = TypedValue::Null;
+		for event in EventReader::new(input) {
+			let event = event.map_err(StreamError::Syntax)?;
+			let schemas = match event {
+				Event::Number(_) | Event::String(_) => validator.upcoming(),
+				_ => None,
+			};
+			validator.feed(&event).map_err(StreamError::Invalid)?;
+			let schemas = schemas.unwrap_or_default();
+			let value = match event {
+				Event::StartObject => {
+					let object = TypedValue::Object(Box::default());
+					stack.push((object, key.take()));
+					continue;
+				}
+				Event::StartArray => {
+					stack.push((
+						TypedValue::Array(Vec::new()),
+						key.take(),
+					));
+					continue;
+				}
+				Event::Key(name) => {
+					key = Some(name);
+					continue;
+				}
+				Event::EndObject | Event::EndArray => {
+					let Some((value, outer)) = stack.pop() else {
+						continue;
+					};
+					key = outer;
+					value
+				}
+				Event::Null => TypedValue::Null,
+				Event::Boolean(b) => TypedValue::Boolean(b),
+				Event::Number(n) => self.typed_number(&schemas, n),
+				Event::String(s) => self.typed_string(&schemas, s),
+			};
+			match stack.last_mut() {
+				Some((TypedValue::Array(items), _)) => items.push(value),
+				Some((TypedValue::Object(map), _)) => {
+					map.insert(key.take().unwrap_or_default(), value);
+				}
+				_ => root = value,
+			}
+		}
+		Ok(root)
+	}
+
+	pub(super) fn schema_keywords<'s: 'a, 'a>(
+		&'s self,
+		schemas: &'a [usize],
+	) -> impl Iterator<Item = &'s KeywordKind> + 'a {
+		schemas
+			.iter()
+			.flat_map(
+				move |&node| match &self.nodes[node].kind {
+					NodeKind::Keywords(keywords) => keywords.as_slice(),
+					NodeKind::Bool(_) => &[],
+				},
+			)
+			.map(|keyword| &keyword.kind)
+	}
+
+	fn typed_number(&self, schemas: &[usize], n: f64) -> TypedValue<'_> {
+		let integer = self.schema_keywords(schemas).any(|keyword| {
+			matches!(keyword, KeywordKind::Type(types)
+				if types.contains(&InstanceType::Integer)
+					&& !types.contains(&InstanceType::Type(JsonType::Number)))
+		});
+		let exact =
+			n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64;
+		if integer && exact {
+			TypedValue::Integer(n as i64)
+		} else {
+			TypedValue::Number(n)
+		}
+	}
+
+	fn typed_string(&self, schemas: &[usize], s: String) -> TypedValue<'_> {
+		let symbol = self.schema_keywords(schemas).find_map(|keyword| {
+			let values = match keyword {
+				KeywordKind::Enum(values) => values.as_slice(),
+				KeywordKind::Const(value) => std::slice::from_ref(value),
+				_ => return None,
+			};
+			values
+				.iter()
+				.find_map(|value| value.as_str().filter(|symbol| *symbol == s))
+		});
+		match symbol {
+			Some(symbol) => TypedValue::Symbol(symbol),
+			None => TypedValue::String(s),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn schema(text: &str) -> Schema {
+		Schema::compile(&parse(text).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn values_take_the_shapes_of_their_schemas() {
+		let schema = schema(
+			r#"{
+				"properties": {
+					"id": {"type": "integer"},
+					"ratio": {"type": "number"},
+					"mode": {"enum": ["fast", "slow"]},
+					"kind": {"const": "point"},
+					"tags": {"items": {"type": "string"}}
+				}
+			}"#,
+		);
+		let typed = schema
+			.parse_typed(
+				r#"{"id": 7, "ratio": 2, "mode": "slow", "kind": "point",
+					"tags": ["fast"], "extra": 3}"#,
+			)
+			.unwrap();
+		let TypedValue::Object(map) = &typed else {
+			panic!("{typed:?}");
+		};
+		assert_eq!(map["id"], TypedValue::Integer(7));
+		assert_eq!(map["ratio"], TypedValue::Number(2.0));
+		assert_eq!(map["mode"], TypedValue::Symbol("slow"));
+		assert_eq!(map["kind"], TypedValue::Symbol("point"));
+		assert_eq!(
+			map["tags"],
+			TypedValue::Array(vec![TypedValue::String(
+				"fast".to_owned()
+			)])
+		);
+		assert_eq!(map["extra"], TypedValue::Number(3.0));
+		assert_eq!(
+			typed.to_value(),
+			parse(
+				r#"{"id": 7, "ratio": 2, "mode": "slow", "kind": "point",
+					"tags": ["fast"], "extra": 3}"#
+			)
+			.unwrap()
+		);
+	}
+
+	#[test]
+	fn integers_must_be_exact() {
+		let schema = schema(r#"{"type": "integer"}"#);
+		assert_eq!(
+			schema.parse_typed("-3").unwrap(),
+			TypedValue::Integer(-3)
+		);
+		assert_eq!(
+			schema.parse_typed("1e19").unwrap(),
+			TypedValue::Number(1e19)
+		);
+		let either = self::schema(r#"{"type": ["integer", "number"]}"#);
+		assert_eq!(
+			either.parse_typed("4").unwrap(),
+			TypedValue::Number(4.0)
+		);
+	}
+
+	#[test]
+	fn errors_stop_the_parse() {
+		let schema = schema(r#"{"items": {"type": "integer"}}"#);
+		assert!(matches!(
+			schema.parse_typed(r#"[1, "2", 3]"#),
+			Err(StreamError::Invalid(_))
+		));
+		assert!(matches!(
+			schema.parse_typed("[1, 2"),
+			Err(StreamError::Syntax(_))
+		));
+	}
+}
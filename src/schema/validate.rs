@@ -0,0 +1,1039 @@
+use std::{
+	collections::{hash_map::DefaultHasher, HashMap, HashSet},
+	hash::{Hash, Hasher},
+};
+
+use super::{Keyword, KeywordKind, NodeKind, Schema, ValidationError};
+use crate::{ErrorKind, JsonPointer, JsonValue};
+
+/// The annotations `unevaluatedItems` and `unevaluatedProperties` depend
+/// on: which parts of an instance some keyword has already looked at.
+#[derive(Debug, Default)]
+pub(crate) struct Evaluated {
+	properties: HashSet<String>,
+	items: HashSet<usize>,
+	all_items: bool,
+}
+
+impl Evaluated {
+	fn merge(&mut self, other: Evaluated) {
+		self.properties.extend(other.properties);
+		self.items.extend(other.items);
+		self.all_items |= other.all_items;
+	}
+}
+
+fn fail(
+	errors: &mut Vec<ValidationError>,
+	path: &JsonPointer,
+	keyword: &Keyword,
+	message: String,
+) {
+	errors.push(ValidationError {
+		instance_path: path.clone(),
+		schema_path: keyword.path.clone(),
+		message,
+		kind: ErrorKind::Syntax,
+	});
+}
+
+/// The violation of a schema applying to a value at `path`, which is
+/// nested deeper than [`Schema::MAX_DEPTH`].
+pub(super) fn too_deep(
+	path: &JsonPointer,
+	schema_path: &JsonPointer,
+) -> ValidationError {
+	ValidationError {
+		instance_path: path.clone(),
+		schema_path: schema_path.clone(),
+		message: format!(
+			"value nested deeper than {} levels",
+			Schema::MAX_DEPTH
+		),
+		kind: ErrorKind::TooDeep,
+	}
+}
+
+/// A hash that values equal under `==` share: numbers by value, so `0`
+/// and `-0` alike, and objects whatever the order of their members.
+fn hash(value: &JsonValue) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	match value {
+		JsonValue::Null => 0u8.hash(&mut hasher),
+		JsonValue::Boolean(b) => (1u8, b).hash(&mut hasher),
+		JsonValue::Number(n) => (2u8, (n + 0.0).to_bits()).hash(&mut hasher),
+		JsonValue::String(s) => (3u8, s).hash(&mut hasher),
+		JsonValue::Array(items) => {
+			4u8.hash(&mut hasher);
+			items.iter().for_each(|item| hash(item).hash(&mut hasher));
+		}
+		JsonValue::Object(map) => {
+			let members = map.iter().fold(0u64, |sum, (key, value)| {
+				let mut member = DefaultHasher::new();
+				(key, hash(value)).hash(&mut member);
+				sum.wrapping_add(member.finish())
+			});
+			(5u8, map.len(), members).hash(&mut hasher);
+		}
+	}
+	hasher.finish()
+}
+
+impl Schema {
+	/// Validates `instance` without reporting errors, for applicators that
+	/// only need to know whether a subschema matched.
+	fn check(
+		&self,
+		node: usize,
+		instance: &JsonValue,
+		path: &mut JsonPointer,
+		scope: &mut Vec<usize>,
+	) -> (bool, Evaluated) {
+		let mut errors = Vec::new();
+		let evaluated =
+			self.validate_node(node, instance, path, scope, &mut errors);
+		(errors.is_empty(), evaluated)
+	}
+
+	fn validate_child(
+		&self,
+		node: usize,
+		instance: &JsonValue,
+		path: &mut JsonPointer,
+		scope: &mut Vec<usize>,
+		token: impl Into<String>,
+		errors: &mut Vec<ValidationError>,
+	) {
+		path.push(token);
+		self.validate_node(node, instance, path, scope, errors);
+		path.pop();
+	}
+
+	/// The schema a `$dynamicRef` to `schema` reaches from `scope`: the
+	/// one its anchor names in the outermost resource that declares it.
+	pub(crate) fn dynamic_target(
+		&self,
+		schema: usize,
+		anchor: &str,
+		scope: &[usize],
+	) -> usize {
+		scope
+			.iter()
+			.find_map(|&id| {
+				self.scopes[id]
+					.iter()
+					.find(|(name, _)| name == anchor)
+					.map(|&(_, schema)| schema)
+			})
+			.unwrap_or(schema)
+	}
+
+	pub(crate) fn validate_node(
+		&self,
+		node: usize,
+		instance: &JsonValue,
+		path: &mut JsonPointer,
+		scope: &mut Vec<usize>,
+		errors: &mut Vec<ValidationError>,
+	) -> Evaluated {
+		let mut evaluated = Evaluated::default();
+		let node = &self.nodes[node];
+		if path.tokens().len() > Self::MAX_DEPTH {
+			errors.push(too_deep(path, &node.path));
+			return evaluated;
+		}
+		let keywords = match &node.kind {
+			NodeKind::Bool(true) => return evaluated,
+			NodeKind::Bool(false) => {
+				errors.push(ValidationError {
+					instance_path: path.clone(),
+					schema_path: node.path.clone(),
+					message: "no value is allowed here".to_owned(),
+					kind: ErrorKind::Syntax,
+				});
+				return evaluated;
+			}
+			NodeKind::Keywords(keywords) => keywords,
+		};
+
+		// The dynamic scope holds the resources entered on the way here
+		// that have dynamic anchors, the outermost first.
+		let entered = match node.scope {
+			Some(id) if scope.last() != Some(&id) => {
+				scope.push(id);
+				true
+			}
+			_ => false,
+		};
+		for keyword in keywords {
+			self.keyword(
+				keyword,
+				instance,
+				path,
+				scope,
+				errors,
+				&mut evaluated,
+			);
+		}
+		if entered {
+			scope.pop();
+		}
+		evaluated
+	}
+
+	pub(crate) fn keyword(
+		&self,
+		keyword: &Keyword,
+		instance: &JsonValue,
+		path: &mut JsonPointer,
+		scope: &mut Vec<usize>,
+		errors: &mut Vec<ValidationError>,
+		evaluated: &mut Evaluated,
+	) {
+		match (&keyword.kind, instance) {
+			(KeywordKind::PrefixItems(schemas), JsonValue::Array(items)) => {
+				for (i, (schema, item)) in schemas.iter().zip(items).enumerate()
+				{
+					self.validate_child(
+						*schema,
+						item,
+						path,
+						scope,
+						i.to_string(),
+						errors,
+					);
+					evaluated.items.insert(i);
+				}
+			}
+			(KeywordKind::Items { schema, skip }, JsonValue::Array(items)) => {
+				for (i, item) in items.iter().enumerate().skip(*skip) {
+					self.validate_child(
+						*schema,
+						item,
+						path,
+						scope,
+						i.to_string(),
+						errors,
+					);
+				}
+				evaluated.all_items = true;
+			}
+			(
+				KeywordKind::Contains { schema, min, max },
+				JsonValue::Array(items),
+			) => {
+				let mut count = 0;
+				for (i, item) in items.iter().enumerate() {
+					path.push(i.to_string());
+					if self.check(*schema, item, path, scope).0 {
+						count += 1;
+						evaluated.items.insert(i);
+					}
+					path.pop();
+				}
+				if count < *min {
+					let message = format!(
+						"array contains {count} matching items, expected at \
+						 least {min}"
+					);
+					fail(errors, path, keyword, message);
+				}
+				if let Some(max) = max.filter(|max| count > *max) {
+					let message = format!(
+						"array contains {count} matching items, expected at \
+						 most {max}"
+					);
+					fail(errors, path, keyword, message);
+				}
+			}
+			(KeywordKind::Properties(schemas), JsonValue::Object(map)) => {
+				for (name, schema) in schemas {
+					if let Some(value) = map.get(name) {
+						self.validate_child(
+							*schema, value, path, scope, name, errors,
+						);
+						evaluated.properties.insert(name.clone());
+					}
+				}
+			}
+			(
+				KeywordKind::PatternProperties(schemas),
+				JsonValue::Object(map),
+			) => {
+				for (key, value) in map.iter() {
+					for (regex, schema) in schemas {
+						match regex.is_match(key) {
+							Ok(true) => {
+								self.validate_child(
+									*schema, value, path, scope, key, errors,
+								);
+								evaluated.properties.insert(key.clone());
+							}
+							Ok(false) => {}
+							Err(e) => {
+								let message =
+									format!("{e} against key {key:?}");
+								fail(errors, path, keyword, message);
+							}
+						}
+					}
+				}
+			}
+			(
+				KeywordKind::AdditionalProperties {
+					schema,
+					known,
+					patterns,
+				},
+				JsonValue::Object(map),
+			) => {
+				for (key, value) in map.iter() {
+					if known.contains(key) {
+						continue;
+					}
+					match patterns
+						.iter()
+						.map(|regex| regex.is_match(key))
+						.find(|matched| *matched != Ok(false))
+						.unwrap_or(Ok(false))
+					{
+						Ok(true) => continue,
+						Ok(false) => {}
+						Err(e) => {
+							let message = format!("{e} against key {key:?}");
+							fail(errors, path, keyword, message);
+							continue;
+						}
+					}
+					self.validate_child(
+						*schema, value, path, scope, key, errors,
+					);
+					evaluated.properties.insert(key.clone());
+				}
+			}
+			(
+				KeywordKind::DependentSchemas(schemas),
+				JsonValue::Object(map),
+			) => {
+				for (name, schema) in schemas {
+					if map.contains_key(name) {
+						evaluated.merge(self.validate_node(
+							*schema, instance, path, scope, errors,
+						));
+					}
+				}
+			}
+			(KeywordKind::PropertyNames(schema), JsonValue::Object(map)) => {
+				for key in map.keys() {
+					let name = JsonValue::String(key.clone());
+					self.validate_node(*schema, &name, path, scope, errors);
+				}
+			}
+
+			(KeywordKind::AllOf(schemas), _) => {
+				for schema in schemas {
+					evaluated.merge(
+						self.validate_node(
+							*schema, instance, path, scope, errors,
+						),
+					);
+				}
+			}
+			(KeywordKind::AnyOf(schemas), _) => {
+				let mut matched = false;
+				for schema in schemas {
+					let (valid, annotations) =
+						self.check(*schema, instance, path, scope);
+					if valid {
+						matched = true;
+						evaluated.merge(annotations);
+					}
+				}
+				if !matched {
+					let message =
+						"value does not match any schema in anyOf".to_owned();
+					fail(errors, path, keyword, message);
+				}
+			}
+			(KeywordKind::OneOf(schemas), _) => {
+				let mut matches = Vec::new();
+				for (i, schema) in schemas.iter().enumerate() {
+					let (valid, annotations) =
+						self.check(*schema, instance, path, scope);
+					if valid {
+						matches.push((i, annotations));
+					}
+				}
+				if matches.len() == 1 {
+					evaluated.merge(matches.pop().unwrap().1);
+				} else {
+					let message = format!(
+						"value matches {} schemas in oneOf, expected exactly one",
+						matches.len()
+					);
+					fail(errors, path, keyword, message);
+				}
+			}
+			(KeywordKind::Not(schema), _)
+				if self.check(*schema, instance, path, scope).0 =>
+			{
+				let message = "value must not match the schema".to_owned();
+				fail(errors, path, keyword, message);
+			}
+			(
+				KeywordKind::If {
+					condition,
+					then,
+					otherwise,
+				},
+				_,
+			) => {
+				let (valid, annotations) =
+					self.check(*condition, instance, path, scope);
+				let branch = if valid {
+					evaluated.merge(annotations);
+					then
+				} else {
+					otherwise
+				};
+				if let Some(branch) = branch {
+					evaluated.merge(
+						self.validate_node(
+							*branch, instance, path, scope, errors,
+						),
+					);
+				}
+			}
+			(KeywordKind::Ref(schema), _) => {
+				evaluated.merge(
+					self.validate_node(*schema, instance, path, scope, errors),
+				);
+			}
+			(KeywordKind::DynamicRef { schema, anchor }, _) => {
+				let target = self.dynamic_target(*schema, anchor, scope);
+				evaluated.merge(
+					self.validate_node(target, instance, path, scope, errors),
+				);
+			}
+
+			(
+				KeywordKind::UnevaluatedItems(schema),
+				JsonValue::Array(items),
+			) => {
+				if !evaluated.all_items {
+					for (i, item) in items.iter().enumerate() {
+						if !evaluated.items.contains(&i) {
+							self.validate_child(
+								*schema,
+								item,
+								path,
+								scope,
+								i.to_string(),
+								errors,
+							);
+						}
+					}
+				}
+				evaluated.all_items = true;
+			}
+			(
+				KeywordKind::UnevaluatedProperties(schema),
+				JsonValue::Object(map),
+			) => {
+				for (key, value) in map.iter() {
+					if !evaluated.properties.contains(key) {
+						self.validate_child(
+							*schema, value, path, scope, key, errors,
+						);
+					}
+				}
+				evaluated.properties.extend(map.keys().cloned());
+			}
+
+			_ => assertion(keyword, instance, path, errors),
+		}
+	}
+}
+
+/// Checks a keyword that looks at `instance` alone, without applying any
+/// subschema. Kept out of [`Schema::keyword`] so that the stack frames of
+/// its recursion stay small.
+#[inline(never)]
+fn assertion(
+	keyword: &Keyword,
+	instance: &JsonValue,
+	path: &JsonPointer,
+	errors: &mut Vec<ValidationError>,
+) {
+	match (&keyword.kind, instance) {
+		(KeywordKind::Type(types), _)
+			if !types.iter().any(|ty| ty.matches(instance)) =>
+		{
+			let expected: Vec<_> =
+				types.iter().map(ToString::to_string).collect();
+			let message = format!(
+				"expected {}, found {}",
+				expected.join(" or "),
+				instance.type_of()
+			);
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::Enum(values), _) if !values.contains(instance) => {
+			let message = format!("{instance} is not an allowed value");
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::Const(value), _) if instance != value => {
+			let message = format!("expected {value}, found {instance}");
+			fail(errors, path, keyword, message);
+		}
+
+		(KeywordKind::MultipleOf(m), JsonValue::Number(n)) => {
+			// The binary fractions nearest `n` and `m` can make the
+			// quotient of exact multiples miss an integer by a few units
+			// in the last place, so that much, scaled to the quotient,
+			// is allowed.
+			let quotient = n / m;
+			let tolerance = quotient.abs() * f64::EPSILON * 2.0;
+			if !quotient.is_finite()
+				|| (quotient - quotient.round()).abs() > tolerance
+			{
+				let message = format!("{n} is not a multiple of {m}");
+				fail(errors, path, keyword, message);
+			}
+		}
+		(KeywordKind::Maximum(m), JsonValue::Number(n)) if n > m => {
+			let message = format!("{n} is greater than the maximum of {m}");
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::ExclusiveMaximum(m), JsonValue::Number(n)) if n >= m => {
+			let message = format!("{n} is not less than {m}");
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::Minimum(m), JsonValue::Number(n)) if n < m => {
+			let message = format!("{n} is less than the minimum of {m}");
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::ExclusiveMinimum(m), JsonValue::Number(n)) if n <= m => {
+			let message = format!("{n} is not greater than {m}");
+			fail(errors, path, keyword, message);
+		}
+
+		(KeywordKind::MaxLength(max), JsonValue::String(s)) => {
+			let len = s.chars().count();
+			if len > *max {
+				let message = format!(
+					"string has {len} characters, expected at most {max}"
+				);
+				fail(errors, path, keyword, message);
+			}
+		}
+		(KeywordKind::MinLength(min), JsonValue::String(s)) => {
+			let len = s.chars().count();
+			if len < *min {
+				let message = format!(
+					"string has {len} characters, expected at least {min}"
+				);
+				fail(errors, path, keyword, message);
+			}
+		}
+		(KeywordKind::Pattern(pattern, regex), JsonValue::String(s)) => {
+			let message = match regex.is_match(s) {
+				Ok(true) => None,
+				Ok(false) => Some(format!(
+					"{instance} does not match pattern {pattern:?}"
+				)),
+				Err(e) => Some(format!(
+					"{e} {pattern:?} against {instance}"
+				)),
+			};
+			if let Some(message) = message {
+				fail(errors, path, keyword, message);
+			}
+		}
+
+		(KeywordKind::MaxItems(max), JsonValue::Array(items))
+			if items.len() > *max =>
+		{
+			let message = format!(
+				"array has {} items, expected at most {max}",
+				items.len()
+			);
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::MinItems(min), JsonValue::Array(items))
+			if items.len() < *min =>
+		{
+			let message = format!(
+				"array has {} items, expected at least {min}",
+				items.len()
+			);
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::UniqueItems, JsonValue::Array(items)) => {
+			// Only items with the same hash need comparing.
+			let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+			let duplicate = items.iter().enumerate().find_map(|(j, b)| {
+				let same = seen.entry(hash(b)).or_default();
+				let i = same.iter().copied().find(|&i| items[i] == *b);
+				same.push(j);
+				i.map(|i| (i, j))
+			});
+			if let Some((i, j)) = duplicate {
+				let message = format!("array items {i} and {j} are equal");
+				fail(errors, path, keyword, message);
+			}
+		}
+
+		(KeywordKind::Required(names), JsonValue::Object(map)) => {
+			for name in names {
+				if !map.contains_key(name) {
+					let message = format!("missing required property {name:?}");
+					fail(errors, path, keyword, message);
+				}
+			}
+		}
+		(KeywordKind::MaxProperties(max), JsonValue::Object(map))
+			if map.len() > *max =>
+		{
+			let message = format!(
+				"object has {} properties, expected at most {max}",
+				map.len()
+			);
+			fail(errors, path, keyword, message);
+		}
+		(KeywordKind::MinProperties(min), JsonValue::Object(map))
+			if map.len() < *min =>
+		{
+			let message = format!(
+				"object has {} properties, expected at least {min}",
+				map.len()
+			);
+			fail(errors, path, keyword, message);
+		}
+		(
+			KeywordKind::DependentRequired(dependencies),
+			JsonValue::Object(map),
+		) => {
+			for (name, required) in dependencies {
+				if !map.contains_key(name) {
+					continue;
+				}
+				for dependency in required {
+					if !map.contains_key(dependency) {
+						let message = format!(
+							"property {name:?} requires property \
+								 {dependency:?}"
+						);
+						fail(errors, path, keyword, message);
+					}
+				}
+			}
+		}
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{parse, schema::Schema};
+
+	fn messages(schema: &str, instance: &str) -> Vec<String> {
+		let schema = Schema::compile(&parse(schema).unwrap()).unwrap();
+		match schema.validate(&parse(instance).unwrap()) {
+			Ok(()) => Vec::new(),
+			Err(errors) => errors.into_iter().map(|e| e.message).collect(),
+		}
+	}
+
+	#[test]
+	fn pattern_too_complex() {
+		let instance = format!("{:?}", "a".repeat(100_000));
+		let errors = messages(r#"{"pattern": "(?=a*b)"}"#, &instance);
+		assert_eq!(errors.len(), 1);
+		assert!(errors[0].starts_with("pattern too complex to match"));
+
+		let key = format!("{{{instance}: 1}}");
+		let errors = messages(
+			r#"{"patternProperties": {"(?=a*b)": {}}}"#,
+			&key,
+		);
+		assert_eq!(errors.len(), 1);
+		assert!(errors[0].starts_with("pattern too complex to match"));
+	}
+
+	fn is_valid(schema: &str, instance: &str) -> bool {
+		let schema = Schema::compile(&parse(schema).unwrap()).unwrap();
+		let streamed = schema.validate_str(instance).is_ok();
+		let valid = schema.is_valid(&parse(instance).unwrap());
+		assert_eq!(streamed, valid, "{instance}");
+		valid
+	}
+
+	#[test]
+	fn dynamic_ref() {
+		let list = r##"{
+			"$id": "https://example.com/root",
+			"$ref": "list",
+			"$defs": {
+				"foo": {"$dynamicAnchor": "items", "type": "string"},
+				"list": {
+					"$id": "list",
+					"type": "array",
+					"items": {"$dynamicRef": "#items"},
+					"$defs": {"items": {"$dynamicAnchor": "items"}}
+				}
+			}
+		}"##;
+		assert!(is_valid(list, r#"["foo", "bar"]"#));
+		assert!(!is_valid(list, r#"["foo", 42]"#));
+
+		let tree = r##"{
+			"$id": "https://example.com/strict-tree",
+			"$dynamicAnchor": "node",
+			"$ref": "tree",
+			"unevaluatedProperties": false,
+			"$defs": {
+				"tree": {
+					"$id": "tree",
+					"$dynamicAnchor": "node",
+					"type": "object",
+					"properties": {
+						"data": true,
+						"children": {
+							"type": "array",
+							"items": {"$dynamicRef": "#node"}
+						}
+					}
+				}
+			}
+		}"##;
+		assert!(is_valid(
+			tree,
+			r#"{"children": [{"data": 1}]}"#
+		));
+		assert!(!is_valid(
+			tree,
+			r#"{"children": [{"daat": 1}]}"#
+		));
+
+		let pointer = r##"{
+			"$defs": {"a": {"$dynamicAnchor": "a", "type": "number"}},
+			"items": {"$dynamicRef": "#/$defs/a"}
+		}"##;
+		assert!(!is_valid(pointer, r#"["x"]"#));
+	}
+
+	#[test]
+	fn multiple_of() {
+		let schema = |m: &str| format!(r#"{{"multipleOf": {m}}}"#);
+		assert!(is_valid(&schema("0.1"), "0.3"));
+		assert!(is_valid(&schema("0.01"), "19.99"));
+		assert!(is_valid(&schema("0.0001"), "0.0075"));
+		assert!(is_valid(&schema("1e-12"), "3e-12"));
+		assert!(is_valid(&schema("1.5"), "4.5"));
+		assert!(!is_valid(&schema("0.1"), "0.1000001"));
+		assert!(!is_valid(&schema("1e-12"), "1.5e-12"));
+		assert!(!is_valid(
+			&schema("1"),
+			"1000000000000000.5"
+		));
+		assert!(!is_valid(&schema("1e-300"), "1e300"));
+	}
+
+	#[test]
+	fn keywords() {
+		let cases: &[(&str, &[&str], &[&str])] = &[
+			(
+				r#"{"type": "integer"}"#,
+				&["1", "-0", "1e3"],
+				&["1.5", "\"1\""],
+			),
+			(
+				r#"{"type": ["string", "null"]}"#,
+				&["\"a\"", "null"],
+				&["0"],
+			),
+			(
+				r#"{"enum": [1, "a", [true]]}"#,
+				&["1.0", "\"a\"", "[true]"],
+				&["2"],
+			),
+			(
+				r#"{"const": {"a": [1]}}"#,
+				&[r#"{"a": [1]}"#],
+				&[r#"{"a": [1, 2]}"#],
+			),
+			(
+				r#"{"minimum": 1, "exclusiveMaximum": 3}"#,
+				&["1", "2.9", "\"x\""],
+				&["0.9", "3"],
+			),
+			(
+				r#"{"maximum": 1, "exclusiveMinimum": -1}"#,
+				&["1", "-0.5"],
+				&["1.1", "-1"],
+			),
+			(
+				r#"{"minLength": 2, "maxLength": 3}"#,
+				&["\"ab\"", "\"é😀\"", "5"],
+				&["\"a\"", "\"abcd\""],
+			),
+			(
+				r#"{"pattern": "^a+$"}"#,
+				&["\"aaa\"", "1"],
+				&["\"ab\""],
+			),
+			(
+				r#"{"prefixItems": [{"type": "string"}], "items": {"type": "number"}}"#,
+				&["[]", "[\"a\", 1, 2]"],
+				&["[1]", "[\"a\", \"b\"]"],
+			),
+			(
+				r#"{"contains": {"const": 1}, "minContains": 2, "maxContains": 3}"#,
+				&["[1, 1, 2]"],
+				&["[1, 2]", "[1, 1, 1, 1]"],
+			),
+			(
+				r#"{"minItems": 1, "maxItems": 2, "uniqueItems": true}"#,
+				&["[1]", "[1, \"1\"]"],
+				&["[]", "[1, 2, 3]", "[1, 1.0]"],
+			),
+			(
+				r#"{"properties": {"a": {"type": "number"}}, "required": ["a"]}"#,
+				&[r#"{"a": 1, "b": 2}"#],
+				&[r#"{"a": "x"}"#, "{}"],
+			),
+			(
+				r#"{"patternProperties": {"^x-": {"type": "string"}}, "additionalProperties": false}"#,
+				&[r#"{"x-a": "b"}"#],
+				&[r#"{"x-a": 1}"#, r#"{"y": 1}"#],
+			),
+			(
+				r#"{"minProperties": 1, "maxProperties": 1}"#,
+				&[r#"{"a": 1}"#],
+				&["{}", r#"{"a": 1, "b": 2}"#],
+			),
+			(
+				r#"{"dependentRequired": {"a": ["b"]}}"#,
+				&["{}", r#"{"a": 1, "b": 2}"#],
+				&[r#"{"a": 1}"#],
+			),
+			(
+				r#"{"dependentSchemas": {"a": {"required": ["b"]}}}"#,
+				&[r#"{"b": 1}"#],
+				&[r#"{"a": 1}"#],
+			),
+			(
+				r#"{"propertyNames": {"maxLength": 2}}"#,
+				&[r#"{"ab": 1}"#],
+				&[r#"{"abc": 1}"#],
+			),
+			(
+				r#"{"allOf": [{"minimum": 1}, {"maximum": 2}]}"#,
+				&["1.5"],
+				&["3"],
+			),
+			(
+				r#"{"anyOf": [{"type": "string"}, {"minimum": 2}]}"#,
+				&["\"a\"", "3"],
+				&["1"],
+			),
+			(
+				r#"{"oneOf": [{"minimum": 2}, {"maximum": 3}]}"#,
+				&["1", "4"],
+				&["2.5"],
+			),
+			(
+				r#"{"not": {"type": "null"}}"#,
+				&["1"],
+				&["null"],
+			),
+			(
+				r#"{"if": {"minimum": 10}, "then": {"multipleOf": 2}, "else": {"maximum": 5}}"#,
+				&["12", "4"],
+				&["11", "6"],
+			),
+			(
+				r##"{"$defs": {"n": {"type": "number"}}, "items": {"$ref": "#/$defs/n"}}"##,
+				&["[1, 2]"],
+				&["[1, \"2\"]"],
+			),
+			(
+				r##"{"$defs": {"a": {"$anchor": "x", "type": "null"}}, "$ref": "#x"}"##,
+				&["null"],
+				&["0"],
+			),
+			(
+				r#"{"prefixItems": [true], "unevaluatedItems": false}"#,
+				&["[1]"],
+				&["[1, 2]"],
+			),
+			(
+				r#"{"allOf": [{"properties": {"a": true}}], "unevaluatedProperties": false}"#,
+				&[r#"{"a": 1}"#],
+				&[r#"{"b": 1}"#],
+			),
+			("true", &["1", "null"], &[]),
+			("false", &[], &["1", "null"]),
+		];
+		for (schema, valid, invalid) in cases {
+			for instance in *valid {
+				assert!(
+					is_valid(schema, instance),
+					"{schema} rejects {instance}"
+				);
+			}
+			for instance in *invalid {
+				assert!(
+					!is_valid(schema, instance),
+					"{schema} accepts {instance}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn recursive_refs() {
+		let tree = r##"{
+			"type": "object",
+			"properties": {
+				"value": {"type": "number"},
+				"children": {"type": "array", "items": {"$ref": "#"}}
+			},
+			"required": ["value"]
+		}"##;
+		assert!(is_valid(
+			tree,
+			r#"{"value": 1, "children": [{"value": 2, "children": []}]}"#
+		));
+		assert!(!is_valid(
+			tree,
+			r#"{"value": 1, "children": [{"children": []}]}"#
+		));
+	}
+
+	#[test]
+	fn invalid_schemas() {
+		for schema in [
+			r#"{"type": "integr"}"#,
+			r#"{"pattern": "("}"#,
+			r#"{"minLength": -1}"#,
+			r##"{"$ref": "#/$defs/missing"}"##,
+			r##"{"$defs": {"a": {"$ref": "#/$defs/b"}, "b": {"$ref": "#/$defs/a"}}, "$ref": "#/$defs/a"}"##,
+		] {
+			assert!(
+				Schema::compile(&parse(schema).unwrap()).is_err(),
+				"{schema}"
+			);
+		}
+	}
+
+	#[test]
+	fn error_locations() {
+		let schema = r#"{"properties": {"a": {"items": {"type": "string"}}}}"#;
+		let schema = Schema::compile(&parse(schema).unwrap()).unwrap();
+		let errors = schema
+			.validate(&parse(r#"{"a": ["x", 1]}"#).unwrap())
+			.unwrap_err();
+		assert_eq!(errors.len(), 1);
+		assert_eq!(
+			errors[0].instance_path.to_string(),
+			"/a/1"
+		);
+	}
+
+	#[test]
+	fn unique_items() {
+		let schema = r#"{"uniqueItems": true}"#;
+		let distinct: Vec<_> = (0..50_000).map(|i| i.to_string()).collect();
+		let instance = format!("[{}]", distinct.join(","));
+		assert!(messages(schema, &instance).is_empty());
+		let instance = format!("[{}, 7]", distinct.join(","));
+		assert_eq!(
+			messages(schema, &instance),
+			["array items 7 and 50000 are equal"]
+		);
+
+		for (instance, equal) in [
+			(
+				r#"[{"a": 1, "b": [0]}, {"b": [-0], "a": 1.0}]"#,
+				true,
+			),
+			(
+				r#"[{"a": 1, "b": 2}, {"a": 2, "b": 1}]"#,
+				false,
+			),
+			(r#"[[1, 2], [2, 1]]"#, false),
+			(r#"[null, false, 0, "", [], {}]"#, false),
+		] {
+			assert_eq!(
+				!messages(schema, instance).is_empty(),
+				equal,
+				"{instance}"
+			);
+		}
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		use crate::{ErrorKind, JsonValue};
+
+		let schema =
+			Schema::compile(&parse(r##"{"items": {"$ref": "#"}}"##).unwrap())
+				.unwrap();
+		let depth = 10_000;
+		let mut instance = JsonValue::Null;
+		for _ in 0..depth {
+			instance = JsonValue::Array(vec![instance]);
+		}
+		let errors = schema.validate(&instance).unwrap_err();
+		assert_eq!(errors.len(), 1);
+		assert_eq!(errors[0].kind, ErrorKind::TooDeep);
+		assert_eq!(
+			errors[0].instance_path.tokens().len(),
+			Schema::MAX_DEPTH + 1
+		);
+		let text = format!(
+			"{}{}",
+			"[".repeat(depth),
+			"]".repeat(depth)
+		);
+		match schema.validate_str(&text) {
+			Err(crate::schema::StreamError::Invalid(e)) => {
+				assert_eq!(e, errors[0])
+			}
+			other => panic!("expected a violation, got {other:?}"),
+		}
+		let limit = Schema::MAX_DEPTH;
+		assert!(is_valid(
+			r##"{"items": {"$ref": "#"}}"##,
+			&format!(
+				"{}{}",
+				"[".repeat(limit),
+				"]".repeat(limit)
+			)
+		));
+
+		let mut not = JsonValue::Boolean(true);
+		for _ in 0..1_000 {
+			not = JsonValue::from_iter([("not".to_owned(), not)]);
+		}
+		let error = Schema::compile(&not).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::TooDeep);
+
+		let defs: Vec<_> = (0..500)
+			.map(|i| {
+				format!(
+					r##""d{i}": {{"$ref": "#/$defs/d{}"}}"##,
+					i + 1
+				)
+			})
+			.collect();
+		let chain = format!(
+			r##"{{"$ref": "#/$defs/d0", "$defs": {{{}, "d500": true}}}}"##,
+			defs.join(", ")
+		);
+		let error = Schema::compile(&parse(&chain).unwrap()).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::TooDeep);
+	}
+}
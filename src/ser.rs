@@ -0,0 +1,374 @@
+use std::{
+	error,
+	fmt::{self, Write},
+};
+
+use crate::JsonValue;
+
+/// Writes `s` as a quoted JSON string literal.
+pub(crate) fn write_string<W: Write + ?Sized>(
+	out: &mut W,
+	s: &str,
+) -> fmt::Result {
+	out.write_char('"')?;
+	write_escaped(out, s)?;
+	out.write_char('"')
+}
+
+/// Writes the contents of the string literal for `s`, without the quotes.
+pub(crate) fn write_escaped<W: Write + ?Sized>(
+	out: &mut W,
+	s: &str,
+) -> fmt::Result {
+	for c in s.chars() {
+		match c {
+			'"' => out.write_str("\\\"")?,
+			'\\' => out.write_str("\\\\")?,
+			'\x08' => out.write_str("\\b")?,
+			'\x0C' => out.write_str("\\f")?,
+			'\n' => out.write_str("\\n")?,
+			'\r' => out.write_str("\\r")?,
+			'\t' => out.write_str("\\t")?,
+			c if c < ' ' => write!(out, "\\u{:04x}", c as u32)?,
+			c => out.write_char(c)?,
+		}
+	}
+	Ok(())
+}
+
+/// Writes a number; JSON has no representation for NaN or infinities, so
+/// those become `null`.
+///
+/// Integral values are written without a fraction or exponent, so a `1`
+/// read from a document is written back as `1` rather than `1.0` or `1e0`.
+/// Other finite values are written with the fewest digits that parse back
+/// to exactly the same `f64`, but always positionally, never in exponent
+/// notation: `1e300` is written out as all 301 of its digits and `1e-300`
+/// with 299 zeros after the point. [`NumberFormat::scientific`] writes
+/// exponents instead.
+///
+/// [`NumberFormat::scientific`]: crate::NumberFormat::scientific
+pub(crate) fn write_number<W: Write + ?Sized>(
+	out: &mut W,
+	n: f64,
+) -> fmt::Result {
+	if n.is_finite() {
+		write!(out, "{n}")
+	} else {
+		out.write_str("null")
+	}
+}
+
+/// Formats the value as compact JSON.
+impl fmt::Display for JsonValue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			JsonValue::Null => f.write_str("null"),
+			JsonValue::Boolean(b) => write!(f, "{b}"),
+			JsonValue::Number(n) => write_number(f, *n),
+			JsonValue::String(s) => write_string(f, s),
+			JsonValue::Array(items) => {
+				f.write_char('[')?;
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{item}")?;
+				}
+				f.write_char(']')
+			}
+			JsonValue::Object(map) => {
+				f.write_char('{')?;
+				for (i, (key, value)) in map.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{value}")?;
+				}
+				f.write_char('}')
+			}
+		}
+	}
+}
+
+impl JsonValue {
+	/// Formats the value as compact JSON, as `to_string()` does, unless
+	/// that takes more than `max_bytes`, for enforcing a cap on response
+	/// size.
+	///
+	/// Writing stops as soon as the output passes the cap, so an oversized
+	/// value costs at most `max_bytes` of buffer and the time to write that
+	/// much. To check a size without writing anything, see
+	/// [`estimated_json_len`](Self::estimated_json_len).
+	pub fn to_string_bounded(
+		&self,
+		max_bytes: usize,
+	) -> Result<String, TooLarge> {
+		let mut out = Bounded {
+			out: String::new(),
+			max_bytes,
+		};
+		match write!(out, "{self}") {
+			Ok(()) => Ok(out.out),
+			Err(fmt::Error) => Err(TooLarge { max_bytes }),
+		}
+	}
+
+	/// Computes the exact length in bytes of the compact serialization
+	/// (`to_string()`), without producing it.
+	pub fn estimated_json_len(&self) -> usize {
+		match self {
+			JsonValue::Null => 4,
+			JsonValue::Boolean(true) => 4,
+			JsonValue::Boolean(false) => 5,
+			JsonValue::Number(n) => {
+				let mut counter = Counter(0);
+				let _ = write_number(&mut counter, *n);
+				counter.0
+			}
+			JsonValue::String(s) => string_len(s),
+			JsonValue::Array(items) => {
+				2 + items.len().saturating_sub(1)
+					+ items
+						.iter()
+						.map(JsonValue::estimated_json_len)
+						.sum::<usize>()
+			}
+			JsonValue::Object(map) => {
+				2 + map.len().saturating_sub(1)
+					+ map
+						.iter()
+						.map(|(k, v)| {
+							string_len(k) + 1 + v.estimated_json_len()
+						})
+						.sum::<usize>()
+			}
+		}
+	}
+}
+
+/// The length of `s` once quoted and escaped by [`write_string`].
+fn string_len(s: &str) -> usize {
+	2 + s
+		.chars()
+		.map(|c| match c {
+			'"' | '\\' | '\x08' | '\x0C' | '\n' | '\r' | '\t' => 2,
+			c if c < ' ' => 6,
+			c => c.len_utf8(),
+		})
+		.sum::<usize>()
+}
+
+struct Counter(usize);
+
+impl Write for Counter {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		self.0 += s.len();
+		Ok(())
+	}
+}
+
+/// The error from [`JsonValue::to_string_bounded`] for a value whose JSON
+/// is longer than the cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLarge {
+	max_bytes: usize,
+}
+
+impl TooLarge {
+	/// The cap that was exceeded.
+	pub fn max_bytes(&self) -> usize {
+		self.max_bytes
+	}
+}
+
+impl fmt::Display for TooLarge {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"JSON is longer than {} bytes",
+			self.max_bytes
+		)
+	}
+}
+
+impl error::Error for TooLarge {}
+
+/// A buffer that refuses to grow past `max_bytes`.
+struct Bounded {
+	out: String,
+	max_bytes: usize,
+}
+
+impl Write for Bounded {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		if self.out.len() + s.len() > self.max_bytes {
+			return Err(fmt::Error);
+		}
+		self.out.push_str(s);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::parse;
+
+	use super::*;
+
+	fn number(n: f64) -> String {
+		let mut out = String::new();
+		write_number(&mut out, n).unwrap();
+		out
+	}
+
+	#[test]
+	fn integers_have_no_fraction() {
+		assert_eq!(number(1.0), "1");
+		assert_eq!(number(-42.0), "-42");
+		assert_eq!(number(-0.0), "-0");
+		assert_eq!(number(1e20), "100000000000000000000");
+		assert_eq!(parse("1.0").unwrap().to_string(), "1");
+		assert_eq!(parse("1e0").unwrap().to_string(), "1");
+		assert_eq!(
+			parse("[1, 2.5]").unwrap().to_string(),
+			"[1,2.5]"
+		);
+	}
+
+	#[test]
+	fn non_finite_numbers_are_null() {
+		assert_eq!(number(f64::NAN), "null");
+		assert_eq!(number(f64::INFINITY), "null");
+		assert_eq!(number(f64::NEG_INFINITY), "null");
+	}
+
+	#[test]
+	fn large_and_small_numbers_are_positional() {
+		let large = number(1e300);
+		assert_eq!(large.len(), 301);
+		assert!(
+			large.starts_with('1') && large[1..].bytes().all(|b| b == b'0')
+		);
+		let small = number(1e-300);
+		assert_eq!(small.len(), 302);
+		assert!(small.starts_with("0.000") && small.ends_with('1'));
+	}
+
+	#[test]
+	fn numbers_round_trip() {
+		let mut values = vec![
+			0.0,
+			-0.0,
+			0.1,
+			1.0 / 3.0,
+			-2.5e-8,
+			123456.789,
+			9_007_199_254_740_993.0,
+			f64::EPSILON,
+			f64::MIN_POSITIVE,
+			5e-324,
+			f64::MAX,
+			f64::MIN,
+		];
+		// A spread of bit patterns, from a fixed seed so failures repeat.
+		let mut state = 0x2545_f491_4f6c_dd1du64;
+		for _ in 0..2000 {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			let n = f64::from_bits(state);
+			if n.is_finite() {
+				values.push(n);
+			}
+		}
+		for n in values {
+			let text = number(n);
+			let parsed = parse(&text).unwrap();
+			let back = parsed.as_f64().unwrap();
+			assert_eq!(back.to_bits(), n.to_bits(), "{text}");
+			assert_eq!(parsed.to_string(), text);
+		}
+	}
+
+	#[test]
+	fn strings_round_trip() {
+		let s = "quote \" backslash \\ \u{8}\u{c}\n\r\t \u{1} \u{7f} é 😀";
+		let mut out = String::new();
+		write_string(&mut out, s).unwrap();
+		assert_eq!(
+			out,
+			"\"quote \\\" backslash \\\\ \\b\\f\\n\\r\\t \\u0001 \u{7f} é 😀\""
+		);
+		assert_eq!(
+			parse(&out).unwrap(),
+			JsonValue::String(s.to_owned())
+		);
+	}
+
+	#[test]
+	fn estimated_json_len_is_exact() {
+		for input in [
+			"null",
+			"false",
+			"-1.5e-7",
+			"[]",
+			"{}",
+			r#""\u0001 \" é 😀 \n""#,
+			r#"[1, [true, null], {"k\t": "v"}, {"a": {}, "b": [2.25]}]"#,
+		] {
+			let value = parse(input).unwrap();
+			assert_eq!(
+				value.estimated_json_len(),
+				value.to_string().len(),
+				"{input}"
+			);
+		}
+		assert_eq!(
+			JsonValue::Number(f64::NAN).estimated_json_len(),
+			"null".len()
+		);
+	}
+
+	#[test]
+	fn to_string_bounded_allows_exactly_the_cap() {
+		let value = parse(r#"[1, "two", {"three": [null, 4.5]}]"#).unwrap();
+		let text = value.to_string();
+		assert_eq!(
+			value.to_string_bounded(text.len()),
+			Ok(text.clone())
+		);
+		assert_eq!(
+			value.to_string_bounded(usize::MAX),
+			Ok(text.clone())
+		);
+
+		let error = value.to_string_bounded(text.len() - 1).unwrap_err();
+		assert_eq!(error.max_bytes(), text.len() - 1);
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"JSON is longer than {} bytes",
+				text.len() - 1
+			)
+		);
+		assert!(JsonValue::Null.to_string_bounded(3).is_err());
+		assert_eq!(
+			JsonValue::Null.to_string_bounded(4),
+			Ok("null".to_owned())
+		);
+	}
+
+	#[test]
+	fn to_string_bounded_stops_early() {
+		let mut out = Bounded {
+			out: String::new(),
+			max_bytes: 10,
+		};
+		let big = JsonValue::Array(vec![JsonValue::from("x"); 100_000]);
+		assert!(write!(out, "{big}").is_err());
+		assert!(out.out.len() <= 10, "{}", out.out);
+		assert!(out.out.starts_with("[\"x\""));
+	}
+}
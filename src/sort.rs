@@ -0,0 +1,181 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{JsonPointer, JsonValue, PointerError};
+
+impl JsonValue {
+	/// Sorts the elements of an array with a comparator, keeping equal
+	/// elements in their original order.
+	///
+	/// Does nothing if the value is not an array.
+	pub fn sort_by(
+		&mut self,
+		compare: impl FnMut(&JsonValue, &JsonValue) -> Ordering,
+	) {
+		if let JsonValue::Array(items) = self {
+			items.sort_by(compare);
+		}
+	}
+
+	/// Sorts the array at `array` by the value each element holds at `key`,
+	/// e.g. `sort_array_by_pointer("/items", "/price")`.
+	///
+	/// Elements without a value at `key` come first. The rest are ordered by
+	/// that value, with `null < false < true < numbers < strings < arrays <
+	/// objects`. Does nothing if there is no array at `array`.
+	pub fn sort_array_by_pointer(
+		&mut self,
+		array: &str,
+		key: &str,
+	) -> Result<(), PointerError> {
+		let (array, key) = (
+			JsonPointer::parse(array)?,
+			JsonPointer::parse(key)?,
+		);
+		if let Some(node) = self.resolve_mut(&array) {
+			node.sort_by(
+				|a, b| match (a.resolve(&key), b.resolve(&key)) {
+					(Some(a), Some(b)) => compare(a, b),
+					(a, b) => a.is_some().cmp(&b.is_some()),
+				},
+			);
+		}
+		Ok(())
+	}
+}
+
+/// A total order over values, the same one jq uses: `null < false < true <
+/// numbers < strings < arrays < objects`, with objects compared by their
+/// sorted keys first. `-0` and `0` are equal, as they are under `==`.
+pub(crate) fn compare(a: &JsonValue, b: &JsonValue) -> Ordering {
+	fn rank(value: &JsonValue) -> u8 {
+		match value {
+			JsonValue::Null => 0,
+			JsonValue::Boolean(false) => 1,
+			JsonValue::Boolean(true) => 2,
+			JsonValue::Number(_) => 3,
+			JsonValue::String(_) => 4,
+			JsonValue::Array(_) => 5,
+			JsonValue::Object(_) => 6,
+		}
+	}
+	match (a, b) {
+		(JsonValue::Number(a), JsonValue::Number(b)) => numbers(*a, *b),
+		(JsonValue::String(a), JsonValue::String(b)) => a.cmp(b),
+		(JsonValue::Array(a), JsonValue::Array(b)) => a
+			.iter()
+			.zip(b)
+			.map(|(a, b)| compare(a, b))
+			.find(|order| order.is_ne())
+			.unwrap_or(a.len().cmp(&b.len())),
+		(JsonValue::Object(a), JsonValue::Object(b)) => {
+			let (a_keys, b_keys) = (sorted_keys(a), sorted_keys(b));
+			a_keys.cmp(&b_keys).then_with(|| {
+				a_keys
+					.iter()
+					.map(|key| compare(&a[*key], &b[*key]))
+					.find(|order| order.is_ne())
+					.unwrap_or(Ordering::Equal)
+			})
+		}
+		_ => rank(a).cmp(&rank(b)),
+	}
+}
+
+pub(crate) fn sorted_keys(map: &HashMap<String, JsonValue>) -> Vec<&String> {
+	let mut keys: Vec<&String> = map.keys().collect();
+	keys.sort();
+	keys
+}
+
+/// Orders numbers by value, so that `-0` and `0` are equal, with NaN, which
+/// no document holds, after every other number.
+pub(crate) fn numbers(a: f64, b: f64) -> Ordering {
+	a.partial_cmp(&b)
+		.unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn sort_by_is_stable() {
+		let mut value =
+			parse(r#"[{"n": 2, "i": 0}, {"n": 1, "i": 1}, {"n": 2, "i": 2}]"#)
+				.unwrap();
+		value.sort_by(|a, b| compare(a.get("n").unwrap(), b.get("n").unwrap()));
+		assert_eq!(
+			value,
+			parse(r#"[{"n": 1, "i": 1}, {"n": 2, "i": 0}, {"n": 2, "i": 2}]"#)
+				.unwrap()
+		);
+		let mut scalar = parse("1").unwrap();
+		scalar.sort_by(|_, _| Ordering::Less);
+		assert_eq!(scalar, parse("1").unwrap());
+	}
+
+	#[test]
+	fn sorts_by_the_value_at_a_pointer() {
+		let mut value = parse(
+			r#"{"items": [{"price": "b"}, {"price": 3}, {}, {"price": null},
+			{"price": [1]}, {"price": 1}]}"#,
+		)
+		.unwrap();
+		value.sort_array_by_pointer("/items", "/price").unwrap();
+		assert_eq!(
+			value,
+			parse(
+				r#"{"items": [{}, {"price": null}, {"price": 1}, {"price": 3},
+				{"price": "b"}, {"price": [1]}]}"#
+			)
+			.unwrap()
+		);
+		assert!(matches!(
+			value.sort_array_by_pointer("items", "/price"),
+			Err(PointerError::Syntax(_))
+		));
+		value.sort_array_by_pointer("/missing", "/price").unwrap();
+	}
+
+	#[test]
+	fn values_have_a_total_order() {
+		let ordered = parse(
+			r#"[null, false, true, -1, 0, 2, "", "a", "b", [], [1], [1, 0],
+			[2], {}, {"a": 2}, {"a": 3}, {"a": 1, "b": 0}, {"b": 0}]"#,
+		)
+		.unwrap();
+		let JsonValue::Array(items) = ordered else {
+			unreachable!()
+		};
+		for (i, a) in items.iter().enumerate() {
+			for (j, b) in items.iter().enumerate() {
+				assert_eq!(compare(a, b), i.cmp(&j), "{a} {b}");
+			}
+		}
+	}
+
+	#[test]
+	fn negative_zero_sorts_as_zero() {
+		assert_eq!(numbers(-0.0, 0.0), Ordering::Equal);
+		assert_eq!(
+			numbers(f64::NAN, f64::INFINITY),
+			Ordering::Greater
+		);
+		assert_eq!(
+			numbers(f64::NAN, f64::NAN),
+			Ordering::Equal
+		);
+		assert_eq!(
+			compare(
+				&parse("[-0]").unwrap(),
+				&parse("[0]").unwrap()
+			),
+			Ordering::Equal
+		);
+
+		let mut value = parse("[0, -0, -1, 0, -0]").unwrap();
+		value.sort_by(compare);
+		assert_eq!(value.to_string(), "[-1,0,-0,0,-0]");
+	}
+}
@@ -0,0 +1,126 @@
+use crate::{JsonPointer, JsonValue};
+
+/// Summary statistics about a document, as returned by
+/// [`JsonValue::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+	pub nulls: usize,
+	pub booleans: usize,
+	pub numbers: usize,
+	pub strings: usize,
+	pub arrays: usize,
+	pub objects: usize,
+	/// The number of tokens in the longest path; a scalar root has depth 0.
+	pub max_depth: usize,
+	/// Total UTF-8 bytes in string values, excluding quotes and escapes.
+	pub string_bytes: usize,
+	/// Total UTF-8 bytes in object keys.
+	pub key_bytes: usize,
+	/// The location and length of the longest array, if any.
+	pub largest_array: Option<(JsonPointer, usize)>,
+	/// The location and entry count of the biggest object, if any.
+	pub largest_object: Option<(JsonPointer, usize)>,
+}
+
+impl Stats {
+	/// The total number of nodes.
+	pub fn nodes(&self) -> usize {
+		self.nulls
+			+ self.booleans
+			+ self.numbers
+			+ self.strings
+			+ self.arrays
+			+ self.objects
+	}
+}
+
+impl JsonValue {
+	pub fn stats(&self) -> Stats {
+		let mut stats = Stats::default();
+
+		self.walk(|path, node| {
+			stats.max_depth = stats.max_depth.max(path.tokens().len());
+			match node {
+				JsonValue::Null => stats.nulls += 1,
+				JsonValue::Boolean(_) => stats.booleans += 1,
+				JsonValue::Number(_) => stats.numbers += 1,
+				JsonValue::String(s) => {
+					stats.strings += 1;
+					stats.string_bytes += s.len();
+				}
+				JsonValue::Array(items) => {
+					stats.arrays += 1;
+					record_largest(
+						&mut stats.largest_array,
+						path,
+						items.len(),
+					);
+				}
+				JsonValue::Object(map) => {
+					stats.objects += 1;
+					stats.key_bytes +=
+						map.keys().map(String::len).sum::<usize>();
+					record_largest(
+						&mut stats.largest_object,
+						path,
+						map.len(),
+					);
+				}
+			}
+		});
+
+		stats
+	}
+}
+
+fn record_largest(
+	largest: &mut Option<(JsonPointer, usize)>,
+	path: &JsonPointer,
+	len: usize,
+) {
+	if largest.as_ref().is_none_or(|(_, max)| len > *max) {
+		*largest = Some((path.clone(), len));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn counts_a_document() {
+		let value = parse(
+			r#"{"name": "héllo", "tags": ["a", "bc", null],
+			"nested": {"deep": [[true, 1.5]]}}"#,
+		)
+		.unwrap();
+		let at = |pointer| JsonPointer::parse(pointer).unwrap();
+		assert_eq!(
+			value.stats(),
+			Stats {
+				nulls: 1,
+				booleans: 1,
+				numbers: 1,
+				strings: 3,
+				arrays: 3,
+				objects: 2,
+				max_depth: 4,
+				string_bytes: 9,
+				key_bytes: 18,
+				largest_array: Some((at("/tags"), 3)),
+				largest_object: Some((at(""), 3)),
+			}
+		);
+		assert_eq!(value.stats().nodes(), 11);
+	}
+
+	#[test]
+	fn scalars_have_no_depth() {
+		let stats = parse(r#""text""#).unwrap().stats();
+		assert_eq!(stats.max_depth, 0);
+		assert_eq!(stats.nodes(), 1);
+		assert_eq!(stats.largest_array, None);
+		assert_eq!(stats.largest_object, None);
+	}
+}
@@ -0,0 +1,824 @@
+//! A syntax tree that remembers where in the source each value is, for
+//! editors and other tools that map values back to text, and that can be
+//! brought up to date after an edit without parsing it all again.
+//!
+//! Parsed with [`parse_spanned_with_comments`], the tree also keeps the
+//! comments of a `jsonc` file, which [`Node::to_string_pretty`] writes back
+//! out.
+
+use std::{iter::Peekable, mem, ops::Range};
+
+use crate::{
+	lexer::{number_error, tokenize, Token, TokenKind, Tokens},
+	unescape_json_string, Diagnostic, Error, ErrorKind, JsonValue,
+};
+
+/// How deeply arrays and objects may nest, so hostile input cannot exhaust
+/// the stack.
+const MAX_DEPTH: usize = 512;
+
+/// A value and the bytes of the source it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+	pub span: Range<usize>,
+	pub kind: NodeKind,
+	pub comments: Comments,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Node>),
+	/// The members in source order, including any whose key repeats.
+	Object(Vec<Member>),
+}
+
+/// A member of an object, with where its key is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+	pub key: String,
+	/// The key's string literal, quotes included.
+	pub key_span: Range<usize>,
+	pub value: Node,
+}
+
+/// The comments around a [`Node`], each as written, `//` or `/* */`
+/// included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Comments {
+	/// Comments before the node, or before its key if it is the value of a
+	/// member, that are not trailing the value before.
+	pub leading: Vec<String>,
+	/// Comments after the node on the same line, on either side of the
+	/// comma after it. The root's trailing comments are all those after
+	/// it.
+	pub trailing: Vec<String>,
+	/// Comments after the last item of an array or object, on lines of
+	/// their own, or all those inside one that is empty.
+	pub dangling: Vec<String>,
+}
+
+impl Comments {
+	pub fn is_empty(&self) -> bool {
+		self.leading.is_empty()
+			&& self.trailing.is_empty()
+			&& self.dangling.is_empty()
+	}
+}
+
+/// A change to a document's text: the bytes in `range` replaced by
+/// `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextEdit {
+	pub range: Range<usize>,
+	pub replacement: String,
+}
+
+/// Parses a complete document into a [`Node`].
+///
+/// Numbers must follow the JSON grammar to the letter, as they must for
+/// [`parse`](crate::parse).
+pub fn parse_spanned(input: &str) -> Result<Node, Error> {
+	SpannedParser::new(input, false).document()
+}
+
+/// Like [`parse_spanned`], but allows `//` and `/* */` comments and keeps
+/// them in the [`Comments`] of the nodes they are next to, for tools that
+/// rewrite `jsonc` files such as editor settings.
+///
+/// [`Node::reparse`] still takes edits as plain JSON, so a tree with
+/// comments is best parsed again in full after an edit.
+pub fn parse_spanned_with_comments(input: &str) -> Result<Node, Error> {
+	SpannedParser::new(input, true).document()
+}
+
+impl Node {
+	/// The value the node holds, without its spans. Of members whose key
+	/// repeats, the last one is kept, as [`parse`](crate::parse) does.
+	pub fn to_value(&self) -> JsonValue {
+		match &self.kind {
+			NodeKind::Null => JsonValue::Null,
+			NodeKind::Boolean(b) => JsonValue::Boolean(*b),
+			NodeKind::Number(n) => JsonValue::Number(*n),
+			NodeKind::String(s) => JsonValue::String(s.clone()),
+			NodeKind::Array(items) => {
+				JsonValue::Array(items.iter().map(Node::to_value).collect())
+			}
+			NodeKind::Object(members) => members
+				.iter()
+				.map(|member| {
+					(
+						member.key.clone(),
+						member.value.to_value(),
+					)
+				})
+				.collect(),
+		}
+	}
+
+	/// A diagnostic for each member of this object whose key is not in
+	/// `known`, spanning the key, as a struct that denies unknown fields
+	/// rejects them. Where a known key is close enough to be a likely typo,
+	/// the message suggests it, as in `unknown field "prot", did you mean
+	/// "port"?`. A node that is not an object has no unknown fields.
+	pub fn unknown_fields(&self, known: &[&str]) -> Vec<Diagnostic> {
+		let NodeKind::Object(members) = &self.kind else {
+			return Vec::new();
+		};
+		members
+			.iter()
+			.filter(|member| !known.contains(&member.key.as_str()))
+			.map(|member| {
+				let mut message = format!("unknown field {:?}", member.key);
+				if let Some(near) = suggestion(&member.key, known) {
+					message += &format!(", did you mean {near:?}?");
+				}
+				Diagnostic {
+					span: member.key_span.clone(),
+					message,
+				}
+			})
+			.collect()
+	}
+
+	/// Applies `edit` to `text`, the source the tree was parsed from, and
+	/// gives the tree of the result.
+	///
+	/// Only the innermost array or object whose brackets enclose the edit is
+	/// parsed again, or the one around that if it no longer parses on its
+	/// own, and so on out to the whole document; everything else in the tree
+	/// is kept, with the spans after the edit moved. Fails as
+	/// [`parse_spanned`] would on the edited text.
+	pub fn reparse(
+		mut self,
+		text: &mut String,
+		edit: &TextEdit,
+	) -> Result<Node, Error> {
+		let removed = edit.range.len();
+		let inserted = edit.replacement.len();
+		text.replace_range(edit.range.clone(), &edit.replacement);
+		let Some(path) = self.enclosing(&edit.range) else {
+			return parse_spanned(text);
+		};
+		for depth in (0..=path.len()).rev() {
+			let old = &self.at(&path[..depth]).span;
+			let span = old.start..old.end - removed + inserted;
+			let Ok(mut node) = parse_spanned(&text[span.clone()]) else {
+				continue;
+			};
+			node.shift(0, span.start);
+			let mut target = &mut self;
+			for &i in &path[..depth] {
+				target.span.end = target.span.end - removed + inserted;
+				target.shift_children(i + 1, removed, inserted);
+				target = target.children_mut().nth(i).unwrap();
+			}
+			// The comments around the node lie outside the text parsed.
+			node.comments.leading = mem::take(&mut target.comments.leading);
+			node.comments.trailing = mem::take(&mut target.comments.trailing);
+			*target = node;
+			return Ok(self);
+		}
+		parse_spanned(text)
+	}
+
+	/// The child indices leading from this node to the innermost array or
+	/// object that `range` lies strictly between the brackets of, or `None`
+	/// if this node is not such a one.
+	fn enclosing(&self, range: &Range<usize>) -> Option<Vec<usize>> {
+		let inside = |node: &Node| {
+			matches!(
+				node.kind,
+				NodeKind::Array(_) | NodeKind::Object(_)
+			) && node.span.start < range.start
+				&& range.end < node.span.end
+		};
+		if !inside(self) {
+			return None;
+		}
+		let mut path = Vec::new();
+		let mut node = self;
+		while let Some((i, child)) =
+			node.children().enumerate().find(|(_, child)| inside(child))
+		{
+			path.push(i);
+			node = child;
+		}
+		Some(path)
+	}
+
+	fn at(&self, path: &[usize]) -> &Node {
+		path.iter().fold(self, |node, &i| {
+			node.children().nth(i).unwrap()
+		})
+	}
+
+	/// The items of an array or the values of an object's members.
+	fn children(&self) -> Box<dyn Iterator<Item = &Node> + '_> {
+		match &self.kind {
+			NodeKind::Array(items) => Box::new(items.iter()),
+			NodeKind::Object(members) => {
+				Box::new(members.iter().map(|member| &member.value))
+			}
+			_ => Box::new(std::iter::empty()),
+		}
+	}
+
+	fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Node> + '_> {
+		match &mut self.kind {
+			NodeKind::Array(items) => Box::new(items.iter_mut()),
+			NodeKind::Object(members) => {
+				Box::new(members.iter_mut().map(|member| &mut member.value))
+			}
+			_ => Box::new(std::iter::empty()),
+		}
+	}
+
+	/// Moves the spans of the children from the one at `from` on, as
+	/// [`Node::shift`] does.
+	fn shift_children(&mut self, from: usize, removed: usize, inserted: usize) {
+		match &mut self.kind {
+			NodeKind::Array(items) => {
+				for item in &mut items[from..] {
+					item.shift(removed, inserted);
+				}
+			}
+			NodeKind::Object(members) => {
+				for member in &mut members[from..] {
+					member.key_span =
+						moved(&member.key_span, removed, inserted);
+					member.value.shift(removed, inserted);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Moves every span in the tree for `removed` bytes before it having
+	/// been replaced by `inserted` ones.
+	fn shift(&mut self, removed: usize, inserted: usize) {
+		self.span = moved(&self.span, removed, inserted);
+		self.shift_children(0, removed, inserted);
+	}
+}
+
+/// Where `span` is once `removed` bytes before it are replaced by
+/// `inserted` ones.
+fn moved(span: &Range<usize>, removed: usize, inserted: usize) -> Range<usize> {
+	span.start - removed + inserted..span.end - removed + inserted
+}
+
+struct SpannedParser<'i> {
+	input: &'i str,
+	tokens: Peekable<Tokens<'i>>,
+	/// The number of arrays and objects being parsed.
+	depth: usize,
+	/// Whether comments are allowed and kept.
+	comments: bool,
+	/// Comments read since the last node, for the next one to lead with.
+	leading: Vec<String>,
+}
+
+impl<'i> SpannedParser<'i> {
+	fn new(input: &'i str, comments: bool) -> Self {
+		Self {
+			input,
+			tokens: tokenize(input).peekable(),
+			depth: 0,
+			comments,
+			leading: Vec::new(),
+		}
+	}
+
+	fn document(mut self) -> Result<Node, Error> {
+		let mut node = self.value()?;
+		match self.next() {
+			Some(token) => Err(self.unexpected(&token)),
+			None => {
+				node.comments.trailing = mem::take(&mut self.leading);
+				Ok(node)
+			}
+		}
+	}
+
+	/// The next token that is not whitespace, or a comment when comments
+	/// are kept, which is put aside for the next node.
+	fn next(&mut self) -> Option<Token> {
+		loop {
+			let token = self.tokens.next()?;
+			match token.kind {
+				TokenKind::Whitespace => {}
+				TokenKind::LineComment | TokenKind::BlockComment
+					if self.comments =>
+				{
+					self.leading.push(self.input[token.span].to_owned());
+				}
+				_ => return Some(token),
+			}
+		}
+	}
+
+	/// Reads the comments after a node on its line into `trailing`,
+	/// stopping at the first line break or token that is not trivia.
+	fn trailing(&mut self, trailing: &mut Vec<String>) {
+		while let Some(token) = self.tokens.peek() {
+			let text = &self.input[token.span.clone()];
+			match token.kind {
+				TokenKind::Whitespace if !text.contains('\n') => {}
+				TokenKind::LineComment | TokenKind::BlockComment
+					if self.comments =>
+				{
+					trailing.push(text.to_owned());
+				}
+				_ => return,
+			}
+			self.tokens.next();
+		}
+	}
+
+	fn unexpected(&self, token: &Token) -> Error {
+		let text = &self.input[token.span.clone()];
+		let message = match token.kind {
+			TokenKind::Error if text.starts_with('"') => {
+				"unterminated string".to_owned()
+			}
+			TokenKind::Error if number_error(text).is_some() => {
+				number_error(text).unwrap_or_default().to_owned()
+			}
+			TokenKind::LineComment | TokenKind::BlockComment => {
+				"comments are not allowed in JSON".to_owned()
+			}
+			_ => match text.chars().next() {
+				Some(c) => format!("unexpected character {c:?}"),
+				None => "unexpected end of input".to_owned(),
+			},
+		};
+		Error::new(self.input, token.span.start, message)
+	}
+
+	fn end_of_input(&self) -> Error {
+		Error::new(
+			self.input,
+			self.input.len(),
+			"unexpected end of input".to_owned(),
+		)
+	}
+
+	fn expect(&mut self, kind: TokenKind) -> Result<Token, Error> {
+		match self.next() {
+			Some(token) if token.kind == kind => Ok(token),
+			Some(token) => Err(self.unexpected(&token)),
+			None => Err(self.end_of_input()),
+		}
+	}
+
+	fn value(&mut self) -> Result<Node, Error> {
+		let token = self.next().ok_or_else(|| self.end_of_input())?;
+		let leading = mem::take(&mut self.leading);
+		let mut node = self.token_value(token)?;
+		node.comments.leading = leading;
+		Ok(node)
+	}
+
+	fn token_value(&mut self, token: Token) -> Result<Node, Error> {
+		let text = &self.input[token.span.clone()];
+		let kind = match token.kind {
+			TokenKind::Null => NodeKind::Null,
+			TokenKind::True => NodeKind::Boolean(true),
+			TokenKind::False => NodeKind::Boolean(false),
+			TokenKind::Number => NodeKind::Number(text.parse().unwrap()),
+			TokenKind::String => NodeKind::String(self.string(&token)?),
+			TokenKind::StartArray | TokenKind::StartObject => {
+				if self.depth == MAX_DEPTH {
+					return Err(Error::new(
+						self.input,
+						token.span.start,
+						"nesting too deep".to_owned(),
+					)
+					.with_kind(ErrorKind::TooDeep));
+				}
+				self.depth += 1;
+				let node = if token.kind == TokenKind::StartArray {
+					self.array(token.span.start)
+				} else {
+					self.object(token.span.start)
+				};
+				self.depth -= 1;
+				return node;
+			}
+			_ => return Err(self.unexpected(&token)),
+		};
+		Ok(Node {
+			span: token.span,
+			kind,
+			comments: Comments::default(),
+		})
+	}
+
+	fn string(&self, token: &Token) -> Result<String, Error> {
+		let start = token.span.start + 1;
+		let contents = &self.input[start..token.span.end - 1];
+		unescape_json_string(contents)
+			.map(|s| s.into_owned())
+			.map_err(|e| {
+				Error::new(
+					self.input,
+					start + e.offset(),
+					e.message().to_owned(),
+				)
+			})
+	}
+
+	fn array(&mut self, start: usize) -> Result<Node, Error> {
+		let mut items: Vec<Node> = Vec::new();
+		let end = loop {
+			let last = items.last_mut().map(|item| &mut item.comments);
+			if let Some(end) = self.close(TokenKind::EndArray, last)? {
+				break end;
+			}
+			items.push(self.value()?);
+		};
+		Ok(Node {
+			span: start..end,
+			kind: NodeKind::Array(items),
+			comments: self.dangling(),
+		})
+	}
+
+	fn object(&mut self, start: usize) -> Result<Node, Error> {
+		let mut members: Vec<Member> = Vec::new();
+		let end = loop {
+			let last =
+				members.last_mut().map(|member| &mut member.value.comments);
+			if let Some(end) = self.close(TokenKind::EndObject, last)? {
+				break end;
+			}
+			let token = self.expect(TokenKind::String)?;
+			let key = self.string(&token)?;
+			self.expect(TokenKind::Colon)?;
+			members.push(Member {
+				key,
+				key_span: token.span,
+				value: self.value()?,
+			});
+		};
+		Ok(Node {
+			span: start..end,
+			kind: NodeKind::Object(members),
+			comments: self.dangling(),
+		})
+	}
+
+	/// The comments of a container just closed, holding those after its
+	/// last item.
+	fn dangling(&mut self) -> Comments {
+		Comments {
+			dangling: mem::take(&mut self.leading),
+			..Comments::default()
+		}
+	}
+
+	/// Reads the `close` bracket, giving the end of the container, or the
+	/// comma before the next item when there is a `last` one, whose
+	/// trailing comments are read on the way.
+	fn close(
+		&mut self,
+		close: TokenKind,
+		mut last: Option<&mut Comments>,
+	) -> Result<Option<usize>, Error> {
+		if let Some(comments) = last.as_deref_mut() {
+			self.trailing(&mut comments.trailing);
+		}
+		loop {
+			let Some(token) = self.tokens.peek() else {
+				return Err(self.end_of_input());
+			};
+			match token.kind {
+				TokenKind::Whitespace => {}
+				TokenKind::LineComment | TokenKind::BlockComment
+					if self.comments =>
+				{
+					let text = &self.input[token.span.clone()];
+					self.leading.push(text.to_owned());
+				}
+				kind if kind == close => {
+					return Ok(self.tokens.next().map(|token| token.span.end));
+				}
+				_ => break,
+			}
+			self.tokens.next();
+		}
+		if let Some(comments) = last {
+			// Comments between the item and its comma are read as leading
+			// the next item, but follow this one.
+			comments.trailing.append(&mut self.leading);
+			self.expect(TokenKind::Comma)?;
+			self.trailing(&mut comments.trailing);
+		}
+		Ok(None)
+	}
+}
+
+/// The name in `known` nearest to `key`, if it is within a third of the
+/// key's length in edits, or one edit for short keys.
+fn suggestion<'k>(key: &str, known: &[&'k str]) -> Option<&'k str> {
+	let limit = (key.chars().count() / 3).max(1);
+	known
+		.iter()
+		.map(|&name| (edit_distance(key, name), name))
+		.filter(|&(distance, _)| distance <= limit)
+		.min_by_key(|&(distance, _)| distance)
+		.map(|(_, name)| name)
+}
+
+/// The edit distance between `a` and `b` in characters, counting an
+/// insertion, deletion, substitution or swap of adjacent characters as one
+/// edit each, so that `prot` is one edit from `port`.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.chars().collect();
+	let b: Vec<char> = b.chars().collect();
+	let mut before: Vec<usize> = Vec::new();
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	for i in 1..=a.len() {
+		let mut row = vec![i; b.len() + 1];
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			row[j] = (previous[j - 1] + cost)
+				.min(previous[j] + 1)
+				.min(row[j - 1] + 1);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				row[j] = row[j].min(before[j - 2] + 1);
+			}
+		}
+		before = std::mem::replace(&mut previous, row);
+	}
+	previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn number_errors() {
+		for (input, message) in [
+			("[01]", "leading zeros are not allowed"),
+			("[+1]", "numbers cannot start with '+'"),
+			(
+				"[.5]",
+				"numbers need a digit before the decimal point",
+			),
+			(
+				"[1.]",
+				"numbers need a digit after the decimal point",
+			),
+			(
+				"[1e]",
+				"exponents need at least one digit",
+			),
+		] {
+			let error = parse_spanned(input).unwrap_err();
+			assert_eq!(error.message(), message, "{input}");
+			assert_eq!(error.offset(), 1, "{input}");
+		}
+	}
+
+	/// A fixed sequence of pseudo-random numbers, so failures repeat.
+	struct Rng(u64);
+
+	impl Rng {
+		fn below(&mut self, n: usize) -> usize {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			(self.0 % n as u64) as usize
+		}
+
+		/// An edit somewhere in `text`, which must be ASCII: mostly a scalar
+		/// replaced by another value, keeping the text valid, and otherwise a
+		/// few bytes replaced by arbitrary JSON punctuation.
+		fn edit(&mut self, text: &str) -> TextEdit {
+			const VALUES: &[&str] = &[
+				"1",
+				"-2.5",
+				"\"x\"",
+				"true",
+				"null",
+				"[]",
+				"[1, 2]",
+				"{\"k\": null}",
+				"{}",
+			];
+			const NOISE: &[&str] =
+				&["", " ", ",", ":", "[", "]", "{", "}", ", \"k\": true", "01"];
+			let scalars: Vec<Token> = tokenize(text)
+				.filter(|token| {
+					matches!(
+						token.kind,
+						TokenKind::Number
+							| TokenKind::String | TokenKind::True
+							| TokenKind::False | TokenKind::Null
+					)
+				})
+				.collect();
+			if !scalars.is_empty() && self.below(4) > 0 {
+				let token = &scalars[self.below(scalars.len())];
+				return TextEdit {
+					range: token.span.clone(),
+					replacement: VALUES[self.below(VALUES.len())].to_owned(),
+				};
+			}
+			let start = self.below(text.len() + 1);
+			let end = start + self.below(text.len() - start + 1).min(4);
+			TextEdit {
+				range: start..end,
+				replacement: NOISE[self.below(NOISE.len())].to_owned(),
+			}
+		}
+	}
+
+	#[test]
+	fn reparse_matches_a_full_parse() {
+		let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+		let start =
+			r#"{"a": [1, 2, {"b": "c"}], "d": {"e": [true, null]}, "f": -1.5}"#;
+		let mut reparsed = 0;
+		for _ in 0..300 {
+			let mut text = start.to_owned();
+			let mut node = parse_spanned(&text).unwrap();
+			for _ in 0..8 {
+				let edit = rng.edit(&text);
+				let result = node.clone().reparse(&mut text, &edit);
+				let expected = parse_spanned(&text);
+				assert_eq!(
+					result.is_ok(),
+					expected.is_ok(),
+					"{text}"
+				);
+				match (result, expected) {
+					(Ok(result), Ok(expected)) => {
+						assert_eq!(result, expected, "{text}");
+						node = result;
+						reparsed += 1;
+					}
+					_ => break,
+				}
+			}
+		}
+		assert!(
+			reparsed > 100,
+			"only {reparsed} edits kept the text valid"
+		);
+	}
+
+	#[test]
+	fn reparse_keeps_unchanged_subtrees() {
+		let mut text = r#"{"a": [1, 2], "b": "c"}"#.to_owned();
+		let node = parse_spanned(&text).unwrap();
+		let edit = TextEdit {
+			range: 10..11,
+			replacement: "20, 30".to_owned(),
+		};
+		let node = node.reparse(&mut text, &edit).unwrap();
+		assert_eq!(text, r#"{"a": [1, 20, 30], "b": "c"}"#);
+		assert_eq!(node, parse_spanned(&text).unwrap());
+		let NodeKind::Object(members) = &node.kind else {
+			panic!("not an object: {node:?}");
+		};
+		assert_eq!(members[1].key_span, 19..22);
+		assert_eq!(members[1].value.span, 24..27);
+	}
+
+	#[test]
+	fn comments_attach_to_nodes() {
+		let input = "// top\n{\n  /* a */ \"a\": 1, // one\n  \"b\": [2 /* two */\n    // end\n  ]\n} // done";
+		let node = parse_spanned_with_comments(input).unwrap();
+		let strings = |comments: &[&str]| {
+			comments.iter().map(|&c| c.to_owned()).collect::<Vec<_>>()
+		};
+		assert_eq!(
+			node.comments.leading,
+			strings(&["// top"])
+		);
+		assert_eq!(
+			node.comments.trailing,
+			strings(&["// done"])
+		);
+		let NodeKind::Object(members) = &node.kind else {
+			panic!("not an object: {node:?}");
+		};
+		assert_eq!(
+			members[0].value.comments.leading,
+			strings(&["/* a */"])
+		);
+		assert_eq!(
+			members[0].value.comments.trailing,
+			strings(&["// one"])
+		);
+		let b = &members[1].value;
+		assert_eq!(
+			b.comments.dangling,
+			strings(&["// end"])
+		);
+		let NodeKind::Array(items) = &b.kind else {
+			panic!("not an array: {b:?}");
+		};
+		assert_eq!(
+			items[0].comments.trailing,
+			strings(&["/* two */"])
+		);
+		assert_eq!(
+			node.to_value(),
+			parse_spanned(r#"{"a": 1, "b": [2]}"#).unwrap().to_value()
+		);
+	}
+
+	#[test]
+	fn comments_need_enabling() {
+		for input in ["// a\n1", "[1 /* a */]", "{\"a\": 1 // a\n}"] {
+			let error = parse_spanned(input).unwrap_err();
+			assert_eq!(
+				error.message(),
+				"comments are not allowed in JSON",
+				"{input}"
+			);
+			assert!(
+				parse_spanned_with_comments(input).is_ok(),
+				"{input}"
+			);
+		}
+		assert!(parse_spanned_with_comments("[1 /* a").is_err());
+	}
+
+	#[test]
+	fn unknown_fields_span_their_keys() {
+		let text = r#"{"port": 1, "prot": 2, "hots": 3, "verbose": true, "colour": {"port": 5}}"#;
+		let tree = parse_spanned(text).unwrap();
+		let known = ["port", "host", "color"];
+		let found: Vec<_> = tree
+			.unknown_fields(&known)
+			.into_iter()
+			.map(|diagnostic| {
+				(
+					&text[diagnostic.span],
+					diagnostic.message,
+				)
+			})
+			.collect();
+		assert_eq!(
+			found,
+			[
+				(
+					r#""prot""#,
+					r#"unknown field "prot", did you mean "port"?"#.to_owned()
+				),
+				(
+					r#""hots""#,
+					r#"unknown field "hots", did you mean "host"?"#.to_owned()
+				),
+				(
+					r#""verbose""#,
+					r#"unknown field "verbose""#.to_owned()
+				),
+				(
+					r#""colour""#,
+					r#"unknown field "colour", did you mean "color"?"#
+						.to_owned()
+				),
+			]
+		);
+
+		let NodeKind::Object(members) = &tree.kind else {
+			panic!("{tree:?}");
+		};
+		assert!(members[4].value.unknown_fields(&known).is_empty());
+		assert_eq!(
+			members[4].value.unknown_fields(&[]).len(),
+			1
+		);
+		assert!(members[0].value.unknown_fields(&[]).is_empty());
+		assert!(parse_spanned("[{}]")
+			.unwrap()
+			.unknown_fields(&[])
+			.is_empty());
+	}
+
+	#[test]
+	fn suggestions_are_close_matches() {
+		assert_eq!(edit_distance("prot", "port"), 1);
+		assert_eq!(edit_distance("kitten", "sitting"), 3);
+		assert_eq!(edit_distance("", "abc"), 3);
+		assert_eq!(edit_distance("é", "e"), 1);
+		assert_eq!(
+			suggestion("hostname", &["host", "name"]),
+			None
+		);
+		assert_eq!(
+			suggestion("nme", &["name", "time"]),
+			Some("name")
+		);
+	}
+}
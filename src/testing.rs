@@ -0,0 +1,443 @@
+//! Assertions for test suites that check JSON, such as API integration
+//! tests, which explain a failure by listing where the values differ
+//! instead of printing both in full.
+//!
+//! ```text
+//! let body = winnow_json::parse(&response)?;
+//! assert_json_eq!(body, expected);
+//! // Only the members and items that `subset` has are compared.
+//! assert_json_includes!(body, subset);
+//! // Every violation of a schema, such as one from an OpenAPI description.
+//! assert_json_matches_schema!(body, user_schema);
+//! // Compares with, or first records, tests/snapshots/user.json.
+//! snapshot(&body, "tests/snapshots/user.json");
+//! ```
+
+use std::{env, fmt::Write, fs, path::Path};
+
+use crate::{schema::Schema, JsonPointer, JsonValue};
+
+/// Asserts that two [`JsonValue`]s are equal, panicking with every place
+/// they differ if not.
+#[macro_export]
+macro_rules! assert_json_eq {
+	($actual:expr, $expected:expr $(,)?) => {
+		$crate::testing::check($crate::testing::differences(
+			&$actual, &$expected,
+		))
+	};
+}
+
+/// Asserts that a [`JsonValue`] includes another: each member of an
+/// expected object must be in the actual one, and each item of an expected
+/// array at the same index of the actual one, which may have more of
+/// either. Scalars must be equal.
+#[macro_export]
+macro_rules! assert_json_includes {
+	($actual:expr, $expected:expr $(,)?) => {
+		$crate::testing::check($crate::testing::inclusion_differences(
+			&$actual, &$expected,
+		))
+	};
+}
+
+/// Asserts that a [`JsonValue`] is valid against a compiled
+/// [`Schema`](crate::schema::Schema), panicking with every violation if
+/// not, for contract tests of API responses.
+#[macro_export]
+macro_rules! assert_json_matches_schema {
+	($actual:expr, $schema:expr $(,)?) => {
+		$crate::testing::check_valid(&$schema, &$actual)
+	};
+}
+
+/// A place where an actual value differs from the expected one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+	pub path: JsonPointer,
+	/// `None` where the expected value has nothing.
+	pub expected: Option<JsonValue>,
+	/// `None` where the actual value has nothing.
+	pub actual: Option<JsonValue>,
+}
+
+/// Where `actual` and `expected` differ, taking object keys in sorted
+/// order. Containers that differ only within are described by the
+/// differences inside them.
+pub fn differences(
+	actual: &JsonValue,
+	expected: &JsonValue,
+) -> Vec<Difference> {
+	let mut found = Vec::new();
+	compare(
+		actual,
+		expected,
+		false,
+		&mut JsonPointer::root(),
+		&mut found,
+	);
+	found
+}
+
+/// Where `actual` fails to include `expected`, as
+/// [`assert_json_includes!`] checks.
+pub fn inclusion_differences(
+	actual: &JsonValue,
+	expected: &JsonValue,
+) -> Vec<Difference> {
+	let mut found = Vec::new();
+	compare(
+		actual,
+		expected,
+		true,
+		&mut JsonPointer::root(),
+		&mut found,
+	);
+	found
+}
+
+/// Panics listing `differences`, if there are any. Called by the
+/// assertion macros.
+#[track_caller]
+pub fn check(differences: Vec<Difference>) {
+	if differences.is_empty() {
+		return;
+	}
+	let mut message = String::from("JSON values differ:");
+	describe(&mut message, &differences);
+	panic!("{message}");
+}
+
+/// Panics listing the ways `value` violates `schema`, if there are any.
+/// Called by [`assert_json_matches_schema!`].
+#[track_caller]
+pub fn check_valid(schema: &Schema, value: &JsonValue) {
+	let Err(errors) = schema.validate(value) else {
+		return;
+	};
+	let mut message = String::from("JSON value does not match the schema:");
+	for error in errors {
+		let _ = write!(
+			message,
+			"\n  at \"{}\": {} (schema \"{}\")",
+			error.instance_path, error.message, error.schema_path
+		);
+	}
+	panic!("{message}");
+}
+
+/// Adds a line to `message` for each of `differences`.
+fn describe(message: &mut String, differences: &[Difference]) {
+	for difference in differences {
+		let _ = write!(
+			message,
+			"\n  at \"{}\": ",
+			difference.path
+		);
+		let _ = match (&difference.expected, &difference.actual) {
+			(Some(expected), Some(actual)) => {
+				write!(
+					message,
+					"expected {expected}, found {actual}"
+				)
+			}
+			(Some(expected), None) => {
+				write!(message, "missing, expected {expected}")
+			}
+			(None, Some(actual)) => write!(message, "unexpected {actual}"),
+			(None, None) => Ok(()),
+		};
+	}
+}
+
+/// Compares `value` with the snapshot stored at `path`, panicking with how
+/// they differ if they do.
+///
+/// Snapshots are written as [`JsonValue::to_string_stable`] does, so they
+/// can be committed and reviewed like any other file, and a change to the
+/// code that produces the value shows up as a diff of them. A snapshot
+/// that doesn't exist yet is written and the check passes. Setting the
+/// `UPDATE_SNAPSHOTS` environment variable to anything but `0` rewrites
+/// every snapshot that is checked instead of comparing with it.
+#[track_caller]
+pub fn snapshot(value: &JsonValue, path: impl AsRef<Path>) {
+	let path = path.as_ref();
+	let text = value.to_string_stable();
+	let update = env::var_os("UPDATE_SNAPSHOTS").is_some_and(|v| v != "0");
+	let stored = match fs::read_to_string(path) {
+		Ok(stored) if !update => stored,
+		_ => {
+			if let Some(dir) = path.parent() {
+				fs::create_dir_all(dir).unwrap_or_else(|e| {
+					panic!("cannot create {}: {e}", dir.display())
+				});
+			}
+			fs::write(path, text).unwrap_or_else(|e| {
+				panic!(
+					"cannot write snapshot {}: {e}",
+					path.display()
+				)
+			});
+			return;
+		}
+	};
+	if stored == text {
+		return;
+	}
+	let hint = "set UPDATE_SNAPSHOTS=1 to accept the new value";
+	let Ok(expected) = crate::parse(&stored) else {
+		panic!(
+			"snapshot {} is not valid JSON; {hint}",
+			path.display()
+		);
+	};
+	let found = differences(value, &expected);
+	if found.is_empty() {
+		let stored: Vec<_> = stored.lines().collect();
+		let now: Vec<_> = text.lines().collect();
+		let line = (0..stored.len().max(now.len()))
+			.find(|&i| stored.get(i) != now.get(i))
+			.unwrap_or(stored.len());
+		let old = stored.get(line).unwrap_or(&"(end of file)");
+		let new = now.get(line).unwrap_or(&"(end of file)");
+		panic!(
+			"snapshot {} is formatted differently at line {}:\n  stored: \
+			 {old}\n  now:    {new}\n{hint}",
+			path.display(),
+			line + 1,
+		);
+	}
+	let mut message = format!(
+		"snapshot {} does not match:",
+		path.display()
+	);
+	describe(&mut message, &found);
+	panic!("{message}\n{hint}");
+}
+
+fn compare(
+	actual: &JsonValue,
+	expected: &JsonValue,
+	subset: bool,
+	path: &mut JsonPointer,
+	found: &mut Vec<Difference>,
+) {
+	match (actual, expected) {
+		(JsonValue::Array(actual), JsonValue::Array(expected)) => {
+			for (i, expected) in expected.iter().enumerate() {
+				path.push(i.to_string());
+				match actual.get(i) {
+					Some(actual) => {
+						compare(actual, expected, subset, path, found)
+					}
+					None => found.push(Difference {
+						path: path.clone(),
+						expected: Some(expected.clone()),
+						actual: None,
+					}),
+				}
+				path.pop();
+			}
+			if !subset {
+				for (i, actual) in
+					actual.iter().enumerate().skip(expected.len())
+				{
+					found.push(Difference {
+						path: path.join(i.to_string()),
+						expected: None,
+						actual: Some(actual.clone()),
+					});
+				}
+			}
+		}
+		(JsonValue::Object(actual), JsonValue::Object(expected)) => {
+			let mut keys: Vec<_> = expected.keys().collect();
+			if !subset {
+				keys.extend(
+					actual.keys().filter(|key| !expected.contains_key(*key)),
+				);
+			}
+			keys.sort();
+			for key in keys {
+				path.push(key.as_str());
+				match (actual.get(key), expected.get(key)) {
+					(Some(actual), Some(expected)) => {
+						compare(actual, expected, subset, path, found)
+					}
+					(actual, expected) => found.push(Difference {
+						path: path.clone(),
+						expected: expected.cloned(),
+						actual: actual.cloned(),
+					}),
+				}
+				path.pop();
+			}
+		}
+		(actual, expected) if actual != expected => found.push(Difference {
+			path: path.clone(),
+			expected: Some(expected.clone()),
+			actual: Some(actual.clone()),
+		}),
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::panic;
+
+	use super::*;
+	use crate::parse;
+
+	fn paths(differences: &[Difference]) -> Vec<String> {
+		differences
+			.iter()
+			.map(|difference| difference.path.to_string())
+			.collect()
+	}
+
+	fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+		let payload = panic::catch_unwind(f).unwrap_err();
+		payload
+			.downcast_ref::<String>()
+			.cloned()
+			.unwrap_or_default()
+	}
+
+	#[test]
+	fn differences_are_listed_by_path() {
+		let actual =
+			parse(r#"{"a": [1, 2, 3], "b": {"c": 1}, "x": 0}"#).unwrap();
+		let expected =
+			parse(r#"{"a": [1, 5], "b": {"c": 1, "d": 2}}"#).unwrap();
+		let found = differences(&actual, &expected);
+		assert_eq!(
+			paths(&found),
+			["/a/1", "/a/2", "/b/d", "/x"]
+		);
+		assert_eq!(
+			found[0],
+			Difference {
+				path: JsonPointer::parse("/a/1").unwrap(),
+				expected: Some(JsonValue::Number(5.0)),
+				actual: Some(JsonValue::Number(2.0)),
+			}
+		);
+		assert_eq!(found[1].expected, None);
+		assert_eq!(found[2].actual, None);
+		assert!(differences(&actual, &actual).is_empty());
+	}
+
+	#[test]
+	fn inclusion_ignores_extra_members_and_items() {
+		let actual =
+			parse(r#"{"a": [1, 2, 3], "b": {"c": 1}, "x": 0}"#).unwrap();
+		let subset = parse(r#"{"a": [1, 2], "b": {}}"#).unwrap();
+		assert!(inclusion_differences(&actual, &subset).is_empty());
+
+		let wrong = parse(r#"{"a": [1, 2, 3, 4], "b": {"c": "1"}}"#).unwrap();
+		assert_eq!(
+			paths(&inclusion_differences(&actual, &wrong)),
+			["/a/3", "/b/c"]
+		);
+	}
+
+	#[test]
+	fn assertions_panic_with_each_difference() {
+		let actual = parse(r#"{"a": 1, "b": [true]}"#).unwrap();
+		assert_json_eq!(
+			actual,
+			parse(r#"{"b": [true], "a": 1}"#).unwrap()
+		);
+		assert_json_includes!(actual, parse(r#"{"b": []}"#).unwrap());
+
+		let message = panic_message(|| {
+			assert_json_eq!(
+				parse(r#"{"a": 1, "b": [true]}"#).unwrap(),
+				parse(r#"{"a": 2, "c": null}"#).unwrap(),
+			)
+		});
+		assert_eq!(
+			message,
+			"JSON values differ:\n  at \"/a\": expected 2, found 1\n  at \
+			 \"/b\": unexpected [true]\n  at \"/c\": missing, expected null"
+		);
+
+		let message = panic_message(|| {
+			assert_json_includes!(
+				parse("[1]").unwrap(),
+				parse("[1, 2]").unwrap()
+			)
+		});
+		assert_eq!(
+			message,
+			"JSON values differ:\n  at \"/1\": missing, expected 2"
+		);
+	}
+
+	#[test]
+	fn snapshots_are_recorded_then_compared() {
+		let dir = env::temp_dir().join(format!(
+			"winnow-json-snapshot-{}",
+			std::process::id()
+		));
+		let path = dir.join("nested").join("user.json");
+		let value = parse(r#"{"name": "a", "id": 1}"#).unwrap();
+		snapshot(&value, &path);
+		let stored = fs::read_to_string(&path).unwrap();
+		snapshot(&value, &path);
+
+		let changed = parse(r#"{"name": "b", "id": 1}"#).unwrap();
+		let message = panic_message(|| snapshot(&changed, &path));
+		fs::write(&path, r#"{"id": 1, "name": "a"}"#).unwrap();
+		let reformatted = panic_message(|| snapshot(&value, &path));
+		fs::write(&path, "{").unwrap();
+		let broken = panic_message(|| snapshot(&value, &path));
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(stored, value.to_string_stable());
+		assert!(
+			message.contains("does not match:\n  at \"/name\": expected \"a\", found \"b\"\n"),
+			"{message}"
+		);
+		assert!(
+			message.ends_with("set UPDATE_SNAPSHOTS=1 to accept the new value")
+		);
+		assert!(
+			reformatted.contains("is formatted differently at line 1:"),
+			"{reformatted}"
+		);
+		assert!(
+			broken.contains("is not valid JSON"),
+			"{broken}"
+		);
+	}
+
+	#[test]
+	fn schema_assertion_lists_each_violation() {
+		let schema = Schema::compile(
+			&parse(r#"{"items": {"type": "integer", "minimum": 0}}"#).unwrap(),
+		)
+		.unwrap();
+		assert_json_matches_schema!(parse("[0, 1]").unwrap(), schema);
+
+		let message = panic_message(|| {
+			assert_json_matches_schema!(parse("[1, -1, 2.5]").unwrap(), schema)
+		});
+		let lines: Vec<_> = message.lines().collect();
+		assert_eq!(
+			lines[0],
+			"JSON value does not match the schema:"
+		);
+		assert_eq!(lines.len(), 3, "{message}");
+		assert!(
+			lines[1].starts_with("  at \"/1\": ")
+				&& lines[1].ends_with("(schema \"/items/minimum\")"),
+			"{message}"
+		);
+		assert!(
+			lines[2].starts_with("  at \"/2\": "),
+			"{message}"
+		);
+	}
+}
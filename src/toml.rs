@@ -0,0 +1,1044 @@
+//! Conversion to and from [TOML](https://toml.io/en/v1.0.0), behind the
+//! `toml` feature.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{ser::write_escaped, ConversionError, Error, ErrorKind, JsonValue};
+
+impl JsonValue {
+	/// Loads a TOML document as an object.
+	///
+	/// Integers and floats become numbers, and `inf` and `nan`, which have no
+	/// JSON equivalent, are rejected. Offset and local date-times, dates and
+	/// times become strings holding their text exactly as written, such as
+	/// `"1979-05-27T07:32:00Z"`.
+	pub fn from_toml(input: &str) -> Result<JsonValue, Error> {
+		Loader {
+			input,
+			pos: 0,
+			depth: 0,
+		}
+		.document()
+	}
+
+	/// Renders an object as a TOML document.
+	///
+	/// Nested objects become tables, and arrays whose items are all objects
+	/// arrays of tables; other arrays and the objects within them are
+	/// written inline. Members are ordered by key, and those that are `null`,
+	/// which TOML cannot represent, are left out. Strings are always written
+	/// as strings, so a date-time loaded by [`JsonValue::from_toml`] comes
+	/// back quoted.
+	///
+	/// Fails if the value is not an object or an array holds a `null`.
+	pub fn to_toml(&self) -> Result<String, ConversionError> {
+		let JsonValue::Object(map) = self else {
+			return Err(ConversionError {
+				expected: "object",
+				found: self.type_of(),
+			});
+		};
+		let mut out = String::new();
+		write_table(map, &mut Vec::new(), &mut out)?;
+		Ok(out)
+	}
+}
+
+fn write_table(
+	map: &HashMap<String, JsonValue>,
+	path: &mut Vec<String>,
+	out: &mut String,
+) -> Result<(), ConversionError> {
+	let mut members: Vec<_> =
+		map.iter().filter(|(_, value)| !value.is_null()).collect();
+	members.sort_unstable_by_key(|&(key, _)| key);
+	let (tables, values): (Vec<_>, Vec<_>) =
+		members.into_iter().partition(|(_, value)| is_table(value));
+	for (key, value) in values {
+		write_key(key, out);
+		out.push_str(" = ");
+		write_value(value, out)?;
+		out.push('\n');
+	}
+	for (key, value) in tables {
+		let mut header = String::new();
+		write_key(key, &mut header);
+		path.push(header);
+		let tables = match value {
+			JsonValue::Array(items) => items.iter().collect(),
+			_ => vec![value],
+		};
+		for table in tables {
+			if !out.is_empty() {
+				out.push('\n');
+			}
+			let array = matches!(value, JsonValue::Array(_));
+			out.push_str(if array { "[[" } else { "[" });
+			out.push_str(&path.join("."));
+			out.push_str(if array { "]]\n" } else { "]\n" });
+			if let JsonValue::Object(map) = table {
+				write_table(map, path, out)?;
+			}
+		}
+		path.pop();
+	}
+	Ok(())
+}
+
+/// Whether a member is written as a table or an array of tables rather
+/// than as `key = value`.
+fn is_table(value: &JsonValue) -> bool {
+	match value {
+		JsonValue::Object(_) => true,
+		JsonValue::Array(items) => {
+			!items.is_empty()
+				&& items
+					.iter()
+					.all(|item| matches!(item, JsonValue::Object(_)))
+		}
+		_ => false,
+	}
+}
+
+fn write_key(key: &str, out: &mut String) {
+	let bare = !key.is_empty()
+		&& key
+			.bytes()
+			.all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+	if bare {
+		out.push_str(key);
+	} else {
+		write_string(key, out);
+	}
+}
+
+/// Writes a basic string, escaping like JSON but also `DEL`, which TOML
+/// does not allow unescaped.
+fn write_string(s: &str, out: &mut String) {
+	out.push('"');
+	for (i, part) in s.split('\x7F').enumerate() {
+		if i > 0 {
+			out.push_str("\\u007F");
+		}
+		let _ = write_escaped(out, part);
+	}
+	out.push('"');
+}
+
+fn write_value(
+	value: &JsonValue,
+	out: &mut String,
+) -> Result<(), ConversionError> {
+	match value {
+		JsonValue::Null => {
+			return Err(ConversionError {
+				expected: "non-null value",
+				found: value.type_of(),
+			})
+		}
+		JsonValue::Boolean(b) => {
+			let _ = write!(out, "{b}");
+		}
+		JsonValue::Number(n) => write_number(*n, out),
+		JsonValue::String(s) => write_string(s, out),
+		JsonValue::Array(items) => {
+			out.push('[');
+			for (i, item) in items.iter().enumerate() {
+				if i > 0 {
+					out.push_str(", ");
+				}
+				write_value(item, out)?;
+			}
+			out.push(']');
+		}
+		JsonValue::Object(map) => {
+			let mut members: Vec<_> =
+				map.iter().filter(|(_, value)| !value.is_null()).collect();
+			members.sort_unstable_by_key(|&(key, _)| key);
+			out.push('{');
+			for (i, (key, value)) in members.into_iter().enumerate() {
+				out.push_str(if i > 0 { ", " } else { " " });
+				write_key(key, out);
+				out.push_str(" = ");
+				write_value(value, out)?;
+			}
+			out.push_str(if map.is_empty() { "}" } else { " }" });
+		}
+	}
+	Ok(())
+}
+
+/// Writes integral values within the range of an `i64` as integers and
+/// others as floats, which TOML tells apart.
+fn write_number(n: f64, out: &mut String) {
+	let _ = if n.is_nan() {
+		write!(out, "nan")
+	} else if n.is_infinite() {
+		write!(
+			out,
+			"{}inf",
+			if n < 0.0 { "-" } else { "" }
+		)
+	} else if n.fract() == 0.0
+		&& n.abs() < 2f64.powi(63)
+		&& !(n == 0.0 && n.is_sign_negative())
+	{
+		write!(out, "{}", n as i64)
+	} else {
+		write!(out, "{n:?}")
+	};
+}
+
+/// How deeply tables and values may nest, so hostile input cannot exhaust
+/// the stack.
+const MAX_DEPTH: usize = 512;
+
+/// A table being loaded, remembering how it came to exist so that it is
+/// defined only once.
+struct Table {
+	entries: HashMap<String, Item>,
+	origin: Origin,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Origin {
+	/// As the parent of a table given by a header, which a header of its
+	/// own may still define.
+	Implicit,
+	/// By a header.
+	Header,
+	/// By a dotted key.
+	Dotted,
+}
+
+enum Item {
+	/// A value that cannot be extended: a scalar, an array or an inline
+	/// table.
+	Value(JsonValue),
+	Table(Table),
+	/// An array of tables, appended to by `[[array]]` headers.
+	Tables(Vec<Table>),
+}
+
+impl Table {
+	fn new(origin: Origin) -> Self {
+		Self {
+			entries: HashMap::new(),
+			origin,
+		}
+	}
+
+	fn into_value(self) -> JsonValue {
+		let map = self
+			.entries
+			.into_iter()
+			.map(|(key, item)| {
+				let value = match item {
+					Item::Value(value) => value,
+					Item::Table(table) => table.into_value(),
+					Item::Tables(tables) => JsonValue::Array(
+						tables.into_iter().map(Table::into_value).collect(),
+					),
+				};
+				(key, value)
+			})
+			.collect();
+		JsonValue::Object(Box::new(map))
+	}
+
+	/// The table that the key/value pairs following the header `path`
+	/// belong to.
+	fn at(&mut self, path: &[String]) -> &mut Table {
+		let mut table = self;
+		for key in path {
+			table = match table.entries.get_mut(key) {
+				Some(Item::Table(table)) => table,
+				Some(Item::Tables(tables)) => tables.last_mut().unwrap(),
+				_ => unreachable!("headers define the tables they name"),
+			};
+		}
+		table
+	}
+}
+
+struct Loader<'i> {
+	input: &'i str,
+	pos: usize,
+	/// The number of tables and values being loaded.
+	depth: usize,
+}
+
+impl<'i> Loader<'i> {
+	fn error_at(&self, offset: usize, message: impl Into<String>) -> Error {
+		Error::new(self.input, offset, message.into())
+	}
+
+	fn error(&self, message: impl Into<String>) -> Error {
+		self.error_at(self.pos, message)
+	}
+
+	fn unexpected(&self) -> Error {
+		match self.input[self.pos..].chars().next() {
+			Some(c) => self.error(format!("unexpected character {c:?}")),
+			None => self.error("unexpected end of input"),
+		}
+	}
+
+	fn byte(&self) -> Option<u8> {
+		self.input.as_bytes().get(self.pos).copied()
+	}
+
+	fn rest(&self) -> &'i str {
+		&self.input[self.pos..]
+	}
+
+	fn expect(&mut self, b: u8) -> Result<(), Error> {
+		if self.byte() != Some(b) {
+			return Err(self.unexpected());
+		}
+		self.pos += 1;
+		Ok(())
+	}
+
+	fn skip_spaces(&mut self) {
+		while matches!(self.byte(), Some(b' ' | b'\t')) {
+			self.pos += 1;
+		}
+	}
+
+	fn skip_comment(&mut self) {
+		if self.byte() == Some(b'#') {
+			self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+			if self.input[..self.pos].ends_with('\r') {
+				self.pos -= 1;
+			}
+		}
+	}
+
+	/// Consumes a line break, if one comes next.
+	fn eat_newline(&mut self) -> bool {
+		let len = if self.rest().starts_with("\r\n") {
+			2
+		} else if self.rest().starts_with('\n') {
+			1
+		} else {
+			0
+		};
+		self.pos += len;
+		len > 0
+	}
+
+	/// Skips the rest of the line, which may only hold a comment.
+	fn end_of_line(&mut self) -> Result<(), Error> {
+		self.skip_spaces();
+		self.skip_comment();
+		if !self.eat_newline() && self.pos < self.input.len() {
+			return Err(self.unexpected());
+		}
+		Ok(())
+	}
+
+	/// Skips whitespace, line breaks and comments.
+	fn skip_blank(&mut self) {
+		loop {
+			self.skip_spaces();
+			self.skip_comment();
+			if !self.eat_newline() {
+				return;
+			}
+		}
+	}
+
+	fn document(&mut self) -> Result<JsonValue, Error> {
+		let mut root = Table::new(Origin::Header);
+		let mut current = Vec::new();
+		loop {
+			self.skip_blank();
+			let start = self.pos;
+			match self.byte() {
+				None => return Ok(root.into_value()),
+				Some(b'[') => {
+					let array = self.rest().starts_with("[[");
+					self.pos += if array { 2 } else { 1 };
+					let keys = self.key()?;
+					if keys.len() > MAX_DEPTH {
+						return Err(self
+							.error_at(start, "nesting too deep")
+							.with_kind(ErrorKind::TooDeep));
+					}
+					self.expect(b']')?;
+					if array {
+						self.expect(b']')?;
+					}
+					self.end_of_line()?;
+					self.define(&mut root, &keys, array, start)?;
+					current = keys;
+				}
+				Some(_) => {
+					let keys = self.key()?;
+					self.expect(b'=')?;
+					self.skip_spaces();
+					self.depth = current.len() + keys.len();
+					if self.depth > MAX_DEPTH {
+						return Err(self
+							.error_at(start, "nesting too deep")
+							.with_kind(ErrorKind::TooDeep));
+					}
+					let value = self.value()?;
+					self.end_of_line()?;
+					self.insert(root.at(&current), &keys, value, start)?;
+				}
+			}
+		}
+	}
+
+	/// Defines the table, or appends to the array of tables, named by a
+	/// header.
+	fn define(
+		&self,
+		root: &mut Table,
+		keys: &[String],
+		array: bool,
+		start: usize,
+	) -> Result<(), Error> {
+		let redefined = || {
+			self.error_at(
+				start,
+				format!(
+					"{:?} is already defined",
+					keys.join(".")
+				),
+			)
+		};
+		let (last, parents) = keys.split_last().unwrap();
+		let mut table = root;
+		for key in parents {
+			table =
+				match table.entries.entry(key.clone()).or_insert_with(|| {
+					Item::Table(Table::new(Origin::Implicit))
+				}) {
+					Item::Table(table) => table,
+					Item::Tables(tables) => tables.last_mut().unwrap(),
+					Item::Value(_) => return Err(redefined()),
+				};
+		}
+		match table.entries.get_mut(last) {
+			None => {
+				let item = if array {
+					Item::Tables(vec![Table::new(Origin::Header)])
+				} else {
+					Item::Table(Table::new(Origin::Header))
+				};
+				table.entries.insert(last.clone(), item);
+			}
+			Some(Item::Tables(tables)) if array => {
+				tables.push(Table::new(Origin::Header))
+			}
+			Some(Item::Table(table))
+				if !array && table.origin == Origin::Implicit =>
+			{
+				table.origin = Origin::Header
+			}
+			Some(_) => return Err(redefined()),
+		}
+		Ok(())
+	}
+
+	/// Sets a possibly dotted key in `table`, creating the tables a dotted
+	/// key implies.
+	fn insert(
+		&self,
+		table: &mut Table,
+		keys: &[String],
+		value: JsonValue,
+		start: usize,
+	) -> Result<(), Error> {
+		let redefined = || {
+			self.error_at(
+				start,
+				format!(
+					"{:?} is already defined",
+					keys.join(".")
+				),
+			)
+		};
+		let (last, parents) = keys.split_last().unwrap();
+		let mut table = table;
+		for key in parents {
+			table = match table
+				.entries
+				.entry(key.clone())
+				.or_insert_with(|| Item::Table(Table::new(Origin::Dotted)))
+			{
+				Item::Table(table) if table.origin == Origin::Dotted => table,
+				_ => return Err(redefined()),
+			};
+		}
+		if table.entries.contains_key(last) {
+			return Err(redefined());
+		}
+		table.entries.insert(last.clone(), Item::Value(value));
+		Ok(())
+	}
+
+	/// Reads a possibly dotted key and the whitespace around it.
+	fn key(&mut self) -> Result<Vec<String>, Error> {
+		let mut keys = Vec::new();
+		loop {
+			self.skip_spaces();
+			keys.push(match self.byte() {
+				Some(b'"') if !self.rest().starts_with("\"\"\"") => {
+					self.basic_string()?
+				}
+				Some(b'\'') if !self.rest().starts_with("'''") => {
+					self.literal_string()?
+				}
+				_ => {
+					let len = self
+						.rest()
+						.find(|c: char| {
+							!(c.is_ascii_alphanumeric() || c == '_' || c == '-')
+						})
+						.unwrap_or(self.rest().len());
+					if len == 0 {
+						return Err(self.unexpected());
+					}
+					self.pos += len;
+					self.input[self.pos - len..self.pos].to_owned()
+				}
+			});
+			self.skip_spaces();
+			if self.byte() != Some(b'.') {
+				return Ok(keys);
+			}
+			self.pos += 1;
+		}
+	}
+
+	fn value(&mut self) -> Result<JsonValue, Error> {
+		if self.depth == MAX_DEPTH {
+			return Err(self
+				.error("nesting too deep")
+				.with_kind(ErrorKind::TooDeep));
+		}
+		self.depth += 1;
+		let value = match self.byte() {
+			Some(b'"') if self.rest().starts_with("\"\"\"") => {
+				self.multiline_basic_string().map(JsonValue::String)
+			}
+			Some(b'"') => self.basic_string().map(JsonValue::String),
+			Some(b'\'') if self.rest().starts_with("'''") => {
+				self.multiline_literal_string().map(JsonValue::String)
+			}
+			Some(b'\'') => self.literal_string().map(JsonValue::String),
+			Some(b'[') => self.array(),
+			Some(b'{') => self.inline_table(),
+			_ => self.scalar(),
+		};
+		self.depth -= 1;
+		value
+	}
+
+	fn array(&mut self) -> Result<JsonValue, Error> {
+		self.pos += 1;
+		let mut items = Vec::new();
+		loop {
+			self.skip_blank();
+			if self.byte() == Some(b']') {
+				break;
+			}
+			items.push(self.value()?);
+			self.skip_blank();
+			match self.byte() {
+				Some(b',') => self.pos += 1,
+				Some(b']') => break,
+				_ => return Err(self.unexpected()),
+			}
+		}
+		self.pos += 1;
+		Ok(JsonValue::Array(items))
+	}
+
+	fn inline_table(&mut self) -> Result<JsonValue, Error> {
+		self.pos += 1;
+		let mut table = Table::new(Origin::Dotted);
+		self.skip_spaces();
+		if self.byte() == Some(b'}') {
+			self.pos += 1;
+			return Ok(table.into_value());
+		}
+		loop {
+			let start = self.pos;
+			let keys = self.key()?;
+			if self.depth + keys.len() > MAX_DEPTH {
+				return Err(self
+					.error_at(start, "nesting too deep")
+					.with_kind(ErrorKind::TooDeep));
+			}
+			self.expect(b'=')?;
+			self.skip_spaces();
+			let value = self.value()?;
+			self.insert(&mut table, &keys, value, start)?;
+			self.skip_spaces();
+			match self.byte() {
+				Some(b',') => self.pos += 1,
+				Some(b'}') => break,
+				_ => return Err(self.unexpected()),
+			}
+			self.skip_spaces();
+		}
+		self.pos += 1;
+		Ok(table.into_value())
+	}
+
+	/// Reads a boolean, number or date-time.
+	fn scalar(&mut self) -> Result<JsonValue, Error> {
+		let start = self.pos;
+		let token_len = |s: &str| {
+			s.find(|c| {
+				matches!(
+					c,
+					' ' | '\t' | '\r' | '\n' | ',' | ']' | '}' | '#'
+				)
+			})
+			.unwrap_or(s.len())
+		};
+		let mut len = token_len(self.rest());
+		// A date and a time may be separated by a space.
+		let after = &self.rest()[len..];
+		if len == 10
+			&& date(&self.rest()[..len]) == Some("")
+			&& after.starts_with(' ')
+			&& time(&after[1..]).is_some()
+		{
+			len += 1 + token_len(&after[1..]);
+		}
+		let token = &self.rest()[..len];
+		if token.is_empty() {
+			return Err(self.unexpected());
+		}
+		self.pos += len;
+		let value = match token {
+			"true" => JsonValue::Boolean(true),
+			"false" => JsonValue::Boolean(false),
+			_ if is_date_time(token) => JsonValue::String(token.to_owned()),
+			_ => match number(token) {
+				Some(n) if n.is_finite() => JsonValue::Number(n),
+				Some(_) => {
+					return Err(self.error_at(
+						start,
+						format!("{token:?} has no JSON equivalent"),
+					))
+				}
+				None => {
+					return Err(self.error_at(
+						start,
+						format!("invalid value {token:?}"),
+					))
+				}
+			},
+		};
+		Ok(value)
+	}
+
+	fn basic_string(&mut self) -> Result<String, Error> {
+		let start = self.pos;
+		self.pos += 1;
+		let mut text = String::new();
+		loop {
+			match self.rest().chars().next() {
+				Some('"') => break,
+				Some('\\') => self.escape(&mut text)?,
+				None | Some('\r' | '\n') => {
+					return Err(self.error_at(start, "unterminated string"))
+				}
+				Some(c) if is_control(c) => {
+					return Err(self.error("control character in string"))
+				}
+				Some(c) => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+			}
+		}
+		self.pos += 1;
+		Ok(text)
+	}
+
+	fn multiline_basic_string(&mut self) -> Result<String, Error> {
+		let start = self.pos;
+		self.pos += 3;
+		self.eat_newline();
+		let mut text = String::new();
+		loop {
+			if self.rest().starts_with("\"\"\"") {
+				return self.close(text, '"');
+			}
+			if self.eat_newline() {
+				text.push('\n');
+				continue;
+			}
+			match self.rest().chars().next() {
+				Some('\\') => {
+					// A backslash ending a line trims the whitespace after it.
+					let after =
+						self.rest()[1..].trim_start_matches([' ', '\t']);
+					if after.starts_with('\n') || after.starts_with("\r\n") {
+						self.pos = self.input.len() - after.len();
+						self.skip_blank_lines();
+					} else {
+						self.escape(&mut text)?;
+					}
+				}
+				None => return Err(self.error_at(start, "unterminated string")),
+				Some(c) if is_control(c) => {
+					return Err(self.error("control character in string"))
+				}
+				Some(c) => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+			}
+		}
+	}
+
+	fn skip_blank_lines(&mut self) {
+		loop {
+			self.skip_spaces();
+			if !self.eat_newline() {
+				return;
+			}
+		}
+	}
+
+	fn literal_string(&mut self) -> Result<String, Error> {
+		let start = self.pos;
+		self.pos += 1;
+		let len = self
+			.rest()
+			.find(['\'', '\n', '\r'])
+			.filter(|&len| self.rest()[len..].starts_with('\''))
+			.ok_or_else(|| self.error_at(start, "unterminated string"))?;
+		let text = &self.rest()[..len];
+		if let Some(at) = text.find(is_control) {
+			return Err(self.error_at(
+				self.pos + at,
+				"control character in string",
+			));
+		}
+		self.pos += len + 1;
+		Ok(text.to_owned())
+	}
+
+	fn multiline_literal_string(&mut self) -> Result<String, Error> {
+		let start = self.pos;
+		self.pos += 3;
+		self.eat_newline();
+		let mut text = String::new();
+		loop {
+			if self.rest().starts_with("'''") {
+				return self.close(text, '\'');
+			}
+			if self.eat_newline() {
+				text.push('\n');
+				continue;
+			}
+			match self.rest().chars().next() {
+				None => return Err(self.error_at(start, "unterminated string")),
+				Some(c) if is_control(c) => {
+					return Err(self.error("control character in string"))
+				}
+				Some(c) => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+			}
+		}
+	}
+
+	/// Ends a multi-line string at a run of three to five `quote`s, the
+	/// first of which may belong to the string.
+	fn close(
+		&mut self,
+		mut text: String,
+		quote: char,
+	) -> Result<String, Error> {
+		let run =
+			self.rest().len() - self.rest().trim_start_matches(quote).len();
+		if run > 5 {
+			return Err(self.error_at(self.pos + 5, "unexpected quote"));
+		}
+		text.extend(std::iter::repeat_n(quote, run - 3));
+		self.pos += run;
+		Ok(text)
+	}
+
+	/// Reads an escape sequence in a basic string.
+	fn escape(&mut self, text: &mut String) -> Result<(), Error> {
+		let start = self.pos;
+		let escaped = self.input.as_bytes().get(start + 1).copied();
+		self.pos += 2;
+		let digits = match escaped {
+			Some(b'u') => 4,
+			Some(b'U') => 8,
+			_ => 0,
+		};
+		let c = match escaped {
+			Some(b'b') => Some('\x08'),
+			Some(b't') => Some('\t'),
+			Some(b'n') => Some('\n'),
+			Some(b'f') => Some('\x0C'),
+			Some(b'r') => Some('\r'),
+			Some(b'"') => Some('"'),
+			Some(b'\\') => Some('\\'),
+			Some(b'u' | b'U') => self
+				.rest()
+				.get(..digits)
+				.filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+				.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+				.and_then(char::from_u32),
+			_ => None,
+		};
+		let c =
+			c.ok_or_else(|| self.error_at(start, "invalid escape sequence"))?;
+		self.pos += digits;
+		text.push(c);
+		Ok(())
+	}
+}
+
+/// Whether `c` is a control character that strings must escape.
+fn is_control(c: char) -> bool {
+	c != '\t' && (c < ' ' || c == '\x7F')
+}
+
+/// Parses a TOML integer or float.
+fn number(token: &str) -> Option<f64> {
+	for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+		if let Some(digits) = token.strip_prefix(prefix) {
+			let digits = underscored(digits, radix)?;
+			return i64::from_str_radix(&digits, radix).ok().map(|n| n as f64);
+		}
+	}
+	let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+	let negative = token.starts_with('-');
+	match unsigned {
+		"inf" if negative => return Some(f64::NEG_INFINITY),
+		"inf" => return Some(f64::INFINITY),
+		"nan" => return Some(f64::NAN),
+		_ => {}
+	}
+	let (mantissa, exponent) = unsigned
+		.split_once(['e', 'E'])
+		.map_or((unsigned, None), |(m, e)| (m, Some(e)));
+	let (whole, fraction) = mantissa
+		.split_once('.')
+		.map_or((mantissa, None), |(w, f)| (w, Some(f)));
+	let whole = underscored(whole, 10)?;
+	if whole.len() > 1 && whole.starts_with('0') {
+		return None;
+	}
+	let sign = if negative { "-" } else { "" };
+	if fraction.is_none() && exponent.is_none() {
+		return format!("{sign}{whole}")
+			.parse::<i64>()
+			.ok()
+			.map(|n| n as f64);
+	}
+	let mut float = format!("{sign}{whole}");
+	if let Some(fraction) = fraction {
+		float.push('.');
+		float.push_str(&underscored(fraction, 10)?);
+	}
+	if let Some(exponent) = exponent {
+		let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+		float.push('e');
+		if exponent.starts_with('-') {
+			float.push('-');
+		}
+		float.push_str(&underscored(digits, 10)?);
+	}
+	float.parse().ok()
+}
+
+/// Removes the underscores from `digits`, each of which must sit between
+/// two digits of the given radix.
+fn underscored(digits: &str, radix: u32) -> Option<String> {
+	let mut clean = String::with_capacity(digits.len());
+	for group in digits.split('_') {
+		if group.is_empty() || !group.chars().all(|c| c.is_digit(radix)) {
+			return None;
+		}
+		clean.push_str(group);
+	}
+	Some(clean)
+}
+
+/// Whether `token` is an offset or local date-time, a local date or a local
+/// time.
+fn is_date_time(token: &str) -> bool {
+	let Some(rest) = date(token) else {
+		return time(token) == Some("");
+	};
+	if rest.is_empty() {
+		return true;
+	}
+	let Some(rest) = rest.strip_prefix(['T', 't', ' ']).and_then(time) else {
+		return false;
+	};
+	match rest.as_bytes() {
+		[] | [b'Z' | b'z'] => true,
+		[b'+' | b'-', ..] => {
+			two_digits(&rest[1..], 23)
+				.and_then(|rest| rest.strip_prefix(':'))
+				.and_then(|rest| two_digits(rest, 59))
+				== Some("")
+		}
+		_ => false,
+	}
+}
+
+/// Reads a `YYYY-MM-DD` date, returning what follows it.
+fn date(s: &str) -> Option<&str> {
+	s.get(..4)
+		.filter(|year| year.bytes().all(|b| b.is_ascii_digit()))?;
+	let rest = s[4..].strip_prefix('-')?;
+	let rest = two_digits(rest, 12).filter(|_| &s[5..7] != "00")?;
+	let rest = rest.strip_prefix('-')?;
+	two_digits(rest, 31).filter(|_| &s[8..10] != "00")
+}
+
+/// Reads an `HH:MM:SS` time with optional fractional seconds, returning
+/// what follows it.
+fn time(s: &str) -> Option<&str> {
+	let rest = two_digits(s, 23)?.strip_prefix(':')?;
+	let rest = two_digits(rest, 59)?.strip_prefix(':')?;
+	let rest = two_digits(rest, 60)?;
+	match rest.strip_prefix('.') {
+		Some(fraction) => {
+			let digits = fraction.len()
+				- fraction
+					.trim_start_matches(|c: char| c.is_ascii_digit())
+					.len();
+			(digits > 0).then(|| &fraction[digits..])
+		}
+		None => Some(rest),
+	}
+}
+
+/// Reads two digits making a number no greater than `max`, returning what
+/// follows them.
+fn two_digits(s: &str, max: u8) -> Option<&str> {
+	let digits = s.get(..2)?.as_bytes();
+	if !digits.iter().all(u8::is_ascii_digit) {
+		return None;
+	}
+	((digits[0] - b'0') * 10 + digits[1] - b'0' <= max).then(|| &s[2..])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[track_caller]
+	fn check(toml: &str, json: &str) {
+		assert_eq!(
+			JsonValue::from_toml(toml),
+			Ok(parse(json).unwrap()),
+			"{toml}"
+		);
+	}
+
+	#[test]
+	fn loads_documents() {
+		check(
+			r#"
+title = "example" # comment
+owner.name = 'Tom'
+dob = 1979-05-27T07:32:00Z
+
+[database]
+ports = [8000, 8001, 0x1F]
+limits = { max = 5_000, ratio = 0.5, on = true }
+
+[[products]]
+name = "hammer"
+
+[[products]]
+name = """
+nail"""
+"#,
+			r#"{"title": "example", "owner": {"name": "Tom"},
+			"dob": "1979-05-27T07:32:00Z",
+			"database": {"ports": [8000, 8001, 31],
+				"limits": {"max": 5000, "ratio": 0.5, "on": true}},
+			"products": [{"name": "hammer"}, {"name": "nail"}]}"#,
+		);
+		check("", "{}");
+	}
+
+	#[test]
+	fn round_trips() {
+		let value = parse(
+			r#"{"a": 1, "b": "two\n\"quoted\"", "c": [1.5, -2, true],
+			"d": {"e": {"f": []}, "g": [{"h": 1}, {"h": 2}]},
+			"odd key": {"x": [{"y": [1]}]}}"#,
+		)
+		.unwrap();
+		let toml = value.to_toml().unwrap();
+		assert_eq!(
+			JsonValue::from_toml(&toml),
+			Ok(value),
+			"{toml}"
+		);
+		assert!(parse("[1]").unwrap().to_toml().is_err());
+		assert!(parse(r#"{"a": [null]}"#).unwrap().to_toml().is_err());
+	}
+
+	#[test]
+	fn rejects_malformed_documents() {
+		for toml in [
+			"a = 1\na = 2",
+			"[t]\n[t]",
+			"a.b = 1\n[a]\nb = 2",
+			"a = inf",
+			"a = nan",
+			"a = [1, 2",
+			"a = { b = 1",
+			"a = \"unterminated",
+			"a = 1 2",
+			"= 1",
+		] {
+			assert!(
+				JsonValue::from_toml(toml).is_err(),
+				"{toml:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let n = 100_000;
+		let key = |depth: usize| vec!["a"; depth].join(".");
+		for toml in [
+			format!("a = {}", "[".repeat(n)),
+			format!("a = {}", "{a = ".repeat(n)),
+			format!("{} = 1", key(n)),
+			format!("[{}]", key(n)),
+			format!("[{}]\n{} = 1", key(300), key(300)),
+		] {
+			let error = JsonValue::from_toml(&toml).unwrap_err();
+			assert_eq!(error.kind(), ErrorKind::TooDeep);
+		}
+		// The key and the number in the innermost array take a level each.
+		let depth = MAX_DEPTH - 2;
+		let arrays = format!(
+			"a = {}1{}",
+			"[".repeat(depth),
+			"]".repeat(depth)
+		);
+		assert!(JsonValue::from_toml(&arrays).is_ok());
+		assert!(JsonValue::from_toml(&format!("[{}]", key(depth))).is_ok());
+	}
+}
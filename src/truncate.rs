@@ -0,0 +1,372 @@
+//! Abbreviating large values to a size that fits in a log line.
+
+use std::{
+	collections::HashMap,
+	fmt::{self, Write},
+};
+
+use crate::{
+	ser::{write_escaped, write_number, write_string},
+	JsonValue,
+};
+
+impl JsonValue {
+	/// An abbreviated copy of the value, of at most `max_nodes` values
+	/// (counting arrays, objects and scalars alike, and at least the root)
+	/// and no string value longer than `max_string_len` characters, for
+	/// logging a payload without dumping all of it.
+	///
+	/// Cut strings end in `…`; keys are kept whole, so that no two members
+	/// run together. An array that was cut ends in an item such as
+	/// `"… 9995 more items"`, and an object that was cut has an extra
+	/// member such as `"…": "12 more members"`, under as many `…` as it
+	/// takes for a key the object does not have. Members are kept in key
+	/// order, so the same value is always abbreviated the same way. Values
+	/// are taken depth-first, so the first items of an array are kept whole
+	/// before any of the later ones.
+	pub fn truncate_for_log(
+		&self,
+		max_nodes: usize,
+		max_string_len: usize,
+	) -> JsonValue {
+		let mut budget = max_nodes.max(1);
+		truncate(self, &mut budget, max_string_len)
+	}
+
+	/// A short rendering of the value for logs and test failures, as
+	/// [`Summary`] describes; the `Debug` output of a large document is
+	/// too long to read.
+	///
+	/// ```text
+	/// log::debug!("received {}", body.fmt_compact().max_depth(2));
+	/// ```
+	pub fn fmt_compact(&self) -> Summary<'_> {
+		Summary {
+			value: self,
+			max_depth: 3,
+			max_items: 10,
+			max_string_len: 40,
+		}
+	}
+}
+
+/// A short rendering of a [`JsonValue`] for logs and assertion messages,
+/// made by [`JsonValue::fmt_compact`].
+///
+/// Formats like compact JSON with members in key order, but strings past
+/// [`max_string_len`](Self::max_string_len) characters are cut with `…`,
+/// arrays past [`max_items`](Self::max_items) items end in a count of the
+/// rest, as in `[1,2,… 98 more]`, and containers deeper than
+/// [`max_depth`](Self::max_depth) are summarized in a count, as in
+/// `{… 3 members}`. `Debug` formats the same way, so `{:?}` can be used
+/// where the derived output would run for pages.
+#[derive(Clone, Copy)]
+pub struct Summary<'a> {
+	value: &'a JsonValue,
+	max_depth: usize,
+	max_items: usize,
+	max_string_len: usize,
+}
+
+impl Summary<'_> {
+	/// How many levels of arrays and objects to show; 3 by default.
+	pub fn max_depth(mut self, depth: usize) -> Self {
+		self.max_depth = depth;
+		self
+	}
+
+	/// How many items or members of a container to show; 10 by default.
+	pub fn max_items(mut self, items: usize) -> Self {
+		self.max_items = items;
+		self
+	}
+
+	/// How many characters of a string or key to show; 40 by default.
+	pub fn max_string_len(mut self, len: usize) -> Self {
+		self.max_string_len = len;
+		self
+	}
+
+	fn write(
+		&self,
+		f: &mut fmt::Formatter<'_>,
+		value: &JsonValue,
+		depth: usize,
+	) -> fmt::Result {
+		match value {
+			JsonValue::Null => f.write_str("null"),
+			JsonValue::Boolean(b) => write!(f, "{b}"),
+			JsonValue::Number(n) => write_number(f, *n),
+			JsonValue::String(s) => self.string(f, s),
+			JsonValue::Array(items) if depth == self.max_depth => {
+				write!(f, "[… {}]", count(items.len(), "item"))
+			}
+			JsonValue::Object(map) if depth == self.max_depth => {
+				write!(
+					f,
+					"{{… {}}}",
+					count(map.len(), "member")
+				)
+			}
+			JsonValue::Array(items) => {
+				f.write_char('[')?;
+				for (i, item) in items.iter().take(self.max_items).enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					self.write(f, item, depth + 1)?;
+				}
+				self.rest(f, items.len())?;
+				f.write_char(']')
+			}
+			JsonValue::Object(map) => {
+				let mut members: Vec<_> = map.iter().collect();
+				members.sort_unstable_by_key(|&(key, _)| key);
+				f.write_char('{')?;
+				for (i, (key, value)) in
+					members.into_iter().take(self.max_items).enumerate()
+				{
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					self.string(f, key)?;
+					f.write_char(':')?;
+					self.write(f, value, depth + 1)?;
+				}
+				self.rest(f, map.len())?;
+				f.write_char('}')
+			}
+		}
+	}
+
+	fn string(&self, f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+		match s.char_indices().nth(self.max_string_len) {
+			Some((end, _)) => {
+				f.write_char('"')?;
+				write_escaped(f, &s[..end])?;
+				f.write_str("…\"")
+			}
+			None => write_string(f, s),
+		}
+	}
+
+	/// Ends a container of `len` items with a count of those not shown.
+	fn rest(&self, f: &mut fmt::Formatter<'_>, len: usize) -> fmt::Result {
+		match len.checked_sub(self.max_items) {
+			Some(more) if more > 0 => {
+				if self.max_items > 0 {
+					f.write_char(',')?;
+				}
+				write!(f, "… {more} more")
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+impl fmt::Display for Summary<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.write(f, self.value, 0)
+	}
+}
+
+impl fmt::Debug for Summary<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+
+/// Copies `value`, which is counted against `budget` by the caller, as far
+/// as `budget` allows.
+fn truncate(
+	value: &JsonValue,
+	budget: &mut usize,
+	max_string_len: usize,
+) -> JsonValue {
+	*budget -= 1;
+	match value {
+		JsonValue::String(s) => match s.char_indices().nth(max_string_len) {
+			Some((end, _)) => JsonValue::String(format!("{}…", &s[..end])),
+			None => value.clone(),
+		},
+		JsonValue::Array(items) => {
+			let mut kept = Vec::new();
+			for item in items {
+				if *budget == 0 {
+					break;
+				}
+				kept.push(truncate(item, budget, max_string_len));
+			}
+			let more = items.len() - kept.len();
+			if more > 0 {
+				kept.push(JsonValue::String(format!(
+					"… {}",
+					count(more, "more item")
+				)));
+			}
+			JsonValue::Array(kept)
+		}
+		JsonValue::Object(map) => {
+			let mut members: Vec<_> = map.iter().collect();
+			members.sort_unstable_by_key(|&(key, _)| key);
+			let mut kept = HashMap::new();
+			let mut members = members.into_iter();
+			while *budget > 0 {
+				let Some((key, value)) = members.next() else {
+					break;
+				};
+				kept.insert(
+					key.clone(),
+					truncate(value, budget, max_string_len),
+				);
+			}
+			let more = members.len();
+			if more > 0 {
+				let mut marker = "…".to_owned();
+				while map.contains_key(&marker) {
+					marker.push('…');
+				}
+				kept.insert(
+					marker,
+					JsonValue::String(count(more, "more member")),
+				);
+			}
+			JsonValue::Object(Box::new(kept))
+		}
+		_ => value.clone(),
+	}
+}
+
+/// `"1 item"`, `"2 items"` and so on.
+fn count(n: usize, noun: &str) -> String {
+	let s = if n == 1 { "" } else { "s" };
+	format!("{n} {noun}{s}")
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{parse, JsonValue};
+
+	#[test]
+	fn truncate_for_log() {
+		let value =
+			parse(r#"{"a": "abcdef", "b": [1, 2, 3, 4], "c": null}"#).unwrap();
+		assert_eq!(
+			value.truncate_for_log(5, 3),
+			parse(
+				r#"{"a": "abc…", "b": [1, 2, "… 2 more items"], "…": "1 more member"}"#
+			)
+			.unwrap()
+		);
+		assert_eq!(
+			value.truncate_for_log(1, 3),
+			parse(r#"{"…": "3 more members"}"#).unwrap()
+		);
+	}
+
+	#[test]
+	fn truncate_for_log_keeps_keys_apart() {
+		let value = parse(
+			r#"{"prefix_one": 1, "prefix_two": 2, "…": 3, "……": 4, "z": 5}"#,
+		)
+		.unwrap();
+		let truncated = value.truncate_for_log(5, 3);
+		let map = truncated.as_object().unwrap();
+		assert_eq!(map.len(), 5);
+		assert_eq!(
+			map.get("prefix_one"),
+			Some(&JsonValue::Number(1.0))
+		);
+		assert_eq!(
+			map.get("prefix_two"),
+			Some(&JsonValue::Number(2.0))
+		);
+		assert_eq!(
+			map.get("…"),
+			Some(&JsonValue::Number(3.0))
+		);
+		assert_eq!(
+			map.get("z"),
+			Some(&JsonValue::Number(5.0))
+		);
+		assert_eq!(
+			map.get("………"),
+			Some(&JsonValue::String(
+				"1 more member".into()
+			))
+		);
+	}
+
+	#[test]
+	fn fmt_compact() {
+		let value =
+			parse(r#"{"b": [1, 2, 3], "a": {"x": {"y": {"z": 1}}}}"#).unwrap();
+		assert_eq!(
+			value.fmt_compact().max_items(2).to_string(),
+			r#"{"a":{"x":{"y":{… 1 member}}},"b":[1,2,… 1 more]}"#
+		);
+		assert_eq!(
+			parse(r#""abcdef""#)
+				.unwrap()
+				.fmt_compact()
+				.max_string_len(2)
+				.to_string(),
+			r#""ab…""#
+		);
+	}
+
+	#[test]
+	fn fmt_compact_defaults() {
+		let items: Vec<_> = (0..25).map(JsonValue::from).collect();
+		let value = JsonValue::Array(vec![
+			JsonValue::Array(items),
+			JsonValue::String("x".repeat(50)),
+			parse("[[[[1, 2]]]]").unwrap(),
+		]);
+		let summary = value.fmt_compact();
+		assert_eq!(
+			summary.to_string(),
+			format!(
+				r#"[[0,1,2,3,4,5,6,7,8,9,… 15 more],"{}…",[[[… 1 item]]]]"#,
+				"x".repeat(40)
+			)
+		);
+		assert_eq!(
+			format!("{summary:?}"),
+			summary.to_string()
+		);
+	}
+
+	#[test]
+	fn fmt_compact_limits() {
+		let value =
+			parse(r#"{"a key that is long": "line\nbreak", "b": []}"#).unwrap();
+		assert_eq!(
+			value.fmt_compact().max_string_len(5).to_string(),
+			r#"{"a key…":"line\n…","b":[]}"#
+		);
+		assert_eq!(
+			value.fmt_compact().max_items(0).to_string(),
+			"{… 2 more}"
+		);
+		assert_eq!(
+			value.fmt_compact().max_depth(0).to_string(),
+			"{… 2 members}"
+		);
+		assert_eq!(
+			parse("[[1]]")
+				.unwrap()
+				.fmt_compact()
+				.max_depth(1)
+				.to_string(),
+			"[[… 1 item]]"
+		);
+		assert_eq!(
+			JsonValue::Number(1.5)
+				.fmt_compact()
+				.max_depth(0)
+				.to_string(),
+			"1.5"
+		);
+	}
+}
@@ -0,0 +1,321 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+	Null,
+	Boolean(bool),
+	/// Negative zero keeps its sign from parsing to writing, as `-0`, but
+	/// is equal to `0` under `==` and in the order values are sorted by, as
+	/// in IEEE 754 and JavaScript; [`JsonValue::is_negative_zero`] tells
+	/// them apart.
+	Number(f64),
+	String(String),
+	Array(Vec<JsonValue>),
+	/// Boxed because a map is twice the size of the other variants, and
+	/// every node of a tree would pay for it.
+	Object(Box<HashMap<String, JsonValue>>),
+}
+
+// `examples/memory.rs` measures what this saves on a whole tree.
+const _: () = assert!(std::mem::size_of::<JsonValue>() == 32);
+
+/// The type of a [`JsonValue`], without its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JsonType {
+	Null,
+	Bool,
+	Number,
+	String,
+	Array,
+	Object,
+}
+
+impl JsonType {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			JsonType::Null => "null",
+			JsonType::Bool => "boolean",
+			JsonType::Number => "number",
+			JsonType::String => "string",
+			JsonType::Array => "array",
+			JsonType::Object => "object",
+		}
+	}
+}
+
+impl fmt::Display for JsonType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl JsonValue {
+	pub fn type_of(&self) -> JsonType {
+		match self {
+			JsonValue::Null => JsonType::Null,
+			JsonValue::Boolean(_) => JsonType::Bool,
+			JsonValue::Number(_) => JsonType::Number,
+			JsonValue::String(_) => JsonType::String,
+			JsonValue::Array(_) => JsonType::Array,
+			JsonValue::Object(_) => JsonType::Object,
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		matches!(self, JsonValue::Null)
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			JsonValue::Boolean(b) => Some(*b),
+			_ => None,
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match self {
+			JsonValue::Number(n) => Some(*n),
+			_ => None,
+		}
+	}
+
+	/// Whether the value is the number `-0`, which is otherwise equal to
+	/// `0`.
+	pub fn is_negative_zero(&self) -> bool {
+		matches!(self, JsonValue::Number(n) if *n == 0.0 && n.is_sign_negative())
+	}
+
+	/// Returns the number as an `i64` if it is integral and in range.
+	pub fn as_i64(&self) -> Option<i64> {
+		// `i64::MAX as f64` rounds up to 2^63, the first number out of
+		// range, and so does `u64::MAX as f64` to 2^64 below.
+		self.as_f64()
+			.filter(|n| {
+				n.fract() == 0.0
+					&& *n >= i64::MIN as f64
+					&& *n < i64::MAX as f64
+			})
+			.map(|n| n as i64)
+	}
+
+	/// Returns the number as a `u64` if it is integral and in range.
+	pub fn as_u64(&self) -> Option<u64> {
+		self.as_f64()
+			.filter(|n| n.fract() == 0.0 && *n >= 0.0 && *n < u64::MAX as f64)
+			.map(|n| n as u64)
+	}
+
+	/// Reads the number as a `T`, such as `f32` or a decimal type, or
+	/// `None` if it is not a number or `T` does not accept it.
+	///
+	/// The number is read from the shortest text that is the same `f64`;
+	/// [`NumberLiterals::number_as`](crate::NumberLiterals::number_as)
+	/// reads it from the literal as written, so nothing is lost to an `f64`
+	/// on the way.
+	pub fn number_as<T: FromStr>(&self) -> Option<T> {
+		match self {
+			JsonValue::Number(n) if n.is_finite() => n.to_string().parse().ok(),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			JsonValue::String(s) => Some(s),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+		match self {
+			JsonValue::Array(items) => Some(items),
+			_ => None,
+		}
+	}
+
+	pub fn as_array_mut(&mut self) -> Option<&mut Vec<JsonValue>> {
+		match self {
+			JsonValue::Array(items) => Some(items),
+			_ => None,
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&HashMap<String, JsonValue>> {
+		match self {
+			JsonValue::Object(map) => Some(map),
+			_ => None,
+		}
+	}
+
+	pub fn as_object_mut(&mut self) -> Option<&mut HashMap<String, JsonValue>> {
+		match self {
+			JsonValue::Object(map) => Some(map),
+			_ => None,
+		}
+	}
+
+	/// Looks up `key` if this is an object.
+	pub fn get(&self, key: &str) -> Option<&JsonValue> {
+		self.as_object()?.get(key)
+	}
+
+	pub fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+		self.as_object_mut()?.get_mut(key)
+	}
+
+	/// Like [`JsonValue::get`], but compares keys ignoring ASCII case.
+	///
+	/// An exact match wins; among several keys differing only in case, the
+	/// lexicographically smallest is used.
+	pub fn get_ignore_ascii_case(&self, key: &str) -> Option<&JsonValue> {
+		let map = self.as_object()?;
+		map.get(key).or_else(|| {
+			map.iter()
+				.filter(|(k, _)| k.eq_ignore_ascii_case(key))
+				.min_by(|(a, _), (b, _)| a.cmp(b))
+				.map(|(_, value)| value)
+		})
+	}
+
+	// The `get_*_or` accessors fall back to `default` when the key is
+	// missing or holds a value of another type.
+
+	pub fn get_bool_or(&self, key: &str, default: bool) -> bool {
+		self.get(key)
+			.and_then(JsonValue::as_bool)
+			.unwrap_or(default)
+	}
+
+	pub fn get_f64_or(&self, key: &str, default: f64) -> f64 {
+		self.get(key).and_then(JsonValue::as_f64).unwrap_or(default)
+	}
+
+	pub fn get_i64_or(&self, key: &str, default: i64) -> i64 {
+		self.get(key).and_then(JsonValue::as_i64).unwrap_or(default)
+	}
+
+	pub fn get_u64_or(&self, key: &str, default: u64) -> u64 {
+		self.get(key).and_then(JsonValue::as_u64).unwrap_or(default)
+	}
+
+	pub fn get_str_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+		self.get(key).and_then(JsonValue::as_str).unwrap_or(default)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn integer_bounds() {
+		let number = JsonValue::Number;
+		assert_eq!(
+			number(-9_223_372_036_854_775_808.0).as_i64(),
+			Some(i64::MIN)
+		);
+		assert_eq!(
+			number(9_223_372_036_854_775_808.0).as_i64(),
+			None
+		);
+		assert_eq!(
+			number(9_223_372_036_854_774_784.0).as_i64(),
+			Some(i64::MAX - 1023)
+		);
+		assert_eq!(
+			number(18_446_744_073_709_551_616.0).as_u64(),
+			None
+		);
+		assert_eq!(
+			number(18_446_744_073_709_549_568.0).as_u64(),
+			Some(u64::MAX - 2047)
+		);
+		assert_eq!(number(-1.0).as_u64(), None);
+		assert_eq!(number(1.5).as_i64(), None);
+		assert_eq!(number(f64::NAN).as_i64(), None);
+	}
+
+	#[test]
+	fn types_are_named_as_in_json() {
+		let types = [
+			(JsonValue::Null, JsonType::Null, "null"),
+			(
+				JsonValue::Boolean(false),
+				JsonType::Bool,
+				"boolean",
+			),
+			(
+				JsonValue::Number(0.0),
+				JsonType::Number,
+				"number",
+			),
+			(
+				JsonValue::String(String::new()),
+				JsonType::String,
+				"string",
+			),
+			(
+				JsonValue::Array(Vec::new()),
+				JsonType::Array,
+				"array",
+			),
+			(
+				JsonValue::Object(Box::default()),
+				JsonType::Object,
+				"object",
+			),
+		];
+		for (value, ty, name) in types {
+			assert_eq!(value.type_of(), ty);
+			assert_eq!(ty.as_str(), name);
+			assert_eq!(ty.to_string(), name);
+		}
+		let error = f64::try_from(JsonValue::from("1")).unwrap_err();
+		assert_eq!(error.found, JsonType::String);
+	}
+
+	#[test]
+	fn keys_may_be_looked_up_ignoring_case() {
+		let value = crate::parse(r#"{"Name": 1, "NAME": 2, "id": 3}"#).unwrap();
+		assert_eq!(
+			value.get_ignore_ascii_case("NAME"),
+			Some(&2.into())
+		);
+		assert_eq!(
+			value.get_ignore_ascii_case("name"),
+			Some(&2.into())
+		);
+		assert_eq!(
+			value.get_ignore_ascii_case("ID"),
+			Some(&3.into())
+		);
+		assert_eq!(
+			value.get_ignore_ascii_case("missing"),
+			None
+		);
+		assert_eq!(
+			JsonValue::Null.get_ignore_ascii_case("id"),
+			None
+		);
+	}
+
+	#[test]
+	fn negative_zero_keeps_its_sign() {
+		for text in ["-0", "-0.0", "-0e5"] {
+			let value = crate::parse(text).unwrap();
+			assert!(value.is_negative_zero(), "{text}");
+			assert_eq!(value.to_string(), "-0");
+			assert_eq!(value, JsonValue::Number(0.0));
+			assert!(crate::parse(&value.to_string())
+				.unwrap()
+				.is_negative_zero());
+		}
+		assert!(!JsonValue::Number(0.0).is_negative_zero());
+		assert!(!JsonValue::Number(-1e-300).is_negative_zero());
+		assert!(!JsonValue::String("-0".to_owned()).is_negative_zero());
+		assert_eq!(
+			crate::parse("[-0]").unwrap(),
+			crate::parse("[0]").unwrap()
+		);
+	}
+}
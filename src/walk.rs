@@ -0,0 +1,145 @@
+use crate::{JsonPointer, JsonValue};
+
+impl JsonValue {
+	/// Visits every node depth-first, parents before children, together with
+	/// its location.
+	pub fn walk<'a>(&'a self, mut f: impl FnMut(&JsonPointer, &'a JsonValue)) {
+		walk(self, &mut JsonPointer::root(), &mut f)
+	}
+
+	/// Like [`JsonValue::walk`], but allows modifying each node.
+	///
+	/// `f` sees a node before its children, so the children visited are
+	/// whatever the node holds after `f` returns.
+	pub fn walk_mut(
+		&mut self,
+		mut f: impl FnMut(&JsonPointer, &mut JsonValue),
+	) {
+		walk_mut(self, &mut JsonPointer::root(), &mut f)
+	}
+
+	/// Collects every node matching `predicate`, in [`JsonValue::walk`]
+	/// order, along with where it was found.
+	pub fn find_all(
+		&self,
+		mut predicate: impl FnMut(&JsonValue) -> bool,
+	) -> Vec<(JsonPointer, &JsonValue)> {
+		let mut found = Vec::new();
+		self.walk(|path, node| {
+			if predicate(node) {
+				found.push((path.clone(), node));
+			}
+		});
+		found
+	}
+}
+
+fn walk<'a, F>(node: &'a JsonValue, path: &mut JsonPointer, f: &mut F)
+where F: FnMut(&JsonPointer, &'a JsonValue) {
+	f(path, node);
+
+	match node {
+		JsonValue::Array(items) => {
+			for (index, item) in items.iter().enumerate() {
+				path.push(index.to_string());
+				walk(item, path, f);
+				path.pop();
+			}
+		}
+		JsonValue::Object(map) => {
+			for (key, value) in map.iter() {
+				path.push(key.as_str());
+				walk(value, path, f);
+				path.pop();
+			}
+		}
+		_ => {}
+	}
+}
+
+fn walk_mut<F>(node: &mut JsonValue, path: &mut JsonPointer, f: &mut F)
+where F: FnMut(&JsonPointer, &mut JsonValue) {
+	f(path, node);
+
+	match node {
+		JsonValue::Array(items) => {
+			for (index, item) in items.iter_mut().enumerate() {
+				path.push(index.to_string());
+				walk_mut(item, path, f);
+				path.pop();
+			}
+		}
+		JsonValue::Object(map) => {
+			for (key, value) in map.iter_mut() {
+				path.push(key.as_str());
+				walk_mut(value, path, f);
+				path.pop();
+			}
+		}
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[test]
+	fn walk_visits_parents_first() {
+		let value = parse(r#"[{"a": [true]}, "x/y"]"#).unwrap();
+		let mut visited = Vec::new();
+		value.walk(|path, node| visited.push((path.to_string(), node.clone())));
+		assert_eq!(
+			visited,
+			[
+				("", value.clone()),
+				("/0", parse(r#"{"a": [true]}"#).unwrap()),
+				("/0/a", parse("[true]").unwrap()),
+				("/0/a/0", true.into()),
+				("/1", "x/y".into()),
+			]
+			.map(|(path, node)| (path.to_owned(), node))
+		);
+	}
+
+	#[test]
+	fn walk_mut_visits_what_each_node_becomes() {
+		let mut value = parse(r#"{"a": 1, "b": [2]}"#).unwrap();
+		let mut visited = Vec::new();
+		value.walk_mut(|path, node| {
+			visited.push(path.to_string());
+			match node {
+				JsonValue::Number(n) => *n *= 10.0,
+				JsonValue::Array(_) => *node = parse("[3, 4]").unwrap(),
+				_ => {}
+			}
+		});
+		assert_eq!(
+			value,
+			parse(r#"{"a": 10, "b": [30, 40]}"#).unwrap()
+		);
+		visited.sort();
+		assert_eq!(
+			visited,
+			["", "/a", "/b", "/b/0", "/b/1"]
+		);
+	}
+
+	#[test]
+	fn find_all_reports_where_matches_are() {
+		let value = parse(r#"[1, {"a": 2, "b": ["x", 3]}, [4]]"#).unwrap();
+		let mut found: Vec<_> = value
+			.find_all(|node| node.as_f64().is_some_and(|n| n > 1.0))
+			.into_iter()
+			.map(|(path, node)| (path.to_string(), node.clone()))
+			.collect();
+		found.sort_by(|a, b| a.0.cmp(&b.0));
+		assert_eq!(
+			found,
+			[("/1/a", 2), ("/1/b/1", 3), ("/2/0", 4)]
+				.map(|(path, n)| (path.to_owned(), JsonValue::from(n)))
+		);
+		assert!(value.find_all(|node| node.is_null()).is_empty());
+	}
+}
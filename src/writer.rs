@@ -0,0 +1,434 @@
+//! Incremental output of JSON, for documents too large to hold as a
+//! [`JsonValue`].
+
+use std::{
+	error, fmt,
+	io::{self, Write},
+};
+
+use crate::{CompactFormatter, Event, Formatter, JsonValue};
+
+/// Writes a document piece by piece, checking that the calls nest properly.
+///
+/// ```text
+/// let mut writer = JsonWriter::new(io::stdout().lock());
+/// writer.begin_object()?;
+/// writer.key("items")?;
+/// writer.begin_array()?;
+/// for item in items {
+///     writer.value(&item)?;
+/// }
+/// writer.end_array()?;
+/// writer.end_object()?;
+/// writer.finish()?;
+/// ```
+///
+/// The text itself comes from a [`Formatter`], compact by default. Output
+/// is not buffered; wrap the destination in an [`io::BufWriter`] when it is
+/// a file or socket.
+#[derive(Debug)]
+pub struct JsonWriter<W: Write, F: Formatter = CompactFormatter> {
+	out: W,
+	formatter: F,
+	/// The open containers.
+	stack: Vec<Frame>,
+	/// Whether a key has been written that still awaits its value.
+	after_key: bool,
+	/// Whether the top-level value is complete.
+	done: bool,
+	sort_keys: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+	object: bool,
+	len: usize,
+}
+
+/// An error from a [`JsonWriter`].
+#[derive(Debug)]
+pub enum WriteError {
+	Io(io::Error),
+	/// The call doesn't fit where the document is, e.g. a [`key`] inside an
+	/// array or [`finish`] with containers still open.
+	///
+	/// [`key`]: JsonWriter::key
+	/// [`finish`]: JsonWriter::finish
+	Misplaced(&'static str),
+}
+
+impl fmt::Display for WriteError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "failed to write JSON: {e}"),
+			Self::Misplaced(message) => f.write_str(message),
+		}
+	}
+}
+
+impl error::Error for WriteError {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Misplaced(_) => None,
+		}
+	}
+}
+
+impl From<io::Error> for WriteError {
+	fn from(e: io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+fn misplaced<T>(message: &'static str) -> Result<T, WriteError> {
+	Err(WriteError::Misplaced(message))
+}
+
+impl<W: Write> JsonWriter<W> {
+	pub fn new(out: W) -> Self {
+		Self::with_formatter(out, CompactFormatter::new())
+	}
+}
+
+impl<W: Write, F: Formatter> JsonWriter<W, F> {
+	pub fn with_formatter(out: W, formatter: F) -> Self {
+		Self {
+			out,
+			formatter,
+			stack: Vec::new(),
+			after_key: false,
+			done: false,
+			sort_keys: false,
+		}
+	}
+
+	/// Makes [`JsonWriter::value`] write object members ordered by key,
+	/// rather than in the map's arbitrary order.
+	pub fn sort_keys(mut self, sort: bool) -> Self {
+		self.sort_keys = sort;
+		self
+	}
+
+	pub fn begin_object(&mut self) -> Result<(), WriteError> {
+		self.begin_value()?;
+		self.formatter.begin_object(&mut self.out)?;
+		self.stack.push(Frame {
+			object: true,
+			len: 0,
+		});
+		Ok(())
+	}
+
+	pub fn end_object(&mut self) -> Result<(), WriteError> {
+		match self.stack.last() {
+			Some(Frame { object: true, .. }) if !self.after_key => {}
+			Some(Frame { object: true, .. }) => {
+				return misplaced("object ended after a key with no value")
+			}
+			_ => return misplaced("end_object outside an object"),
+		}
+		self.stack.pop();
+		self.formatter.end_object(&mut self.out)?;
+		self.end_value()
+	}
+
+	pub fn begin_array(&mut self) -> Result<(), WriteError> {
+		self.begin_value()?;
+		self.formatter.begin_array(&mut self.out)?;
+		self.stack.push(Frame {
+			object: false,
+			len: 0,
+		});
+		Ok(())
+	}
+
+	pub fn end_array(&mut self) -> Result<(), WriteError> {
+		let Some(Frame { object: false, .. }) = self.stack.last() else {
+			return misplaced("end_array outside an array");
+		};
+		self.stack.pop();
+		self.formatter.end_array(&mut self.out)?;
+		self.end_value()
+	}
+
+	/// Writes the key of the next member of the innermost object.
+	pub fn key(&mut self, key: &str) -> Result<(), WriteError> {
+		let Some(frame) = self.stack.last_mut().filter(|frame| frame.object)
+		else {
+			return misplaced("key outside an object");
+		};
+		if self.after_key {
+			return misplaced("key written where a value was expected");
+		}
+		self.formatter
+			.begin_object_key(&mut self.out, frame.len == 0)?;
+		frame.len += 1;
+		self.formatter.write_string(&mut self.out, key)?;
+		self.formatter.end_object_key(&mut self.out)?;
+		self.after_key = true;
+		Ok(())
+	}
+
+	pub fn null(&mut self) -> Result<(), WriteError> {
+		self.begin_value()?;
+		self.formatter.write_null(&mut self.out)?;
+		self.end_value()
+	}
+
+	pub fn bool(&mut self, b: bool) -> Result<(), WriteError> {
+		self.begin_value()?;
+		self.formatter.write_bool(&mut self.out, b)?;
+		self.end_value()
+	}
+
+	/// Writes a number; with the built-in formatters NaN and infinities
+	/// become `null`.
+	pub fn number(&mut self, n: f64) -> Result<(), WriteError> {
+		self.begin_value()?;
+		self.formatter.write_number(&mut self.out, n)?;
+		self.end_value()
+	}
+
+	pub fn string(&mut self, s: &str) -> Result<(), WriteError> {
+		self.begin_value()?;
+		self.formatter.write_string(&mut self.out, s)?;
+		self.end_value()
+	}
+
+	/// Writes a complete value.
+	pub fn value(&mut self, value: &JsonValue) -> Result<(), WriteError> {
+		match value {
+			JsonValue::Null => self.null(),
+			JsonValue::Boolean(b) => self.bool(*b),
+			JsonValue::Number(n) => self.number(*n),
+			JsonValue::String(s) => self.string(s),
+			JsonValue::Array(items) => {
+				self.begin_array()?;
+				for item in items {
+					self.value(item)?;
+				}
+				self.end_array()
+			}
+			JsonValue::Object(map) if self.sort_keys => {
+				let mut members: Vec<_> = map.iter().collect();
+				members.sort_unstable_by_key(|&(key, _)| key);
+				self.begin_object()?;
+				for (key, value) in members {
+					self.key(key)?;
+					self.value(value)?;
+				}
+				self.end_object()
+			}
+			JsonValue::Object(map) => {
+				self.begin_object()?;
+				for (key, value) in map.iter() {
+					self.key(key)?;
+					self.value(value)?;
+				}
+				self.end_object()
+			}
+		}
+	}
+
+	/// Writes the step an [`EventReader`](crate::EventReader) would report,
+	/// so event streams can be written back out.
+	pub fn event(&mut self, event: &Event) -> Result<(), WriteError> {
+		match event {
+			Event::StartObject => self.begin_object(),
+			Event::EndObject => self.end_object(),
+			Event::StartArray => self.begin_array(),
+			Event::EndArray => self.end_array(),
+			Event::Key(key) => self.key(key),
+			Event::Null => self.null(),
+			Event::Boolean(b) => self.bool(*b),
+			Event::Number(n) => self.number(*n),
+			Event::String(s) => self.string(s),
+		}
+	}
+
+	/// Checks that the document is complete, flushes and returns the
+	/// destination.
+	pub fn finish(mut self) -> Result<W, WriteError> {
+		if !self.done {
+			return misplaced("document is incomplete");
+		}
+		self.out.flush()?;
+		Ok(self.out)
+	}
+
+	/// Prepares for a value at the current position.
+	fn begin_value(&mut self) -> Result<(), WriteError> {
+		match self.stack.last_mut() {
+			None if self.done => misplaced("document is already complete"),
+			None => Ok(()),
+			Some(Frame { object: true, .. }) if !self.after_key => {
+				misplaced("value written where a key was expected")
+			}
+			Some(Frame { object: true, .. }) => {
+				self.after_key = false;
+				self.formatter.begin_object_value(&mut self.out)?;
+				Ok(())
+			}
+			Some(frame) => {
+				self.formatter
+					.begin_array_value(&mut self.out, frame.len == 0)?;
+				frame.len += 1;
+				Ok(())
+			}
+		}
+	}
+
+	/// Finishes a value in whatever contains it.
+	fn end_value(&mut self) -> Result<(), WriteError> {
+		match self.stack.last() {
+			None => self.done = true,
+			Some(Frame { object: true, .. }) => {
+				self.formatter.end_object_value(&mut self.out)?
+			}
+			Some(_) => self.formatter.end_array_value(&mut self.out)?,
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn written(
+		write: impl FnOnce(&mut JsonWriter<Vec<u8>>) -> Result<(), WriteError>,
+	) -> String {
+		let mut writer = JsonWriter::new(Vec::new());
+		write(&mut writer).unwrap();
+		String::from_utf8(writer.finish().unwrap()).unwrap()
+	}
+
+	#[test]
+	fn writes_documents_piece_by_piece() {
+		let out = written(|w| {
+			w.begin_object()?;
+			w.key("items")?;
+			w.begin_array()?;
+			w.number(1.5)?;
+			w.string("a\"b")?;
+			w.value(&parse(r#"{"k": [null]}"#).unwrap())?;
+			w.end_array()?;
+			w.key("ok")?;
+			w.bool(true)?;
+			w.key("none")?;
+			w.null()?;
+			w.end_object()
+		});
+		assert_eq!(
+			out,
+			r#"{"items":[1.5,"a\"b",{"k":[null]}],"ok":true,"none":null}"#
+		);
+	}
+
+	#[test]
+	fn keys_may_be_sorted() {
+		let value = parse(r#"{"b": 1, "a": {"d": 2, "c": 3}}"#).unwrap();
+		let mut writer = JsonWriter::new(Vec::new()).sort_keys(true);
+		writer.value(&value).unwrap();
+		let out = writer.finish().unwrap();
+		assert_eq!(out, br#"{"a":{"c":3,"d":2},"b":1}"#);
+	}
+
+	#[test]
+	fn misplaced_calls_are_errors() {
+		fn misplaced(
+			calls: impl FnOnce(&mut JsonWriter<Vec<u8>>) -> Result<(), WriteError>,
+		) -> &'static str {
+			let mut writer = JsonWriter::new(Vec::new());
+			let error = match calls(&mut writer) {
+				Ok(()) => writer.finish().unwrap_err(),
+				Err(error) => error,
+			};
+			match error {
+				WriteError::Misplaced(message) => message,
+				WriteError::Io(e) => panic!("{e}"),
+			}
+		}
+		assert_eq!(
+			misplaced(|w| w.key("k")),
+			"key outside an object"
+		);
+		assert_eq!(
+			misplaced(|w| {
+				w.begin_object()?;
+				w.null()
+			}),
+			"value written where a key was expected"
+		);
+		assert_eq!(
+			misplaced(|w| {
+				w.begin_object()?;
+				w.key("a")?;
+				w.key("b")
+			}),
+			"key written where a value was expected"
+		);
+		assert_eq!(
+			misplaced(|w| {
+				w.begin_object()?;
+				w.key("a")?;
+				w.end_object()
+			}),
+			"object ended after a key with no value"
+		);
+		assert_eq!(
+			misplaced(|w| {
+				w.begin_array()?;
+				w.end_object()
+			}),
+			"end_object outside an object"
+		);
+		assert_eq!(
+			misplaced(|w| w.end_array()),
+			"end_array outside an array"
+		);
+		assert_eq!(
+			misplaced(|w| {
+				w.null()?;
+				w.null()
+			}),
+			"document is already complete"
+		);
+		assert_eq!(
+			misplaced(|w| w.begin_array()),
+			"document is incomplete"
+		);
+		assert_eq!(
+			misplaced(|_| Ok(())),
+			"document is incomplete"
+		);
+	}
+
+	#[test]
+	fn events_stream_between_reader_and_writer() {
+		let input =
+			r#"{"a": [1, "two", null, {"b": false}], "c": {}, "d": []}"#;
+		let mut writer = JsonWriter::with_formatter(
+			Vec::new(),
+			crate::PrettyFormatter::new(),
+		);
+		for event in crate::EventReader::new(input) {
+			writer.event(&event.unwrap()).unwrap();
+		}
+		let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+		assert_eq!(
+			parse(&out).unwrap(),
+			parse(input).unwrap()
+		);
+		assert!(out.contains("\n    \"two\",\n"));
+
+		let mut writer = JsonWriter::new(Vec::new());
+		writer.event(&Event::StartArray).unwrap();
+		assert!(matches!(
+			writer.event(&Event::EndObject),
+			Err(WriteError::Misplaced(_))
+		));
+	}
+}
@@ -0,0 +1,1169 @@
+//! Loading [YAML](https://yaml.org/spec/1.2.2/) documents, behind the `yaml`
+//! feature.
+//!
+//! Block and flow collections, the plain, quoted and block scalar styles,
+//! comments, multiple documents, anchors, aliases and `<<` merge keys are
+//! understood. Tags and complex (`?`) keys are not, and are reported as
+//! errors rather than guessed at.
+
+use std::collections::HashMap;
+
+use crate::{Error, ErrorKind, JsonValue};
+
+impl JsonValue {
+	/// Loads a YAML stream holding at most one document; an empty stream
+	/// loads as `null`.
+	///
+	/// Plain scalars are resolved with the YAML 1.2 core schema: `null`, `~`
+	/// and nothing at all become `null`, `true` and `false` (in lower, title
+	/// or upper case) booleans, and decimal, `0x` hexadecimal and `0o` octal
+	/// integers and decimal floats numbers. Everything else, including the
+	/// YAML 1.1 `yes`, `no`, `on` and `off`, is a string, as are quoted and
+	/// block scalars. `.inf` and `.nan` have no JSON equivalent and are
+	/// rejected. Mapping keys are always strings, taken as written.
+	///
+	/// A key repeated within a mapping keeps its last value, like
+	/// [`parse`](crate::parse), and keys set explicitly win over merged
+	/// ones.
+	pub fn from_yaml(input: &str) -> Result<JsonValue, Error> {
+		let mut documents = Loader::new(input).documents()?;
+		if let Some(&(offset, _)) = documents.get(1) {
+			return Err(Error::new(
+				input,
+				offset,
+				"expected a single document".to_owned(),
+			));
+		}
+		Ok(documents.pop().map_or(JsonValue::Null, |(_, value)| value))
+	}
+
+	/// Loads every document of a YAML stream, as [`JsonValue::from_yaml`]
+	/// does a single one.
+	pub fn from_yaml_documents(input: &str) -> Result<Vec<JsonValue>, Error> {
+		let documents = Loader::new(input).documents()?;
+		Ok(documents.into_iter().map(|(_, value)| value).collect())
+	}
+}
+
+/// How deeply collections may nest, so hostile input cannot exhaust the
+/// stack.
+const MAX_DEPTH: usize = 256;
+
+/// How many nodes aliases may copy in all, so a handful of nested aliases
+/// cannot expand into an enormous value.
+const MAX_ALIASED_NODES: usize = 1 << 20;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Chomping {
+	Strip,
+	Clip,
+	Keep,
+}
+
+struct Loader<'i> {
+	input: &'i str,
+	pos: usize,
+	/// The number of collections being loaded.
+	depth: usize,
+	/// The values of the current document's anchors.
+	anchors: HashMap<&'i str, JsonValue>,
+	/// The number of nodes copied by aliases so far.
+	aliased: usize,
+}
+
+impl<'i> Loader<'i> {
+	fn new(input: &'i str) -> Self {
+		Self {
+			input,
+			pos: 0,
+			depth: 0,
+			anchors: HashMap::new(),
+			aliased: 0,
+		}
+	}
+
+	fn error_at(&self, offset: usize, message: impl Into<String>) -> Error {
+		Error::new(self.input, offset, message.into())
+	}
+
+	fn error(&self, message: impl Into<String>) -> Error {
+		self.error_at(self.pos, message)
+	}
+
+	fn unexpected(&self) -> Error {
+		match self.input[self.pos..].chars().next() {
+			Some(c) => self.error(format!("unexpected character {c:?}")),
+			None => self.error("unexpected end of input"),
+		}
+	}
+
+	fn byte(&self, ahead: usize) -> Option<u8> {
+		self.input.as_bytes().get(self.pos + ahead).copied()
+	}
+
+	/// Whether the byte `ahead` of the position separates tokens, as the end
+	/// of the input does.
+	fn is_blank(&self, ahead: usize) -> bool {
+		matches!(
+			self.byte(ahead),
+			None | Some(b' ' | b'\t' | b'\r' | b'\n')
+		)
+	}
+
+	/// Whether a block sequence entry starts at the position.
+	fn at_dash(&self) -> bool {
+		self.byte(0) == Some(b'-') && self.is_blank(1)
+	}
+
+	/// Whether the `:` separating a block mapping key from its value is at
+	/// the position.
+	fn at_colon(&self) -> bool {
+		self.byte(0) == Some(b':') && self.is_blank(1)
+	}
+
+	/// Whether nothing but a comment is left on the line.
+	fn at_line_end(&self) -> bool {
+		matches!(
+			self.byte(0),
+			None | Some(b'\r' | b'\n' | b'#')
+		)
+	}
+
+	fn line_start(&self) -> usize {
+		self.input[..self.pos]
+			.rfind('\n')
+			.map_or(0, |newline| newline + 1)
+	}
+
+	fn column(&self) -> usize {
+		self.pos - self.line_start()
+	}
+
+	fn skip_spaces(&mut self) {
+		while matches!(self.byte(0), Some(b' ' | b'\t')) {
+			self.pos += 1;
+		}
+	}
+
+	/// Skips the rest of the line, which may only hold a comment, stopping
+	/// at the line break.
+	fn end_of_line(&mut self) -> Result<(), Error> {
+		self.skip_spaces();
+		if self.byte(0) == Some(b'#') {
+			self.pos += self.input[self.pos..]
+				.find('\n')
+				.map_or(self.input.len() - self.pos, |newline| {
+					newline
+				});
+		}
+		if self.byte(0) == Some(b'\r') {
+			self.pos += 1;
+		}
+		match self.byte(0) {
+			None | Some(b'\n') => Ok(()),
+			Some(_) => Err(self.unexpected()),
+		}
+	}
+
+	/// Moves to the first character of the next line with content,
+	/// returning its indentation, or `None` at the end of the input or of
+	/// the document. Calling it again without moving on does nothing.
+	fn next_content(&mut self) -> Result<Option<usize>, Error> {
+		let mut line = if self.input[self.line_start()..self.pos]
+			.bytes()
+			.all(|b| b == b' ')
+		{
+			self.line_start()
+		} else {
+			self.end_of_line()?;
+			self.pos + 1
+		};
+		while line < self.input.len() {
+			let text = self.input[line..].split('\n').next().unwrap_or("");
+			let indent = text.len() - text.trim_start_matches(' ').len();
+			let content = text.trim_start_matches([' ', '\t']);
+			if content.is_empty() || content == "\r" || content.starts_with('#')
+			{
+				line += text.len() + 1;
+				continue;
+			}
+			if indent == 0 && is_marker(text) {
+				self.pos = line;
+				return Ok(None);
+			}
+			self.pos = line + indent;
+			if text[indent..].starts_with('\t') {
+				return Err(self.error("tabs cannot be used for indentation"));
+			}
+			return Ok(Some(indent));
+		}
+		self.pos = self.input.len();
+		Ok(None)
+	}
+
+	/// Loads every document along with the offset it starts at.
+	fn documents(&mut self) -> Result<Vec<(usize, JsonValue)>, Error> {
+		let mut documents = Vec::new();
+		// Whether a document may start without a `---` marker.
+		let mut open = true;
+		loop {
+			match self.next_content()? {
+				Some(0) if open && self.byte(0) == Some(b'%') => {
+					self.pos += self.input[self.pos..]
+						.find('\n')
+						.unwrap_or(self.input.len() - self.pos);
+				}
+				Some(_) if open => {
+					let start = self.pos;
+					self.anchors.clear();
+					documents.push((start, self.block_value(-1, false)?));
+					open = false;
+				}
+				Some(_) => {
+					return Err(
+						self.error("unexpected content after the document")
+					)
+				}
+				None if self.pos == self.input.len() => return Ok(documents),
+				None if self.input[self.pos..].starts_with("---") => {
+					let start = self.pos;
+					self.pos += 3;
+					self.anchors.clear();
+					documents.push((start, self.block_value(-1, true)?));
+					open = false;
+				}
+				None => {
+					self.pos += 3;
+					open = true;
+				}
+			}
+		}
+	}
+
+	/// Loads the node following a mapping key, a sequence entry's `-` or a
+	/// document marker, either on the same line or indented below it.
+	/// `parent` is the indentation of the collection it belongs to.
+	fn block_value(
+		&mut self,
+		parent: isize,
+		after_key: bool,
+	) -> Result<JsonValue, Error> {
+		self.skip_spaces();
+		let anchor = self.anchor()?;
+		let value = if self.at_line_end() {
+			match self.next_content()? {
+				Some(indent) if indent as isize > parent => {
+					self.node(indent, parent, true)?
+				}
+				// A sequence may be indented no deeper than its key.
+				Some(indent)
+					if after_key
+						&& indent as isize == parent
+						&& self.at_dash() =>
+				{
+					self.node(indent, parent, true)?
+				}
+				_ => JsonValue::Null,
+			}
+		} else {
+			let column = self.column();
+			self.node(column, parent, !after_key)?
+		};
+		Ok(self.anchored(anchor, value))
+	}
+
+	/// Loads a node starting at `column`. Only when `compact` is set may a
+	/// block collection start here rather than on a line of its own.
+	fn node(
+		&mut self,
+		column: usize,
+		parent: isize,
+		compact: bool,
+	) -> Result<JsonValue, Error> {
+		if self.depth == MAX_DEPTH {
+			return Err(self
+				.error("nesting too deep")
+				.with_kind(ErrorKind::TooDeep));
+		}
+		self.depth += 1;
+		let node = self.block_node(column, parent, compact);
+		self.depth -= 1;
+		node
+	}
+
+	fn block_node(
+		&mut self,
+		column: usize,
+		parent: isize,
+		compact: bool,
+	) -> Result<JsonValue, Error> {
+		let start = self.pos;
+		match self.byte(0) {
+			Some(b'-') if self.is_blank(1) => {
+				if !compact {
+					return Err(
+						self.error("a block sequence must start on a new line")
+					);
+				}
+				self.sequence(column)
+			}
+			Some(b'[' | b'{') => self.flow(),
+			Some(b'|' | b'>') => {
+				self.block_scalar(parent).map(JsonValue::String)
+			}
+			Some(b'*') => self.alias(),
+			Some(b'?') if self.is_blank(1) => {
+				Err(self.error("complex mapping keys are not supported"))
+			}
+			Some(b'"' | b'\'') => {
+				let text = self.quoted()?;
+				self.skip_spaces();
+				if !self.at_colon() {
+					return Ok(JsonValue::String(text));
+				}
+				if self.input[start..self.pos].contains('\n') {
+					return Err(self.error_at(
+						start,
+						"a mapping key must fit on one line",
+					));
+				}
+				self.mapping(column, compact, (start, text, false))
+			}
+			Some(b',' | b']' | b'}' | b'@' | b'`' | b'%') => {
+				Err(self.unexpected())
+			}
+			_ => {
+				let first = self.plain(false);
+				if first.is_empty() {
+					return Err(self.unexpected());
+				}
+				self.skip_spaces();
+				if self.at_colon() {
+					return self.mapping(
+						column,
+						compact,
+						(start, first.to_owned(), true),
+					);
+				}
+				let mut text = first.to_owned();
+				self.block_continuation(parent, &mut text);
+				resolve(&text).map_err(|message| self.error_at(start, message))
+			}
+		}
+	}
+
+	/// Loads a block sequence whose entries' `-` are at `column`.
+	fn sequence(&mut self, column: usize) -> Result<JsonValue, Error> {
+		let mut items = Vec::new();
+		loop {
+			self.pos += 1;
+			items.push(self.block_value(column as isize, false)?);
+			match self.next_content()? {
+				Some(indent) if indent == column && self.at_dash() => {}
+				Some(indent) if indent > column => {
+					return Err(self.error("unexpected indentation"))
+				}
+				_ => return Ok(JsonValue::Array(items)),
+			}
+		}
+	}
+
+	/// Loads a block mapping whose keys are at `column`, the first of which,
+	/// with its offset and whether it is plain, has been read up to its `:`.
+	fn mapping(
+		&mut self,
+		column: usize,
+		compact: bool,
+		first: (usize, String, bool),
+	) -> Result<JsonValue, Error> {
+		let (mut start, mut key, mut plain) = first;
+		if !compact {
+			return Err(self.error_at(
+				start,
+				"a block mapping must start on a new line",
+			));
+		}
+		let mut map = HashMap::new();
+		let mut merged = Vec::new();
+		loop {
+			self.pos += 1;
+			let value = self.block_value(column as isize, true)?;
+			self.insert(
+				&mut map,
+				&mut merged,
+				(start, key, plain),
+				value,
+			)?;
+			match self.next_content()? {
+				Some(indent) if indent == column => {}
+				Some(indent) if indent > column => {
+					return Err(self.error("unexpected indentation"))
+				}
+				_ => return Ok(merge(map, merged)),
+			}
+			start = self.pos;
+			(key, plain) = match self.byte(0) {
+				Some(b'"' | b'\'') => (self.quoted()?, false),
+				Some(
+					b'-' | b'?' | b'[' | b'{' | b'*' | b'&' | b'!' | b'|'
+					| b'>',
+				)
+				| None => return Err(self.error("expected a mapping key")),
+				_ => (self.plain(false).to_owned(), true),
+			};
+			self.skip_spaces();
+			if !self.at_colon()
+				|| (plain && key.is_empty())
+				|| self.input[start..self.pos].contains('\n')
+			{
+				return Err(self.error_at(start, "expected a mapping key"));
+			}
+		}
+	}
+
+	/// Adds an entry to a mapping being loaded, setting merged mappings
+	/// aside until it is complete.
+	fn insert(
+		&self,
+		map: &mut HashMap<String, JsonValue>,
+		merged: &mut Vec<JsonValue>,
+		(start, key, plain): (usize, String, bool),
+		value: JsonValue,
+	) -> Result<(), Error> {
+		if !plain || key != "<<" {
+			map.insert(key, value);
+			return Ok(());
+		}
+		match value {
+			JsonValue::Object(_) => merged.push(value),
+			JsonValue::Array(items)
+				if items
+					.iter()
+					.all(|item| matches!(item, JsonValue::Object(_))) =>
+			{
+				merged.extend(items)
+			}
+			_ => return Err(self.error_at(
+				start,
+				"a merge key must map to a mapping or a sequence of mappings",
+			)),
+		}
+		Ok(())
+	}
+
+	/// Reads an anchor, if one is at the position.
+	fn anchor(&mut self) -> Result<Option<&'i str>, Error> {
+		match self.byte(0) {
+			Some(b'&') => {
+				self.pos += 1;
+				let name = self.name()?;
+				self.skip_spaces();
+				if self.byte(0) == Some(b'!') {
+					return Err(self.error("tags are not supported"));
+				}
+				Ok(Some(name))
+			}
+			Some(b'!') => Err(self.error("tags are not supported")),
+			_ => Ok(None),
+		}
+	}
+
+	fn anchored(
+		&mut self,
+		anchor: Option<&'i str>,
+		value: JsonValue,
+	) -> JsonValue {
+		if let Some(name) = anchor {
+			self.anchors.insert(name, value.clone());
+		}
+		value
+	}
+
+	fn alias(&mut self) -> Result<JsonValue, Error> {
+		let start = self.pos;
+		self.pos += 1;
+		let name = self.name()?;
+		let Some(value) = self.anchors.get(name) else {
+			return Err(self.error_at(start, format!("unknown alias {name:?}")));
+		};
+		self.aliased += nodes(value);
+		if self.aliased > MAX_ALIASED_NODES {
+			return Err(self
+				.error_at(
+					start,
+					"aliases expand to too many nodes",
+				)
+				.with_kind(ErrorKind::BudgetExceeded));
+		}
+		Ok(value.clone())
+	}
+
+	/// Reads the name of an anchor or alias.
+	fn name(&mut self) -> Result<&'i str, Error> {
+		let start = self.pos;
+		let len = self.input[start..]
+			.find(|c| {
+				matches!(
+					c,
+					' ' | '\t' | '\r' | '\n' | ',' | '[' | ']' | '{' | '}'
+				)
+			})
+			.unwrap_or(self.input.len() - start);
+		if len == 0 {
+			return Err(self.error("expected an anchor name"));
+		}
+		self.pos += len;
+		Ok(&self.input[start..self.pos])
+	}
+
+	/// Reads one line's worth of a plain scalar, without surrounding
+	/// whitespace, in block or `flow` context.
+	fn plain(&mut self, flow: bool) -> &'i str {
+		let start = self.pos;
+		let mut end = start;
+		let mut chars = self.input[start..].char_indices().peekable();
+		while let Some((i, c)) = chars.next() {
+			let next = chars.peek().map(|&(_, c)| c);
+			let ends = match c {
+				'\r' | '\n' => true,
+				':' => match next {
+					None | Some(' ' | '\t' | '\r' | '\n') => true,
+					Some(',' | '[' | ']' | '{' | '}') => flow,
+					_ => false,
+				},
+				'#' => {
+					i > 0
+						&& matches!(
+							self.input.as_bytes()[start + i - 1],
+							b' ' | b'\t'
+						)
+				}
+				',' | '[' | ']' | '{' | '}' => flow,
+				_ => false,
+			};
+			if ends {
+				break;
+			}
+			if !matches!(c, ' ' | '\t') {
+				end = start + i + c.len_utf8();
+			}
+		}
+		self.pos = end;
+		&self.input[start..end]
+	}
+
+	/// Adds the lines continuing a plain scalar in block context, which must
+	/// be indented deeper than `parent`.
+	fn block_continuation(&mut self, parent: isize, text: &mut String) {
+		loop {
+			let save = self.pos;
+			self.skip_spaces();
+			if self.byte(0) == Some(b'\r') {
+				self.pos += 1;
+			}
+			if self.byte(0) != Some(b'\n') {
+				self.pos = save;
+				return;
+			}
+			let mut line = self.pos;
+			let mut breaks = 0;
+			let (indent, content) = loop {
+				line += 1;
+				breaks += 1;
+				let text = self.input[line..].split('\n').next().unwrap_or("");
+				let content = text.trim_start_matches([' ', '\t']);
+				if (content.is_empty() || content == "\r")
+					&& line + text.len() < self.input.len()
+				{
+					line += text.len();
+					continue;
+				}
+				let indent = text.len() - text.trim_start_matches(' ').len();
+				break (
+					indent,
+					line + text.len() - content.len(),
+				);
+			};
+			let rest = &self.input[content..];
+			if indent as isize <= parent
+				|| matches!(
+					rest.bytes().next(),
+					None | Some(b'#' | b'\r')
+				) || (indent == 0 && is_marker(rest))
+			{
+				self.pos = save;
+				return;
+			}
+			self.pos = content;
+			let more = self.plain(false);
+			if more.is_empty() {
+				self.pos = save;
+				return;
+			}
+			fold(text, breaks);
+			text.push_str(more);
+		}
+	}
+
+	/// Adds the lines continuing a plain scalar in flow context.
+	fn flow_continuation(&mut self, text: &mut String) {
+		loop {
+			let save = self.pos;
+			let mut breaks = 0;
+			while let Some(b) = self.byte(0) {
+				match b {
+					b' ' | b'\t' | b'\r' => {}
+					b'\n' => breaks += 1,
+					_ => break,
+				}
+				self.pos += 1;
+			}
+			let more = match self.byte(0) {
+				Some(b',' | b'[' | b']' | b'{' | b'}' | b'#' | b':') | None => {
+					""
+				}
+				Some(_) if breaks > 0 => self.plain(true),
+				Some(_) => "",
+			};
+			if more.is_empty() {
+				self.pos = save;
+				return;
+			}
+			fold(text, breaks);
+			text.push_str(more);
+		}
+	}
+
+	/// Reads a single or double quoted scalar.
+	fn quoted(&mut self) -> Result<String, Error> {
+		let start = self.pos;
+		let quote = self.input.as_bytes()[start];
+		self.pos += 1;
+		let mut text = String::new();
+		// The length of `text` that line folding may not trim, so escaped
+		// whitespace survives.
+		let mut kept = 0;
+		loop {
+			let Some(c) = self.input[self.pos..].chars().next() else {
+				return Err(self.error_at(start, "unterminated string"));
+			};
+			match c {
+				'\'' if quote == b'\'' && self.byte(1) == Some(b'\'') => {
+					text.push('\'');
+					self.pos += 2;
+				}
+				'\'' if quote == b'\'' => break,
+				'"' if quote == b'"' => break,
+				'\\' if quote == b'"' => {
+					self.escape(&mut text)?;
+					kept = text.len();
+				}
+				'\r' | '\n' => {
+					let trimmed =
+						text[kept..].trim_end_matches([' ', '\t']).len();
+					text.truncate(kept + trimmed);
+					let mut breaks = 0;
+					while let Some(b) = self.byte(0) {
+						match b {
+							b' ' | b'\t' | b'\r' => {}
+							b'\n' => breaks += 1,
+							_ => break,
+						}
+						self.pos += 1;
+					}
+					fold(&mut text, breaks);
+				}
+				c => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+			}
+		}
+		self.pos += 1;
+		Ok(text)
+	}
+
+	/// Reads an escape sequence in a double quoted scalar.
+	fn escape(&mut self, text: &mut String) -> Result<(), Error> {
+		let start = self.pos;
+		let escaped = self.byte(1);
+		self.pos += 2;
+		let c = match escaped {
+			Some(b'0') => '\0',
+			Some(b'a') => '\x07',
+			Some(b'b') => '\x08',
+			Some(b't' | b'\t') => '\t',
+			Some(b'n') => '\n',
+			Some(b'v') => '\x0B',
+			Some(b'f') => '\x0C',
+			Some(b'r') => '\r',
+			Some(b'e') => '\x1B',
+			Some(b' ') => ' ',
+			Some(b'"') => '"',
+			Some(b'/') => '/',
+			Some(b'\\') => '\\',
+			Some(b'N') => '\u{85}',
+			Some(b'_') => '\u{A0}',
+			Some(b'L') => '\u{2028}',
+			Some(b'P') => '\u{2029}',
+			Some(b'x') => self.code_point(start, 2)?,
+			Some(b'u') => self.code_point(start, 4)?,
+			Some(b'U') => self.code_point(start, 8)?,
+			// An escaped line break joins the lines without a space.
+			Some(b'\r' | b'\n') => {
+				self.pos = start + 1;
+				if self.byte(0) == Some(b'\r') {
+					self.pos += 1;
+				}
+				self.pos += 1;
+				self.skip_spaces();
+				return Ok(());
+			}
+			_ => return Err(self.error_at(start, "invalid escape sequence")),
+		};
+		text.push(c);
+		Ok(())
+	}
+
+	/// Reads the `digits` hex digits of a code point escape.
+	fn code_point(
+		&mut self,
+		start: usize,
+		digits: usize,
+	) -> Result<char, Error> {
+		self.input
+			.get(self.pos..self.pos + digits)
+			.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+			.and_then(char::from_u32)
+			.inspect(|_| self.pos += digits)
+			.ok_or_else(|| self.error_at(start, "invalid escape sequence"))
+	}
+
+	/// Reads a literal (`|`) or folded (`>`) block scalar whose content is
+	/// indented deeper than `parent`.
+	fn block_scalar(&mut self, parent: isize) -> Result<String, Error> {
+		let literal = self.byte(0) == Some(b'|');
+		self.pos += 1;
+		let mut chomping = Chomping::Clip;
+		let mut indent = None;
+		for _ in 0..2 {
+			match self.byte(0) {
+				Some(b'-') if chomping == Chomping::Clip => {
+					chomping = Chomping::Strip
+				}
+				Some(b'+') if chomping == Chomping::Clip => {
+					chomping = Chomping::Keep
+				}
+				Some(digit @ b'1'..=b'9') if indent.is_none() => {
+					indent = Some(
+						parent.max(0) as usize + usize::from(digit - b'0'),
+					);
+				}
+				_ => break,
+			}
+			self.pos += 1;
+		}
+		if !self.is_blank(0) && self.byte(0) != Some(b'#') {
+			return Err(self.error("invalid block scalar header"));
+		}
+		self.end_of_line()?;
+
+		let mut lines = Vec::new();
+		// Whether the last line of content ends in a line break.
+		let mut broken = false;
+		while self.pos < self.input.len() {
+			let line = self.pos + 1;
+			let raw = self.input[line..].split('\n').next().unwrap_or("");
+			let text = raw.strip_suffix('\r').unwrap_or(raw);
+			let spaces = text.len() - text.trim_start_matches(' ').len();
+			if spaces == text.len() {
+				lines.push("");
+			} else {
+				let indent = *indent.get_or_insert(spaces);
+				if spaces < indent
+					|| spaces as isize <= parent
+					|| (spaces == 0 && is_marker(text))
+				{
+					break;
+				}
+				lines.push(&text[indent..]);
+				broken = line + raw.len() < self.input.len();
+			}
+			self.pos = line + raw.len();
+		}
+
+		let Some(last) = lines.iter().rposition(|line| !line.is_empty()) else {
+			return Ok(match chomping {
+				Chomping::Keep => "\n".repeat(lines.len()),
+				_ => String::new(),
+			});
+		};
+		let mut text = String::new();
+		if literal {
+			text = lines[..=last].join("\n");
+		} else {
+			// Whether the previous line of content was more indented, and
+			// how many empty lines followed it.
+			let mut previous = None;
+			let mut empty = 0;
+			for line in &lines[..=last] {
+				if line.is_empty() {
+					empty += 1;
+					continue;
+				}
+				let more = line.starts_with([' ', '\t']);
+				let breaks = match previous {
+					Some(false) if !more && empty == 0 => {
+						text.push(' ');
+						0
+					}
+					Some(false) if !more => empty,
+					Some(_) => empty + 1,
+					None => empty,
+				};
+				text.push_str(&"\n".repeat(breaks));
+				text.push_str(line);
+				previous = Some(more);
+				empty = 0;
+			}
+		}
+		let trailing = match chomping {
+			Chomping::Strip => 0,
+			Chomping::Clip => usize::from(broken),
+			Chomping::Keep => usize::from(broken) + lines.len() - last - 1,
+		};
+		text.push_str(&"\n".repeat(trailing));
+		Ok(text)
+	}
+
+	/// Loads a flow collection.
+	fn flow(&mut self) -> Result<JsonValue, Error> {
+		let open = self.byte(0);
+		self.pos += 1;
+		if open == Some(b'[') {
+			let mut items = Vec::new();
+			loop {
+				self.flow_space();
+				if self.byte(0) == Some(b']') {
+					break;
+				}
+				items.push(self.flow_node()?);
+				self.flow_space();
+				match self.byte(0) {
+					Some(b',') => self.pos += 1,
+					Some(b']') => break,
+					_ => return Err(self.unexpected()),
+				}
+			}
+			self.pos += 1;
+			return Ok(JsonValue::Array(items));
+		}
+		let mut map = HashMap::new();
+		let mut merged = Vec::new();
+		loop {
+			self.flow_space();
+			if self.byte(0) == Some(b'}') {
+				break;
+			}
+			let start = self.pos;
+			let (key, plain) = match self.byte(0) {
+				Some(b'"' | b'\'') => (self.quoted()?, false),
+				Some(b'[' | b'{' | b'*' | b'&' | b'!' | b'?') => {
+					return Err(self.error("expected a mapping key"))
+				}
+				_ => (self.plain(true).to_owned(), true),
+			};
+			if plain && key.is_empty() {
+				return Err(self.unexpected());
+			}
+			self.flow_space();
+			let value = if self.byte(0) == Some(b':') {
+				self.pos += 1;
+				self.flow_space();
+				match self.byte(0) {
+					Some(b',' | b'}') => JsonValue::Null,
+					_ => self.flow_node()?,
+				}
+			} else {
+				JsonValue::Null
+			};
+			self.insert(
+				&mut map,
+				&mut merged,
+				(start, key, plain),
+				value,
+			)?;
+			self.flow_space();
+			match self.byte(0) {
+				Some(b',') => self.pos += 1,
+				Some(b'}') => break,
+				_ => return Err(self.unexpected()),
+			}
+		}
+		self.pos += 1;
+		Ok(merge(map, merged))
+	}
+
+	fn flow_node(&mut self) -> Result<JsonValue, Error> {
+		if self.depth == MAX_DEPTH {
+			return Err(self
+				.error("nesting too deep")
+				.with_kind(ErrorKind::TooDeep));
+		}
+		let anchor = self.anchor()?;
+		self.depth += 1;
+		let start = self.pos;
+		let value = match self.byte(0) {
+			Some(b'[' | b'{') => self.flow(),
+			Some(b'"' | b'\'') => self.quoted().map(JsonValue::String),
+			Some(b'*') => self.alias(),
+			_ => {
+				let mut text = self.plain(true).to_owned();
+				if text.is_empty() {
+					Err(self.unexpected())
+				} else {
+					self.flow_continuation(&mut text);
+					resolve(&text)
+						.map_err(|message| self.error_at(start, message))
+				}
+			}
+		};
+		self.depth -= 1;
+		Ok(self.anchored(anchor, value?))
+	}
+
+	/// Skips whitespace, line breaks and comments between flow tokens.
+	fn flow_space(&mut self) {
+		loop {
+			match self.byte(0) {
+				Some(b' ' | b'\t' | b'\r' | b'\n') => self.pos += 1,
+				Some(b'#') => {
+					self.pos += self.input[self.pos..]
+						.find('\n')
+						.unwrap_or(self.input.len() - self.pos);
+				}
+				_ => return,
+			}
+		}
+	}
+}
+
+/// Whether `line` starts with a `---` or `...` document marker.
+fn is_marker(line: &str) -> bool {
+	(line.starts_with("---") || line.starts_with("..."))
+		&& matches!(
+			line.as_bytes().get(3),
+			None | Some(b' ' | b'\t' | b'\r' | b'\n')
+		)
+}
+
+/// Joins two lines of a scalar separated by `breaks` line breaks: a single
+/// one becomes a space, and each further one a newline.
+fn fold(text: &mut String, breaks: usize) {
+	match breaks {
+		0 => {}
+		1 => text.push(' '),
+		_ => text.push_str(&"\n".repeat(breaks - 1)),
+	}
+}
+
+/// Completes a mapping with the entries of those merged into it, which do
+/// not replace its own and of which the first merged takes precedence.
+fn merge(
+	mut map: HashMap<String, JsonValue>,
+	merged: Vec<JsonValue>,
+) -> JsonValue {
+	for other in merged {
+		if let JsonValue::Object(other) = other {
+			for (key, value) in *other {
+				map.entry(key).or_insert(value);
+			}
+		}
+	}
+	JsonValue::Object(Box::new(map))
+}
+
+fn nodes(value: &JsonValue) -> usize {
+	1 + match value {
+		JsonValue::Array(items) => items.iter().map(nodes).sum(),
+		JsonValue::Object(map) => map.values().map(nodes).sum(),
+		_ => 0,
+	}
+}
+
+/// Resolves a plain scalar with the core schema.
+fn resolve(text: &str) -> Result<JsonValue, String> {
+	Ok(match text {
+		"" | "~" | "null" | "Null" | "NULL" => JsonValue::Null,
+		"true" | "True" | "TRUE" => JsonValue::Boolean(true),
+		"false" | "False" | "FALSE" => JsonValue::Boolean(false),
+		_ => match number(text) {
+			Some(n) if n.is_finite() => JsonValue::Number(n),
+			Some(_) => {
+				return Err(format!(
+					"{text:?} has no JSON equivalent"
+				));
+			}
+			None => JsonValue::String(text.to_owned()),
+		},
+	})
+}
+
+/// Parses a core schema integer or float.
+fn number(text: &str) -> Option<f64> {
+	let radix = |digits: &str, radix| {
+		(!digits.is_empty()).then_some(())?;
+		digits.chars().try_fold(0.0, |n: f64, c| {
+			Some(n * f64::from(radix) + f64::from(c.to_digit(radix)?))
+		})
+	};
+	if let Some(hex) = text.strip_prefix("0x") {
+		return radix(hex, 16);
+	}
+	if let Some(octal) = text.strip_prefix("0o") {
+		return radix(octal, 8);
+	}
+	let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+	if matches!(unsigned, ".inf" | ".Inf" | ".INF") {
+		return Some(if text.starts_with('-') {
+			f64::NEG_INFINITY
+		} else {
+			f64::INFINITY
+		});
+	}
+	if matches!(text, ".nan" | ".NaN" | ".NAN") {
+		return Some(f64::NAN);
+	}
+	let (mantissa, exponent) = unsigned
+		.split_once(['e', 'E'])
+		.map_or((unsigned, None), |(m, e)| (m, Some(e)));
+	let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+	let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+	let valid = digits(whole)
+		&& digits(fraction)
+		&& (!whole.is_empty() || !fraction.is_empty())
+		&& exponent.is_none_or(|e| {
+			let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+			!e.is_empty() && digits(e)
+		});
+	valid.then(|| text.parse().ok()).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	#[track_caller]
+	fn check(yaml: &str, json: &str) {
+		assert_eq!(
+			JsonValue::from_yaml(yaml),
+			Ok(parse(json).unwrap()),
+			"{yaml}"
+		);
+	}
+
+	#[test]
+	fn loads_documents() {
+		check(
+			"name: app\nports:\n  - 80\n  - 443\nenv: {debug: true, level: ~}\n",
+			r#"{"name": "app", "ports": [80, 443],
+			"env": {"debug": true, "level": null}}"#,
+		);
+		check(
+			"- a: 1\n  b: [x, 'y z', \"\\u00e9\"]\n- - nested\n",
+			r#"[{"a": 1, "b": ["x", "y z", "é"]}, ["nested"]]"#,
+		);
+		check(
+			"text: |\n  line one\n  line two\nfolded: >-\n  a\n  b\n",
+			r#"{"text": "line one\nline two\n", "folded": "a b"}"#,
+		);
+		check(
+			"# comment\nvalues: [0x1F, 0o17, -1.5e3, yes, 'true', 1_0]\n",
+			r#"{"values": [31, 15, -1500, "yes", "true", "1_0"]}"#,
+		);
+		check("", "null");
+		assert_eq!(
+			JsonValue::from_yaml_documents("---\na: 1\n---\n- 2\n...\n"),
+			Ok(vec![
+				parse(r#"{"a": 1}"#).unwrap(),
+				parse("[2]").unwrap()
+			])
+		);
+		assert!(JsonValue::from_yaml("a: 1\n---\nb: 2\n").is_err());
+	}
+
+	#[test]
+	fn anchors_aliases_and_merges() {
+		check(
+			"base: &base {x: 1, y: 2}\ncopy: *base\nmerged:\n  <<: *base\n  y: 3\n",
+			r#"{"base": {"x": 1, "y": 2}, "copy": {"x": 1, "y": 2},
+			"merged": {"x": 1, "y": 3}}"#,
+		);
+		let error = JsonValue::from_yaml("a: *missing\n").unwrap_err();
+		assert_eq!(
+			error.message(),
+			r#"unknown alias "missing""#
+		);
+	}
+
+	#[test]
+	fn rejects_what_it_does_not_support() {
+		for yaml in [
+			"!!str 1",
+			"? complex\n: key\n",
+			"a: .inf\n",
+			"[1, 2",
+			"{a: 1",
+			"a: 'unterminated\n",
+		] {
+			assert!(
+				JsonValue::from_yaml(yaml).is_err(),
+				"{yaml}"
+			);
+		}
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		// Mappings each indented one column more than the last.
+		let block = |depth: usize| {
+			(0..depth)
+				.map(|i| format!("{}k:\n", " ".repeat(i)))
+				.collect::<String>()
+		};
+		let n = 100_000;
+		for yaml in [
+			"[".repeat(n),
+			"{a: ".repeat(n),
+			"- ".repeat(n) + "x",
+			block(2_000),
+		] {
+			let error = JsonValue::from_yaml(&yaml).unwrap_err();
+			assert_eq!(error.kind(), ErrorKind::TooDeep);
+		}
+		let depth = MAX_DEPTH - 1;
+		let flow = format!(
+			"{}1{}",
+			"[".repeat(depth),
+			"]".repeat(depth)
+		);
+		assert!(JsonValue::from_yaml(&flow).is_ok());
+		assert!(JsonValue::from_yaml(&block(depth - 1)).is_ok());
+	}
+
+	#[test]
+	fn alias_bombs_are_rejected() {
+		let mut yaml = "a0: &a0 [x, x, x, x, x, x, x, x, x, x]\n".to_owned();
+		for i in 1..10 {
+			let aliases = vec![format!("*a{}", i - 1); 10].join(", ");
+			yaml += &format!("a{i}: &a{i} [{aliases}]\n");
+		}
+		let error = JsonValue::from_yaml(&yaml).unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::BudgetExceeded);
+		assert_eq!(
+			error.message(),
+			"aliases expand to too many nodes"
+		);
+	}
+}